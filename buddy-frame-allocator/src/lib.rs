@@ -0,0 +1,191 @@
+//! A buddy system allocator of page frames, used by the kernel with the `buddy-frame-allocator`
+//! feature. It only depends on `alloc`, so it is tested on the host.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::{collections::BTreeSet, vec::Vec};
+
+/// Blocks larger than `2^MAX_ORDER` frames are never formed
+pub const MAX_ORDER: usize = 20;
+
+/// Free blocks of `2^k` frames are aligned to their size and kept in the `k`-th free list, and a
+/// freed block is merged with its buddy whenever the buddy is free too. Frames are identified by
+/// their numbers.
+pub struct BuddyAllocator {
+    /// Start frames of free blocks of each order
+    free_lists: Vec<BTreeSet<usize>>,
+    /// Lowest and highest frames ever added
+    start: usize,
+    end: usize,
+    total: usize,
+}
+
+impl BuddyAllocator {
+    pub fn new() -> Self {
+        Self {
+            free_lists: (0..=MAX_ORDER).map(|_| BTreeSet::new()).collect(),
+            start: 0,
+            end: 0,
+            total: 0,
+        }
+    }
+
+    /// Hand frames in `[l, r)` to the allocator, cut into the largest aligned blocks. Can be called
+    /// for several disjoint ranges.
+    pub fn add_range(&mut self, l: usize, r: usize) {
+        self.total += r - l;
+        if self.start == self.end {
+            (self.start, self.end) = (l, r);
+        } else {
+            self.start = self.start.min(l);
+            self.end = self.end.max(r);
+        }
+        let mut frame = l;
+        while frame < r {
+            let k = (frame.trailing_zeros() as usize)
+                .min((r - frame).ilog2() as usize)
+                .min(MAX_ORDER);
+            self.free_lists[k].insert(frame);
+            frame += 1 << k;
+        }
+    }
+
+    /// Allocate `2^order` contiguous frames aligned to their total size, splitting a larger block
+    /// if there is no free block of that size
+    pub fn alloc(&mut self, order: usize) -> Option<usize> {
+        let mut k = (order..=MAX_ORDER).find(|&k| !self.free_lists[k].is_empty())?;
+        let frame = self.free_lists[k].pop_first().unwrap();
+        // Split the block and keep the upper halves
+        while k > order {
+            k -= 1;
+            self.free_lists[k].insert(frame + (1 << k));
+        }
+        Some(frame)
+    }
+
+    /// Free the `2^order` frames from `frame`, which must have been allocated together
+    pub fn dealloc(&mut self, mut frame: usize, order: usize) {
+        if order > MAX_ORDER
+            || !frame.is_multiple_of(1 << order)
+            || frame < self.start
+            || frame + (1 << order) > self.end
+            || self.is_partly_free(frame, order)
+        {
+            panic!("Frame ppn = {:#x} has not been allocated!", frame);
+        }
+
+        let mut k = order;
+        while k < MAX_ORDER && self.free_lists[k].remove(&(frame ^ (1 << k))) {
+            frame &= !(1 << k);
+            k += 1;
+        }
+        self.free_lists[k].insert(frame);
+    }
+
+    /// Frames ever handed to the allocator
+    pub fn total_frames(&self) -> usize {
+        self.total
+    }
+
+    /// Frames which can be allocated now
+    pub fn free_frames(&self) -> usize {
+        self.free_lists
+            .iter()
+            .enumerate()
+            .map(|(k, blocks)| blocks.len() << k)
+            .sum()
+    }
+
+    /// Whether some frames of the block are already free
+    fn is_partly_free(&self, frame: usize, order: usize) -> bool {
+        // A free block containing this block
+        let in_free_block = (order..=MAX_ORDER)
+            .any(|k| self.free_lists[k].contains(&(frame & !((1 << k) - 1))));
+        // A free block inside this block
+        let has_free_block = (0..order)
+            .any(|k| self.free_lists[k].range(frame..frame + (1 << order)).next().is_some());
+        in_free_block || has_free_block
+    }
+}
+
+impl Default for BuddyAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_and_merges_buddies() {
+        let mut allocator = BuddyAllocator::new();
+        allocator.add_range(0, 16);
+        assert_eq!(allocator.free_lists[4].len(), 1);
+
+        // The only block is split down to single frames
+        assert_eq!(allocator.alloc(0), Some(0));
+        assert_eq!(allocator.free_frames(), 15);
+        assert!((0..4).all(|k| allocator.free_lists[k].contains(&(1 << k))));
+        assert_eq!(allocator.alloc(1), Some(2));
+
+        // Frame 0 merges with its buddy 1, but not with 2..4 which is still allocated
+        allocator.dealloc(0, 0);
+        assert!(allocator.free_lists[1].contains(&0));
+        assert!(allocator.free_lists[0].is_empty());
+        // Then everything merges back into one block
+        allocator.dealloc(2, 1);
+        assert_eq!(allocator.free_frames(), 16);
+        assert!((0..4).all(|k| allocator.free_lists[k].is_empty()));
+        assert_eq!(allocator.alloc(4), Some(0));
+        assert_eq!(allocator.alloc(0), None);
+    }
+
+    #[test]
+    fn allocates_aligned_contiguous_blocks() {
+        let mut allocator = BuddyAllocator::new();
+        allocator.add_range(3, 40);
+        allocator.add_range(64, 72);
+        assert_eq!(allocator.total_frames(), 45);
+
+        let mut blocks: Vec<usize> = core::iter::from_fn(|| allocator.alloc(3)).collect();
+        blocks.sort();
+        assert_eq!(blocks, [8, 16, 24, 32, 64]);
+        // Only the unaligned frames at the start are left, which form no block of 8
+        assert_eq!(allocator.free_frames(), 5);
+        assert_eq!(allocator.alloc(2), Some(4));
+        assert_eq!(allocator.alloc(5), None);
+
+        for block in blocks {
+            allocator.dealloc(block, 3);
+        }
+        allocator.dealloc(4, 2);
+        assert_eq!(allocator.free_frames(), 45);
+        // 16..32 merges back, while 8..16 has no free buddy
+        assert!(allocator.free_lists[4].contains(&16));
+        assert!(allocator.free_lists[3].contains(&8));
+    }
+
+    #[test]
+    #[should_panic(expected = "has not been allocated")]
+    fn rejects_double_free() {
+        let mut allocator = BuddyAllocator::new();
+        allocator.add_range(0, 8);
+        let frame = allocator.alloc(1).unwrap();
+        allocator.dealloc(frame, 1);
+        allocator.dealloc(frame, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "has not been allocated")]
+    fn rejects_freeing_part_of_a_free_block() {
+        let mut allocator = BuddyAllocator::new();
+        allocator.add_range(0, 8);
+        allocator.alloc(0).unwrap();
+        // 4..8 is a free block of order 2
+        allocator.dealloc(6, 1);
+    }
+}
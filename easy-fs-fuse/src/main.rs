@@ -0,0 +1,271 @@
+//! Build and inspect easy-fs images on the host, e.g.
+//!
+//! ```text
+//! easy-fs-fuse create -o fs.img --apps ../user/target/riscv64gc-unknown-none-elf/release/ --data ../user/data/
+//! easy-fs-fuse ls fs.img
+//! easy-fs-fuse extract fs.img 00hello_world -o hello.elf
+//! easy-fs-fuse verify fs.img
+//! ```
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{Arc, Mutex},
+};
+
+use clap::{Parser, Subcommand};
+use easy_fs::{
+    block_cache_stats, block_cache_sync_all, BlockDevice, BlockError, DiskInodeType,
+    EasyFileSystem, Inode, BLOCK_SZ,
+};
+
+const INODE_BITMAP_BLOCKS: u32 = 1;
+
+struct BlockFile(Mutex<File>);
+
+impl BlockDevice for BlockFile {
+    fn read_block(
+        &self,
+        block_id: usize,
+        buf: &mut [u8],
+    ) -> std::result::Result<(), BlockError> {
+        let mut file = self.0.lock().unwrap();
+        file.seek(SeekFrom::Start((block_id * BLOCK_SZ) as u64))
+            .and_then(|_| file.read_exact(buf))
+            .map_err(|_| BlockError)
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) -> std::result::Result<(), BlockError> {
+        let mut file = self.0.lock().unwrap();
+        file.seek(SeekFrom::Start((block_id * BLOCK_SZ) as u64))
+            .and_then(|_| file.write_all(buf))
+            .map_err(|_| BlockError)
+    }
+
+    fn num_blocks(&self) -> usize {
+        let file = self.0.lock().unwrap();
+        file.metadata().unwrap().len() as usize / BLOCK_SZ
+    }
+}
+
+#[derive(Parser)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create an image
+    Create {
+        /// The image to create
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Size of the image in MiB
+        #[arg(long, default_value_t = 16)]
+        size: u32,
+        /// Put the ELF files of this directory into `/`. Other files and subdirectories are
+        /// skipped, so this can be the cargo target directory of the user programs.
+        #[arg(short, long)]
+        apps: Option<PathBuf>,
+        /// Copy this directory tree into `/` as is, e.g. test fixtures
+        #[arg(short, long)]
+        data: Vec<PathBuf>,
+    },
+    /// List a directory of an image recursively
+    Ls {
+        image: PathBuf,
+        #[arg(default_value = "/")]
+        path: String,
+    },
+    /// Copy a file or a directory tree out of an image
+    Extract {
+        image: PathBuf,
+        path: String,
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Check the consistency of an image
+    Verify { image: PathBuf },
+}
+
+type Result<T> = std::result::Result<T, String>;
+
+/// Open an existing image read-only
+fn open_image(image: &Path) -> Result<Arc<dyn BlockDevice>> {
+    let file = File::open(image).map_err(|e| format!("Cannot open {}: {e}", image.display()))?;
+    Ok(Arc::new(BlockFile(Mutex::new(file))))
+}
+
+fn not_easy_fs(image: &Path) -> String {
+    format!("{} is not an easy-fs image", image.display())
+}
+
+fn find(image: &Path, path: &str) -> Result<Arc<Inode>> {
+    let efs = EasyFileSystem::open(open_image(image)?).ok_or_else(|| not_easy_fs(image))?;
+    Arc::new(EasyFileSystem::root_inode(&efs))
+        .find_path(path)
+        .ok_or_else(|| format!("{path} does not exist"))
+}
+
+fn is_elf(path: &Path) -> bool {
+    let mut magic = [0u8; 4];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok_and(|_| &magic == b"\x7fELF")
+}
+
+fn write_file(dir: &Inode, name: &str, path: &Path) -> Result<()> {
+    let data = fs::read(path).map_err(|e| format!("Cannot read {}: {e}", path.display()))?;
+    let inode = dir
+        .create(name, DiskInodeType::File)
+        .ok_or_else(|| format!("Cannot create {name}, it exists or the name is invalid"))?;
+    if inode.write_at(0, &data) != data.len() {
+        return Err(format!("The image is full when writing {}", path.display()));
+    }
+    println!("{}: {} bytes", path.display(), data.len());
+    Ok(())
+}
+
+/// Copy the host directory `src` into `dir`, merging with existing directories
+fn copy_tree(dir: &Inode, src: &Path) -> Result<()> {
+    let mut entries: Vec<_> = fs::read_dir(src)
+        .map_err(|e| format!("Cannot read {}: {e}", src.display()))?
+        .map(|entry| entry.unwrap())
+        .collect();
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let name = entry.file_name().into_string().unwrap();
+        let path = entry.path();
+        if path.is_dir() {
+            let sub_dir = match dir.find(&name) {
+                Some(inode) if inode.is_dir() => inode,
+                Some(_) => return Err(format!("{name} exists and is not a directory")),
+                None => dir
+                    .create(&name, DiskInodeType::Directory)
+                    .ok_or_else(|| format!("Cannot create directory {name}"))?,
+            };
+            copy_tree(&sub_dir, &path)?;
+        } else {
+            write_file(dir, &name, &path)?;
+        }
+    }
+    Ok(())
+}
+
+fn create(output: &Path, size: u32, apps: Option<PathBuf>, data: Vec<PathBuf>) -> Result<()> {
+    let total_blocks = size * (1 << 20) / BLOCK_SZ as u32;
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(output)
+        .map_err(|e| format!("Cannot create {}: {e}", output.display()))?;
+    file.set_len(total_blocks as u64 * BLOCK_SZ as u64)
+        .map_err(|e| e.to_string())?;
+    let efs = EasyFileSystem::create(
+        Arc::new(BlockFile(Mutex::new(file))),
+        total_blocks,
+        INODE_BITMAP_BLOCKS,
+    );
+    let root_inode = EasyFileSystem::root_inode(&efs);
+
+    if let Some(apps) = apps {
+        let mut elfs: Vec<PathBuf> = fs::read_dir(&apps)
+            .map_err(|e| format!("Cannot read {}: {e}", apps.display()))?
+            .map(|entry| entry.unwrap().path())
+            // The cargo target directory has copies with extensions
+            .filter(|path| path.is_file() && path.extension().is_none() && is_elf(path))
+            .collect();
+        elfs.sort();
+        for path in elfs {
+            let name = path.file_name().unwrap().to_str().unwrap().to_string();
+            write_file(&root_inode, &name, &path)?;
+        }
+    }
+    for dir in data {
+        copy_tree(&root_inode, &dir)?;
+    }
+    block_cache_sync_all().map_err(|_| format!("Cannot write {}", output.display()))?;
+    let stats = block_cache_stats();
+    if stats.errors > 0 {
+        return Err(format!("{} blocks of {} failed", stats.errors, output.display()));
+    }
+    println!(
+        "Block cache: {} hits, {} misses, {} blocks written",
+        stats.hits, stats.misses, stats.writebacks
+    );
+    Ok(())
+}
+
+fn ls(inode: &Inode, path: &str) {
+    for name in inode.ls() {
+        let child = inode.find(&name).unwrap();
+        let child_path = format!("{}/{}", path.trim_end_matches('/'), name);
+        if child.is_dir() {
+            println!("{:>10}  {}/", "-", child_path);
+            ls(&child, &child_path);
+        } else {
+            println!("{:>10}  {}", child.size(), child_path);
+        }
+    }
+}
+
+fn extract(inode: &Inode, output: &Path) -> Result<()> {
+    if inode.is_dir() {
+        fs::create_dir_all(output).map_err(|e| e.to_string())?;
+        for name in inode.ls() {
+            extract(&inode.find(&name).unwrap(), &output.join(name))?;
+        }
+    } else {
+        let mut data = vec![0u8; inode.size()];
+        inode.read_at(0, &mut data);
+        fs::write(output, data).map_err(|e| format!("Cannot write {}: {e}", output.display()))?;
+    }
+    Ok(())
+}
+
+fn run(args: Args) -> Result<()> {
+    match args.command {
+        Command::Create {
+            output,
+            size,
+            apps,
+            data,
+        } => create(&output, size, apps, data),
+        Command::Ls { image, path } => {
+            ls(&*find(&image, &path)?, &path);
+            Ok(())
+        }
+        Command::Extract {
+            image,
+            path,
+            output,
+        } => extract(&*find(&image, &path)?, &output),
+        Command::Verify { image } => {
+            let efs = EasyFileSystem::open(open_image(&image)?).ok_or_else(|| not_easy_fs(&image))?;
+            let problems = efs.lock().check();
+            for problem in problems.iter() {
+                println!("{problem}");
+            }
+            match problems.len() {
+                0 => Ok(()),
+                n => Err(format!("{n} problems found")),
+            }
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    match run(Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
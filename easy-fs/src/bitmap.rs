@@ -0,0 +1,84 @@
+use alloc::sync::Arc;
+
+use crate::{get_block_cache, BlockDevice, BLOCK_SZ};
+
+/// Bits in one block, as 64 `u64`s
+type BitmapBlock = [u64; 64];
+
+const BLOCK_BITS: usize = BLOCK_SZ * 8;
+
+/// Allocation state of `len` resources, stored in `blocks` blocks starting at `start_block_id`
+pub struct Bitmap {
+    start_block_id: usize,
+    blocks: usize,
+    len: usize,
+}
+
+/// Return (block position, bits64 position, inner position)
+fn decomposition(mut bit: usize) -> (usize, usize, usize) {
+    let block_pos = bit / BLOCK_BITS;
+    bit %= BLOCK_BITS;
+    (block_pos, bit / 64, bit % 64)
+}
+
+impl Bitmap {
+    pub fn new(start_block_id: usize, blocks: usize, len: usize) -> Self {
+        assert!(len <= blocks * BLOCK_BITS);
+        Self {
+            start_block_id,
+            blocks,
+            len,
+        }
+    }
+
+    /// Find the lowest clear bit and set it
+    pub fn alloc(&self, block_device: &Arc<dyn BlockDevice>) -> Option<usize> {
+        for block_id in 0..self.blocks {
+            let pos = get_block_cache(block_id + self.start_block_id, Arc::clone(block_device))
+                .lock()
+                .modify(0, |bitmap_block: &mut BitmapBlock| {
+                    let (bits64_pos, bits64) = bitmap_block
+                        .iter_mut()
+                        .enumerate()
+                        .find(|(_, bits64)| **bits64 != u64::MAX)?;
+                    let inner_pos = bits64.trailing_ones() as usize;
+                    let pos = block_id * BLOCK_BITS + bits64_pos * 64 + inner_pos;
+                    // Bits beyond `len` are never set, the lowest one being clear means all
+                    // valid bits are set
+                    if pos >= self.len {
+                        return None;
+                    }
+                    *bits64 |= 1u64 << inner_pos;
+                    Some(pos)
+                });
+            if pos.is_some() || (block_id + 1) * BLOCK_BITS >= self.len {
+                return pos;
+            }
+        }
+        None
+    }
+
+    pub fn dealloc(&self, block_device: &Arc<dyn BlockDevice>, bit: usize) {
+        let (block_pos, bits64_pos, inner_pos) = decomposition(bit);
+        get_block_cache(block_pos + self.start_block_id, Arc::clone(block_device))
+            .lock()
+            .modify(0, |bitmap_block: &mut BitmapBlock| {
+                assert!(bitmap_block[bits64_pos] & (1u64 << inner_pos) != 0);
+                bitmap_block[bits64_pos] &= !(1u64 << inner_pos);
+            });
+    }
+
+    pub fn is_allocated(&self, block_device: &Arc<dyn BlockDevice>, bit: usize) -> bool {
+        let (block_pos, bits64_pos, inner_pos) = decomposition(bit);
+        get_block_cache(block_pos + self.start_block_id, Arc::clone(block_device))
+            .lock()
+            .read(0, |bitmap_block: &BitmapBlock| {
+                bitmap_block[bits64_pos] & (1u64 << inner_pos) != 0
+            })
+    }
+
+    /// How many resources are managed
+    pub fn capacity(&self) -> usize {
+        self.len
+    }
+}
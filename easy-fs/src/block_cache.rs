@@ -0,0 +1,421 @@
+//! A fixed number of blocks cached in memory for all devices. Modified blocks are written back
+//! when they are evicted or by [`block_cache_sync_all`], so the filesystems must sync before the
+//! machine stops.
+//!
+//! A block which fails to be read is marked failed rather than handed out as valid data, see
+//! [`BlockCache::is_failed`]. Failures of the devices are also counted by
+//! [`block_device_errors`], for filesystems which cannot check each block.
+
+use alloc::{collections::VecDeque, sync::Arc};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+use crate::{BlockDevice, BlockError, BLOCK_SZ};
+
+/// A block in memory, written back when it is dropped if modified
+pub struct BlockCache {
+    cache: [u8; BLOCK_SZ],
+    block_id: usize,
+    block_device: Arc<dyn BlockDevice>,
+    modified: bool,
+    /// The block could not be read, so `cache` is not its content
+    failed: bool,
+}
+
+impl BlockCache {
+    pub fn new(block_id: usize, block_device: Arc<dyn BlockDevice>) -> Self {
+        let mut cache = [0u8; BLOCK_SZ];
+        let failed = block_device.read_block(block_id, &mut cache).is_err();
+        if failed {
+            ERRORS.fetch_add(1, Ordering::Relaxed);
+        }
+        Self {
+            cache,
+            block_id,
+            block_device,
+            modified: false,
+            failed,
+        }
+    }
+
+    /// Whether the block could not be read. Its content is meaningless then, and it is never
+    /// written back.
+    pub fn is_failed(&self) -> bool {
+        self.failed
+    }
+
+    fn addr_of_offset(&self, offset: usize) -> usize {
+        &self.cache[offset] as *const _ as usize
+    }
+
+    pub fn get_ref<T: Sized>(&self, offset: usize) -> &T {
+        let type_size = core::mem::size_of::<T>();
+        assert!(offset + type_size <= BLOCK_SZ);
+        let addr = self.addr_of_offset(offset);
+        unsafe { &*(addr as *const T) }
+    }
+
+    pub fn get_mut<T: Sized>(&mut self, offset: usize) -> &mut T {
+        let type_size = core::mem::size_of::<T>();
+        assert!(offset + type_size <= BLOCK_SZ);
+        self.modified = true;
+        let addr = self.addr_of_offset(offset);
+        unsafe { &mut *(addr as *mut T) }
+    }
+
+    /// Access the `T` at `offset` of the block
+    pub fn read<T, V>(&self, offset: usize, f: impl FnOnce(&T) -> V) -> V {
+        f(self.get_ref(offset))
+    }
+
+    /// Modify the `T` at `offset` of the block, which marks the block dirty
+    pub fn modify<T, V>(&mut self, offset: usize, f: impl FnOnce(&mut T) -> V) -> V {
+        f(self.get_mut(offset))
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    /// Write the block back if modified. It stays modified if the device fails.
+    pub fn sync(&mut self) -> Result<(), BlockError> {
+        if !self.modified || self.failed {
+            return Ok(());
+        }
+        if let Err(err) = self.block_device.write_block(self.block_id, &self.cache) {
+            ERRORS.fetch_add(1, Ordering::Relaxed);
+            return Err(err);
+        }
+        self.modified = false;
+        WRITEBACKS.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+impl Drop for BlockCache {
+    /// A failed write back is only counted by [`block_device_errors`], as nobody is left to see it
+    fn drop(&mut self) {
+        let _ = self.sync();
+    }
+}
+
+const BLOCK_CACHE_SIZE: usize = 64;
+
+static WRITEBACKS: AtomicUsize = AtomicUsize::new(0);
+static ERRORS: AtomicUsize = AtomicUsize::new(0);
+
+/// Counters since the start, and the current state of the cache
+#[derive(Clone, Copy, Debug)]
+pub struct BlockCacheStats {
+    pub capacity: usize,
+    pub cached: usize,
+    /// Cached blocks which are modified and not written back yet
+    pub dirty: usize,
+    pub hits: usize,
+    pub misses: usize,
+    /// Blocks written to their devices, on eviction or sync
+    pub writebacks: usize,
+    /// Failed reads and writes of blocks
+    pub errors: usize,
+}
+
+/// Blocks are identified by the device address and the block id
+type BlockKey = (usize, usize);
+
+pub struct BlockCacheManager {
+    /// From the least recently used block to the most recently used one
+    queue: VecDeque<(BlockKey, Arc<Mutex<BlockCache>>)>,
+    hits: usize,
+    misses: usize,
+}
+
+impl BlockCacheManager {
+    pub fn new() -> Self {
+        Self {
+            queue: VecDeque::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// The least recently used block is replaced, skipping those still in use. If all of them are
+    /// in use, the cache grows beyond [`BLOCK_CACHE_SIZE`] and shrinks back on later misses.
+    /// A failed block is read again once it is not in use.
+    pub fn get_block_cache(
+        &mut self,
+        block_id: usize,
+        block_device: Arc<dyn BlockDevice>,
+    ) -> Arc<Mutex<BlockCache>> {
+        let key = (device_key(&block_device), block_id);
+        if let Some(idx) = self.queue.iter().position(|(k, _)| *k == key) {
+            let entry = self.queue.remove(idx).unwrap();
+            if Arc::strong_count(&entry.1) > 1 || !entry.1.lock().is_failed() {
+                self.hits += 1;
+                let cache = entry.1.clone();
+                self.queue.push_back(entry);
+                return cache;
+            }
+        }
+
+        self.misses += 1;
+        while self.queue.len() >= BLOCK_CACHE_SIZE {
+            let Some(idx) = self
+                .queue
+                .iter()
+                .position(|(_, cache)| Arc::strong_count(cache) == 1)
+            else {
+                break;
+            };
+            // Written back when dropped
+            self.queue.remove(idx);
+        }
+        let block_cache = Arc::new(Mutex::new(BlockCache::new(block_id, block_device)));
+        self.queue.push_back((key, block_cache.clone()));
+        block_cache
+    }
+
+    /// Write all modified blocks back to their devices. Fail if any of them cannot be written.
+    pub fn sync_all(&self) -> Result<(), BlockError> {
+        let mut result = Ok(());
+        for (_, cache) in self.queue.iter() {
+            result = result.and(cache.lock().sync());
+        }
+        result
+    }
+}
+
+impl Default for BlockCacheManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn device_key(block_device: &Arc<dyn BlockDevice>) -> usize {
+    Arc::as_ptr(block_device) as *const () as usize
+}
+
+lazy_static! {
+    pub static ref BLOCK_CACHE_MANAGER: Mutex<BlockCacheManager> =
+        Mutex::new(BlockCacheManager::new());
+}
+
+pub fn get_block_cache(
+    block_id: usize,
+    block_device: Arc<dyn BlockDevice>,
+) -> Arc<Mutex<BlockCache>> {
+    BLOCK_CACHE_MANAGER
+        .lock()
+        .get_block_cache(block_id, block_device)
+}
+
+/// Write all modified blocks back to their devices
+pub fn block_cache_sync_all() -> Result<(), BlockError> {
+    BLOCK_CACHE_MANAGER.lock().sync_all()
+}
+
+/// Reads and writes of blocks which failed so far. An operation of a filesystem which cannot check
+/// each block it uses fails if this changes during it.
+pub fn block_device_errors() -> usize {
+    ERRORS.load(Ordering::Relaxed)
+}
+
+pub fn block_cache_stats() -> BlockCacheStats {
+    let manager = BLOCK_CACHE_MANAGER.lock();
+    BlockCacheStats {
+        capacity: BLOCK_CACHE_SIZE,
+        cached: manager.queue.len(),
+        dirty: manager
+            .queue
+            .iter()
+            .filter(|(_, cache)| cache.lock().is_modified())
+            .count(),
+        hits: manager.hits,
+        misses: manager.misses,
+        writebacks: WRITEBACKS.load(Ordering::Relaxed),
+        errors: ERRORS.load(Ordering::Relaxed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use core::sync::atomic::AtomicBool;
+
+    use super::*;
+    use crate::MemBlockDevice;
+
+    fn device() -> Arc<dyn BlockDevice> {
+        Arc::new(MemBlockDevice::new(BLOCK_CACHE_SIZE * 2))
+    }
+
+    /// Ids of the cached blocks, from the least recently used one
+    fn cached(manager: &BlockCacheManager) -> Vec<usize> {
+        manager.queue.iter().map(|((_, block_id), _)| *block_id).collect()
+    }
+
+    fn read_byte(device: &Arc<dyn BlockDevice>, block_id: usize) -> u8 {
+        let mut buf = [0u8; BLOCK_SZ];
+        device.read_block(block_id, &mut buf).unwrap();
+        buf[0]
+    }
+
+    /// A device in memory which fails all requests while `failing` is set
+    struct FlakyDevice {
+        inner: MemBlockDevice,
+        failing: AtomicBool,
+    }
+
+    impl BlockDevice for FlakyDevice {
+        fn read_block(&self, block_id: usize, buf: &mut [u8]) -> Result<(), BlockError> {
+            match self.failing.load(Ordering::Relaxed) {
+                true => Err(BlockError),
+                false => self.inner.read_block(block_id, buf),
+            }
+        }
+
+        fn write_block(&self, block_id: usize, buf: &[u8]) -> Result<(), BlockError> {
+            match self.failing.load(Ordering::Relaxed) {
+                true => Err(BlockError),
+                false => self.inner.write_block(block_id, buf),
+            }
+        }
+
+        fn num_blocks(&self) -> usize {
+            self.inner.num_blocks()
+        }
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let device = device();
+        let mut manager = BlockCacheManager::new();
+        let mut in_use = None;
+        for block_id in 0..BLOCK_CACHE_SIZE {
+            let cache = manager.get_block_cache(block_id, device.clone());
+            if block_id == 2 {
+                in_use = Some(cache);
+            }
+        }
+        manager.get_block_cache(0, device.clone());
+        manager.get_block_cache(BLOCK_CACHE_SIZE, device.clone());
+        let blocks = cached(&manager);
+        assert_eq!(blocks.len(), BLOCK_CACHE_SIZE);
+        assert_eq!(blocks[0], 2);
+        assert_eq!(blocks[BLOCK_CACHE_SIZE - 2..], [0, BLOCK_CACHE_SIZE]);
+
+        // Block 2 is the least recently used one now, but it is skipped while in use
+        manager.get_block_cache(BLOCK_CACHE_SIZE + 1, device.clone());
+        let blocks = cached(&manager);
+        assert_eq!(blocks[..2], [2, 4]);
+        drop(in_use);
+        manager.get_block_cache(BLOCK_CACHE_SIZE + 2, device.clone());
+        assert_eq!(cached(&manager)[0], 4);
+    }
+
+    #[test]
+    fn grows_when_all_blocks_in_use() {
+        let device = device();
+        let mut manager = BlockCacheManager::new();
+        let in_use: Vec<_> = (0..BLOCK_CACHE_SIZE)
+            .map(|block_id| manager.get_block_cache(block_id, device.clone()))
+            .collect();
+        manager.get_block_cache(BLOCK_CACHE_SIZE, device.clone());
+        assert_eq!(manager.queue.len(), BLOCK_CACHE_SIZE + 1);
+        drop(in_use);
+        manager.get_block_cache(BLOCK_CACHE_SIZE + 1, device.clone());
+        assert_eq!(manager.queue.len(), BLOCK_CACHE_SIZE);
+    }
+
+    #[test]
+    fn writes_back_dirty_block_on_eviction() {
+        let device = device();
+        let mut manager = BlockCacheManager::new();
+        manager
+            .get_block_cache(0, device.clone())
+            .lock()
+            .modify(0, |byte: &mut u8| *byte = 7);
+        assert_eq!(read_byte(&device, 0), 0);
+        for block_id in 1..=BLOCK_CACHE_SIZE {
+            manager.get_block_cache(block_id, device.clone());
+        }
+        assert!(!cached(&manager).contains(&0));
+        assert_eq!(read_byte(&device, 0), 7);
+    }
+
+    #[test]
+    fn sync_all_writes_back_and_keeps_blocks() {
+        let device = device();
+        let mut manager = BlockCacheManager::new();
+        for block_id in 0..3 {
+            manager
+                .get_block_cache(block_id, device.clone())
+                .lock()
+                .modify(0, |byte: &mut u8| *byte = block_id as u8 + 1);
+        }
+        assert_eq!(manager.sync_all(), Ok(()));
+        for block_id in 0..3 {
+            assert_eq!(read_byte(&device, block_id), block_id as u8 + 1);
+            assert!(!manager.get_block_cache(block_id, device.clone()).lock().is_modified());
+        }
+        assert_eq!(cached(&manager), [0, 1, 2]);
+    }
+
+    #[test]
+    fn counts_hits_and_misses() {
+        let (first, second) = (device(), device());
+        let mut manager = BlockCacheManager::new();
+        manager.get_block_cache(0, first.clone());
+        manager.get_block_cache(1, first.clone());
+        manager.get_block_cache(0, first.clone());
+        // The same block id of another device is another block
+        manager.get_block_cache(0, second);
+        assert_eq!((manager.hits, manager.misses), (1, 3));
+    }
+
+    #[test]
+    fn failed_read_is_marked_and_retried() {
+        let flaky = Arc::new(FlakyDevice {
+            inner: MemBlockDevice::new(4),
+            failing: AtomicBool::new(true),
+        });
+        let device: Arc<dyn BlockDevice> = flaky.clone();
+        flaky.inner.write_block(0, &[7; BLOCK_SZ]).unwrap();
+        let mut manager = BlockCacheManager::new();
+        let errors = block_device_errors();
+
+        let cache = manager.get_block_cache(0, device.clone());
+        assert!(cache.lock().is_failed());
+        assert!(block_device_errors() > errors);
+        // Modifications of a failed block never reach the device
+        cache.lock().modify(0, |byte: &mut u8| *byte = 9);
+        drop(cache);
+        flaky.failing.store(false, Ordering::Relaxed);
+        assert_eq!(manager.sync_all(), Ok(()));
+        assert_eq!(read_byte(&device, 0), 7);
+
+        let cache = manager.get_block_cache(0, device.clone());
+        assert!(!cache.lock().is_failed());
+        assert_eq!(cache.lock().read(0, |byte: &u8| *byte), 7);
+    }
+
+    #[test]
+    fn failed_write_back_stays_modified() {
+        let flaky = Arc::new(FlakyDevice {
+            inner: MemBlockDevice::new(4),
+            failing: AtomicBool::new(false),
+        });
+        let device: Arc<dyn BlockDevice> = flaky.clone();
+        let mut manager = BlockCacheManager::new();
+        let cache = manager.get_block_cache(0, device.clone());
+        cache.lock().modify(0, |byte: &mut u8| *byte = 9);
+
+        flaky.failing.store(true, Ordering::Relaxed);
+        assert_eq!(manager.sync_all(), Err(BlockError));
+        assert!(cache.lock().is_modified());
+        flaky.failing.store(false, Ordering::Relaxed);
+        assert_eq!(manager.sync_all(), Ok(()));
+        assert_eq!(read_byte(&device, 0), 9);
+    }
+}
@@ -0,0 +1,45 @@
+use alloc::{vec, vec::Vec};
+use core::any::Any;
+use spin::Mutex;
+
+use crate::BLOCK_SZ;
+
+/// The device failed to read or write a block
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockError;
+
+/// A device made of `BLOCK_SZ` bytes blocks. Buffers must be exactly one block long.
+pub trait BlockDevice: Send + Sync + Any {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) -> Result<(), BlockError>;
+    fn write_block(&self, block_id: usize, buf: &[u8]) -> Result<(), BlockError>;
+    fn num_blocks(&self) -> usize;
+}
+
+/// A device in memory, e.g. to try a filesystem without a disk
+pub struct MemBlockDevice(Mutex<Vec<u8>>);
+
+impl MemBlockDevice {
+    /// A device of `num_blocks` zeroed blocks
+    pub fn new(num_blocks: usize) -> Self {
+        Self(Mutex::new(vec![0; num_blocks * BLOCK_SZ]))
+    }
+}
+
+impl BlockDevice for MemBlockDevice {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) -> Result<(), BlockError> {
+        let start = block_id * BLOCK_SZ;
+        buf.copy_from_slice(self.0.lock().get(start..start + BLOCK_SZ).ok_or(BlockError)?);
+        Ok(())
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) -> Result<(), BlockError> {
+        let start = block_id * BLOCK_SZ;
+        let mut data = self.0.lock();
+        data.get_mut(start..start + BLOCK_SZ).ok_or(BlockError)?.copy_from_slice(buf);
+        Ok(())
+    }
+
+    fn num_blocks(&self) -> usize {
+        self.0.lock().len() / BLOCK_SZ
+    }
+}
@@ -0,0 +1,108 @@
+use alloc::{collections::BTreeSet, format, string::String, vec, vec::Vec};
+
+use crate::{
+    get_block_cache,
+    layout::{DirEntry, DiskInode, DIRENT_SZ, MAX_FILE_SIZE},
+    EasyFileSystem,
+};
+
+impl EasyFileSystem {
+    fn is_data_block(&self, block_id: u32) -> bool {
+        block_id >= self.data_area_start_block
+            && ((block_id - self.data_area_start_block) as usize) < self.data_bitmap.capacity()
+    }
+
+    /// Walk the directory tree from the root and compare what is reachable with the bitmaps.
+    /// Return a description of each problem found, which is empty for a consistent filesystem.
+    pub fn check(&self) -> Vec<String> {
+        let block_device = &self.block_device;
+        let mut problems = Vec::new();
+        let mut used_inodes = BTreeSet::new();
+        let mut used_blocks = BTreeSet::new();
+        let mut stack = vec![(String::new(), 0u32)];
+
+        while let Some((path, inode_id)) = stack.pop() {
+            let path = if path.is_empty() { "/" } else { &path };
+            if inode_id as usize >= self.inode_bitmap.capacity() {
+                problems.push(format!("{path}: inode {inode_id} is out of range"));
+                continue;
+            }
+            if !used_inodes.insert(inode_id) {
+                problems.push(format!("{path}: inode {inode_id} is linked more than once"));
+                continue;
+            }
+            if !self.inode_bitmap.is_allocated(block_device, inode_id as usize) {
+                problems.push(format!("{path}: inode {inode_id} is not marked as allocated"));
+            }
+
+            let (block_id, block_offset) = self.get_disk_inode_pos(inode_id);
+            let block_cache = get_block_cache(block_id as usize, block_device.clone());
+            let block_cache = block_cache.lock();
+            let disk_inode: &DiskInode = block_cache.get_ref(block_offset);
+            if !disk_inode.has_valid_type() {
+                problems.push(format!("{path}: inode {inode_id} has an unknown type"));
+                continue;
+            }
+            if disk_inode.size as usize > MAX_FILE_SIZE {
+                problems.push(format!("{path}: size {} is too large", disk_inode.size));
+                continue;
+            }
+            let block_ids = match disk_inode.block_ids(block_device, |id| self.is_data_block(id)) {
+                Ok(block_ids) => block_ids,
+                Err(id) => {
+                    problems.push(format!("{path}: index block {id} is outside the data area"));
+                    continue;
+                }
+            };
+
+            let mut blocks_valid = true;
+            for id in block_ids {
+                if !self.is_data_block(id) {
+                    problems.push(format!("{path}: block {id} is outside the data area"));
+                    blocks_valid = false;
+                    continue;
+                }
+                if !used_blocks.insert(id) {
+                    problems.push(format!("{path}: block {id} is used more than once"));
+                }
+                let bit = (id - self.data_area_start_block) as usize;
+                if !self.data_bitmap.is_allocated(block_device, bit) {
+                    problems.push(format!("{path}: block {id} is not marked as allocated"));
+                }
+            }
+
+            if !disk_inode.is_dir() || !blocks_valid {
+                continue;
+            }
+            if !(disk_inode.size as usize).is_multiple_of(DIRENT_SZ) {
+                problems.push(format!("{path}: directory size is not a multiple of entries"));
+            }
+            for i in 0..disk_inode.size as usize / DIRENT_SZ {
+                let mut dirent = DirEntry::empty();
+                disk_inode.read_at(i * DIRENT_SZ, dirent.as_bytes_mut(), block_device);
+                if !DirEntry::is_valid_name(dirent.name()) {
+                    problems.push(format!("{path}: entry {i} has an invalid name"));
+                    continue;
+                }
+                let child = format!("{}/{}", path.trim_end_matches('/'), dirent.name());
+                stack.push((child, dirent.inode_number()));
+            }
+        }
+
+        for inode_id in 0..self.inode_bitmap.capacity() {
+            if self.inode_bitmap.is_allocated(block_device, inode_id)
+                && !used_inodes.contains(&(inode_id as u32))
+            {
+                problems.push(format!("inode {inode_id} is allocated but unreachable"));
+            }
+        }
+        let leaked_blocks = (0..self.data_bitmap.capacity())
+            .filter(|&bit| self.data_bitmap.is_allocated(block_device, bit))
+            .filter(|&bit| !used_blocks.contains(&(bit as u32 + self.data_area_start_block)))
+            .count();
+        if leaked_blocks > 0 {
+            problems.push(format!("{leaked_blocks} blocks are allocated but unused"));
+        }
+        problems
+    }
+}
@@ -0,0 +1,170 @@
+use alloc::sync::Arc;
+use spin::Mutex;
+
+use crate::{
+    bitmap::Bitmap,
+    block_cache::{block_cache_sync_all, get_block_cache},
+    layout::{DiskInode, DiskInodeType, SuperBlock},
+    vfs::Inode,
+    BlockDevice, BLOCK_SZ,
+};
+
+/// Inodes in one block
+const INODES_PER_BLOCK: u32 = (BLOCK_SZ / core::mem::size_of::<DiskInode>()) as u32;
+
+pub struct EasyFileSystem {
+    pub block_device: Arc<dyn BlockDevice>,
+    pub inode_bitmap: Bitmap,
+    pub data_bitmap: Bitmap,
+    inode_area_start_block: u32,
+    pub(crate) data_area_start_block: u32,
+}
+
+type DataBlock = [u8; BLOCK_SZ];
+
+impl EasyFileSystem {
+    /// Create a filesystem on the first `total_blocks` blocks of `block_device` with an empty root
+    /// directory
+    pub fn create(
+        block_device: Arc<dyn BlockDevice>,
+        total_blocks: u32,
+        inode_bitmap_blocks: u32,
+    ) -> Arc<Mutex<Self>> {
+        let inode_bitmap = Bitmap::new(
+            1,
+            inode_bitmap_blocks as usize,
+            inode_bitmap_blocks as usize * BLOCK_SZ * 8,
+        );
+        let inode_num = inode_bitmap.capacity();
+        let inode_area_blocks = (inode_num as u32).div_ceil(INODES_PER_BLOCK);
+        let inode_total_blocks = inode_bitmap_blocks + inode_area_blocks;
+        let data_total_blocks = total_blocks - 1 - inode_total_blocks;
+        // Each bitmap block manages 4096 data blocks
+        let data_bitmap_blocks = data_total_blocks.div_ceil(4097);
+        let data_area_blocks = data_total_blocks - data_bitmap_blocks;
+        let data_bitmap = Bitmap::new(
+            (1 + inode_total_blocks) as usize,
+            data_bitmap_blocks as usize,
+            data_area_blocks as usize,
+        );
+        let mut efs = Self {
+            block_device: Arc::clone(&block_device),
+            inode_bitmap,
+            data_bitmap,
+            inode_area_start_block: 1 + inode_bitmap_blocks,
+            data_area_start_block: 1 + inode_total_blocks + data_bitmap_blocks,
+        };
+
+        // clear all blocks
+        for i in 0..total_blocks {
+            get_block_cache(i as usize, Arc::clone(&block_device))
+                .lock()
+                .modify(0, |data_block: &mut DataBlock| data_block.fill(0));
+        }
+        get_block_cache(0, Arc::clone(&block_device))
+            .lock()
+            .modify(0, |super_block: &mut SuperBlock| {
+                super_block.initialize(
+                    total_blocks,
+                    inode_bitmap_blocks,
+                    inode_area_blocks,
+                    data_bitmap_blocks,
+                    data_area_blocks,
+                );
+            });
+
+        // The root directory is inode 0
+        assert_eq!(efs.alloc_inode(), Some(0));
+        let (root_inode_block_id, root_inode_offset) = efs.get_disk_inode_pos(0);
+        get_block_cache(root_inode_block_id as usize, Arc::clone(&block_device))
+            .lock()
+            .modify(root_inode_offset, |disk_inode: &mut DiskInode| {
+                disk_inode.initialize(DiskInodeType::Directory);
+            });
+        // Failures of the device are left to the caller, see `block_device_errors`
+        let _ = block_cache_sync_all();
+        Arc::new(Mutex::new(efs))
+    }
+
+    /// Open the filesystem on `block_device`, or `None` if there is no valid super block
+    pub fn open(block_device: Arc<dyn BlockDevice>) -> Option<Arc<Mutex<Self>>> {
+        get_block_cache(0, Arc::clone(&block_device))
+            .lock()
+            .read(0, |super_block: &SuperBlock| {
+                if !super_block.is_valid(block_device.num_blocks()) {
+                    return None;
+                }
+                let inode_total_blocks =
+                    super_block.inode_bitmap_blocks + super_block.inode_area_blocks;
+                let inode_num = (super_block.inode_area_blocks * INODES_PER_BLOCK).min(
+                    super_block.inode_bitmap_blocks * BLOCK_SZ as u32 * 8,
+                );
+                let efs = Self {
+                    block_device: Arc::clone(&block_device),
+                    inode_bitmap: Bitmap::new(
+                        1,
+                        super_block.inode_bitmap_blocks as usize,
+                        inode_num as usize,
+                    ),
+                    data_bitmap: Bitmap::new(
+                        (1 + inode_total_blocks) as usize,
+                        super_block.data_bitmap_blocks as usize,
+                        (super_block.data_area_blocks as usize)
+                            .min(super_block.data_bitmap_blocks as usize * BLOCK_SZ * 8),
+                    ),
+                    inode_area_start_block: 1 + super_block.inode_bitmap_blocks,
+                    data_area_start_block: 1 + inode_total_blocks + super_block.data_bitmap_blocks,
+                };
+                Some(Arc::new(Mutex::new(efs)))
+            })
+    }
+
+    pub fn root_inode(efs: &Arc<Mutex<Self>>) -> Inode {
+        let block_device = Arc::clone(&efs.lock().block_device);
+        let (block_id, block_offset) = efs.lock().get_disk_inode_pos(0);
+        Inode::new(0, block_id, block_offset, Arc::clone(efs), block_device)
+    }
+
+    /// The block and the offset in it where inode `inode_id` is stored
+    pub fn get_disk_inode_pos(&self, inode_id: u32) -> (u32, usize) {
+        let inode_size = core::mem::size_of::<DiskInode>();
+        let block_id = self.inode_area_start_block + inode_id / INODES_PER_BLOCK;
+        (
+            block_id,
+            (inode_id % INODES_PER_BLOCK) as usize * inode_size,
+        )
+    }
+
+    pub fn get_data_block_id(&self, data_block_id: u32) -> u32 {
+        self.data_area_start_block + data_block_id
+    }
+
+    pub fn alloc_inode(&mut self) -> Option<u32> {
+        self.inode_bitmap
+            .alloc(&self.block_device)
+            .map(|id| id as u32)
+    }
+
+    pub fn dealloc_inode(&mut self, inode_id: u32) {
+        self.inode_bitmap
+            .dealloc(&self.block_device, inode_id as usize)
+    }
+
+    /// Return the block id, not the index in the data area
+    pub fn alloc_data(&mut self) -> Option<u32> {
+        self.data_bitmap
+            .alloc(&self.block_device)
+            .map(|id| id as u32 + self.data_area_start_block)
+    }
+
+    /// Zero the block and mark it free
+    pub fn dealloc_data(&mut self, block_id: u32) {
+        get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .modify(0, |data_block: &mut DataBlock| data_block.fill(0));
+        self.data_bitmap.dealloc(
+            &self.block_device,
+            (block_id - self.data_area_start_block) as usize,
+        )
+    }
+}
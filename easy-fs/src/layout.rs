@@ -0,0 +1,435 @@
+use alloc::{sync::Arc, vec::Vec};
+
+use crate::{get_block_cache, BlockDevice, BLOCK_SZ};
+
+/// Magic number of the super block
+const EFS_MAGIC: u32 = 0x3b800001;
+const INODE_DIRECT_COUNT: usize = 28;
+pub const NAME_LENGTH_LIMIT: usize = 27;
+/// Block ids in an index block
+const INODE_INDIRECT1_COUNT: usize = BLOCK_SZ / 4;
+const INODE_INDIRECT2_COUNT: usize = INODE_INDIRECT1_COUNT * INODE_INDIRECT1_COUNT;
+const DIRECT_BOUND: usize = INODE_DIRECT_COUNT;
+const INDIRECT1_BOUND: usize = DIRECT_BOUND + INODE_INDIRECT1_COUNT;
+const INDIRECT2_BOUND: usize = INDIRECT1_BOUND + INODE_INDIRECT2_COUNT;
+/// What the direct and indirect blocks of one inode can address
+pub const MAX_FILE_SIZE: usize = INDIRECT2_BOUND * BLOCK_SZ;
+
+#[repr(C)]
+pub struct SuperBlock {
+    magic: u32,
+    pub total_blocks: u32,
+    pub inode_bitmap_blocks: u32,
+    pub inode_area_blocks: u32,
+    pub data_bitmap_blocks: u32,
+    pub data_area_blocks: u32,
+}
+
+impl SuperBlock {
+    pub fn initialize(
+        &mut self,
+        total_blocks: u32,
+        inode_bitmap_blocks: u32,
+        inode_area_blocks: u32,
+        data_bitmap_blocks: u32,
+        data_area_blocks: u32,
+    ) {
+        *self = Self {
+            magic: EFS_MAGIC,
+            total_blocks,
+            inode_bitmap_blocks,
+            inode_area_blocks,
+            data_bitmap_blocks,
+            data_area_blocks,
+        }
+    }
+
+    /// Whether the areas fit in the disk, so that nothing read from this super block can point
+    /// outside of the disk
+    pub fn is_valid(&self, device_blocks: usize) -> bool {
+        let used = 1u64
+            + self.inode_bitmap_blocks as u64
+            + self.inode_area_blocks as u64
+            + self.data_bitmap_blocks as u64
+            + self.data_area_blocks as u64;
+        self.magic == EFS_MAGIC
+            && used <= self.total_blocks as u64
+            && self.total_blocks as usize <= device_blocks
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DiskInodeType {
+    File,
+    Directory,
+}
+
+impl DiskInodeType {
+    fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            0 => Some(Self::File),
+            1 => Some(Self::Directory),
+            _ => None,
+        }
+    }
+
+    fn to_raw(self) -> u32 {
+        match self {
+            Self::File => 0,
+            Self::Directory => 1,
+        }
+    }
+}
+
+/// An index block
+type IndirectBlock = [u32; BLOCK_SZ / 4];
+type DataBlock = [u8; BLOCK_SZ];
+
+/// 128 bytes, so that a block holds 4 inodes
+#[repr(C)]
+pub struct DiskInode {
+    pub size: u32,
+    pub direct: [u32; INODE_DIRECT_COUNT],
+    pub indirect1: u32,
+    pub indirect2: u32,
+    /// Stored as `u32` so that a corrupted disk cannot produce an invalid `DiskInodeType`
+    type_: u32,
+}
+
+impl DiskInode {
+    /// Indirect blocks are allocated later, when the inode grows
+    pub fn initialize(&mut self, type_: DiskInodeType) {
+        self.size = 0;
+        self.direct.fill(0);
+        self.indirect1 = 0;
+        self.indirect2 = 0;
+        self.type_ = type_.to_raw();
+    }
+
+    /// Unknown types are treated as files
+    pub fn type_(&self) -> DiskInodeType {
+        DiskInodeType::from_raw(self.type_).unwrap_or(DiskInodeType::File)
+    }
+
+    pub fn has_valid_type(&self) -> bool {
+        DiskInodeType::from_raw(self.type_).is_some()
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.type_() == DiskInodeType::Directory
+    }
+
+    /// Data blocks needed for `size` bytes
+    fn data_blocks_for(size: u32) -> u32 {
+        (size as usize).div_ceil(BLOCK_SZ) as u32
+    }
+
+    /// Data blocks and index blocks needed for `size` bytes
+    pub fn total_blocks(size: u32) -> u32 {
+        let data_blocks = Self::data_blocks_for(size) as usize;
+        let mut total = data_blocks;
+        if data_blocks > DIRECT_BOUND {
+            total += 1;
+        }
+        if data_blocks > INDIRECT1_BOUND {
+            // The second level index block and the first level ones under it
+            total += 1;
+            total += (data_blocks - INDIRECT1_BOUND).div_ceil(INODE_INDIRECT1_COUNT);
+        }
+        total as u32
+    }
+
+    /// How many blocks have to be allocated to grow to `new_size` bytes
+    pub fn blocks_num_needed(&self, new_size: u32) -> u32 {
+        assert!(new_size >= self.size);
+        Self::total_blocks(new_size) - Self::total_blocks(self.size)
+    }
+
+    /// The block holding the `inner_id`th data block
+    pub fn get_block_id(&self, inner_id: u32, block_device: &Arc<dyn BlockDevice>) -> u32 {
+        let inner_id = inner_id as usize;
+        if inner_id < DIRECT_BOUND {
+            self.direct[inner_id]
+        } else if inner_id < INDIRECT1_BOUND {
+            get_block_cache(self.indirect1 as usize, Arc::clone(block_device))
+                .lock()
+                .read(0, |indirect_block: &IndirectBlock| {
+                    indirect_block[inner_id - DIRECT_BOUND]
+                })
+        } else {
+            let last = inner_id - INDIRECT1_BOUND;
+            let indirect1 = get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
+                .lock()
+                .read(0, |indirect2: &IndirectBlock| {
+                    indirect2[last / INODE_INDIRECT1_COUNT]
+                });
+            get_block_cache(indirect1 as usize, Arc::clone(block_device))
+                .lock()
+                .read(0, |indirect1: &IndirectBlock| {
+                    indirect1[last % INODE_INDIRECT1_COUNT]
+                })
+        }
+    }
+
+    /// Grow to `new_size` bytes using `new_blocks`, which holds exactly
+    /// `blocks_num_needed(new_size)` zeroed blocks
+    pub fn increase_size(
+        &mut self,
+        new_size: u32,
+        new_blocks: Vec<u32>,
+        block_device: &Arc<dyn BlockDevice>,
+    ) {
+        let mut current_blocks = Self::data_blocks_for(self.size) as usize;
+        self.size = new_size;
+        let mut total_blocks = Self::data_blocks_for(self.size) as usize;
+        let mut new_blocks = new_blocks.into_iter();
+
+        // fill direct
+        while current_blocks < total_blocks.min(INODE_DIRECT_COUNT) {
+            self.direct[current_blocks] = new_blocks.next().unwrap();
+            current_blocks += 1;
+        }
+
+        // alloc indirect1
+        if total_blocks > INODE_DIRECT_COUNT {
+            if current_blocks == INODE_DIRECT_COUNT {
+                self.indirect1 = new_blocks.next().unwrap();
+            }
+            current_blocks -= INODE_DIRECT_COUNT;
+            total_blocks -= INODE_DIRECT_COUNT;
+        } else {
+            return;
+        }
+
+        // fill indirect1
+        get_block_cache(self.indirect1 as usize, Arc::clone(block_device))
+            .lock()
+            .modify(0, |indirect1: &mut IndirectBlock| {
+                while current_blocks < total_blocks.min(INODE_INDIRECT1_COUNT) {
+                    indirect1[current_blocks] = new_blocks.next().unwrap();
+                    current_blocks += 1;
+                }
+            });
+
+        // alloc indirect2
+        if total_blocks > INODE_INDIRECT1_COUNT {
+            if current_blocks == INODE_INDIRECT1_COUNT {
+                self.indirect2 = new_blocks.next().unwrap();
+            }
+            current_blocks -= INODE_INDIRECT1_COUNT;
+            total_blocks -= INODE_INDIRECT1_COUNT;
+        } else {
+            return;
+        }
+
+        // fill indirect2 from (a0, b0) -> (a1, b1)
+        let mut a0 = current_blocks / INODE_INDIRECT1_COUNT;
+        let mut b0 = current_blocks % INODE_INDIRECT1_COUNT;
+        let a1 = total_blocks / INODE_INDIRECT1_COUNT;
+        let b1 = total_blocks % INODE_INDIRECT1_COUNT;
+        get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
+            .lock()
+            .modify(0, |indirect2: &mut IndirectBlock| {
+                while (a0 < a1) || (a0 == a1 && b0 < b1) {
+                    if b0 == 0 {
+                        indirect2[a0] = new_blocks.next().unwrap();
+                    }
+                    get_block_cache(indirect2[a0] as usize, Arc::clone(block_device))
+                        .lock()
+                        .modify(0, |indirect1: &mut IndirectBlock| {
+                            indirect1[b0] = new_blocks.next().unwrap();
+                        });
+                    b0 += 1;
+                    if b0 == INODE_INDIRECT1_COUNT {
+                        b0 = 0;
+                        a0 += 1;
+                    }
+                }
+            });
+    }
+
+    /// All blocks used by this inode, including index blocks. Index blocks for which `is_valid`
+    /// returns false are not read, their id is returned as the error instead.
+    pub fn block_ids(
+        &self,
+        block_device: &Arc<dyn BlockDevice>,
+        is_valid: impl Fn(u32) -> bool,
+    ) -> Result<Vec<u32>, u32> {
+        let mut data_blocks = (Self::data_blocks_for(self.size) as usize).min(INDIRECT2_BOUND);
+        let mut v: Vec<u32> = Vec::new();
+        let read_index_block = |block_id: u32, count: usize, v: &mut Vec<u32>| {
+            if !is_valid(block_id) {
+                return Err(block_id);
+            }
+            v.push(block_id);
+            get_block_cache(block_id as usize, Arc::clone(block_device))
+                .lock()
+                .read(0, |indirect: &IndirectBlock| {
+                    v.extend_from_slice(&indirect[..count]);
+                });
+            Ok(())
+        };
+
+        // direct
+        v.extend_from_slice(&self.direct[..data_blocks.min(INODE_DIRECT_COUNT)]);
+        if data_blocks <= INODE_DIRECT_COUNT {
+            return Ok(v);
+        }
+        data_blocks -= INODE_DIRECT_COUNT;
+
+        // indirect1
+        read_index_block(
+            self.indirect1,
+            data_blocks.min(INODE_INDIRECT1_COUNT),
+            &mut v,
+        )?;
+        if data_blocks <= INODE_INDIRECT1_COUNT {
+            return Ok(v);
+        }
+        data_blocks -= INODE_INDIRECT1_COUNT;
+
+        // indirect2, whose entries are indirect1 blocks
+        let mut indirect1_blocks = Vec::new();
+        read_index_block(
+            self.indirect2,
+            data_blocks.div_ceil(INODE_INDIRECT1_COUNT),
+            &mut indirect1_blocks,
+        )?;
+        v.push(indirect1_blocks.remove(0));
+        for (i, block_id) in indirect1_blocks.into_iter().enumerate() {
+            let count = (data_blocks - i * INODE_INDIRECT1_COUNT).min(INODE_INDIRECT1_COUNT);
+            read_index_block(block_id, count, &mut v)?;
+        }
+        Ok(v)
+    }
+
+    /// Shrink to 0 bytes and return all blocks which were used, including index blocks
+    pub fn clear_size(&mut self, block_device: &Arc<dyn BlockDevice>) -> Vec<u32> {
+        let v = self.block_ids(block_device, |_| true).unwrap();
+        self.size = 0;
+        self.direct.fill(0);
+        self.indirect1 = 0;
+        self.indirect2 = 0;
+        v
+    }
+
+    /// Read from `offset` into `buf`, returning how many bytes are read
+    pub fn read_at(
+        &self,
+        offset: usize,
+        buf: &mut [u8],
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> usize {
+        let mut start = offset;
+        let end = (offset + buf.len()).min(self.size as usize);
+        if start >= end {
+            return 0;
+        }
+        let mut start_block = start / BLOCK_SZ;
+        let mut read_size = 0usize;
+        loop {
+            // calculate end of current block
+            let end_current_block = ((start / BLOCK_SZ + 1) * BLOCK_SZ).min(end);
+            let block_read_size = end_current_block - start;
+            let dst = &mut buf[read_size..read_size + block_read_size];
+            get_block_cache(
+                self.get_block_id(start_block as u32, block_device) as usize,
+                Arc::clone(block_device),
+            )
+            .lock()
+            .read(0, |data_block: &DataBlock| {
+                let src = &data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_read_size];
+                dst.copy_from_slice(src);
+            });
+            read_size += block_read_size;
+            if end_current_block == end {
+                break;
+            }
+            start_block += 1;
+            start = end_current_block;
+        }
+        read_size
+    }
+
+    /// Write `buf` at `offset`. The inode must be large enough already.
+    pub fn write_at(
+        &mut self,
+        offset: usize,
+        buf: &[u8],
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> usize {
+        let mut start = offset;
+        let end = (offset + buf.len()).min(self.size as usize);
+        assert!(start <= end);
+        let mut start_block = start / BLOCK_SZ;
+        let mut write_size = 0usize;
+        while start < end {
+            let end_current_block = ((start / BLOCK_SZ + 1) * BLOCK_SZ).min(end);
+            let block_write_size = end_current_block - start;
+            get_block_cache(
+                self.get_block_id(start_block as u32, block_device) as usize,
+                Arc::clone(block_device),
+            )
+            .lock()
+            .modify(0, |data_block: &mut DataBlock| {
+                let src = &buf[write_size..write_size + block_write_size];
+                let dst = &mut data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_write_size];
+                dst.copy_from_slice(src);
+            });
+            write_size += block_write_size;
+            start_block += 1;
+            start = end_current_block;
+        }
+        write_size
+    }
+}
+
+#[repr(C)]
+pub struct DirEntry {
+    name: [u8; NAME_LENGTH_LIMIT + 1],
+    inode_number: u32,
+}
+
+pub const DIRENT_SZ: usize = 32;
+
+impl DirEntry {
+    pub fn empty() -> Self {
+        Self {
+            name: [0u8; NAME_LENGTH_LIMIT + 1],
+            inode_number: 0,
+        }
+    }
+
+    /// `name` must be at most `NAME_LENGTH_LIMIT` bytes
+    pub fn new(name: &str, inode_number: u32) -> Self {
+        assert!(name.len() <= NAME_LENGTH_LIMIT);
+        let mut bytes = [0u8; NAME_LENGTH_LIMIT + 1];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Self {
+            name: bytes,
+            inode_number,
+        }
+    }
+
+    pub fn is_valid_name(name: &str) -> bool {
+        !name.is_empty() && name.len() <= NAME_LENGTH_LIMIT && !name.contains(['/', '\0'])
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self as *const _ as usize as *const u8, DIRENT_SZ) }
+    }
+
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self as *mut _ as usize as *mut u8, DIRENT_SZ) }
+    }
+
+    /// Names which are not valid UTF-8 read as empty
+    pub fn name(&self) -> &str {
+        let len = self.name.iter().position(|&b| b == 0).unwrap_or(self.name.len());
+        core::str::from_utf8(&self.name[..len]).unwrap_or("")
+    }
+
+    pub fn inode_number(&self) -> u32 {
+        self.inode_number
+    }
+}
@@ -0,0 +1,29 @@
+//! A simple block based filesystem. It only depends on `alloc`, so the same code is used by the
+//! kernel and by the host tool building disk images.
+//!
+//! The disk is laid out as
+//!
+//! | super block | inode bitmap | inode area | data bitmap | data area |
+
+#![no_std]
+
+extern crate alloc;
+
+mod bitmap;
+mod block_cache;
+mod block_dev;
+mod check;
+mod efs;
+mod layout;
+mod vfs;
+
+pub const BLOCK_SZ: usize = 512;
+
+pub use block_cache::{
+    block_cache_stats, block_cache_sync_all, block_device_errors, get_block_cache,
+    BlockCacheStats,
+};
+pub use block_dev::{BlockDevice, BlockError, MemBlockDevice};
+pub use efs::EasyFileSystem;
+pub use layout::{DiskInodeType, MAX_FILE_SIZE, NAME_LENGTH_LIMIT};
+pub use vfs::Inode;
@@ -0,0 +1,236 @@
+use alloc::{string::String, sync::Arc, vec::Vec};
+use spin::{Mutex, MutexGuard};
+
+use crate::{
+    block_cache::get_block_cache,
+    efs::EasyFileSystem,
+    layout::{DirEntry, DiskInode, DiskInodeType, DIRENT_SZ, MAX_FILE_SIZE},
+    BlockDevice,
+};
+
+/// An inode in memory, pointing to where its `DiskInode` is stored
+pub struct Inode {
+    inode_id: u32,
+    block_id: usize,
+    block_offset: usize,
+    fs: Arc<Mutex<EasyFileSystem>>,
+    block_device: Arc<dyn BlockDevice>,
+}
+
+impl Inode {
+    pub fn new(
+        inode_id: u32,
+        block_id: u32,
+        block_offset: usize,
+        fs: Arc<Mutex<EasyFileSystem>>,
+        block_device: Arc<dyn BlockDevice>,
+    ) -> Self {
+        Self {
+            inode_id,
+            block_id: block_id as usize,
+            block_offset,
+            fs,
+            block_device,
+        }
+    }
+
+    pub fn inode_id(&self) -> u32 {
+        self.inode_id
+    }
+
+    fn read_disk_inode<V>(&self, f: impl FnOnce(&DiskInode) -> V) -> V {
+        get_block_cache(self.block_id, Arc::clone(&self.block_device))
+            .lock()
+            .read(self.block_offset, f)
+    }
+
+    fn modify_disk_inode<V>(&self, f: impl FnOnce(&mut DiskInode) -> V) -> V {
+        get_block_cache(self.block_id, Arc::clone(&self.block_device))
+            .lock()
+            .modify(self.block_offset, f)
+    }
+
+    pub fn type_(&self) -> DiskInodeType {
+        self.read_disk_inode(|disk_inode| disk_inode.type_())
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.read_disk_inode(|disk_inode| disk_inode.is_dir())
+    }
+
+    pub fn size(&self) -> usize {
+        self.read_disk_inode(|disk_inode| disk_inode.size as usize)
+    }
+
+    fn inode_from_id(&self, fs: &MutexGuard<EasyFileSystem>, inode_id: u32) -> Arc<Inode> {
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        Arc::new(Self::new(
+            inode_id,
+            block_id,
+            block_offset,
+            self.fs.clone(),
+            self.block_device.clone(),
+        ))
+    }
+
+    /// All valid entries of a directory
+    fn dir_entries(&self, disk_inode: &DiskInode) -> Vec<DirEntry> {
+        let file_count = disk_inode.size as usize / DIRENT_SZ;
+        let mut entries = Vec::with_capacity(file_count);
+        for i in 0..file_count {
+            let mut dirent = DirEntry::empty();
+            assert_eq!(
+                disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device),
+                DIRENT_SZ,
+            );
+            entries.push(dirent);
+        }
+        entries
+    }
+
+    fn find_inode_id(&self, name: &str, disk_inode: &DiskInode) -> Option<u32> {
+        // assert it is a directory
+        assert!(disk_inode.is_dir());
+        self.dir_entries(disk_inode)
+            .iter()
+            .find(|dirent| dirent.name() == name)
+            .map(|dirent| dirent.inode_number())
+    }
+
+    /// Find `name` in this directory
+    pub fn find(&self, name: &str) -> Option<Arc<Inode>> {
+        let fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| {
+            if !disk_inode.is_dir() {
+                return None;
+            }
+            self.find_inode_id(name, disk_inode)
+                .map(|inode_id| self.inode_from_id(&fs, inode_id))
+        })
+    }
+
+    /// Find a `/` separated path relative to this directory. Empty components are skipped.
+    pub fn find_path(self: &Arc<Self>, path: &str) -> Option<Arc<Inode>> {
+        path.split('/')
+            .filter(|name| !name.is_empty())
+            .try_fold(self.clone(), |inode, name| inode.find(name))
+    }
+
+    /// Grow the disk inode to `new_size` bytes, or fail without changing anything if there are
+    /// not enough free blocks or the size is too large
+    fn increase_size(
+        &self,
+        new_size: u32,
+        disk_inode: &mut DiskInode,
+        fs: &mut MutexGuard<EasyFileSystem>,
+    ) -> bool {
+        if new_size <= disk_inode.size {
+            return true;
+        }
+        if new_size as usize > MAX_FILE_SIZE {
+            return false;
+        }
+        let blocks_needed = disk_inode.blocks_num_needed(new_size);
+        let mut v: Vec<u32> = Vec::new();
+        for _ in 0..blocks_needed {
+            match fs.alloc_data() {
+                Some(block_id) => v.push(block_id),
+                None => {
+                    for block_id in v {
+                        fs.dealloc_data(block_id);
+                    }
+                    return false;
+                }
+            }
+        }
+        disk_inode.increase_size(new_size, v, &self.block_device);
+        true
+    }
+
+    /// Create an empty file or directory called `name` in this directory. Return `None` if `name`
+    /// exists already, is not a valid name or the filesystem is full.
+    pub fn create(&self, name: &str, type_: DiskInodeType) -> Option<Arc<Inode>> {
+        if !DirEntry::is_valid_name(name) {
+            return None;
+        }
+        let mut fs = self.fs.lock();
+        let can_create = self.read_disk_inode(|root_inode| {
+            root_inode.is_dir() && self.find_inode_id(name, root_inode).is_none()
+        });
+        if !can_create {
+            return None;
+        }
+
+        // create a new file
+        let new_inode_id = fs.alloc_inode()?;
+        let (new_inode_block_id, new_inode_block_offset) = fs.get_disk_inode_pos(new_inode_id);
+        get_block_cache(new_inode_block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .modify(new_inode_block_offset, |new_inode: &mut DiskInode| {
+                new_inode.initialize(type_);
+            });
+
+        // append the entry to the directory
+        let added = self.modify_disk_inode(|root_inode| {
+            let file_count = root_inode.size as usize / DIRENT_SZ;
+            let new_size = (file_count + 1) * DIRENT_SZ;
+            if !self.increase_size(new_size as u32, root_inode, &mut fs) {
+                return false;
+            }
+            let dirent = DirEntry::new(name, new_inode_id);
+            root_inode.write_at(file_count * DIRENT_SZ, dirent.as_bytes(), &self.block_device);
+            true
+        });
+        if !added {
+            fs.dealloc_inode(new_inode_id);
+            return None;
+        }
+
+        Some(self.inode_from_id(&fs, new_inode_id))
+    }
+
+    /// Names of the entries in this directory
+    pub fn ls(&self) -> Vec<String> {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| {
+            if !disk_inode.is_dir() {
+                return Vec::new();
+            }
+            self.dir_entries(disk_inode)
+                .iter()
+                .map(|dirent| String::from(dirent.name()))
+                .collect()
+        })
+    }
+
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.read_at(offset, buf, &self.block_device))
+    }
+
+    /// Write `buf` at `offset`, growing the file as needed. Return how many bytes are written,
+    /// which is 0 if the filesystem is full.
+    pub fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let mut fs = self.fs.lock();
+        self.modify_disk_inode(|disk_inode| {
+            let Ok(end) = u32::try_from(offset + buf.len()) else {
+                return 0;
+            };
+            if !self.increase_size(end, disk_inode, &mut fs) {
+                return 0;
+            }
+            disk_inode.write_at(offset, buf, &self.block_device)
+        })
+    }
+
+    /// Truncate to 0 bytes and free the data blocks
+    pub fn clear(&self) {
+        let mut fs = self.fs.lock();
+        self.modify_disk_inode(|disk_inode| {
+            let data_blocks_dealloc = disk_inode.clear_size(&self.block_device);
+            for data_block in data_blocks_dealloc.into_iter() {
+                fs.dealloc_data(data_block);
+            }
+        });
+    }
+}
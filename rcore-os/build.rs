@@ -4,9 +4,13 @@ const SRC: &str = "./src/";
 const LINK_APP_ASM: &str = "./src/link_app.asm";
 const DOT: &str = ".";
 
+/// Programs needed before the disk is available. The others are loaded from `fs.img`, so changing
+/// them does not rebuild the kernel.
+const BOOT_APPS: [&str; 2] = ["initproc", "user_shell"];
+
 fn main() {
     // build link_app.asm for the batch system
-    println!("cargo:rerun-if-changed={USER_LIB}");
+    println!("cargo:rerun-if-changed={USER_LIB}/src/bin");
     println!("cargo:rerun-if-changed={SRC}");
     build_link_app();
 }
@@ -17,6 +21,7 @@ fn build_link_app() {
     let mut output = std::fs::OpenOptions::new()
         .write(true)
         .create(true)
+        .truncate(true)
         .open(LINK_APP_ASM)
         .unwrap();
 
@@ -28,7 +33,12 @@ fn build_link_app() {
             name_with_ext
         })
         .collect();
+    let embed_all = std::env::var_os("CARGO_FEATURE_EMBED_ALL_APPS").is_some();
+    apps.retain(|app| embed_all || BOOT_APPS.contains(&app.as_str()));
     apps.sort();
+    for app in apps.iter() {
+        println!("cargo:rerun-if-changed={BIN_DIR}/{app}.bin");
+    }
 
     writeln!(
         output,
@@ -0,0 +1,163 @@
+//! Information about the machine, discovered from the device tree at boot
+
+use alloc::{string::String, vec::Vec};
+use lazy_static::lazy_static;
+
+use crate::{
+    config::{CLOCK_FREQ, MEMORY_END, PAGE_SIZE},
+    error,
+    fdt::Fdt,
+    log,
+    timer::TICKS_PER_SEC,
+    upsync::UPSyncCell,
+    warn,
+};
+
+/// Start of RAM in the QEMU virt machine, used when there is no device tree
+const DEFAULT_MEMORY_START: usize = 0x80000000;
+
+#[derive(Clone)]
+pub struct BoardInfo {
+    /// `[start, end)` of each RAM region
+    pub memory: Vec<(usize, usize)>,
+    /// Frequency of the `time` CSR
+    pub clock_freq: usize,
+    /// `[start, end)` of the MMIO registers of each device
+    pub uart: Option<(usize, usize)>,
+    pub plic: Option<(usize, usize)>,
+    pub virtio: Vec<(usize, usize)>,
+    pub bootargs: String,
+    /// `[start, end)` of the initial ramdisk, from `linux,initrd-start` and `linux,initrd-end`
+    pub initrd: Option<(usize, usize)>,
+}
+
+impl BoardInfo {
+    fn fallback() -> Self {
+        Self {
+            memory: vec![(DEFAULT_MEMORY_START, MEMORY_END)],
+            clock_freq: CLOCK_FREQ,
+            uart: None,
+            plic: None,
+            virtio: vec![],
+            bootargs: String::new(),
+            initrd: None,
+        }
+    }
+
+    fn from_fdt(fdt: &Fdt) -> Self {
+        let mut info = Self {
+            memory: vec![],
+            ..Self::fallback()
+        };
+
+        for node in fdt.nodes() {
+            let range = node.reg().next().map(|(start, size)| (start, start + size));
+            if node.prop_str("device_type") == Some("memory") {
+                info.memory.extend(node.reg().map(|(start, size)| (start, start + size)));
+            } else if node.depth == 1 && node.name == "cpus" {
+                match node.prop_usize("timebase-frequency") {
+                    // Timer interrupts are set `clock_freq / TICKS_PER_SEC` ticks apart
+                    Some(freq) if freq < TICKS_PER_SEC => {
+                        error!("Ignoring timebase-frequency {} Hz", freq);
+                    }
+                    Some(freq) => info.clock_freq = freq,
+                    None => {}
+                }
+            } else if node.depth == 1 && node.name == "chosen" {
+                if let Some(bootargs) = node.prop_str("bootargs") {
+                    info.bootargs = bootargs.into();
+                }
+                if let (Some(start), Some(end)) = (
+                    node.prop_usize("linux,initrd-start"),
+                    node.prop_usize("linux,initrd-end"),
+                ) {
+                    info.initrd = Some((start, end)).filter(|&(start, end)| start < end);
+                }
+            } else if node.is_compatible("ns16550a") {
+                info.uart = info.uart.or(range);
+            } else if node.is_compatible("riscv,plic0") || node.is_compatible("sifive,plic-1.0.0") {
+                info.plic = info.plic.or(range);
+            } else if node.is_compatible("virtio,mmio") {
+                info.virtio.extend(range);
+            }
+        }
+
+        if info.memory.is_empty() {
+            warn!("No memory node in the device tree");
+            info.memory = Self::fallback().memory;
+        }
+        info.memory.sort();
+        info.virtio.sort();
+        info
+    }
+}
+
+lazy_static! {
+    static ref BOARD_INFO: UPSyncCell<BoardInfo> =
+        unsafe { UPSyncCell::new(BoardInfo::fallback()) };
+}
+
+/// Parse the device tree at `dtb`. The blob is not used after this, so it can be overwritten by
+/// the frame allocator.
+pub fn init(dtb: usize) {
+    let info = match unsafe { Fdt::from_ptr(dtb) } {
+        Ok(fdt) => BoardInfo::from_fdt(&fdt),
+        Err(err) => {
+            error!("Cannot parse the device tree at {:#x}: {:?}", dtb, err);
+            BoardInfo::fallback()
+        }
+    };
+
+    for (start, end) in info.memory.iter() {
+        log!("Memory {:#x}..{:#x}", start, end);
+    }
+    log!("Clock frequency {} Hz", info.clock_freq);
+    if let Some((start, end)) = info.uart {
+        log!("UART {:#x}..{:#x}", start, end);
+    }
+    if let Some((start, end)) = info.plic {
+        log!("PLIC {:#x}..{:#x}", start, end);
+    }
+    for (start, end) in info.virtio.iter() {
+        log!("Virtio MMIO {:#x}..{:#x}", start, end);
+    }
+    if let Some((start, end)) = info.initrd {
+        log!("Initrd {:#x}..{:#x} from the device tree", start, end);
+    }
+    if !info.bootargs.is_empty() {
+        log!("Boot arguments: {}", info.bootargs);
+    }
+
+    *BOARD_INFO.exclusive_access() = info;
+}
+
+pub fn board_info() -> BoardInfo {
+    BOARD_INFO.borrow().clone()
+}
+
+pub fn clock_freq() -> usize {
+    BOARD_INFO.borrow().clock_freq
+}
+
+/// MMIO ranges which the kernel maps into its address space, rounded to pages and merged so that
+/// no page is mapped twice
+pub fn mmio_ranges() -> Vec<(usize, usize)> {
+    let info = BOARD_INFO.borrow();
+    let mut ranges: Vec<(usize, usize)> = info
+        .uart
+        .iter()
+        .chain(info.plic.iter())
+        .chain(info.virtio.iter())
+        .map(|&(start, end)| (start & !(PAGE_SIZE - 1), end.next_multiple_of(PAGE_SIZE)))
+        .collect();
+    ranges.sort();
+
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
@@ -3,16 +3,44 @@ pub const APP_BASEADDR: usize = 0x80400000;
 pub const APP_SIZE_LIMIT: usize = 0x200000;
 
 pub const USER_STACK_SIZE: usize = 0x2000;
+/// Bytes of the strings and pointers of `argv` and `envp` which `exec` places on the user stack,
+/// leaving the rest of the stack to the program
+pub const ARG_MAX: usize = USER_STACK_SIZE / 2;
 pub const KERNEL_STACK_SIZE: usize = 0x4000;
 
-/// Clock frequency in qemu
+/// Clock frequency in qemu, used when the device tree does not provide one
 pub const CLOCK_FREQ: usize = 12500000;
 
-pub const KERNEL_HEAP_SIZE: usize = 0x4000;
+/// Large enough for the bookkeeping of the frame allocator with all memory of the board
+pub const KERNEL_HEAP_SIZE: usize = 0x30_0000;
 
+/// End of memory when there is no device tree
 pub const MEMORY_END: usize = 0x80800000;
+/// Where the initial ramdisk is looked for when the device tree does not tell. It should match the
+/// `addr` of the QEMU loader device in the justfile.
+pub const INITRD_BASE: usize = 0x84000000;
+
+/// Size in blocks of the RAM disk made for each easy-fs mounted from the source `ram`
+pub const RAMDISK_BLOCKS: usize = 1024;
 
 pub const PAGE_SIZE: usize = 1 << 12;
+/// Trap context of the main thread. Those of the other threads are in the pages below.
 pub const TRAP_CONTEXT: usize = usize::MAX - PAGE_SIZE * 2 + 1;
 pub const TRAMPOLINE: usize = TRAP_CONTEXT + PAGE_SIZE;
 
+/// User space is the lower half of Sv39 address space
+pub const USER_SPACE_END: usize = 1 << 38;
+/// File descriptors of a task are below this
+pub const FD_LIMIT: usize = 256;
+/// Bytes of a path passed to a syscall, without the `\0`
+pub const PATH_MAX: usize = 4096;
+/// Threads of a process are below this
+pub const THREAD_LIMIT: usize = 256;
+/// Mutexes, semaphores and condition variables of a process are each below this
+pub const SYNC_LIMIT: usize = 256;
+/// Directories searched in order by `exec` for a program name without `/`. Programs packed into
+/// the disk are at its root, and the ones of the kernel image and the initrd at `/bin`.
+pub const EXEC_SEARCH_PATH: [&str; 2] = ["/", "/bin"];
+
+/// Where the kernel starts to look for free space for `mmap` without an address hint
+pub const MMAP_BASE: usize = 0x10_0000_0000;
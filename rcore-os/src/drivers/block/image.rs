@@ -0,0 +1,28 @@
+use super::{BlockDevice, BlockError, BLOCK_SIZE};
+
+/// A read-only device over an image in memory, e.g. a file of the initrd. A partial block at the
+/// end of the image is not part of the device.
+pub struct ImageBlockDevice(&'static [u8]);
+
+impl ImageBlockDevice {
+    pub fn new(image: &'static [u8]) -> Self {
+        Self(image)
+    }
+}
+
+impl BlockDevice for ImageBlockDevice {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) -> Result<(), BlockError> {
+        let start = block_id * BLOCK_SIZE;
+        buf.copy_from_slice(self.0.get(start..start + BLOCK_SIZE).ok_or(BlockError)?);
+        Ok(())
+    }
+
+    /// The image cannot be modified, and the read-only filesystems on it never write
+    fn write_block(&self, _block_id: usize, _buf: &[u8]) -> Result<(), BlockError> {
+        Err(BlockError)
+    }
+
+    fn num_blocks(&self) -> usize {
+        self.0.len() / BLOCK_SIZE
+    }
+}
@@ -0,0 +1,29 @@
+mod image;
+mod virtio_blk;
+
+use alloc::{sync::Arc, vec::Vec};
+use lazy_static::lazy_static;
+
+use crate::upsync::UPSyncCell;
+
+/// Shared with the filesystems, which all go through the block cache of easy-fs
+pub use easy_fs::{
+    block_cache_stats, block_cache_sync_all, block_device_errors, get_block_cache, BlockDevice,
+    BlockError, MemBlockDevice, BLOCK_SZ as BLOCK_SIZE,
+};
+pub use image::ImageBlockDevice;
+pub use virtio_blk::VirtIOBlock;
+
+lazy_static! {
+    /// Block devices in the order they are found in the device tree
+    static ref BLOCK_DEVICES: UPSyncCell<Vec<Arc<dyn BlockDevice>>> =
+        unsafe { UPSyncCell::new(Vec::new()) };
+}
+
+pub fn register_block_device(device: Arc<dyn BlockDevice>) {
+    BLOCK_DEVICES.exclusive_access().push(device);
+}
+
+pub fn block_device(index: usize) -> Option<Arc<dyn BlockDevice>> {
+    BLOCK_DEVICES.borrow().get(index).cloned()
+}
@@ -0,0 +1,145 @@
+use core::mem::size_of;
+
+use super::{BlockDevice, BlockError, BLOCK_SIZE};
+use crate::{
+    drivers::virtio::{queue::VirtQueue, VirtioError, VirtioMmio},
+    mem::frame_allocator::{frame_alloc, FrameTracker},
+    mem::address::PhysAddr,
+    upsync::UPSyncCell,
+    error,
+};
+
+const REQ_IN: u32 = 0;
+const REQ_OUT: u32 = 1;
+
+const STATUS_OK: u8 = 0;
+
+/// The device has only one queue
+const QUEUE_SIZE: u16 = 16;
+
+/// Offset of `capacity` in the configuration space, counted in 512 bytes sectors
+const CONFIG_CAPACITY: usize = 0;
+
+#[repr(C)]
+struct RequestHeader {
+    kind: u32,
+    reserved: u32,
+    sector: u64,
+}
+
+/// Where the parts of a request are placed in the DMA frame
+const HEADER_OFFSET: usize = 0;
+const STATUS_OFFSET: usize = size_of::<RequestHeader>();
+const DATA_OFFSET: usize = BLOCK_SIZE;
+
+struct VirtIOBlockInner {
+    transport: VirtioMmio,
+    queue: VirtQueue,
+    /// Requests are built here rather than in the caller's buffers, which may live on a kernel
+    /// stack that is not identity mapped
+    dma: FrameTracker,
+    capacity: usize,
+}
+
+/// A virtio block device driven by polling. Only one request is in flight at a time. Failed
+/// requests are logged and returned as [`BlockError`].
+pub struct VirtIOBlock {
+    inner: UPSyncCell<VirtIOBlockInner>,
+}
+
+impl VirtIOBlock {
+    /// SAFETY: `transport` must belong to a block device which is not driven by anyone else
+    pub unsafe fn new(transport: VirtioMmio) -> Result<Self, VirtioError> {
+        transport.begin_init(0)?;
+        let queue = transport.setup_queue(0, QUEUE_SIZE)?;
+        let dma = frame_alloc().ok_or(VirtioError::NoMemory)?;
+        let capacity = transport.config_read_u64(CONFIG_CAPACITY) as usize;
+        transport.finish_init();
+
+        Ok(Self {
+            inner: UPSyncCell::new(VirtIOBlockInner {
+                transport,
+                queue,
+                dma,
+                capacity,
+            }),
+        })
+    }
+}
+
+impl VirtIOBlockInner {
+    fn dma_addr(&self) -> usize {
+        PhysAddr::from(self.dma.ppn).0
+    }
+
+    /// Submit a request whose data is already in the DMA frame and wait for it
+    fn request(&mut self, kind: u32, block_id: usize) -> Result<(), BlockError> {
+        if block_id >= self.capacity {
+            error!("Block {} is beyond the end of the device", block_id);
+            return Err(BlockError);
+        }
+        let base = self.dma_addr();
+        let header = RequestHeader {
+            kind,
+            reserved: 0,
+            sector: block_id as u64,
+        };
+        unsafe {
+            ((base + HEADER_OFFSET) as *mut RequestHeader).write_volatile(header);
+            ((base + STATUS_OFFSET) as *mut u8).write_volatile(u8::MAX);
+        }
+
+        let header = (base + HEADER_OFFSET, size_of::<RequestHeader>());
+        let data = (base + DATA_OFFSET, BLOCK_SIZE);
+        let status = (base + STATUS_OFFSET, 1);
+        let head = match kind {
+            REQ_IN => self.queue.add(&[header], &[data, status]),
+            _ => self.queue.add(&[header, data], &[status]),
+        };
+        let Some(head) = head else {
+            error!("The queue of the block device is full");
+            return Err(BlockError);
+        };
+        self.transport.notify(&self.queue);
+
+        while !self.queue.can_pop() {
+            core::hint::spin_loop();
+        }
+        let (used, _) = self.queue.pop_used().unwrap();
+        self.transport.ack_interrupt();
+        if used != head {
+            error!("Block device used request {} instead of {}", used, head);
+            return Err(BlockError);
+        }
+
+        let status = unsafe { ((base + STATUS_OFFSET) as *const u8).read_volatile() };
+        if status != STATUS_OK {
+            error!("Block device request on block {} failed with status {}", block_id, status);
+            return Err(BlockError);
+        }
+        Ok(())
+    }
+
+    fn data(&mut self) -> &mut [u8] {
+        &mut self.dma.ppn.get_byte_array()[DATA_OFFSET..DATA_OFFSET + BLOCK_SIZE]
+    }
+}
+
+impl BlockDevice for VirtIOBlock {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) -> Result<(), BlockError> {
+        let mut inner = self.inner.exclusive_access();
+        inner.request(REQ_IN, block_id)?;
+        buf.copy_from_slice(inner.data());
+        Ok(())
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) -> Result<(), BlockError> {
+        let mut inner = self.inner.exclusive_access();
+        inner.data().copy_from_slice(buf);
+        inner.request(REQ_OUT, block_id)
+    }
+
+    fn num_blocks(&self) -> usize {
+        self.inner.borrow().capacity
+    }
+}
@@ -0,0 +1,43 @@
+pub mod block;
+pub mod virtio;
+
+use alloc::sync::Arc;
+
+use crate::{board, error, log};
+
+use self::{
+    block::{register_block_device, VirtIOBlock},
+    virtio::{VirtioError, VirtioMmio, DEVICE_ID_BLOCK},
+};
+
+/// Probe the virtio devices found in the device tree. Their registers must be mapped already.
+pub fn init() {
+    for (start, _) in board::board_info().virtio {
+        let transport = match unsafe { VirtioMmio::new(start) } {
+            Ok(transport) => transport,
+            Err(VirtioError::NoDevice) => continue,
+            Err(err) => {
+                error!("Cannot probe virtio device at {:#x}: {:?}", start, err);
+                continue;
+            }
+        };
+        match transport.device_id() {
+            DEVICE_ID_BLOCK => match unsafe { VirtIOBlock::new(transport) } {
+                Ok(device) => {
+                    log!(
+                        "Virtio block device at {:#x}, {} blocks",
+                        start,
+                        block::BlockDevice::num_blocks(&device)
+                    );
+                    register_block_device(Arc::new(device));
+                }
+                Err(err) => {
+                    error!("Cannot initialize virtio block device at {:#x}: {:?}", start, err);
+                }
+            },
+            id => {
+                log!("Ignoring virtio device {} at {:#x}", id, start);
+            }
+        }
+    }
+}
@@ -0,0 +1,213 @@
+//! The virtio-mmio transport. Both the legacy (version 1) and the modern (version 2) register
+//! layouts are supported.
+
+pub mod queue;
+
+use core::ptr::{read_volatile, write_volatile};
+
+use crate::config::PAGE_SIZE;
+
+use self::queue::VirtQueue;
+
+const VIRTIO_MAGIC: u32 = 0x74726976;
+
+pub const DEVICE_ID_BLOCK: u32 = 2;
+
+/// Offsets of the registers
+mod reg {
+    pub const MAGIC_VALUE: usize = 0x000;
+    pub const VERSION: usize = 0x004;
+    pub const DEVICE_ID: usize = 0x008;
+    pub const DEVICE_FEATURES: usize = 0x010;
+    pub const DEVICE_FEATURES_SEL: usize = 0x014;
+    pub const DRIVER_FEATURES: usize = 0x020;
+    pub const DRIVER_FEATURES_SEL: usize = 0x024;
+    /// Legacy only
+    pub const GUEST_PAGE_SIZE: usize = 0x028;
+    pub const QUEUE_SEL: usize = 0x030;
+    pub const QUEUE_NUM_MAX: usize = 0x034;
+    pub const QUEUE_NUM: usize = 0x038;
+    /// Legacy only
+    pub const QUEUE_ALIGN: usize = 0x03c;
+    /// Legacy only
+    pub const QUEUE_PFN: usize = 0x040;
+    /// Modern only
+    pub const QUEUE_READY: usize = 0x044;
+    pub const QUEUE_NOTIFY: usize = 0x050;
+    pub const INTERRUPT_STATUS: usize = 0x060;
+    pub const INTERRUPT_ACK: usize = 0x064;
+    pub const STATUS: usize = 0x070;
+    /// Modern only, the following are pairs of low and high halves
+    pub const QUEUE_DESC_LOW: usize = 0x080;
+    pub const QUEUE_DRIVER_LOW: usize = 0x090;
+    pub const QUEUE_DEVICE_LOW: usize = 0x0a0;
+    pub const CONFIG: usize = 0x100;
+}
+
+mod status {
+    pub const ACKNOWLEDGE: u32 = 1;
+    pub const DRIVER: u32 = 2;
+    pub const DRIVER_OK: u32 = 4;
+    pub const FEATURES_OK: u32 = 8;
+    pub const FAILED: u32 = 128;
+}
+
+/// Must be accepted by drivers of modern devices
+const VIRTIO_F_VERSION_1: u64 = 1 << 32;
+
+#[derive(Debug)]
+pub enum VirtioError {
+    BadMagic(u32),
+    UnsupportedVersion(u32),
+    /// No device behind this slot
+    NoDevice,
+    FeaturesRejected,
+    QueueUnavailable,
+    NoMemory,
+}
+
+pub struct VirtioMmio {
+    base: usize,
+    version: u32,
+}
+
+impl VirtioMmio {
+    /// SAFETY: `base` must be the identity mapped register window of a virtio-mmio device
+    pub unsafe fn new(base: usize) -> Result<Self, VirtioError> {
+        let transport = Self { base, version: 0 };
+        let magic = transport.read(reg::MAGIC_VALUE);
+        if magic != VIRTIO_MAGIC {
+            return Err(VirtioError::BadMagic(magic));
+        }
+        let version = transport.read(reg::VERSION);
+        if version != 1 && version != 2 {
+            return Err(VirtioError::UnsupportedVersion(version));
+        }
+        if transport.read(reg::DEVICE_ID) == 0 {
+            return Err(VirtioError::NoDevice);
+        }
+        Ok(Self { base, version })
+    }
+
+    pub fn is_legacy(&self) -> bool {
+        self.version == 1
+    }
+
+    pub fn device_id(&self) -> u32 {
+        self.read(reg::DEVICE_ID)
+    }
+
+    fn read(&self, offset: usize) -> u32 {
+        unsafe { read_volatile((self.base + offset) as *const u32) }
+    }
+
+    fn write(&self, offset: usize, value: u32) {
+        unsafe { write_volatile((self.base + offset) as *mut u32, value) }
+    }
+
+    fn write_u64(&self, low_offset: usize, value: u64) {
+        self.write(low_offset, value as u32);
+        self.write(low_offset + 4, (value >> 32) as u32);
+    }
+
+    fn device_features(&self) -> u64 {
+        self.write(reg::DEVICE_FEATURES_SEL, 0);
+        let low = self.read(reg::DEVICE_FEATURES) as u64;
+        self.write(reg::DEVICE_FEATURES_SEL, 1);
+        let high = self.read(reg::DEVICE_FEATURES) as u64;
+        high << 32 | low
+    }
+
+    fn set_driver_features(&self, features: u64) {
+        self.write(reg::DRIVER_FEATURES_SEL, 0);
+        self.write(reg::DRIVER_FEATURES, features as u32);
+        self.write(reg::DRIVER_FEATURES_SEL, 1);
+        self.write(reg::DRIVER_FEATURES, (features >> 32) as u32);
+    }
+
+    /// Reset the device and negotiate the features. Only the features in `supported` offered by
+    /// the device are accepted, which are returned.
+    pub fn begin_init(&self, supported: u64) -> Result<u64, VirtioError> {
+        self.write(reg::STATUS, 0);
+        self.write(reg::STATUS, status::ACKNOWLEDGE);
+        self.write(reg::STATUS, status::ACKNOWLEDGE | status::DRIVER);
+
+        let device_features = self.device_features();
+        let mut features = device_features & supported;
+        if !self.is_legacy() {
+            if device_features & VIRTIO_F_VERSION_1 == 0 {
+                self.fail();
+                return Err(VirtioError::FeaturesRejected);
+            }
+            features |= VIRTIO_F_VERSION_1;
+        }
+        self.set_driver_features(features);
+
+        if self.is_legacy() {
+            self.write(reg::GUEST_PAGE_SIZE, PAGE_SIZE as u32);
+        } else {
+            let status = self.read(reg::STATUS) | status::FEATURES_OK;
+            self.write(reg::STATUS, status);
+            if self.read(reg::STATUS) & status::FEATURES_OK == 0 {
+                self.fail();
+                return Err(VirtioError::FeaturesRejected);
+            }
+        }
+        Ok(features)
+    }
+
+    pub fn finish_init(&self) {
+        let status = self.read(reg::STATUS) | status::DRIVER_OK;
+        self.write(reg::STATUS, status);
+    }
+
+    fn fail(&self) {
+        let status = self.read(reg::STATUS) | status::FAILED;
+        self.write(reg::STATUS, status);
+    }
+
+    /// Create the virtqueue `index` with at most `max_size` descriptors and hand it to the device
+    pub fn setup_queue(&self, index: u32, max_size: u16) -> Result<VirtQueue, VirtioError> {
+        self.write(reg::QUEUE_SEL, index);
+        let device_max = self.read(reg::QUEUE_NUM_MAX);
+        if device_max == 0 {
+            return Err(VirtioError::QueueUnavailable);
+        }
+        let size = (device_max as u16).min(max_size);
+        let queue = VirtQueue::new(index, size).ok_or(VirtioError::NoMemory)?;
+
+        self.write(reg::QUEUE_NUM, size as u32);
+        if self.is_legacy() {
+            self.write(reg::QUEUE_ALIGN, PAGE_SIZE as u32);
+            self.write(reg::QUEUE_PFN, (queue.desc_addr() / PAGE_SIZE) as u32);
+        } else {
+            self.write_u64(reg::QUEUE_DESC_LOW, queue.desc_addr() as u64);
+            self.write_u64(reg::QUEUE_DRIVER_LOW, queue.avail_addr() as u64);
+            self.write_u64(reg::QUEUE_DEVICE_LOW, queue.used_addr() as u64);
+            self.write(reg::QUEUE_READY, 1);
+        }
+        Ok(queue)
+    }
+
+    pub fn notify(&self, queue: &VirtQueue) {
+        self.write(reg::QUEUE_NOTIFY, queue.index());
+    }
+
+    /// Acknowledge all pending interrupts and return their causes
+    pub fn ack_interrupt(&self) -> u32 {
+        let status = self.read(reg::INTERRUPT_STATUS);
+        self.write(reg::INTERRUPT_ACK, status);
+        status
+    }
+
+    /// Read a field of the device specific configuration space
+    pub fn config_read_u32(&self, offset: usize) -> u32 {
+        self.read(reg::CONFIG + offset)
+    }
+
+    pub fn config_read_u64(&self, offset: usize) -> u64 {
+        let low = self.config_read_u32(offset) as u64;
+        let high = self.config_read_u32(offset + 4) as u64;
+        high << 32 | low
+    }
+}
@@ -0,0 +1,178 @@
+//! Split virtqueues in the layout required by legacy devices, which modern devices accept too
+
+use core::{
+    mem::size_of,
+    ptr::{read_volatile, write_volatile},
+    sync::atomic::{fence, Ordering},
+};
+
+use crate::{
+    config::PAGE_SIZE,
+    mem::{
+        address::PhysAddr,
+        frame_allocator::{frame_alloc_contiguous, ContiguousFrameTracker},
+    },
+};
+
+const DESC_F_NEXT: u16 = 1;
+/// The buffer is written by the device
+const DESC_F_WRITE: u16 = 2;
+
+#[repr(C)]
+struct Descriptor {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+#[repr(C)]
+struct UsedElem {
+    id: u32,
+    len: u32,
+}
+
+pub struct VirtQueue {
+    index: u32,
+    size: u16,
+    frames: ContiguousFrameTracker,
+    avail_offset: usize,
+    used_offset: usize,
+    /// Head of the list of free descriptors chained by `next`
+    free_head: u16,
+    num_free: u16,
+    /// Next entry of the used ring to look at
+    last_used_idx: u16,
+}
+
+impl VirtQueue {
+    /// The rings live in physically contiguous frames. Kernel memory is identity mapped, so their
+    /// physical addresses are also where the kernel accesses them.
+    pub fn new(index: u32, size: u16) -> Option<Self> {
+        let n = size as usize;
+        let avail_offset = n * size_of::<Descriptor>();
+        let avail_size = 6 + 2 * n;
+        let used_offset = (avail_offset + avail_size).next_multiple_of(PAGE_SIZE);
+        let used_size = 6 + n * size_of::<UsedElem>();
+        let pages = (used_offset + used_size).div_ceil(PAGE_SIZE);
+        let frames = frame_alloc_contiguous(pages.next_power_of_two().trailing_zeros() as usize)?;
+
+        let queue = Self {
+            index,
+            size,
+            frames,
+            avail_offset,
+            used_offset,
+            free_head: 0,
+            num_free: size,
+            last_used_idx: 0,
+        };
+        for i in 0..size {
+            queue.desc(i).next = i + 1;
+        }
+        Some(queue)
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn desc_addr(&self) -> usize {
+        PhysAddr::from(self.frames.ppn).0
+    }
+
+    pub fn avail_addr(&self) -> usize {
+        self.desc_addr() + self.avail_offset
+    }
+
+    pub fn used_addr(&self) -> usize {
+        self.desc_addr() + self.used_offset
+    }
+
+    #[allow(clippy::mut_from_ref)]
+    fn desc(&self, i: u16) -> &mut Descriptor {
+        unsafe { &mut *(self.desc_addr() as *mut Descriptor).add(i as usize) }
+    }
+
+    fn avail_idx(&self) -> *mut u16 {
+        (self.avail_addr() + 2) as *mut u16
+    }
+
+    fn avail_ring(&self, i: u16) -> *mut u16 {
+        (self.avail_addr() + 4 + 2 * (i % self.size) as usize) as *mut u16
+    }
+
+    fn used_idx(&self) -> *const u16 {
+        (self.used_addr() + 2) as *const u16
+    }
+
+    fn used_ring(&self, i: u16) -> *const UsedElem {
+        (self.used_addr() + 4 + size_of::<UsedElem>() * (i % self.size) as usize) as *const UsedElem
+    }
+
+    /// Make a chain of `(address, length)` buffers available to the device. The device reads
+    /// `inputs` and writes `outputs`. Returns the head of the chain, or `None` if there are not
+    /// enough free descriptors.
+    pub fn add(&mut self, inputs: &[(usize, usize)], outputs: &[(usize, usize)]) -> Option<u16> {
+        let count = inputs.len() + outputs.len();
+        if count == 0 || count > self.num_free as usize {
+            return None;
+        }
+
+        let head = self.free_head;
+        let mut last = head;
+        let buffers = inputs
+            .iter()
+            .map(|&buf| (buf, 0))
+            .chain(outputs.iter().map(|&buf| (buf, DESC_F_WRITE)));
+        for ((addr, len), flags) in buffers {
+            let desc = self.desc(self.free_head);
+            desc.addr = addr as u64;
+            desc.len = len as u32;
+            desc.flags = flags | DESC_F_NEXT;
+            last = self.free_head;
+            self.free_head = desc.next;
+        }
+        self.desc(last).flags &= !DESC_F_NEXT;
+        self.num_free -= count as u16;
+
+        unsafe {
+            let idx = read_volatile(self.avail_idx());
+            write_volatile(self.avail_ring(idx), head);
+            // The descriptors must be visible before the new index
+            fence(Ordering::SeqCst);
+            write_volatile(self.avail_idx(), idx.wrapping_add(1));
+        }
+        fence(Ordering::SeqCst);
+        Some(head)
+    }
+
+    pub fn can_pop(&self) -> bool {
+        fence(Ordering::SeqCst);
+        self.last_used_idx != unsafe { read_volatile(self.used_idx()) }
+    }
+
+    /// Take a chain used by the device and recycle its descriptors. Returns the head of the chain
+    /// and how many bytes the device has written.
+    pub fn pop_used(&mut self) -> Option<(u16, u32)> {
+        if !self.can_pop() {
+            return None;
+        }
+        let elem = unsafe { read_volatile(self.used_ring(self.last_used_idx)) };
+        self.last_used_idx = self.last_used_idx.wrapping_add(1);
+
+        let head = elem.id as u16;
+        let mut i = head;
+        loop {
+            self.num_free += 1;
+            let desc = self.desc(i);
+            if desc.flags & DESC_F_NEXT == 0 {
+                desc.next = self.free_head;
+                break;
+            }
+            i = desc.next;
+        }
+        self.free_head = head;
+        Some((head, elem.len))
+    }
+}
@@ -0,0 +1,264 @@
+//! A minimal parser of the flattened device tree handed over by the SBI firmware. It walks the
+//! structure block in place without allocating.
+
+const FDT_MAGIC: u32 = 0xd00dfeed;
+const FDT_HEADER_SIZE: usize = 40;
+
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+
+/// Nodes deeper than this are skipped
+const MAX_DEPTH: usize = 16;
+
+#[derive(Debug)]
+pub enum FdtError {
+    NullPointer,
+    BadMagic(u32),
+    Truncated,
+}
+
+#[derive(Copy, Clone)]
+pub struct Fdt<'a> {
+    data: &'a [u8],
+    struct_offset: usize,
+    strings_offset: usize,
+}
+
+fn align4(n: usize) -> usize {
+    (n + 3) & !3
+}
+
+fn read_be_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_cstr(data: &[u8], offset: usize) -> Option<&str> {
+    let bytes = data.get(offset..)?;
+    let len = bytes.iter().position(|&b| b == 0)?;
+    core::str::from_utf8(&bytes[..len]).ok()
+}
+
+impl Fdt<'static> {
+    /// SAFETY: `addr` must point to a device tree blob which stays valid while it is parsed
+    pub unsafe fn from_ptr(addr: usize) -> Result<Self, FdtError> {
+        if addr == 0 {
+            return Err(FdtError::NullPointer);
+        }
+        let header = core::slice::from_raw_parts(addr as *const u8, FDT_HEADER_SIZE);
+        let magic = read_be_u32(header, 0).unwrap();
+        if magic != FDT_MAGIC {
+            return Err(FdtError::BadMagic(magic));
+        }
+        let total_size = read_be_u32(header, 4).unwrap() as usize;
+        Self::new(core::slice::from_raw_parts(addr as *const u8, total_size))
+    }
+}
+
+impl<'a> Fdt<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self, FdtError> {
+        let field = |offset| read_be_u32(data, offset).ok_or(FdtError::Truncated);
+        let magic = field(0)?;
+        if magic != FDT_MAGIC {
+            return Err(FdtError::BadMagic(magic));
+        }
+        let total_size = field(4)? as usize;
+        if total_size > data.len() {
+            return Err(FdtError::Truncated);
+        }
+        Ok(Self {
+            data: &data[..total_size],
+            struct_offset: field(8)? as usize,
+            strings_offset: field(12)? as usize,
+        })
+    }
+
+    /// All nodes in depth-first order
+    pub fn nodes(&self) -> NodeIter<'a> {
+        NodeIter {
+            fdt: *self,
+            offset: self.struct_offset,
+            depth: 0,
+            cells: [(2, 1); MAX_DEPTH],
+        }
+    }
+
+    fn u32_at(&self, offset: usize) -> Option<u32> {
+        read_be_u32(self.data, offset)
+    }
+}
+
+pub struct NodeIter<'a> {
+    fdt: Fdt<'a>,
+    offset: usize,
+    depth: usize,
+    /// `#address-cells` and `#size-cells` of the nodes on the current path
+    cells: [(u32, u32); MAX_DEPTH],
+}
+
+impl<'a> Iterator for NodeIter<'a> {
+    type Item = Node<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let token = self.fdt.u32_at(self.offset)?;
+            self.offset += 4;
+            match token {
+                FDT_BEGIN_NODE => {
+                    let name = read_cstr(self.fdt.data, self.offset)?;
+                    self.offset += align4(name.len() + 1);
+                    let (address_cells, size_cells) = match self.depth {
+                        0 => (2, 1),
+                        depth => self.cells[(depth - 1).min(MAX_DEPTH - 1)],
+                    };
+                    let node = Node {
+                        fdt: self.fdt,
+                        name,
+                        depth: self.depth,
+                        props_offset: self.offset,
+                        address_cells,
+                        size_cells,
+                    };
+                    if self.depth < MAX_DEPTH {
+                        self.cells[self.depth] = (
+                            node.prop_u32("#address-cells").unwrap_or(2),
+                            node.prop_u32("#size-cells").unwrap_or(1),
+                        );
+                    }
+                    self.depth += 1;
+                    self.offset = node.props().end_offset();
+                    return Some(node);
+                }
+                FDT_END_NODE => {
+                    self.depth = self.depth.checked_sub(1)?;
+                }
+                FDT_PROP => {
+                    let len = self.fdt.u32_at(self.offset)? as usize;
+                    self.offset += 8 + align4(len);
+                }
+                FDT_NOP => {}
+                _ => return None,
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Node<'a> {
+    fdt: Fdt<'a>,
+    /// Node name with the unit address, e.g. `memory@80000000`
+    pub name: &'a str,
+    /// The root node has depth 0
+    pub depth: usize,
+    props_offset: usize,
+    /// `#address-cells` of the parent, used to decode `reg`
+    address_cells: u32,
+    /// `#size-cells` of the parent, used to decode `reg`
+    size_cells: u32,
+}
+
+impl<'a> Node<'a> {
+    /// The name without the unit address
+    pub fn base_name(&self) -> &'a str {
+        self.name.split('@').next().unwrap()
+    }
+
+    pub fn props(&self) -> PropIter<'a> {
+        PropIter {
+            fdt: self.fdt,
+            offset: self.props_offset,
+        }
+    }
+
+    pub fn prop(&self, name: &str) -> Option<&'a [u8]> {
+        self.props().find(|(n, _)| *n == name).map(|(_, value)| value)
+    }
+
+    pub fn prop_u32(&self, name: &str) -> Option<u32> {
+        read_be_u32(self.prop(name)?, 0)
+    }
+
+    /// Cells of `value` are either one `u32` or two, depending on the length
+    pub fn prop_usize(&self, name: &str) -> Option<usize> {
+        let value = self.prop(name)?;
+        match value.len() {
+            4 => Some(read_be_u32(value, 0)? as usize),
+            8 => Some((read_be_u32(value, 0)? as usize) << 32 | read_be_u32(value, 4)? as usize),
+            _ => None,
+        }
+    }
+
+    pub fn prop_str(&self, name: &str) -> Option<&'a str> {
+        read_cstr(self.prop(name)?, 0)
+    }
+
+    /// Whether `compatible` contains `compat`
+    pub fn is_compatible(&self, compat: &str) -> bool {
+        self.prop("compatible").map_or(false, |value| {
+            value
+                .split(|&b| b == 0)
+                .any(|s| s == compat.as_bytes())
+        })
+    }
+
+    /// `(address, size)` pairs in `reg`
+    pub fn reg(&self) -> impl Iterator<Item = (usize, usize)> + 'a {
+        let address_cells = self.address_cells as usize;
+        let size_cells = self.size_cells as usize;
+        let entry_size = (address_cells + size_cells) * 4;
+        let value = match entry_size {
+            0 => &[],
+            _ => self.prop("reg").unwrap_or(&[]),
+        };
+        let read_cells = |data: &[u8], offset: usize, cells: usize| {
+            (0..cells).fold(0usize, |acc, i| {
+                acc << 32 | read_be_u32(data, offset + i * 4).unwrap() as usize
+            })
+        };
+        value
+            .chunks_exact(entry_size.max(1))
+            .map(move |entry| {
+                (
+                    read_cells(entry, 0, address_cells),
+                    read_cells(entry, address_cells * 4, size_cells),
+                )
+            })
+    }
+}
+
+pub struct PropIter<'a> {
+    fdt: Fdt<'a>,
+    offset: usize,
+}
+
+impl<'a> PropIter<'a> {
+    /// Where the properties of the node end, i.e. the first child or the end of the node
+    fn end_offset(mut self) -> usize {
+        while self.next().is_some() {}
+        self.offset
+    }
+}
+
+impl<'a> Iterator for PropIter<'a> {
+    type Item = (&'a str, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.fdt.u32_at(self.offset)? {
+                FDT_PROP => {
+                    let len = self.fdt.u32_at(self.offset + 4)? as usize;
+                    let name_offset = self.fdt.u32_at(self.offset + 8)? as usize;
+                    let value_start = self.offset + 12;
+                    let value = self.fdt.data.get(value_start..value_start + len)?;
+                    let name = read_cstr(self.fdt.data, self.fdt.strings_offset + name_offset)?;
+                    self.offset = value_start + align4(len);
+                    return Some((name, value));
+                }
+                FDT_NOP => self.offset += 4,
+                _ => return None,
+            }
+        }
+    }
+}
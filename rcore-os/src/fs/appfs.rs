@@ -0,0 +1,73 @@
+//! The programs linked into the kernel and those in the initrd, as a read-only flat directory
+
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+
+use super::vfs::{FileSystem, Inode, InodeType};
+use crate::{loader, syscall::errno::ENOENT};
+
+pub struct AppFs;
+
+impl FileSystem for AppFs {
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        Arc::new(AppDir)
+    }
+}
+
+/// Inode 1, and the programs are numbered from 2 in the order of [`loader::app_names`]
+struct AppDir;
+
+impl Inode for AppDir {
+    fn type_(&self) -> InodeType {
+        InodeType::Dir
+    }
+
+    fn ino(&self) -> u64 {
+        1
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, isize> {
+        let data = loader::get_app_data_by_name(name).ok_or(ENOENT)?;
+        let index = loader::app_names()
+            .iter()
+            .position(|&app| app == name)
+            .ok_or(ENOENT)?;
+        Ok(Arc::new(AppFile {
+            ino: index as u64 + 2,
+            data,
+        }))
+    }
+
+    fn list(&self) -> Result<Vec<String>, isize> {
+        Ok(loader::app_names().into_iter().map(|name| name.to_string()).collect())
+    }
+}
+
+struct AppFile {
+    ino: u64,
+    data: &'static [u8],
+}
+
+impl Inode for AppFile {
+    fn type_(&self) -> InodeType {
+        InodeType::File
+    }
+
+    fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, isize> {
+        let data = self.data.get(offset..).unwrap_or(&[]);
+        let len = data.len().min(buf.len());
+        buf[..len].copy_from_slice(&data[..len]);
+        Ok(len)
+    }
+}
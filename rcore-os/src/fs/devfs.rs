@@ -0,0 +1,131 @@
+//! Character devices under `/dev`. They have no offsets, so reads and writes ignore `offset`.
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+use lazy_static::lazy_static;
+
+use super::{
+    stdio::console_read_byte,
+    vfs::{FileSystem, Inode, InodeType},
+};
+use crate::{
+    sbi::console_put_char,
+    syscall::errno::ENOENT,
+    timer::get_time,
+    upsync::UPSyncCell,
+};
+
+pub struct DevFs;
+
+impl FileSystem for DevFs {
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        Arc::new(DevDir)
+    }
+}
+
+/// `/dev`, inode 1
+struct DevDir;
+
+const DEVICES: [(&str, CharDevice); 4] = [
+    ("console", CharDevice::Console),
+    ("null", CharDevice::Null),
+    ("random", CharDevice::Random),
+    ("zero", CharDevice::Zero),
+];
+
+impl Inode for DevDir {
+    fn type_(&self) -> InodeType {
+        InodeType::Dir
+    }
+
+    fn ino(&self) -> u64 {
+        1
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, isize> {
+        let &(_, device) = DEVICES.iter().find(|(n, _)| *n == name).ok_or(ENOENT)?;
+        Ok(Arc::new(device))
+    }
+
+    fn list(&self) -> Result<Vec<String>, isize> {
+        Ok(DEVICES.iter().map(|&(name, _)| String::from(name)).collect())
+    }
+}
+
+/// Numbered from inode 2 in the order of the variants
+#[derive(Copy, Clone)]
+enum CharDevice {
+    /// Reads at most one character, waiting until there is one, like [`super::Stdin`]
+    Console,
+    /// Reads nothing and discards what is written
+    Null,
+    /// Pseudo-random bytes, which are not good enough for cryptography
+    Random,
+    /// Reads zeros and discards what is written
+    Zero,
+}
+
+impl Inode for CharDevice {
+    fn type_(&self) -> InodeType {
+        InodeType::CharDevice
+    }
+
+    fn ino(&self) -> u64 {
+        *self as u64 + 2
+    }
+
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> Result<usize, isize> {
+        match self {
+            Self::Console => {
+                let Some(byte) = buf.first_mut() else {
+                    return Ok(0);
+                };
+                *byte = console_read_byte()?;
+                Ok(1)
+            }
+            Self::Null => Ok(0),
+            Self::Random => {
+                let mut state = RANDOM_STATE.exclusive_access();
+                for chunk in buf.chunks_mut(8) {
+                    let bytes = xorshift64star(&mut state).to_ne_bytes();
+                    chunk.copy_from_slice(&bytes[..chunk.len()]);
+                }
+                Ok(buf.len())
+            }
+            Self::Zero => {
+                buf.fill(0);
+                Ok(buf.len())
+            }
+        }
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> Result<usize, isize> {
+        match self {
+            Self::Console => buf.iter().for_each(|&c| console_put_char(c as usize)),
+            // What is written is mixed into the state, like the entropy pool of Linux
+            Self::Random => {
+                let mut state = RANDOM_STATE.exclusive_access();
+                for &byte in buf {
+                    *state = state.rotate_left(8) ^ byte as u64;
+                    xorshift64star(&mut state);
+                }
+            }
+            Self::Null | Self::Zero => {}
+        }
+        Ok(buf.len())
+    }
+}
+
+lazy_static! {
+    /// Seeded with the time of the first use, and never 0, which xorshift cannot leave
+    static ref RANDOM_STATE: UPSyncCell<u64> = unsafe { UPSyncCell::new(get_time() as u64 | 1) };
+}
+
+fn xorshift64star(state: &mut u64) -> u64 {
+    *state ^= *state >> 12;
+    *state ^= *state << 25;
+    *state ^= *state >> 27;
+    if *state == 0 {
+        *state = 1;
+    }
+    state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+}
@@ -0,0 +1,130 @@
+//! easy-fs on a block device, as seen by the VFS
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+use easy_fs::{DiskInodeType, EasyFileSystem, NAME_LENGTH_LIMIT};
+
+use super::vfs::{FileSystem, Inode, InodeType};
+use crate::{
+    drivers::block::{block_device_errors, BlockDevice},
+    syscall::errno::{EINVAL, EIO, ENAMETOOLONG, ENOENT, ENOSPC, ENOTDIR, EPERM},
+};
+
+/// Run `f` on easy-fs, which cannot see failures of the device. Fail with `EIO` if the device
+/// failed meanwhile, as `f` may have worked on blocks which could not be read.
+fn check_io<T>(f: impl FnOnce() -> Result<T, isize>) -> Result<T, isize> {
+    let errors = block_device_errors();
+    let result = f();
+    match block_device_errors() == errors {
+        true => result,
+        false => Err(EIO),
+    }
+}
+
+pub struct EasyFs {
+    root_inode: Arc<easy_fs::Inode>,
+}
+
+impl EasyFs {
+    /// Open the easy-fs on `device`, or `None` if there is none or it cannot be read
+    pub fn open(device: Arc<dyn BlockDevice>) -> Option<Self> {
+        let efs = check_io(|| EasyFileSystem::open(device).ok_or(EINVAL)).ok()?;
+        Some(Self {
+            root_inode: Arc::new(EasyFileSystem::root_inode(&efs)),
+        })
+    }
+
+    /// Make an empty easy-fs on the whole `device`
+    pub fn format(device: Arc<dyn BlockDevice>) -> Self {
+        let total_blocks = device.num_blocks() as u32;
+        let efs = EasyFileSystem::create(device, total_blocks, 1);
+        Self {
+            root_inode: Arc::new(EasyFileSystem::root_inode(&efs)),
+        }
+    }
+}
+
+impl FileSystem for EasyFs {
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        Arc::new(EasyFsInode(self.root_inode.clone()))
+    }
+}
+
+struct EasyFsInode(Arc<easy_fs::Inode>);
+
+impl Inode for EasyFsInode {
+    fn type_(&self) -> InodeType {
+        match self.0.is_dir() {
+            true => InodeType::Dir,
+            false => InodeType::File,
+        }
+    }
+
+    /// The root is inode 0 of easy-fs, which cannot be an inode number
+    fn ino(&self) -> u64 {
+        self.0.inode_id() as u64 + 1
+    }
+
+    fn size(&self) -> usize {
+        self.0.size()
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, isize> {
+        check_io(|| Ok(self.0.read_at(offset, buf)))
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, isize> {
+        check_io(|| Ok(self.0.write_at(offset, buf)))
+    }
+
+    /// easy-fs can only drop all blocks of a file, so it cannot shrink to a size other than 0
+    fn truncate(&self, size: usize) -> Result<(), isize> {
+        check_io(|| {
+            let old_size = self.0.size();
+            if size == 0 {
+                self.0.clear();
+            } else if size > old_size {
+                // New blocks are zeroed when freed, so writing the last byte fills the file with
+                // zeros
+                if self.0.write_at(size - 1, &[0]) == 0 {
+                    return Err(ENOSPC);
+                }
+            } else if size < old_size {
+                return Err(EINVAL);
+            }
+            Ok(())
+        })
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, isize> {
+        if !self.0.is_dir() {
+            return Err(ENOTDIR);
+        }
+        let inode = check_io(|| self.0.find(name).ok_or(ENOENT))?;
+        Ok(Arc::new(EasyFsInode(inode)))
+    }
+
+    fn create(&self, name: &str, type_: InodeType) -> Result<Arc<dyn Inode>, isize> {
+        let type_ = match type_ {
+            InodeType::File => DiskInodeType::File,
+            InodeType::Dir => DiskInodeType::Directory,
+            InodeType::CharDevice | InodeType::Symlink => return Err(EINVAL),
+        };
+        if name.len() > NAME_LENGTH_LIMIT {
+            return Err(ENAMETOOLONG);
+        }
+        let inode = check_io(|| self.0.create(name, type_).ok_or(ENOSPC))?;
+        Ok(Arc::new(EasyFsInode(inode)))
+    }
+
+    /// A file of easy-fs has exactly one name
+    fn link(&self, _name: &str, _inode: Arc<dyn Inode>) -> Result<(), isize> {
+        Err(EPERM)
+    }
+
+    fn list(&self) -> Result<Vec<String>, isize> {
+        if !self.0.is_dir() {
+            return Err(ENOTDIR);
+        }
+        check_io(|| Ok(self.0.ls()))
+    }
+}
@@ -0,0 +1,1310 @@
+//! ext2 revisions 0 and 1, e.g. on a disk made on the host by `mke2fs -t ext2` and checked by
+//! `e2fsck -f` after a run. Everything read from the disk is checked, so a corrupt image makes
+//! operations fail with `EIO` instead of panicking.
+//!
+//! Only the primary superblock and group descriptors are updated, like Linux does, and the
+//! filesystem is never marked as mounted. Files keep the permissions they were made with, and new
+//! ones belong to root. Access times are not updated.
+
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec, vec::Vec};
+
+use super::vfs::{AsAny, FileSystem, Inode, InodeType};
+use crate::{
+    drivers::block::{get_block_cache, BlockDevice, BLOCK_SIZE},
+    syscall::errno::{
+        EEXIST, EFBIG, EINVAL, EIO, EISDIR, EMLINK, ENAMETOOLONG, ENODEV, ENOENT, ENOSPC, ENOTDIR,
+        ENOTEMPTY, EPERM, EROFS, EXDEV,
+    },
+    timer::{get_time_us, MICRO_PER_SEC},
+    upsync::UPSyncCell,
+};
+
+const SUPERBLOCK_OFFSET: u64 = 1024;
+const MAGIC: u16 = 0xef53;
+const DYNAMIC_REV: u32 = 1;
+/// Size of inodes and number of the first inode which is not reserved in revision 0
+const GOOD_OLD_INODE_SIZE: usize = 128;
+const GOOD_OLD_FIRST_INO: u32 = 11;
+const ROOT_INO: u32 = 2;
+const GROUP_DESC_SIZE: u64 = 32;
+
+/// Directory entries have a file type, in the high byte of the name length of revision 0
+const INCOMPAT_FILETYPE: u32 = 0x2;
+/// Read-only compatible features which the driver keeps when it writes. Others, e.g. `dir_nlink`
+/// of ext4, make the filesystem read-only.
+const RO_COMPAT_SPARSE_SUPER: u32 = 0x1;
+const RO_COMPAT_LARGE_FILE: u32 = 0x2;
+
+const S_IFMT: u16 = 0xf000;
+const S_IFLNK: u16 = 0xa000;
+const S_IFREG: u16 = 0x8000;
+const S_IFDIR: u16 = 0x4000;
+
+const FT_REG_FILE: u8 = 1;
+const FT_DIR: u8 = 2;
+const FT_SYMLINK: u8 = 7;
+
+/// Directory indexed by a hash tree, which becomes a plain one when the driver changes it
+const INDEX_FL: u32 = 0x1000;
+/// Limit of `i_links_count` of Linux
+const LINK_MAX: u16 = 32000;
+/// Blocks numbered in `i_block` directly, before the indirect, double and triple indirect ones
+const DIRECT_BLOCKS: u32 = 12;
+const INDIRECT_SLOTS: [usize; 3] = [12, 13, 14];
+/// Targets shorter than the 60 bytes of `i_block` are stored there
+const FAST_SYMLINK_SIZE: usize = 60;
+const MAX_NAME_LEN: usize = 255;
+/// `inode`, `rec_len`, `name_len` and `file_type` of a directory entry, before its name
+const DIR_ENTRY_HEADER_SIZE: usize = 8;
+/// `h_refcount` of an extended attribute block, which inodes may share
+const XATTR_REFCOUNT_OFFSET: u64 = 4;
+
+fn le16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn le32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// Size on the disk of a directory entry with a name of `name_len` bytes
+fn dir_entry_size(name_len: usize) -> usize {
+    (DIR_ENTRY_HEADER_SIZE + name_len).next_multiple_of(4)
+}
+
+fn check_range(device: &Arc<dyn BlockDevice>, offset: u64, len: usize) -> Result<(), isize> {
+    let device_size = (device.num_blocks() * BLOCK_SIZE) as u64;
+    match offset.checked_add(len as u64) {
+        Some(end) if end <= device_size => Ok(()),
+        _ => Err(EIO),
+    }
+}
+
+/// Read `buf.len()` bytes from `offset` of `device` through the block cache
+fn read_device(device: &Arc<dyn BlockDevice>, offset: u64, buf: &mut [u8]) -> Result<(), isize> {
+    check_range(device, offset, buf.len())?;
+    let mut done = 0;
+    while done < buf.len() {
+        let pos = offset + done as u64;
+        let start = (pos % BLOCK_SIZE as u64) as usize;
+        let len = (BLOCK_SIZE - start).min(buf.len() - done);
+        let block = get_block_cache((pos / BLOCK_SIZE as u64) as usize, device.clone());
+        let block = block.lock();
+        if block.is_failed() {
+            return Err(EIO);
+        }
+        block.read(0, |block: &[u8; BLOCK_SIZE]| {
+            buf[done..done + len].copy_from_slice(&block[start..start + len]);
+        });
+        done += len;
+    }
+    Ok(())
+}
+
+/// The first 128 bytes of an inode, which are all revision 0 has. The rest is left untouched.
+#[derive(Clone)]
+struct DiskInode([u8; GOOD_OLD_INODE_SIZE]);
+
+impl DiskInode {
+    fn mode(&self) -> u16 {
+        le16(&self.0, 0)
+    }
+
+    fn set_mode(&mut self, mode: u16) {
+        self.0[0..2].copy_from_slice(&mode.to_le_bytes());
+    }
+
+    fn file_type(&self) -> u16 {
+        self.mode() & S_IFMT
+    }
+
+    /// The high 32 bits are only used by regular files, as they are `i_dir_acl` of directories
+    fn size(&self) -> u64 {
+        let high = match self.file_type() {
+            S_IFREG => le32(&self.0, 108) as u64,
+            _ => 0,
+        };
+        le32(&self.0, 4) as u64 | high << 32
+    }
+
+    fn set_size(&mut self, size: u64) {
+        self.set32(4, size as u32);
+        if self.file_type() == S_IFREG {
+            self.set32(108, (size >> 32) as u32);
+        }
+    }
+
+    fn links(&self) -> u16 {
+        le16(&self.0, 26)
+    }
+
+    fn set_links(&mut self, links: u16) {
+        self.0[26..28].copy_from_slice(&links.to_le_bytes());
+    }
+
+    /// `i_blocks`, in 512-byte sectors
+    fn sectors(&self) -> u32 {
+        le32(&self.0, 28)
+    }
+
+    /// Add to `i_blocks`, which stays in range even if it was wrong
+    fn add_sectors(&mut self, sectors: i32) {
+        self.set32(28, self.sectors().saturating_add_signed(sectors));
+    }
+
+    fn flags(&self) -> u32 {
+        le32(&self.0, 32)
+    }
+
+    fn set_flags(&mut self, flags: u32) {
+        self.set32(32, flags);
+    }
+
+    fn block(&self, slot: usize) -> u32 {
+        le32(&self.0, 40 + slot * 4)
+    }
+
+    fn set_block(&mut self, slot: usize, block: u32) {
+        self.set32(40 + slot * 4, block);
+    }
+
+    /// The 60 bytes of `i_block`, which hold the target of a fast symbolic link
+    fn block_bytes(&mut self) -> &mut [u8] {
+        &mut self.0[40..40 + FAST_SYMLINK_SIZE]
+    }
+
+    /// Block of extended attributes, 0 without one
+    fn xattr_block(&self) -> u32 {
+        le32(&self.0, 104)
+    }
+
+    fn set_atime(&mut self, now: u32) {
+        self.set32(8, now);
+    }
+
+    /// Set `i_ctime` and `i_mtime`
+    fn touch(&mut self, now: u32) {
+        self.set32(12, now);
+        self.set32(16, now);
+    }
+
+    fn set_dtime(&mut self, now: u32) {
+        self.set32(20, now);
+    }
+
+    fn set32(&mut self, offset: usize, value: u32) {
+        self.0[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+/// A block group descriptor, of which only the counters change
+struct GroupDesc {
+    block_bitmap: u32,
+    inode_bitmap: u32,
+    inode_table: u32,
+    free_blocks: u16,
+    free_inodes: u16,
+    used_dirs: u16,
+}
+
+/// A directory entry as it is on the disk
+struct DirEntry {
+    /// Byte offset of the entry on the device
+    offset: u64,
+    /// Offset of the entry before it in the same block, which grows over it when it is removed
+    prev_offset: Option<u64>,
+    ino: u32,
+    rec_len: usize,
+    name: Vec<u8>,
+}
+
+struct Volume {
+    device: Arc<dyn BlockDevice>,
+    block_size: u64,
+    blocks_count: u32,
+    inodes_count: u32,
+    first_data_block: u32,
+    blocks_per_group: u32,
+    inodes_per_group: u32,
+    group_count: u32,
+    inode_size: u64,
+    first_ino: u32,
+    /// Directory entries have a file type
+    filetype: bool,
+    large_file: bool,
+    read_only: bool,
+    /// Seconds since the epoch when the filesystem was last written, as there is no clock. The
+    /// time is counted from it.
+    write_time: u32,
+    /// Number of [`Ext2Inode`]s of each inode. An inode without names is freed when it has none.
+    handles: UPSyncCell<BTreeMap<u32, usize>>,
+}
+
+impl Volume {
+    fn read(&self, offset: u64, buf: &mut [u8]) -> Result<(), isize> {
+        read_device(&self.device, offset, buf)
+    }
+
+    /// Write `buf` at `offset` of the device
+    fn write(&self, offset: u64, buf: &[u8]) -> Result<(), isize> {
+        check_range(&self.device, offset, buf.len())?;
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = offset + done as u64;
+            let start = (pos % BLOCK_SIZE as u64) as usize;
+            let len = (BLOCK_SIZE - start).min(buf.len() - done);
+            let block = get_block_cache((pos / BLOCK_SIZE as u64) as usize, self.device.clone());
+            let mut block = block.lock();
+            // The rest of the block is unknown, so it must not be written back
+            if block.is_failed() {
+                return Err(EIO);
+            }
+            block.modify(0, |block: &mut [u8; BLOCK_SIZE]| {
+                block[start..start + len].copy_from_slice(&buf[done..done + len]);
+            });
+            done += len;
+        }
+        Ok(())
+    }
+
+    fn read32(&self, offset: u64) -> Result<u32, isize> {
+        let mut bytes = [0u8; 4];
+        self.read(offset, &mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn write32(&self, offset: u64, value: u32) -> Result<(), isize> {
+        self.write(offset, &value.to_le_bytes())
+    }
+
+    fn check_writable(&self) -> Result<(), isize> {
+        match self.read_only {
+            true => Err(EROFS),
+            false => Ok(()),
+        }
+    }
+
+    fn now(&self) -> u32 {
+        self.write_time
+            .saturating_add((get_time_us() / MICRO_PER_SEC) as u32)
+    }
+
+    fn check_block(&self, block: u32) -> Result<(), isize> {
+        match block >= self.first_data_block && block < self.blocks_count {
+            true => Ok(()),
+            false => Err(EIO),
+        }
+    }
+
+    fn block_offset(&self, block: u32) -> u64 {
+        block as u64 * self.block_size
+    }
+
+    fn read_block(&self, block: u32) -> Result<Vec<u8>, isize> {
+        self.check_block(block)?;
+        let mut data = vec![0u8; self.block_size as usize];
+        self.read(self.block_offset(block), &mut data)?;
+        Ok(data)
+    }
+
+    /// Entry `index` of the indirect block `block`
+    fn block_entry_offset(&self, block: u32, index: u64) -> u64 {
+        self.block_offset(block) + index * 4
+    }
+
+    /// Sectors of `i_blocks` in a block
+    fn block_sectors(&self) -> i32 {
+        (self.block_size / 512) as i32
+    }
+
+    /// Block numbers in an indirect block
+    fn pointers_per_block(&self) -> u64 {
+        self.block_size / 4
+    }
+
+    fn group_desc_offset(&self, group: u32) -> u64 {
+        self.block_offset(self.first_data_block + 1) + group as u64 * GROUP_DESC_SIZE
+    }
+
+    fn group(&self, group: u32) -> Result<GroupDesc, isize> {
+        let mut desc = [0u8; 18];
+        self.read(self.group_desc_offset(group), &mut desc)?;
+        Ok(GroupDesc {
+            block_bitmap: le32(&desc, 0),
+            inode_bitmap: le32(&desc, 4),
+            inode_table: le32(&desc, 8),
+            free_blocks: le16(&desc, 12),
+            free_inodes: le16(&desc, 14),
+            used_dirs: le16(&desc, 16),
+        })
+    }
+
+    /// Write the counters of a group descriptor
+    fn set_group_counts(&self, group: u32, desc: &GroupDesc) -> Result<(), isize> {
+        let mut counts = [0u8; 6];
+        counts[0..2].copy_from_slice(&desc.free_blocks.to_le_bytes());
+        counts[2..4].copy_from_slice(&desc.free_inodes.to_le_bytes());
+        counts[4..6].copy_from_slice(&desc.used_dirs.to_le_bytes());
+        self.write(self.group_desc_offset(group) + 12, &counts)
+    }
+
+    /// Add `delta` to the counter of the superblock at `offset`
+    fn add_to_superblock(&self, offset: u64, delta: i32) -> Result<(), isize> {
+        let count = self.read32(SUPERBLOCK_OFFSET + offset)?;
+        self.write32(SUPERBLOCK_OFFSET + offset, count.wrapping_add_signed(delta))
+    }
+
+    /// Find a clear bit in the first `len` bits of the bitmap in `block`, and set it
+    fn take_bit(&self, block: u32, len: u32) -> Result<Option<u32>, isize> {
+        let mut bitmap = self.read_block(block)?;
+        let len = len.min(bitmap.len() as u32 * 8);
+        let Some(bit) = (0..len).find(|&bit| bitmap[bit as usize / 8] & 1 << (bit % 8) == 0) else {
+            return Ok(None);
+        };
+        bitmap[bit as usize / 8] |= 1 << (bit % 8);
+        self.write(self.block_offset(block) + bit as u64 / 8, &bitmap[bit as usize / 8..][..1])?;
+        Ok(Some(bit))
+    }
+
+    /// Clear a bit set in the bitmap in `block`
+    fn clear_bit(&self, block: u32, bit: u32) -> Result<(), isize> {
+        self.check_block(block)?;
+        let offset = self.block_offset(block) + bit as u64 / 8;
+        let mut byte = [0u8];
+        self.read(offset, &mut byte)?;
+        if byte[0] & 1 << (bit % 8) == 0 {
+            return Err(EIO);
+        }
+        byte[0] &= !(1 << (bit % 8));
+        self.write(offset, &byte)
+    }
+
+    /// Allocate a zeroed block, in the group `goal` if it has a free one
+    fn alloc_block(&self, goal: u32) -> Result<u32, isize> {
+        for group in (goal..self.group_count).chain(0..goal) {
+            let mut desc = self.group(group)?;
+            if desc.free_blocks == 0 {
+                continue;
+            }
+            let first = self.first_data_block + group * self.blocks_per_group;
+            let len = (self.blocks_count - first).min(self.blocks_per_group);
+            let Some(bit) = self.take_bit(desc.block_bitmap, len)? else {
+                continue;
+            };
+            desc.free_blocks -= 1;
+            self.set_group_counts(group, &desc)?;
+            self.add_to_superblock(12, -1)?;
+            let block = first + bit;
+            self.write(self.block_offset(block), &vec![0u8; self.block_size as usize])?;
+            return Ok(block);
+        }
+        Err(ENOSPC)
+    }
+
+    fn free_block(&self, block: u32) -> Result<(), isize> {
+        self.check_block(block)?;
+        let group = (block - self.first_data_block) / self.blocks_per_group;
+        let mut desc = self.group(group)?;
+        self.clear_bit(desc.block_bitmap, (block - self.first_data_block) % self.blocks_per_group)?;
+        desc.free_blocks = desc.free_blocks.saturating_add(1);
+        self.set_group_counts(group, &desc)?;
+        self.add_to_superblock(12, 1)
+    }
+
+    fn group_of(&self, ino: u32) -> u32 {
+        (ino - 1) / self.inodes_per_group
+    }
+
+    /// Allocate a zeroed inode, in the group `goal` if it has a free one
+    fn alloc_inode(&self, goal: u32, is_dir: bool) -> Result<u32, isize> {
+        for group in (goal..self.group_count).chain(0..goal) {
+            let mut desc = self.group(group)?;
+            if desc.free_inodes == 0 {
+                continue;
+            }
+            let Some(bit) = self.take_bit(desc.inode_bitmap, self.inodes_per_group)? else {
+                continue;
+            };
+            let ino = group * self.inodes_per_group + bit + 1;
+            // Reserved inodes are marked as used, so this only happens to a corrupt bitmap
+            if ino < self.first_ino || ino > self.inodes_count {
+                return Err(EIO);
+            }
+            desc.free_inodes -= 1;
+            desc.used_dirs = desc.used_dirs.saturating_add(is_dir as u16);
+            self.set_group_counts(group, &desc)?;
+            self.add_to_superblock(16, -1)?;
+            self.write(self.inode_offset(ino)?, &vec![0u8; self.inode_size as usize])?;
+            return Ok(ino);
+        }
+        Err(ENOSPC)
+    }
+
+    fn free_inode(&self, ino: u32, is_dir: bool) -> Result<(), isize> {
+        let group = self.group_of(ino);
+        let mut desc = self.group(group)?;
+        self.clear_bit(desc.inode_bitmap, (ino - 1) % self.inodes_per_group)?;
+        desc.free_inodes = desc.free_inodes.saturating_add(1);
+        desc.used_dirs = desc.used_dirs.saturating_sub(is_dir as u16);
+        self.set_group_counts(group, &desc)?;
+        self.add_to_superblock(16, 1)
+    }
+
+    fn inode_offset(&self, ino: u32) -> Result<u64, isize> {
+        if ino == 0 || ino > self.inodes_count {
+            return Err(EIO);
+        }
+        let desc = self.group(self.group_of(ino))?;
+        let index = ((ino - 1) % self.inodes_per_group) as u64;
+        Ok(self.block_offset(desc.inode_table) + index * self.inode_size)
+    }
+
+    fn read_inode(&self, ino: u32) -> Result<DiskInode, isize> {
+        let mut inode = DiskInode([0; GOOD_OLD_INODE_SIZE]);
+        self.read(self.inode_offset(ino)?, &mut inode.0)?;
+        Ok(inode)
+    }
+
+    fn write_inode(&self, ino: u32, inode: &DiskInode) -> Result<(), isize> {
+        self.write(self.inode_offset(ino)?, &inode.0)
+    }
+
+    /// The block holding block `index` of an inode, or 0 for a hole. With `alloc`, the missing
+    /// blocks on the way are allocated in the group of inode `ino`.
+    fn map_block(
+        &self,
+        ino: u32,
+        inode: &mut DiskInode,
+        index: u64,
+        alloc: bool,
+    ) -> Result<u32, isize> {
+        // Indices into `i_block` and then into each indirect block
+        let ppb = self.pointers_per_block();
+        let mut path = Vec::with_capacity(4);
+        if index < DIRECT_BLOCKS as u64 {
+            path.push(index as usize);
+        } else {
+            let mut rest = index - DIRECT_BLOCKS as u64;
+            let mut span = ppb;
+            let mut level = 0;
+            while rest >= span {
+                rest -= span;
+                span *= ppb;
+                level += 1;
+                if level == INDIRECT_SLOTS.len() {
+                    return Err(EFBIG);
+                }
+            }
+            path.push(INDIRECT_SLOTS[level]);
+            for _ in 0..=level {
+                span /= ppb;
+                path.push((rest / span) as usize);
+                rest %= span;
+            }
+        }
+
+        let mut block = inode.block(path[0]);
+        if block == 0 {
+            if !alloc {
+                return Ok(0);
+            }
+            block = self.alloc_block(self.group_of(ino))?;
+            inode.set_block(path[0], block);
+            inode.add_sectors(self.block_sectors());
+        }
+        for &entry in &path[1..] {
+            self.check_block(block)?;
+            let entry_offset = self.block_entry_offset(block, entry as u64);
+            let mut next = self.read32(entry_offset)?;
+            if next == 0 {
+                if !alloc {
+                    return Ok(0);
+                }
+                next = self.alloc_block(self.group_of(ino))?;
+                self.write32(entry_offset, next)?;
+                inode.add_sectors(self.block_sectors());
+            }
+            block = next;
+        }
+        self.check_block(block)?;
+        Ok(block)
+    }
+
+    /// Free the blocks of an inode from block `first` on, with the indirect blocks left empty
+    fn free_blocks_from(&self, inode: &mut DiskInode, first: u64) -> Result<(), isize> {
+        for slot in first.min(DIRECT_BLOCKS as u64) as usize..DIRECT_BLOCKS as usize {
+            let block = inode.block(slot);
+            if block != 0 {
+                self.free_block(block)?;
+                inode.set_block(slot, 0);
+                inode.add_sectors(-self.block_sectors());
+            }
+        }
+        let mut base = DIRECT_BLOCKS as u64;
+        let mut span = 1;
+        for (depth, slot) in INDIRECT_SLOTS.into_iter().enumerate() {
+            span *= self.pointers_per_block();
+            let block = inode.block(slot);
+            if block != 0 && first < base + span {
+                let start = first.saturating_sub(base);
+                if self.free_tree(inode, block, depth as u32 + 1, start)? {
+                    self.free_block(block)?;
+                    inode.set_block(slot, 0);
+                    inode.add_sectors(-self.block_sectors());
+                }
+            }
+            base += span;
+        }
+        Ok(())
+    }
+
+    /// Free the blocks from `start` on under the indirect block `block`, which is `depth` levels
+    /// above the data, and return whether none are left in it
+    fn free_tree(
+        &self,
+        inode: &mut DiskInode,
+        block: u32,
+        depth: u32,
+        start: u64,
+    ) -> Result<bool, isize> {
+        let entries = self.read_block(block)?;
+        let child_span = self.pointers_per_block().pow(depth - 1);
+        let mut empty = true;
+        for (i, entry) in entries.chunks_exact(4).enumerate() {
+            let child = le32(entry, 0);
+            if child == 0 {
+                continue;
+            }
+            let child_start = i as u64 * child_span;
+            if child_start + child_span <= start {
+                empty = false;
+                continue;
+            }
+            let child_empty = match depth {
+                1 => true,
+                _ => self.free_tree(inode, child, depth - 1, start.saturating_sub(child_start))?,
+            };
+            if child_empty {
+                self.free_block(child)?;
+                self.write32(self.block_entry_offset(block, i as u64), 0)?;
+                inode.add_sectors(-self.block_sectors());
+            } else {
+                empty = false;
+            }
+        }
+        Ok(empty)
+    }
+
+    /// Free an inode and its blocks, which is left without names
+    fn release(&self, ino: u32) -> Result<(), isize> {
+        let mut inode = self.read_inode(ino)?;
+        let is_fast_symlink = self.is_fast_symlink(&inode);
+        if !is_fast_symlink {
+            self.free_blocks_from(&mut inode, 0)?;
+        }
+        let xattr_block = inode.xattr_block();
+        if xattr_block != 0 {
+            let refcount_offset = self.block_offset(xattr_block) + XATTR_REFCOUNT_OFFSET;
+            self.check_block(xattr_block)?;
+            match self.read32(refcount_offset)? {
+                0 | 1 => self.free_block(xattr_block)?,
+                refcount => self.write32(refcount_offset, refcount - 1)?,
+            }
+        }
+        inode.set_links(0);
+        inode.set_dtime(self.now());
+        self.write_inode(ino, &inode)?;
+        self.free_inode(ino, inode.file_type() == S_IFDIR)
+    }
+
+    /// Whether the target of a symbolic link is stored in its inode. The block of extended
+    /// attributes counts in `i_blocks` as well.
+    fn is_fast_symlink(&self, inode: &DiskInode) -> bool {
+        let xattr_sectors = match inode.xattr_block() {
+            0 => 0,
+            _ => (self.block_size / 512) as u32,
+        };
+        inode.file_type() == S_IFLNK && inode.sectors() == xattr_sectors
+    }
+
+    /// Entries of directory `ino`, including the unused ones
+    fn dir_entries(&self, ino: u32) -> Result<Vec<DirEntry>, isize> {
+        let mut inode = self.read_inode(ino)?;
+        if inode.file_type() != S_IFDIR {
+            return Err(ENOTDIR);
+        }
+        let mut entries = Vec::new();
+        for index in 0..inode.size().div_ceil(self.block_size) {
+            let block = self.map_block(ino, &mut inode, index, false)?;
+            if block == 0 {
+                return Err(EIO);
+            }
+            let data = self.read_block(block)?;
+            let mut pos = 0;
+            let mut prev_offset = None;
+            while pos < data.len() {
+                if pos + DIR_ENTRY_HEADER_SIZE > data.len() {
+                    return Err(EIO);
+                }
+                let rec_len = le16(&data, pos + 4) as usize;
+                let name_len = match self.filetype {
+                    true => data[pos + 6] as usize,
+                    false => le16(&data, pos + 6) as usize,
+                };
+                if rec_len % 4 != 0
+                    || rec_len < dir_entry_size(name_len)
+                    || pos + rec_len > data.len()
+                {
+                    return Err(EIO);
+                }
+                let offset = self.block_offset(block) + pos as u64;
+                let name_start = pos + DIR_ENTRY_HEADER_SIZE;
+                entries.push(DirEntry {
+                    offset,
+                    prev_offset,
+                    ino: le32(&data, pos),
+                    rec_len,
+                    name: data[name_start..name_start + name_len].to_vec(),
+                });
+                prev_offset = Some(offset);
+                pos += rec_len;
+            }
+        }
+        Ok(entries)
+    }
+
+    /// The used entry `name` of directory `ino`
+    fn find_entry(&self, ino: u32, name: &str) -> Result<DirEntry, isize> {
+        self.dir_entries(ino)?
+            .into_iter()
+            .find(|entry| entry.ino != 0 && entry.name == name.as_bytes())
+            .ok_or(ENOENT)
+    }
+
+    /// Write an entry at `offset` of the device
+    fn write_entry(
+        &self,
+        offset: u64,
+        rec_len: usize,
+        name: &[u8],
+        ino: u32,
+        file_type: u8,
+    ) -> Result<(), isize> {
+        let mut entry = vec![0u8; dir_entry_size(name.len())];
+        entry[0..4].copy_from_slice(&ino.to_le_bytes());
+        entry[4..6].copy_from_slice(&(rec_len as u16).to_le_bytes());
+        entry[6] = name.len() as u8;
+        entry[7] = if self.filetype { file_type } else { 0 };
+        entry[DIR_ENTRY_HEADER_SIZE..DIR_ENTRY_HEADER_SIZE + name.len()].copy_from_slice(name);
+        self.write(offset, &entry)
+    }
+
+    /// Add the entry `name` for inode `child` to directory `ino`, in the first gap large enough
+    /// or in a new block at the end
+    fn add_entry(&self, ino: u32, name: &str, child: u32, file_type: u8) -> Result<(), isize> {
+        if name.len() > MAX_NAME_LEN {
+            return Err(ENAMETOOLONG);
+        }
+        let size = dir_entry_size(name.len());
+        for entry in self.dir_entries(ino)? {
+            if entry.ino == 0 && entry.rec_len >= size {
+                return self.write_entry(entry.offset, entry.rec_len, name.as_bytes(), child, file_type);
+            }
+            let used = dir_entry_size(entry.name.len());
+            if entry.ino != 0 && entry.rec_len - used >= size {
+                let rec_len = (used as u16).to_le_bytes();
+                self.write(entry.offset + 4, &rec_len)?;
+                let offset = entry.offset + used as u64;
+                let rec_len = entry.rec_len - used;
+                return self.write_entry(offset, rec_len, name.as_bytes(), child, file_type);
+            }
+        }
+        let mut inode = self.read_inode(ino)?;
+        let index = inode.size().div_ceil(self.block_size);
+        let block = self.map_block(ino, &mut inode, index, true);
+        // Blocks allocated on the way to a failure are still counted
+        self.write_inode(ino, &inode)?;
+        let offset = self.block_offset(block?);
+        self.write_entry(offset, self.block_size as usize, name.as_bytes(), child, file_type)?;
+        inode.set_size((index + 1) * self.block_size);
+        inode.touch(self.now());
+        self.write_inode(ino, &inode)
+    }
+
+    /// Remove the entry `name` from directory `ino`, and return it
+    fn remove_entry(&self, ino: u32, name: &str) -> Result<DirEntry, isize> {
+        let entry = self.find_entry(ino, name)?;
+        match entry.prev_offset {
+            Some(prev_offset) => {
+                let mut rec_len = [0u8; 2];
+                self.read(prev_offset + 4, &mut rec_len)?;
+                let rec_len = u16::from_le_bytes(rec_len) as usize + entry.rec_len;
+                self.write(prev_offset + 4, &(rec_len as u16).to_le_bytes())?;
+            }
+            None => self.write32(entry.offset, 0)?,
+        }
+        self.touch_dir(ino)?;
+        Ok(entry)
+    }
+
+    /// Update the times of a changed directory, which is no longer indexed if it was
+    fn touch_dir(&self, ino: u32) -> Result<(), isize> {
+        let mut inode = self.read_inode(ino)?;
+        inode.touch(self.now());
+        inode.set_flags(inode.flags() & !INDEX_FL);
+        self.write_inode(ino, &inode)
+    }
+
+    /// Add `delta` to the number of links of inode `ino`, and free it when it has no names and no
+    /// handles
+    fn add_links(&self, ino: u32, delta: i16) -> Result<(), isize> {
+        let mut inode = self.read_inode(ino)?;
+        let links = inode.links().saturating_add_signed(delta);
+        if links >= LINK_MAX {
+            return Err(EMLINK);
+        }
+        inode.set_links(links);
+        inode.touch(self.now());
+        self.write_inode(ino, &inode)?;
+        if links == 0 && !self.handles.borrow().contains_key(&ino) {
+            self.release(ino)?;
+        }
+        Ok(())
+    }
+
+    /// Whether directory `ino` has no entries other than `.` and `..`
+    fn is_empty_dir(&self, ino: u32) -> Result<bool, isize> {
+        Ok(self
+            .dir_entries(ino)?
+            .iter()
+            .all(|entry| entry.ino == 0 || entry.name == b"." || entry.name == b".."))
+    }
+
+    /// Allocate inode with `mode` and one link, near its parent directory `parent`
+    fn new_inode(&self, parent: u32, mode: u16) -> Result<(u32, DiskInode), isize> {
+        let ino = self.alloc_inode(self.group_of(parent), mode & S_IFMT == S_IFDIR)?;
+        let mut inode = self.read_inode(ino)?;
+        inode.set_mode(mode);
+        inode.set_links(1);
+        let now = self.now();
+        inode.set_atime(now);
+        inode.touch(now);
+        self.write_inode(ino, &inode)?;
+        Ok((ino, inode))
+    }
+}
+
+pub struct Ext2 {
+    volume: Arc<Volume>,
+}
+
+impl Ext2 {
+    /// Open the ext2 filesystem on `device`. Fail with `EINVAL` if there is none, or if it has
+    /// incompatible features. Read-only compatible features which are not supported make it
+    /// read-only.
+    pub fn open(device: Arc<dyn BlockDevice>) -> Result<Self, isize> {
+        let mut sb = [0u8; 1024];
+        read_device(&device, SUPERBLOCK_OFFSET, &mut sb).map_err(|_| EINVAL)?;
+        let device_size = (device.num_blocks() * BLOCK_SIZE) as u64;
+        if le16(&sb, 56) != MAGIC {
+            return Err(EINVAL);
+        }
+        let inodes_count = le32(&sb, 0);
+        let blocks_count = le32(&sb, 4);
+        let first_data_block = le32(&sb, 20);
+        let log_block_size = le32(&sb, 24);
+        let blocks_per_group = le32(&sb, 32);
+        let inodes_per_group = le32(&sb, 40);
+        let write_time = le32(&sb, 48);
+        let rev_level = le32(&sb, 76);
+        let (inode_size, first_ino, incompat, ro_compat) = match rev_level {
+            0 => (GOOD_OLD_INODE_SIZE as u64, GOOD_OLD_FIRST_INO, 0, 0),
+            DYNAMIC_REV => (le16(&sb, 88) as u64, le32(&sb, 84), le32(&sb, 96), le32(&sb, 100)),
+            _ => return Err(EINVAL),
+        };
+        if incompat & !INCOMPAT_FILETYPE != 0 || log_block_size > 6 {
+            return Err(EINVAL);
+        }
+        let block_size = 1024u64 << log_block_size;
+        let bits_per_block = block_size as u32 * 8;
+        // The superblock is in block 1 of 1 KiB blocks, and in block 0 of larger ones
+        let data_block_ok = first_data_block == (block_size == 1024) as u32;
+        if !data_block_ok
+            || blocks_count <= first_data_block
+            || blocks_per_group == 0
+            || blocks_per_group > bits_per_block
+            || inodes_per_group == 0
+            || inodes_per_group > bits_per_block
+            || !inode_size.is_power_of_two()
+            || inode_size < GOOD_OLD_INODE_SIZE as u64
+            || inode_size > block_size
+            || first_ino <= ROOT_INO
+            || blocks_count as u64 * block_size > device_size
+        {
+            return Err(EINVAL);
+        }
+        let group_count = (blocks_count - first_data_block).div_ceil(blocks_per_group);
+        if inodes_count > group_count.saturating_mul(inodes_per_group) {
+            return Err(EINVAL);
+        }
+
+        let volume = Volume {
+            device,
+            block_size,
+            blocks_count,
+            inodes_count,
+            first_data_block,
+            blocks_per_group,
+            inodes_per_group,
+            group_count,
+            inode_size,
+            first_ino,
+            filetype: incompat & INCOMPAT_FILETYPE != 0,
+            large_file: ro_compat & RO_COMPAT_LARGE_FILE != 0,
+            read_only: ro_compat & !(RO_COMPAT_SPARSE_SUPER | RO_COMPAT_LARGE_FILE) != 0,
+            write_time,
+            handles: unsafe { UPSyncCell::new(BTreeMap::new()) },
+        };
+        if volume.read_inode(ROOT_INO)?.file_type() != S_IFDIR {
+            return Err(EINVAL);
+        }
+        Ok(Self {
+            volume: Arc::new(volume),
+        })
+    }
+}
+
+impl FileSystem for Ext2 {
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        Arc::new(Ext2Inode::new(self.volume.clone(), ROOT_INO, InodeType::Dir))
+    }
+}
+
+/// An inode in use, which keeps it from being freed when it has no names
+struct Ext2Inode {
+    volume: Arc<Volume>,
+    ino: u32,
+    type_: InodeType,
+}
+
+impl Ext2Inode {
+    fn new(volume: Arc<Volume>, ino: u32, type_: InodeType) -> Self {
+        *volume.handles.exclusive_access().entry(ino).or_insert(0) += 1;
+        Self { volume, ino, type_ }
+    }
+
+    /// Block devices, FIFOs and sockets cannot be opened, and are taken as character devices
+    fn open(volume: &Arc<Volume>, ino: u32) -> Result<Self, isize> {
+        let type_ = match volume.read_inode(ino)?.file_type() {
+            S_IFREG => InodeType::File,
+            S_IFDIR => InodeType::Dir,
+            S_IFLNK => InodeType::Symlink,
+            _ => InodeType::CharDevice,
+        };
+        Ok(Self::new(volume.clone(), ino, type_))
+    }
+
+    fn check_dir(&self) -> Result<(), isize> {
+        match self.type_ {
+            InodeType::Dir => Ok(()),
+            _ => Err(ENOTDIR),
+        }
+    }
+
+    fn check_file(&self) -> Result<(), isize> {
+        match self.type_ {
+            InodeType::File => Ok(()),
+            InodeType::Dir => Err(EISDIR),
+            InodeType::CharDevice => Err(ENODEV),
+            InodeType::Symlink => Err(EINVAL),
+        }
+    }
+
+    /// Check that `name` can be added to this directory
+    fn check_new_name(&self, name: &str) -> Result<(), isize> {
+        self.check_dir()?;
+        self.volume.check_writable()?;
+        if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+            return Err(EINVAL);
+        }
+        if name.len() > MAX_NAME_LEN {
+            return Err(ENAMETOOLONG);
+        }
+        match self.volume.find_entry(self.ino, name) {
+            Ok(_) => Err(EEXIST),
+            Err(ENOENT) => Ok(()),
+            Err(errno) => Err(errno),
+        }
+    }
+
+    /// Write `buf` at `offset` from `pos` on, allocating blocks, and advance `pos` past what is
+    /// written
+    fn write_blocks(
+        &self,
+        inode: &mut DiskInode,
+        offset: u64,
+        buf: &[u8],
+        pos: &mut u64,
+    ) -> Result<(), isize> {
+        let volume = &self.volume;
+        let end = offset + buf.len() as u64;
+        while *pos < end {
+            let start = *pos % volume.block_size;
+            let len = (volume.block_size - start).min(end - *pos);
+            let block = volume.map_block(self.ino, inode, *pos / volume.block_size, true)?;
+            let src = &buf[(*pos - offset) as usize..(*pos - offset + len) as usize];
+            volume.write(volume.block_offset(block) + start, src)?;
+            *pos += len;
+        }
+        Ok(())
+    }
+
+    fn largest_size(&self) -> u64 {
+        match self.volume.large_file {
+            true => u64::MAX,
+            false => i32::MAX as u64,
+        }
+    }
+
+    fn mkdir(&self, name: &str) -> Result<u32, isize> {
+        let volume = &self.volume;
+        let (ino, mut inode) = volume.new_inode(self.ino, S_IFDIR | 0o755)?;
+        let block = match volume.map_block(ino, &mut inode, 0, true) {
+            Ok(block) => block,
+            Err(errno) => {
+                volume.release(ino)?;
+                return Err(errno);
+            }
+        };
+        let offset = volume.block_offset(block);
+        let dot_len = dir_entry_size(1);
+        volume.write_entry(offset, dot_len, b".", ino, FT_DIR)?;
+        let dotdot_len = volume.block_size as usize - dot_len;
+        volume.write_entry(offset + dot_len as u64, dotdot_len, b"..", self.ino, FT_DIR)?;
+        inode.set_size(volume.block_size);
+        inode.set_links(2);
+        volume.write_inode(ino, &inode)?;
+        if let Err(errno) = volume.add_entry(self.ino, name, ino, FT_DIR) {
+            volume.release(ino)?;
+            return Err(errno);
+        }
+        volume.add_links(self.ino, 1)?;
+        Ok(ino)
+    }
+
+    fn mkfile(&self, name: &str) -> Result<u32, isize> {
+        let volume = &self.volume;
+        let (ino, _) = volume.new_inode(self.ino, S_IFREG | 0o644)?;
+        if let Err(errno) = volume.add_entry(self.ino, name, ino, FT_REG_FILE) {
+            volume.release(ino)?;
+            return Err(errno);
+        }
+        Ok(ino)
+    }
+}
+
+impl Drop for Ext2Inode {
+    fn drop(&mut self) {
+        let mut handles = self.volume.handles.exclusive_access();
+        let count = handles.get_mut(&self.ino).unwrap();
+        *count -= 1;
+        if *count > 0 {
+            return;
+        }
+        handles.remove(&self.ino);
+        drop(handles);
+        let unlinked = matches!(self.volume.read_inode(self.ino), Ok(inode) if inode.links() == 0);
+        if unlinked && !self.volume.read_only {
+            // Nothing can report the error, and the inode is left for `e2fsck`
+            let _ = self.volume.release(self.ino);
+        }
+    }
+}
+
+impl Inode for Ext2Inode {
+    fn type_(&self) -> InodeType {
+        self.type_
+    }
+
+    fn ino(&self) -> u64 {
+        self.ino as u64
+    }
+
+    fn nlink(&self) -> usize {
+        self.volume
+            .read_inode(self.ino)
+            .map_or(0, |inode| inode.links() as usize)
+    }
+
+    fn size(&self) -> usize {
+        self.volume
+            .read_inode(self.ino)
+            .map_or(0, |inode| inode.size() as usize)
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, isize> {
+        self.check_file()?;
+        let volume = &self.volume;
+        let mut inode = volume.read_inode(self.ino)?;
+        let offset = offset as u64;
+        let end = inode.size().min(offset.saturating_add(buf.len() as u64));
+        let mut pos = offset;
+        while pos < end {
+            let start = pos % volume.block_size;
+            let len = (volume.block_size - start).min(end - pos);
+            let dst = &mut buf[(pos - offset) as usize..(pos - offset + len) as usize];
+            match volume.map_block(self.ino, &mut inode, pos / volume.block_size, false)? {
+                0 => dst.fill(0),
+                block => volume.read(volume.block_offset(block) + start, dst)?,
+            }
+            pos += len;
+        }
+        Ok(end.saturating_sub(offset) as usize)
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, isize> {
+        self.check_file()?;
+        let volume = &self.volume;
+        volume.check_writable()?;
+        let offset = offset as u64;
+        let end = offset.saturating_add(buf.len() as u64);
+        if end > self.largest_size() {
+            return Err(EFBIG);
+        }
+        let mut inode = volume.read_inode(self.ino)?;
+        let mut pos = offset;
+        let result = self.write_blocks(&mut inode, offset, buf, &mut pos);
+        // Blocks allocated before a failure are still counted
+        if pos > inode.size() {
+            inode.set_size(pos);
+        }
+        inode.touch(volume.now());
+        volume.write_inode(self.ino, &inode)?;
+        match result {
+            // A full disk is a short write
+            Ok(()) | Err(ENOSPC) => Ok((pos - offset) as usize),
+            Err(errno) if pos == offset => Err(errno),
+            Err(_) => Ok((pos - offset) as usize),
+        }
+    }
+
+    /// The end of the last block is zeroed when a file shrinks, so it reads as zeros if it grows
+    /// again. A file which grows has a hole at its end.
+    fn truncate(&self, size: usize) -> Result<(), isize> {
+        self.check_file()?;
+        let volume = &self.volume;
+        volume.check_writable()?;
+        let size = size as u64;
+        if size > self.largest_size() {
+            return Err(EFBIG);
+        }
+        let mut inode = volume.read_inode(self.ino)?;
+        if size < inode.size() {
+            volume.free_blocks_from(&mut inode, size.div_ceil(volume.block_size))?;
+            let tail = size % volume.block_size;
+            if tail != 0 {
+                let block = volume.map_block(self.ino, &mut inode, size / volume.block_size, false)?;
+                if block != 0 {
+                    let zeros = vec![0u8; (volume.block_size - tail) as usize];
+                    volume.write(volume.block_offset(block) + tail, &zeros)?;
+                }
+            }
+        }
+        inode.set_size(size);
+        inode.touch(volume.now());
+        volume.write_inode(self.ino, &inode)
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, isize> {
+        self.check_dir()?;
+        let entry = self.volume.find_entry(self.ino, name)?;
+        Ok(Arc::new(Ext2Inode::open(&self.volume, entry.ino)?))
+    }
+
+    fn create(&self, name: &str, type_: InodeType) -> Result<Arc<dyn Inode>, isize> {
+        self.check_new_name(name)?;
+        let ino = match type_ {
+            InodeType::File => self.mkfile(name)?,
+            InodeType::Dir => self.mkdir(name)?,
+            InodeType::CharDevice | InodeType::Symlink => return Err(EINVAL),
+        };
+        Ok(Arc::new(Ext2Inode::new(self.volume.clone(), ino, type_)))
+    }
+
+    fn symlink(&self, name: &str, target: &str) -> Result<(), isize> {
+        self.check_new_name(name)?;
+        let volume = &self.volume;
+        if target.len() >= volume.block_size as usize {
+            return Err(ENAMETOOLONG);
+        }
+        let (ino, mut inode) = volume.new_inode(self.ino, S_IFLNK | 0o777)?;
+        let mut result = Ok(());
+        if target.len() < FAST_SYMLINK_SIZE {
+            inode.block_bytes()[..target.len()].copy_from_slice(target.as_bytes());
+        } else {
+            result = volume
+                .map_block(ino, &mut inode, 0, true)
+                .and_then(|block| volume.write(volume.block_offset(block), target.as_bytes()));
+        }
+        inode.set_size(target.len() as u64);
+        volume.write_inode(ino, &inode)?;
+        result = result.and_then(|_| volume.add_entry(self.ino, name, ino, FT_SYMLINK));
+        if result.is_err() {
+            volume.release(ino)?;
+        }
+        result
+    }
+
+    fn read_link(&self) -> Result<String, isize> {
+        if self.type_ != InodeType::Symlink {
+            return Err(EINVAL);
+        }
+        let volume = &self.volume;
+        let mut inode = volume.read_inode(self.ino)?;
+        let size = inode.size() as usize;
+        let target = if volume.is_fast_symlink(&inode) {
+            inode.block_bytes().get(..size).ok_or(EIO)?.to_vec()
+        } else {
+            let block = volume.map_block(self.ino, &mut inode, 0, false)?;
+            if block == 0 || size > volume.block_size as usize {
+                return Err(EIO);
+            }
+            let mut target = vec![0u8; size];
+            volume.read(volume.block_offset(block), &mut target)?;
+            target
+        };
+        String::from_utf8(target).map_err(|_| EIO)
+    }
+
+    fn link(&self, name: &str, inode: Arc<dyn Inode>) -> Result<(), isize> {
+        let inode = AsAny::into_any(inode)
+            .downcast::<Ext2Inode>()
+            .map_err(|_| EXDEV)?;
+        if !Arc::ptr_eq(&inode.volume, &self.volume) {
+            return Err(EXDEV);
+        }
+        if inode.type_ == InodeType::Dir {
+            return Err(EPERM);
+        }
+        self.check_new_name(name)?;
+        let volume = &self.volume;
+        volume.add_links(inode.ino, 1)?;
+        let file_type = match inode.type_ {
+            InodeType::Symlink => FT_SYMLINK,
+            _ => FT_REG_FILE,
+        };
+        if let Err(errno) = volume.add_entry(self.ino, name, inode.ino, file_type) {
+            volume.add_links(inode.ino, -1)?;
+            return Err(errno);
+        }
+        Ok(())
+    }
+
+    fn unlink(&self, name: &str) -> Result<(), isize> {
+        self.check_dir()?;
+        let volume = &self.volume;
+        volume.check_writable()?;
+        let entry = volume.find_entry(self.ino, name)?;
+        if volume.read_inode(entry.ino)?.file_type() == S_IFDIR {
+            return Err(EISDIR);
+        }
+        volume.remove_entry(self.ino, name)?;
+        volume.add_links(entry.ino, -1)
+    }
+
+    fn rmdir(&self, name: &str) -> Result<(), isize> {
+        self.check_dir()?;
+        let volume = &self.volume;
+        volume.check_writable()?;
+        let entry = volume.find_entry(self.ino, name)?;
+        let mut inode = volume.read_inode(entry.ino)?;
+        if inode.file_type() != S_IFDIR {
+            return Err(ENOTDIR);
+        }
+        if !volume.is_empty_dir(entry.ino)? {
+            return Err(ENOTEMPTY);
+        }
+        volume.remove_entry(self.ino, name)?;
+        // Its `.` and the entry in this directory
+        inode.set_links(1);
+        volume.write_inode(entry.ino, &inode)?;
+        volume.add_links(entry.ino, -1)?;
+        // Its `..`
+        volume.add_links(self.ino, -1)
+    }
+
+    fn rename(&self, old_name: &str, new_dir: &dyn Inode, new_name: &str) -> Result<(), isize> {
+        let new_dir = AsAny::as_any(new_dir)
+            .downcast_ref::<Ext2Inode>()
+            .ok_or(EXDEV)?;
+        if !Arc::ptr_eq(&new_dir.volume, &self.volume) {
+            return Err(EXDEV);
+        }
+        self.check_dir()?;
+        new_dir.check_dir()?;
+        let volume = &self.volume;
+        volume.check_writable()?;
+        if new_name.is_empty() || new_name == "." || new_name == ".." || new_name.contains('/') {
+            return Err(EINVAL);
+        }
+        if new_name.len() > MAX_NAME_LEN {
+            return Err(ENAMETOOLONG);
+        }
+        let entry = volume.find_entry(self.ino, old_name)?;
+        let inode = volume.read_inode(entry.ino)?;
+        let is_dir = inode.file_type() == S_IFDIR;
+        let file_type = match inode.file_type() {
+            S_IFDIR => FT_DIR,
+            S_IFLNK => FT_SYMLINK,
+            _ => FT_REG_FILE,
+        };
+
+        match volume.find_entry(new_dir.ino, new_name) {
+            Ok(existing) if existing.ino == entry.ino => return Ok(()),
+            Ok(existing) => {
+                let existing_is_dir = volume.read_inode(existing.ino)?.file_type() == S_IFDIR;
+                match (is_dir, existing_is_dir) {
+                    (false, true) => return Err(EISDIR),
+                    (true, false) => return Err(ENOTDIR),
+                    (true, true) if !volume.is_empty_dir(existing.ino)? => return Err(ENOTEMPTY),
+                    _ => {}
+                }
+                // The entry is reused, so replacing cannot run out of space
+                volume.write32(existing.offset, entry.ino)?;
+                if volume.filetype {
+                    volume.write(existing.offset + 7, &[file_type])?;
+                }
+                volume.touch_dir(new_dir.ino)?;
+                if existing_is_dir {
+                    let mut existing_inode = volume.read_inode(existing.ino)?;
+                    existing_inode.set_links(1);
+                    volume.write_inode(existing.ino, &existing_inode)?;
+                    volume.add_links(new_dir.ino, -1)?;
+                }
+                volume.add_links(existing.ino, -1)?;
+            }
+            Err(ENOENT) => volume.add_entry(new_dir.ino, new_name, entry.ino, file_type)?,
+            Err(errno) => return Err(errno),
+        }
+        volume.remove_entry(self.ino, old_name)?;
+
+        if is_dir && new_dir.ino != self.ino {
+            let dotdot = volume.find_entry(entry.ino, "..")?;
+            volume.write32(dotdot.offset, new_dir.ino)?;
+            volume.add_links(self.ino, -1)?;
+            volume.add_links(new_dir.ino, 1)?;
+        }
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<String>, isize> {
+        self.check_dir()?;
+        Ok(self
+            .volume
+            .dir_entries(self.ino)?
+            .into_iter()
+            .filter(|entry| entry.ino != 0 && entry.name != b"." && entry.name != b"..")
+            .map(|entry| String::from_utf8_lossy(&entry.name).into_owned())
+            .collect())
+    }
+}
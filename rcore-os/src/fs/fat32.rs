@@ -0,0 +1,426 @@
+//! Read-only FAT32 with long file names, e.g. on a disk made on the host by `mkfs.vfat` and
+//! `mcopy`. Everything read from the disk is checked, so a corrupt image makes operations fail
+//! with `EIO` instead of panicking.
+
+use alloc::{string::String, sync::Arc, vec, vec::Vec};
+
+use super::vfs::{FileSystem, Inode, InodeType};
+use crate::{
+    drivers::block::{get_block_cache, BlockDevice, BLOCK_SIZE},
+    syscall::errno::{EINVAL, EIO, EISDIR, ENOENT, ENOTDIR},
+};
+
+const BOOT_SIGNATURE: u16 = 0xaa55;
+
+const DIR_ENTRY_SIZE: usize = 32;
+/// A directory has at most this many entries, including the free ones
+const MAX_DIR_ENTRIES: usize = 65536;
+const FREE_ENTRY: u8 = 0xe5;
+/// The first byte of a name is `0xe5` when it is stored as this
+const ESCAPED_FREE_ENTRY: u8 = 0x05;
+
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+/// Attributes of the entries holding parts of long names
+const ATTR_LONG_NAME: u8 = 0x0f;
+const ATTR_LONG_NAME_MASK: u8 = 0x3f;
+/// In the sequence number of the part with the end of a long name, which comes first
+const LAST_LONG_ENTRY: u8 = 0x40;
+const LONG_ENTRY_ORDER_MASK: u8 = 0x1f;
+/// Offsets of the 13 UTF-16 units of a long name part
+const LONG_NAME_UNITS: [usize; 13] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+/// Flags of Windows NT for short names which are shown in lowercase
+const LOWERCASE_BASE: u8 = 0x08;
+const LOWERCASE_EXT: u8 = 0x10;
+
+const FAT_ENTRY_MASK: u32 = 0x0fff_ffff;
+const BAD_CLUSTER: u32 = 0x0fff_fff7;
+/// Entries from this one end a cluster chain
+const END_OF_CHAIN: u32 = 0x0fff_fff8;
+/// Number of the first cluster of the data area
+const FIRST_CLUSTER: u32 = 2;
+
+fn le16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn le32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+struct Volume {
+    device: Arc<dyn BlockDevice>,
+    /// Byte offsets of the first FAT and of the data area on the device
+    fat_offset: u64,
+    data_offset: u64,
+    cluster_size: usize,
+    /// Clusters are numbered from `FIRST_CLUSTER` to `FIRST_CLUSTER + cluster_count - 1`
+    cluster_count: u32,
+    root_cluster: u32,
+}
+
+impl Volume {
+    /// Read `buf.len()` bytes from `offset` of the device
+    fn read(&self, offset: u64, buf: &mut [u8]) -> Result<(), isize> {
+        let device_size = (self.device.num_blocks() * BLOCK_SIZE) as u64;
+        if offset + buf.len() as u64 > device_size {
+            return Err(EIO);
+        }
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = offset + done as u64;
+            let block_id = (pos / BLOCK_SIZE as u64) as usize;
+            let start = (pos % BLOCK_SIZE as u64) as usize;
+            let len = (BLOCK_SIZE - start).min(buf.len() - done);
+            let block = get_block_cache(block_id, self.device.clone());
+            let block = block.lock();
+            if block.is_failed() {
+                return Err(EIO);
+            }
+            block.read(0, |block: &[u8; BLOCK_SIZE]| {
+                buf[done..done + len].copy_from_slice(&block[start..start + len]);
+            });
+            done += len;
+        }
+        Ok(())
+    }
+
+    fn check_cluster(&self, cluster: u32) -> Result<(), isize> {
+        match cluster.checked_sub(FIRST_CLUSTER) {
+            Some(index) if index < self.cluster_count => Ok(()),
+            _ => Err(EIO),
+        }
+    }
+
+    fn cluster_offset(&self, cluster: u32) -> u64 {
+        self.data_offset + (cluster - FIRST_CLUSTER) as u64 * self.cluster_size as u64
+    }
+
+    /// The cluster after `cluster` in its chain, or `None` at the end
+    fn next_cluster(&self, cluster: u32) -> Result<Option<u32>, isize> {
+        self.check_cluster(cluster)?;
+        let mut entry = [0u8; 4];
+        self.read(self.fat_offset + cluster as u64 * 4, &mut entry)?;
+        match u32::from_le_bytes(entry) & FAT_ENTRY_MASK {
+            next if next >= END_OF_CHAIN => Ok(None),
+            BAD_CLUSTER => Err(EIO),
+            next => self.check_cluster(next).map(|_| Some(next)),
+        }
+    }
+
+    /// Clusters of the chain starting at `first`, which is 0 for an empty file
+    fn chain(&self, first: u32) -> Chain<'_> {
+        Chain {
+            volume: self,
+            next: (first != 0).then_some(first),
+            len: 0,
+        }
+    }
+}
+
+/// Stops after an error, which a chain longer than the data area is as it must have a loop
+struct Chain<'a> {
+    volume: &'a Volume,
+    next: Option<u32>,
+    len: u32,
+}
+
+impl Iterator for Chain<'_> {
+    type Item = Result<u32, isize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let cluster = self.next.take()?;
+        if self.len == self.volume.cluster_count {
+            return Some(Err(EIO));
+        }
+        self.len += 1;
+        match self.volume.next_cluster(cluster) {
+            Ok(next) => {
+                self.next = next;
+                Some(Ok(cluster))
+            }
+            Err(errno) => Some(Err(errno)),
+        }
+    }
+}
+
+pub struct Fat32 {
+    volume: Arc<Volume>,
+}
+
+impl Fat32 {
+    /// Open the FAT32 filesystem on `device`. Fail with `EINVAL` if there is none.
+    pub fn open(device: Arc<dyn BlockDevice>) -> Result<Self, isize> {
+        let mut boot = [0u8; BLOCK_SIZE];
+        if device.num_blocks() == 0 {
+            return Err(EINVAL);
+        }
+        device.read_block(0, &mut boot).map_err(|_| EIO)?;
+        if le16(&boot, 510) != BOOT_SIGNATURE {
+            return Err(EINVAL);
+        }
+        let bytes_per_sector = le16(&boot, 11) as u64;
+        let sectors_per_cluster = boot[13] as u64;
+        let reserved_sectors = le16(&boot, 14) as u64;
+        let fat_count = boot[16] as u64;
+        let root_entry_count = le16(&boot, 17);
+        let total_sectors = match le16(&boot, 19) {
+            0 => le32(&boot, 32) as u64,
+            total => total as u64,
+        };
+        let fat_size_16 = le16(&boot, 22);
+        let fat_size = le32(&boot, 36) as u64;
+        let root_cluster = le32(&boot, 44);
+
+        // FAT12 and FAT16 have a fixed root directory and a 16-bit FAT size
+        let is_fat32 = root_entry_count == 0 && fat_size_16 == 0 && fat_size != 0;
+        let sector_size_ok = matches!(bytes_per_sector, 512 | 1024 | 2048 | 4096);
+        if !is_fat32
+            || !sector_size_ok
+            || !sectors_per_cluster.is_power_of_two()
+            || reserved_sectors == 0
+            || fat_count == 0
+        {
+            return Err(EINVAL);
+        }
+        let data_sector = reserved_sectors + fat_count * fat_size;
+        let device_size = (device.num_blocks() * BLOCK_SIZE) as u64;
+        if data_sector >= total_sectors || total_sectors * bytes_per_sector > device_size {
+            return Err(EINVAL);
+        }
+        // Clusters without an entry in the FAT cannot be used
+        let cluster_count = ((total_sectors - data_sector) / sectors_per_cluster)
+            .min(fat_size * bytes_per_sector / 4 - FIRST_CLUSTER as u64);
+
+        let volume = Volume {
+            device,
+            fat_offset: reserved_sectors * bytes_per_sector,
+            data_offset: data_sector * bytes_per_sector,
+            cluster_size: (sectors_per_cluster * bytes_per_sector) as usize,
+            cluster_count: cluster_count as u32,
+            root_cluster,
+        };
+        volume.check_cluster(root_cluster).map_err(|_| EINVAL)?;
+        Ok(Self {
+            volume: Arc::new(volume),
+        })
+    }
+}
+
+impl FileSystem for Fat32 {
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        Arc::new(FatInode {
+            volume: self.volume.clone(),
+            ino: 1,
+            first_cluster: self.volume.root_cluster,
+            size: 0,
+            is_dir: true,
+        })
+    }
+}
+
+/// The parts of a long name, which come in reverse order before the short entry they belong to
+#[derive(Default)]
+struct LongName {
+    /// UTF-16 units of each part, from the last part to the first
+    parts: Vec<[u16; 13]>,
+    checksum: u8,
+    /// Order of the next part, 0 after the first part, and `None` without a valid long name
+    next: Option<u8>,
+}
+
+impl LongName {
+    fn push(&mut self, entry: &[u8]) {
+        let order = entry[0] & LONG_ENTRY_ORDER_MASK;
+        let checksum = entry[13];
+        if entry[0] & LAST_LONG_ENTRY != 0 {
+            self.parts.clear();
+            self.checksum = checksum;
+            self.next = Some(order);
+        }
+        if order == 0 || self.next != Some(order) || checksum != self.checksum {
+            self.next = None;
+            return;
+        }
+        self.parts.push(LONG_NAME_UNITS.map(|offset| le16(entry, offset)));
+        self.next = Some(order - 1);
+    }
+
+    /// The long name of the short entry `short_name`, if the parts before it make one
+    fn take(&mut self, short_name: &[u8]) -> Option<String> {
+        let complete = self.next.take() == Some(0) && self.checksum == checksum(short_name);
+        let parts = core::mem::take(&mut self.parts);
+        if !complete {
+            return None;
+        }
+        // The name ends with a NUL if it does not fill the last part, which is padded with 0xffff
+        let units = parts.iter().rev().flatten().copied().take_while(|&unit| unit != 0);
+        let name: String = char::decode_utf16(units)
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect();
+        (!name.is_empty()).then_some(name)
+    }
+}
+
+/// Checksum of an 11-byte short name stored in the parts of its long name
+fn checksum(short_name: &[u8]) -> u8 {
+    short_name
+        .iter()
+        .fold(0u8, |sum, &c| sum.rotate_right(1).wrapping_add(c))
+}
+
+/// `NAME.EXT` of a short entry, in lowercase as Windows NT marks it. Bytes outside ASCII are taken
+/// as Latin-1, as the code page is unknown.
+fn short_name(entry: &[u8]) -> String {
+    let part = |bytes: &[u8], lowercase: bool| -> String {
+        let bytes = bytes.trim_ascii_end();
+        bytes
+            .iter()
+            .enumerate()
+            .map(|(i, &c)| match c {
+                ESCAPED_FREE_ENTRY if i == 0 => FREE_ENTRY,
+                c if lowercase => c.to_ascii_lowercase(),
+                c => c,
+            })
+            .map(char::from)
+            .collect()
+    };
+    let mut name = part(&entry[..8], entry[12] & LOWERCASE_BASE != 0);
+    let ext = part(&entry[8..11], entry[12] & LOWERCASE_EXT != 0);
+    if !ext.is_empty() {
+        name.push('.');
+        name.push_str(&ext);
+    }
+    name
+}
+
+struct DirEntry {
+    name: String,
+    inode: FatInode,
+}
+
+struct FatInode {
+    volume: Arc<Volume>,
+    ino: u64,
+    first_cluster: u32,
+    /// Size in bytes of a file. Directories have none.
+    size: usize,
+    is_dir: bool,
+}
+
+impl FatInode {
+    /// Entries of a directory without `.` and `..`
+    fn entries(&self) -> Result<Vec<DirEntry>, isize> {
+        if !self.is_dir {
+            return Err(ENOTDIR);
+        }
+        let volume = &self.volume;
+        let mut entries = Vec::new();
+        let mut long_name = LongName::default();
+        let mut cluster_data = vec![0u8; volume.cluster_size];
+        let mut count = 0;
+        for cluster in volume.chain(self.first_cluster) {
+            let offset = volume.cluster_offset(cluster?);
+            volume.read(offset, &mut cluster_data)?;
+            for (i, entry) in cluster_data.chunks_exact(DIR_ENTRY_SIZE).enumerate() {
+                count += 1;
+                if count > MAX_DIR_ENTRIES {
+                    return Err(EIO);
+                }
+                match entry[0] {
+                    // No entries after this one are used
+                    0 => return Ok(entries),
+                    FREE_ENTRY => {
+                        long_name = LongName::default();
+                        continue;
+                    }
+                    _ => {}
+                }
+                let attr = entry[11];
+                if attr & ATTR_LONG_NAME_MASK == ATTR_LONG_NAME {
+                    long_name.push(entry);
+                    continue;
+                }
+                let name = long_name.take(&entry[..11]);
+                if attr & ATTR_VOLUME_ID != 0 {
+                    continue;
+                }
+                let name = name.unwrap_or_else(|| short_name(entry));
+                if name == "." || name == ".." {
+                    continue;
+                }
+                let first_cluster = (le16(entry, 20) as u32) << 16 | le16(entry, 26) as u32;
+                let entry_offset = offset + (i * DIR_ENTRY_SIZE) as u64;
+                entries.push(DirEntry {
+                    name,
+                    inode: FatInode {
+                        volume: volume.clone(),
+                        // Unique as entries do not move, and above the 1 of the root
+                        ino: entry_offset / DIR_ENTRY_SIZE as u64 + 2,
+                        first_cluster,
+                        size: le32(entry, 28) as usize,
+                        is_dir: attr & ATTR_DIRECTORY != 0,
+                    },
+                });
+            }
+        }
+        Ok(entries)
+    }
+}
+
+impl Inode for FatInode {
+    fn type_(&self) -> InodeType {
+        match self.is_dir {
+            true => InodeType::Dir,
+            false => InodeType::File,
+        }
+    }
+
+    fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, isize> {
+        if self.is_dir {
+            return Err(EISDIR);
+        }
+        let end = self.size.min(offset.saturating_add(buf.len()));
+        if offset >= end {
+            return Ok(0);
+        }
+        let cluster_size = self.volume.cluster_size;
+        // A chain shorter than the size is corrupt
+        let mut chain = self.volume.chain(self.first_cluster);
+        for _ in 0..offset / cluster_size {
+            chain.next().ok_or(EIO)??;
+        }
+        let mut pos = offset;
+        while pos < end {
+            let cluster = chain.next().ok_or(EIO)??;
+            let start = pos % cluster_size;
+            let len = (cluster_size - start).min(end - pos);
+            let dst = &mut buf[pos - offset..pos - offset + len];
+            self.volume.read(self.volume.cluster_offset(cluster) + start as u64, dst)?;
+            pos += len;
+        }
+        Ok(end - offset)
+    }
+
+    /// Names are matched ignoring ASCII case, like FAT does
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, isize> {
+        let entry = self
+            .entries()?
+            .into_iter()
+            .find(|entry| entry.name.eq_ignore_ascii_case(name))
+            .ok_or(ENOENT)?;
+        Ok(Arc::new(entry.inode))
+    }
+
+    fn list(&self) -> Result<Vec<String>, isize> {
+        Ok(self.entries()?.into_iter().map(|entry| entry.name).collect())
+    }
+}
@@ -0,0 +1,239 @@
+use alloc::{string::String, sync::Arc, vec::Vec};
+use bitflags::bitflags;
+
+use super::{
+    vfs::{self, Inode, InodeType},
+    File, Stat, S_IFCHR, S_IFDIR, S_IFLNK, S_IFREG,
+};
+use crate::{
+    config::PAGE_SIZE,
+    mem::page_table::UserBuffer,
+    syscall::errno::{EINVAL, EISDIR, ENOENT, ENOSPC},
+    upsync::UPSyncCell,
+};
+
+/// `d_ino`, `d_off`, `d_reclen` and `d_type` of `struct linux_dirent64`, before `d_name`
+const DIRENT64_HEADER_SIZE: usize = 19;
+const DT_UNKNOWN: u8 = 0;
+const DT_CHR: u8 = 2;
+const DT_DIR: u8 = 4;
+const DT_REG: u8 = 8;
+const DT_LNK: u8 = 10;
+
+/// A file or directory of a filesystem opened by a process
+pub struct OSInode {
+    readable: bool,
+    writable: bool,
+    /// `st_dev` of the filesystem the file was opened in
+    dev: u64,
+    inner: UPSyncCell<OSInodeInner>,
+}
+
+struct OSInodeInner {
+    offset: usize,
+    inode: Arc<dyn Inode>,
+}
+
+impl OSInode {
+    pub fn new(readable: bool, writable: bool, dev: u64, inode: Arc<dyn Inode>) -> Self {
+        Self {
+            readable,
+            writable,
+            dev,
+            inner: unsafe { UPSyncCell::new(OSInodeInner { offset: 0, inode }) },
+        }
+    }
+
+    /// Read from the current offset to the end
+    pub fn read_all(&self) -> Result<Vec<u8>, isize> {
+        let mut inner = self.inner.exclusive_access();
+        let mut buffer = [0u8; 512];
+        let mut v: Vec<u8> = Vec::new();
+        loop {
+            let len = inner.inode.read_at(inner.offset, &mut buffer)?;
+            if len == 0 {
+                break;
+            }
+            inner.offset += len;
+            v.extend_from_slice(&buffer[..len]);
+        }
+        Ok(v)
+    }
+}
+
+impl File for OSInode {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+
+    fn truncate(&self, size: usize) -> Result<(), isize> {
+        if !self.writable {
+            return Err(EINVAL);
+        }
+        self.inner.exclusive_access().inode.truncate(size)
+    }
+
+    /// The offset of a directory counts the entries which have been read
+    fn read_dir(&self, buf: &mut [u8]) -> Result<usize, isize> {
+        let mut inner = self.inner.exclusive_access();
+        let names = inner.inode.list()?;
+        let mut len = 0;
+        for name in names.iter().skip(inner.offset) {
+            let Some(reclen) = DIRENT64_HEADER_SIZE
+                .checked_add(name.len() + 1)
+                .map(|size| size.next_multiple_of(8))
+                .filter(|reclen| len + reclen <= buf.len())
+            else {
+                break;
+            };
+            // An entry removed since `list` is still returned, but without its inode
+            let (ino, d_type) = match inner.inode.lookup(name) {
+                Ok(inode) => (
+                    inode.ino(),
+                    match inode.type_() {
+                        InodeType::File => DT_REG,
+                        InodeType::Dir => DT_DIR,
+                        InodeType::CharDevice => DT_CHR,
+                        InodeType::Symlink => DT_LNK,
+                    },
+                ),
+                Err(_) => (0, DT_UNKNOWN),
+            };
+            inner.offset += 1;
+            // `d_off` is the offset of the next entry
+            let record = &mut buf[len..len + reclen];
+            record[0..8].copy_from_slice(&ino.to_ne_bytes());
+            record[8..16].copy_from_slice(&(inner.offset as i64).to_ne_bytes());
+            record[16..18].copy_from_slice(&(reclen as u16).to_ne_bytes());
+            record[18] = d_type;
+            record[DIRENT64_HEADER_SIZE..DIRENT64_HEADER_SIZE + name.len()]
+                .copy_from_slice(name.as_bytes());
+            record[DIRENT64_HEADER_SIZE + name.len()..].fill(0);
+            len += reclen;
+        }
+        if len == 0 && inner.offset < names.len() {
+            // Not even one entry fits
+            return Err(EINVAL);
+        }
+        Ok(len)
+    }
+
+    fn writable(&self) -> bool {
+        self.writable
+    }
+
+    fn read(&self, mut buf: UserBuffer) -> Result<usize, isize> {
+        // Reading a device may block, which switches to other tasks that may use this file
+        let (inode, mut offset) = {
+            let inner = self.inner.exclusive_access();
+            (inner.inode.clone(), inner.offset)
+        };
+        if inode.is_dir() {
+            return Err(EISDIR);
+        }
+        let mut total_read_size = 0usize;
+        for slice in buf.buffers.iter_mut() {
+            let read_size = inode.read_at(offset, slice)?;
+            offset += read_size;
+            total_read_size += read_size;
+            if read_size < slice.len() {
+                break;
+            }
+        }
+        self.inner.exclusive_access().offset = offset;
+        Ok(total_read_size)
+    }
+
+    fn write(&self, buf: UserBuffer) -> Result<usize, isize> {
+        let mut inner = self.inner.exclusive_access();
+        let mut total_write_size = 0usize;
+        for slice in buf.buffers.iter() {
+            let write_size = inner.inode.write_at(inner.offset, slice)?;
+            if write_size < slice.len() {
+                // The disk is full
+                return match total_write_size {
+                    0 => Err(ENOSPC),
+                    _ => Ok(total_write_size),
+                };
+            }
+            inner.offset += write_size;
+            total_write_size += write_size;
+        }
+        Ok(total_write_size)
+    }
+
+    fn stat(&self) -> Stat {
+        let inode = self.inner.exclusive_access().inode.clone();
+        inode_stat(self.dev, inode.as_ref())
+    }
+}
+
+/// `stat` of `inode` in the filesystem with `st_dev` `dev`
+pub fn inode_stat(dev: u64, inode: &dyn Inode) -> Stat {
+    let mode = match inode.type_() {
+        InodeType::File => S_IFREG | 0o644,
+        InodeType::Dir => S_IFDIR | 0o755,
+        InodeType::CharDevice => S_IFCHR | 0o666,
+        InodeType::Symlink => S_IFLNK | 0o777,
+    };
+    let size = inode.size();
+    Stat {
+        dev,
+        ino: inode.ino(),
+        mode,
+        nlink: inode.nlink() as u32,
+        size: size as i64,
+        blksize: PAGE_SIZE as i32,
+        blocks: size.div_ceil(512) as i64,
+        ..Stat::default()
+    }
+}
+
+bitflags! {
+    /// Flags of `open`, with the values of Linux
+    pub struct OpenFlags: u32 {
+        const RDONLY = 0;
+        const WRONLY = 1 << 0;
+        const RDWR = 1 << 1;
+        const CREATE = 1 << 6;
+        const TRUNC = 1 << 9;
+    }
+}
+
+impl OpenFlags {
+    /// Return (readable, writable)
+    pub fn read_write(&self) -> (bool, bool) {
+        if self.contains(Self::RDWR) {
+            (true, true)
+        } else if self.contains(Self::WRONLY) {
+            (false, true)
+        } else {
+            (true, false)
+        }
+    }
+}
+
+/// Open the absolute path `path`, see [`vfs::absolute_path`]
+pub fn open_file(path: &str, flags: OpenFlags) -> Result<Arc<OSInode>, isize> {
+    let (readable, writable) = flags.read_write();
+
+    let (path, inode): (String, _) = match vfs::resolve(path, true) {
+        Ok((path, inode)) => {
+            if inode.is_dir() && writable {
+                return Err(EISDIR);
+            }
+            if flags.contains(OpenFlags::TRUNC) && writable {
+                inode.truncate(0)?;
+            }
+            (path, inode)
+        }
+        Err(ENOENT) if flags.contains(OpenFlags::CREATE) => {
+            let (parent, name) = vfs::split_parent(path).ok_or(EISDIR)?;
+            let (parent_path, parent) = vfs::resolve(parent, true)?;
+            let inode = parent.create(name, InodeType::File)?;
+            (vfs::absolute_path(&parent_path, name), inode)
+        }
+        Err(errno) => return Err(errno),
+    };
+    Ok(Arc::new(OSInode::new(readable, writable, vfs::device_of(&path), inode)))
+}
@@ -0,0 +1,180 @@
+mod appfs;
+mod devfs;
+mod easyfs;
+mod ext2;
+mod fat32;
+mod inode;
+mod pipe;
+mod procfs;
+mod stdio;
+mod tmpfs;
+pub mod vfs;
+
+use alloc::sync::Arc;
+
+use crate::{
+    config::{PAGE_SIZE, RAMDISK_BLOCKS},
+    drivers::block::{
+        block_cache_sync_all, block_device, BlockDevice, ImageBlockDevice, MemBlockDevice,
+    },
+    initrd,
+    log,
+    mem::page_table::UserBuffer,
+    syscall::errno::{EINVAL, EIO, ENODEV, ENOENT, ENOTDIR},
+    warn,
+};
+
+pub use inode::{inode_stat, open_file, OpenFlags};
+pub use pipe::make_pipe;
+pub use stdio::{poll_console, Stdin, Stdout};
+
+use self::{
+    appfs::AppFs, devfs::DevFs, easyfs::EasyFs, ext2::Ext2, fat32::Fat32, procfs::ProcFs, tmpfs::TmpFs,
+    vfs::FileSystem,
+};
+
+/// Anything a file descriptor can refer to. Errors are errnos from [`crate::syscall::errno`].
+pub trait File: Send + Sync {
+    fn readable(&self) -> bool;
+    fn writable(&self) -> bool;
+    /// Read into `buf` and return how many bytes are read. May block.
+    fn read(&self, buf: UserBuffer) -> Result<usize, isize>;
+    /// Write from `buf` and return how many bytes are written. May block.
+    fn write(&self, buf: UserBuffer) -> Result<usize, isize>;
+    /// Set the size of a regular file
+    fn truncate(&self, _size: usize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+    /// Fill `buf` with the next entries of a directory as `struct linux_dirent64` records and
+    /// return how many bytes are filled, 0 at the end of the directory
+    fn read_dir(&self, _buf: &mut [u8]) -> Result<usize, isize> {
+        Err(ENOTDIR)
+    }
+    fn stat(&self) -> Stat;
+}
+
+/// Type bits of [`Stat::mode`]
+pub const S_IFIFO: u32 = 0o010000;
+pub const S_IFCHR: u32 = 0o020000;
+pub const S_IFDIR: u32 = 0o040000;
+pub const S_IFREG: u32 = 0o100000;
+pub const S_IFLNK: u32 = 0o120000;
+
+/// `struct stat` of Linux on RISC-V. There are no owners or timestamps, so those fields are 0.
+#[repr(C)]
+#[derive(Default)]
+pub struct Stat {
+    pub dev: u64,
+    pub ino: u64,
+    /// Type and permission bits
+    pub mode: u32,
+    pub nlink: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub rdev: u64,
+    __pad1: u64,
+    pub size: i64,
+    pub blksize: i32,
+    __pad2: i32,
+    /// Number of 512-byte blocks
+    pub blocks: i64,
+    pub atime_sec: i64,
+    pub atime_nsec: i64,
+    pub mtime_sec: i64,
+    pub mtime_nsec: i64,
+    pub ctime_sec: i64,
+    pub ctime_nsec: i64,
+    __unused: [u32; 2],
+}
+
+impl Stat {
+    /// A file which is not in any filesystem, e.g. a pipe
+    pub fn anonymous(mode: u32) -> Self {
+        Self {
+            mode,
+            nlink: 1,
+            blksize: PAGE_SIZE as i32,
+            ..Self::default()
+        }
+    }
+}
+
+/// Create a filesystem of type `fstype` for `mount`. `source` names the block device for
+/// filesystems on a disk, e.g. `/dev/vda` for the first one, and is ignored by the others. An
+/// easy-fs from `ram` is a new one on a RAM disk, which is lost when it is unmounted. FAT32 can
+/// also be read from an image in the initrd, named by its path in the archive. ext2 is read and
+/// written on a block device only.
+pub fn new_filesystem(fstype: &str, source: &str) -> Result<Arc<dyn FileSystem>, isize> {
+    match fstype {
+        "easyfs" if source == "ram" => {
+            let device = Arc::new(MemBlockDevice::new(RAMDISK_BLOCKS));
+            Ok(Arc::new(EasyFs::format(device)))
+        }
+        "easyfs" => {
+            let device = block_device_index(source).and_then(block_device).ok_or(ENOENT)?;
+            Ok(Arc::new(EasyFs::open(device).ok_or(EINVAL)?))
+        }
+        "ext2" => {
+            let device = block_device_index(source).and_then(block_device).ok_or(ENOENT)?;
+            Ok(Arc::new(Ext2::open(device)?))
+        }
+        "fat32" | "vfat" => {
+            let device = block_device_index(source)
+                .and_then(block_device)
+                .or_else(|| {
+                    let image = initrd::get_file(source)?;
+                    Some(Arc::new(ImageBlockDevice::new(image)) as Arc<dyn BlockDevice>)
+                })
+                .ok_or(ENOENT)?;
+            Ok(Arc::new(Fat32::open(device)?))
+        }
+        "appfs" => Ok(Arc::new(AppFs)),
+        "tmpfs" => Ok(Arc::new(TmpFs::new())),
+        "procfs" => Ok(Arc::new(ProcFs)),
+        "devfs" => Ok(Arc::new(DevFs)),
+        _ => Err(ENODEV),
+    }
+}
+
+/// `/dev/vda` is the first block device, `/dev/vdb` the second, and so on
+fn block_device_index(source: &str) -> Option<usize> {
+    let letter = source.strip_prefix("/dev/vd")?;
+    match letter.as_bytes() {
+        &[c @ b'a'..=b'z'] => Some((c - b'a') as usize),
+        _ => None,
+    }
+}
+
+/// Mount the easy-fs on the first block device at `/`, the programs of the kernel at `/bin`, a
+/// tmpfs at `/tmp`, the procfs at `/proc` and the devices at `/dev`. A FAT32 filesystem on the
+/// second block device is mounted at `/fat`, an ext2 one at `/ext2`.
+pub fn init() {
+    let root: Arc<dyn FileSystem> = match new_filesystem("easyfs", "/dev/vda") {
+        Ok(fs) => {
+            log!("easy-fs on /dev/vda mounted at /");
+            fs
+        }
+        Err(_) => {
+            warn!("No easy-fs on /dev/vda, / is a tmpfs");
+            Arc::new(TmpFs::new())
+        }
+    };
+    vfs::mount("/", root).unwrap();
+    vfs::mount("/bin", Arc::new(AppFs)).unwrap();
+    vfs::mount("/tmp", Arc::new(TmpFs::new())).unwrap();
+    vfs::mount("/proc", Arc::new(ProcFs)).unwrap();
+    vfs::mount("/dev", Arc::new(DevFs)).unwrap();
+    if let Ok(fs) = new_filesystem("fat32", "/dev/vdb") {
+        log!("FAT32 on /dev/vdb mounted at /fat");
+        vfs::mount("/fat", fs).unwrap();
+    } else if let Ok(fs) = new_filesystem("ext2", "/dev/vdb") {
+        log!("ext2 on /dev/vdb mounted at /ext2");
+        vfs::mount("/ext2", fs).unwrap();
+    }
+}
+
+/// Write the blocks cached for all filesystems back to their devices. Fail with `EIO` if any of
+/// them cannot be written.
+pub fn sync() -> Result<(), isize> {
+    block_cache_sync_all().map_err(|_| EIO)
+}
@@ -0,0 +1,152 @@
+use alloc::sync::{Arc, Weak};
+
+use super::{File, Stat, S_IFIFO};
+use crate::{
+    mem::page_table::UserBuffer,
+    syscall::errno::{EINTR, EPIPE},
+    task::{signal::signal_pending, suspend_and_run_next},
+    upsync::UPSyncCell,
+};
+
+const PIPE_BUFFER_SIZE: usize = 4096;
+
+/// One end of a pipe
+pub struct Pipe {
+    readable: bool,
+    writable: bool,
+    buffer: Arc<UPSyncCell<PipeRingBuffer>>,
+}
+
+struct PipeRingBuffer {
+    data: [u8; PIPE_BUFFER_SIZE],
+    head: usize,
+    len: usize,
+    /// The ends are gone once all of their file descriptors are closed
+    read_end: Weak<Pipe>,
+    write_end: Weak<Pipe>,
+}
+
+impl PipeRingBuffer {
+    fn new() -> Self {
+        Self {
+            data: [0; PIPE_BUFFER_SIZE],
+            head: 0,
+            len: 0,
+            read_end: Weak::new(),
+            write_end: Weak::new(),
+        }
+    }
+
+    fn pop(&mut self) -> u8 {
+        let byte = self.data[self.head];
+        self.head = (self.head + 1) % PIPE_BUFFER_SIZE;
+        self.len -= 1;
+        byte
+    }
+
+    fn push(&mut self, byte: u8) {
+        self.data[(self.head + self.len) % PIPE_BUFFER_SIZE] = byte;
+        self.len += 1;
+    }
+
+    fn all_read_ends_closed(&self) -> bool {
+        self.read_end.upgrade().is_none()
+    }
+
+    fn all_write_ends_closed(&self) -> bool {
+        self.write_end.upgrade().is_none()
+    }
+}
+
+/// Return the read end and the write end of a new pipe
+pub fn make_pipe() -> (Arc<Pipe>, Arc<Pipe>) {
+    let buffer = Arc::new(unsafe { UPSyncCell::new(PipeRingBuffer::new()) });
+    let read_end = Arc::new(Pipe {
+        readable: true,
+        writable: false,
+        buffer: buffer.clone(),
+    });
+    let write_end = Arc::new(Pipe {
+        readable: false,
+        writable: true,
+        buffer: buffer.clone(),
+    });
+    let mut inner = buffer.exclusive_access();
+    inner.read_end = Arc::downgrade(&read_end);
+    inner.write_end = Arc::downgrade(&write_end);
+    drop(inner);
+    (read_end, write_end)
+}
+
+impl File for Pipe {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+
+    fn writable(&self) -> bool {
+        self.writable
+    }
+
+    /// Wait until there is something to read, then read as much as available. Return 0 at the end
+    /// of file, i.e. when the buffer is empty and all write ends are closed. Fails with `EINTR` if
+    /// a signal arrives while waiting.
+    fn read(&self, mut buf: UserBuffer) -> Result<usize, isize> {
+        let want = buf.len();
+        if want == 0 {
+            return Ok(0);
+        }
+        loop {
+            let mut ring = self.buffer.exclusive_access();
+            if ring.len == 0 {
+                if ring.all_write_ends_closed() {
+                    return Ok(0);
+                }
+                if signal_pending() {
+                    return Err(EINTR);
+                }
+                drop(ring);
+                suspend_and_run_next();
+                continue;
+            }
+
+            let count = want.min(ring.len);
+            for byte in buf.iter_mut().take(count) {
+                *byte = ring.pop();
+            }
+            return Ok(count);
+        }
+    }
+
+    /// Write everything, waiting for readers to make room. Fails with `EPIPE` if all read ends
+    /// are closed, or with `EINTR` if a signal arrives, before anything is written.
+    fn write(&self, buf: UserBuffer) -> Result<usize, isize> {
+        let mut written = 0;
+        let mut bytes = buf.buffers.iter().flat_map(|b| b.iter());
+        let want = buf.len();
+        while written < want {
+            let mut ring = self.buffer.exclusive_access();
+            if ring.all_read_ends_closed() {
+                return if written == 0 { Err(EPIPE) } else { Ok(written) };
+            }
+            if ring.len == PIPE_BUFFER_SIZE {
+                if signal_pending() {
+                    return if written == 0 { Err(EINTR) } else { Ok(written) };
+                }
+                drop(ring);
+                suspend_and_run_next();
+                continue;
+            }
+
+            let count = (want - written).min(PIPE_BUFFER_SIZE - ring.len);
+            for &byte in bytes.by_ref().take(count) {
+                ring.push(byte);
+            }
+            written += count;
+        }
+        Ok(written)
+    }
+
+    fn stat(&self) -> Stat {
+        Stat::anonymous(S_IFIFO | 0o600)
+    }
+}
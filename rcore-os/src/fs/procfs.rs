@@ -0,0 +1,221 @@
+//! Synthetic files describing the kernel and the processes. The content of a file is generated when it
+//! is looked up, so an open file is a snapshot which does not change while it is read.
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::fmt::Write;
+
+use super::vfs::{FileSystem, Inode, InodeType};
+use crate::{
+    config::PAGE_SIZE,
+    drivers::block::block_cache_stats,
+    mem::{
+        frame_allocator::frame_stats,
+        heap_allocator::heap_stats,
+        memory_set::MapPermission,
+    },
+    syscall::errno::ENOENT,
+    task::{
+        manager::{all_processes, find_process},
+        process::ProcessControlBlock,
+        TaskStatus,
+    },
+    timer::{get_time_us, MICRO_PER_SEC},
+};
+
+pub struct ProcFs;
+
+impl FileSystem for ProcFs {
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        Arc::new(ProcRoot)
+    }
+}
+
+/// Inode numbers: `/proc` is 1, and the files about the kernel are below `TASK_INO_STEP`. The
+/// directory of a process is `TASK_INO_STEP * (pid + 1)`, followed by its files.
+const MEMINFO_INO: u64 = 2;
+const UPTIME_INO: u64 = 3;
+const BLOCKCACHE_INO: u64 = 4;
+const TASK_INO_STEP: u64 = 8;
+const STATUS_INO_OFFSET: u64 = 1;
+const MAPS_INO_OFFSET: u64 = 2;
+
+/// `/proc`, with a directory for each process and the files about the kernel
+struct ProcRoot;
+
+impl Inode for ProcRoot {
+    fn type_(&self) -> InodeType {
+        InodeType::Dir
+    }
+
+    fn ino(&self) -> u64 {
+        1
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, isize> {
+        let (ino, content) = match name {
+            "meminfo" => (MEMINFO_INO, meminfo()),
+            "uptime" => (UPTIME_INO, uptime()),
+            "blockcache" => (BLOCKCACHE_INO, blockcache()),
+            _ => {
+                let pid = name.parse().map_err(|_| ENOENT)?;
+                find_process(pid).ok_or(ENOENT)?;
+                return Ok(Arc::new(ProcProcessDir(pid)));
+            }
+        };
+        Ok(Arc::new(ProcFile { ino, content }))
+    }
+
+    fn list(&self) -> Result<Vec<String>, isize> {
+        let mut names: Vec<String> = ["blockcache", "meminfo", "uptime"].map(String::from).into();
+        names.extend(all_processes().iter().map(|process| process.get_pid().to_string()));
+        Ok(names)
+    }
+}
+
+/// `/proc/<pid>`
+struct ProcProcessDir(usize);
+
+impl Inode for ProcProcessDir {
+    fn type_(&self) -> InodeType {
+        InodeType::Dir
+    }
+
+    fn ino(&self) -> u64 {
+        TASK_INO_STEP * (self.0 as u64 + 1)
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, isize> {
+        let process = find_process(self.0).ok_or(ENOENT)?;
+        let (offset, content) = match name {
+            "status" => (STATUS_INO_OFFSET, status(&process)),
+            "maps" => (MAPS_INO_OFFSET, maps(&process)),
+            _ => return Err(ENOENT),
+        };
+        Ok(Arc::new(ProcFile {
+            ino: self.ino() + offset,
+            content,
+        }))
+    }
+
+    fn list(&self) -> Result<Vec<String>, isize> {
+        Ok(["maps", "status"].map(String::from).into())
+    }
+}
+
+struct ProcFile {
+    ino: u64,
+    content: String,
+}
+
+impl Inode for ProcFile {
+    fn type_(&self) -> InodeType {
+        InodeType::File
+    }
+
+    fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn size(&self) -> usize {
+        self.content.len()
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, isize> {
+        let data = self.content.as_bytes().get(offset..).unwrap_or(&[]);
+        let len = data.len().min(buf.len());
+        buf[..len].copy_from_slice(&data[..len]);
+        Ok(len)
+    }
+}
+
+/// `State` is the one of the main thread, and `Threads` counts the threads which have not exited
+fn status(process: &Arc<ProcessControlBlock>) -> String {
+    let inner = process.inner_exclusive_access();
+    let parent = inner.parent.as_ref().and_then(|parent| parent.upgrade());
+    let children: Vec<String> = inner
+        .children
+        .iter()
+        .map(|child| child.get_pid().to_string())
+        .collect();
+    let state = match &inner.tasks[0] {
+        _ if inner.is_zombie() => TaskStatus::Zombie,
+        Some(main_task) => main_task.inner_exclusive_access().get_status(),
+        None => TaskStatus::Exited,
+    };
+    format!(
+        "Pid:\t{}\nPPid:\t{}\nState:\t{:?}\nThreads:\t{}\nChildren:\t{}\nExitCode:\t{}\nCwd:\t{}\n",
+        process.get_pid(),
+        parent.map_or(0, |parent| parent.get_pid()),
+        state,
+        inner.live_tasks().count(),
+        children.join(" "),
+        inner.exit_code,
+        inner.cwd,
+    )
+}
+
+/// One line for each area: `start-end rwxu resident_pages`, like `/proc/<pid>/maps` of Linux
+fn maps(process: &Arc<ProcessControlBlock>) -> String {
+    let inner = process.inner_exclusive_access();
+    let mut content = String::new();
+    for area in inner.memory_set.areas() {
+        let (start, end) = area.range();
+        let perm = area.permission();
+        let flag = |bit, c| if perm.contains(bit) { c } else { '-' };
+        writeln!(
+            content,
+            "{:016x}-{:016x} {}{}{}{} {}",
+            usize::from(start),
+            usize::from(end),
+            flag(MapPermission::R, 'r'),
+            flag(MapPermission::W, 'w'),
+            flag(MapPermission::X, 'x'),
+            flag(MapPermission::U, 'u'),
+            area.resident_pages(),
+        )
+        .unwrap();
+    }
+    content
+}
+
+/// Sizes in KiB, like `/proc/meminfo` of Linux
+fn meminfo() -> String {
+    let (total_frames, free_frames) = frame_stats();
+    let (heap_total, heap_used) = heap_stats();
+    let kib = |frames: usize| frames * PAGE_SIZE / 1024;
+    format!(
+        "MemTotal:\t{} kB\nMemFree:\t{} kB\nMemUsed:\t{} kB\nHeapTotal:\t{} kB\nHeapUsed:\t{} kB\n",
+        kib(total_frames),
+        kib(free_frames),
+        kib(total_frames - free_frames),
+        heap_total / 1024,
+        heap_used / 1024,
+    )
+}
+
+/// Blocks of the cache shared by all filesystems on block devices
+fn blockcache() -> String {
+    let stats = block_cache_stats();
+    format!(
+        "Capacity:\t{}\nCached:\t{}\nDirty:\t{}\nHits:\t{}\nMisses:\t{}\nWritebacks:\t{}\n\
+         Errors:\t{}\n",
+        stats.capacity,
+        stats.cached,
+        stats.dirty,
+        stats.hits,
+        stats.misses,
+        stats.writebacks,
+        stats.errors,
+    )
+}
+
+/// Seconds since the machine started
+fn uptime() -> String {
+    let us = get_time_us();
+    format!("{}.{:02}\n", us / MICRO_PER_SEC, us % MICRO_PER_SEC / 10_000)
+}
@@ -0,0 +1,114 @@
+use alloc::collections::VecDeque;
+use lazy_static::lazy_static;
+
+use super::{File, Stat, S_IFCHR};
+use crate::{
+    mem::page_table::UserBuffer,
+    sbi::{console_get_char, console_put_char},
+    syscall::errno::{EBADF, EINTR},
+    task::{
+        signal::{signal_all, signal_pending, SIGINT},
+        suspend_and_run_next,
+    },
+    upsync::UPSyncCell,
+};
+
+/// What Ctrl-C types
+const CTRL_C: u8 = 0x03;
+
+lazy_static! {
+    /// Characters typed on the console which have not been read yet
+    static ref CONSOLE_INPUT: UPSyncCell<VecDeque<u8>> =
+        unsafe { UPSyncCell::new(VecDeque::new()) };
+}
+
+/// Move what has been typed on the console to [`CONSOLE_INPUT`], except Ctrl-C, which sends
+/// `SIGINT` to all tasks. The timer interrupt calls this, so Ctrl-C works while nobody reads.
+pub fn poll_console() {
+    loop {
+        // No character is 0 for RustSBI and -1 for the SBI specification
+        let c = match console_get_char() {
+            0 | usize::MAX => return,
+            c => c as u8,
+        };
+        if c == CTRL_C {
+            signal_all(SIGINT);
+        } else {
+            CONSOLE_INPUT.exclusive_access().push_back(c);
+        }
+    }
+}
+
+/// Read a character from the console, waiting until there is one. Fails with `EINTR` if a signal
+/// arrives first.
+pub fn console_read_byte() -> Result<u8, isize> {
+    loop {
+        poll_console();
+        if let Some(c) = CONSOLE_INPUT.exclusive_access().pop_front() {
+            return Ok(c);
+        }
+        if signal_pending() {
+            return Err(EINTR);
+        }
+        suspend_and_run_next();
+    }
+}
+
+pub struct Stdin;
+
+/// Used for both stdout and stderr
+pub struct Stdout;
+
+impl File for Stdin {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    /// Read at most one character, waiting until there is one
+    fn read(&self, mut buf: UserBuffer) -> Result<usize, isize> {
+        let Some(byte) = buf.iter_mut().next() else {
+            return Ok(0);
+        };
+        *byte = console_read_byte()?;
+        Ok(1)
+    }
+
+    fn write(&self, _buf: UserBuffer) -> Result<usize, isize> {
+        Err(EBADF)
+    }
+
+    fn stat(&self) -> Stat {
+        Stat::anonymous(S_IFCHR | 0o620)
+    }
+}
+
+impl File for Stdout {
+    fn readable(&self) -> bool {
+        false
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, _buf: UserBuffer) -> Result<usize, isize> {
+        Err(EBADF)
+    }
+
+    fn write(&self, buf: UserBuffer) -> Result<usize, isize> {
+        for buffer in buf.buffers.iter() {
+            for &c in buffer.iter() {
+                console_put_char(c as usize);
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn stat(&self) -> Stat {
+        Stat::anonymous(S_IFCHR | 0o620)
+    }
+}
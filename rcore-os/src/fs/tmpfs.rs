@@ -0,0 +1,280 @@
+//! A writable filesystem in memory. The content of each file is kept in frames of the frame
+//! allocator, which are returned when the file is truncated or removed and no longer open.
+
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::{
+    cell::RefMut,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use super::vfs::{AsAny, FileSystem, Inode, InodeType};
+use crate::{
+    config::PAGE_SIZE,
+    mem::frame_allocator::{frame_alloc, FrameTracker},
+    syscall::errno::{EEXIST, EINVAL, EISDIR, ENOENT, ENOSPC, ENOTDIR, ENOTEMPTY, EPERM, EXDEV},
+    upsync::UPSyncCell,
+};
+
+pub struct TmpFs {
+    root: Arc<TmpInode>,
+}
+
+impl TmpFs {
+    pub fn new() -> Self {
+        Self {
+            root: Arc::new(TmpInode::new_dir()),
+        }
+    }
+}
+
+impl FileSystem for TmpFs {
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+}
+
+/// Inode numbers are unique among all tmpfs instances
+static NEXT_INO: AtomicU64 = AtomicU64::new(1);
+
+struct TmpInode {
+    ino: u64,
+    content: TmpContent,
+}
+
+enum TmpContent {
+    File(UPSyncCell<TmpFile>),
+    Dir(UPSyncCell<BTreeMap<String, Arc<TmpInode>>>),
+}
+
+struct TmpFile {
+    /// Number of directory entries of the file
+    nlink: usize,
+    size: usize,
+    /// Bytes after `size` in the last frames are always 0, so growing a file needs no zeroing
+    frames: Vec<FrameTracker>,
+}
+
+impl TmpFile {
+    /// Allocate frames until `size` bytes fit, or as many as there are. Return whether they fit.
+    fn reserve(&mut self, size: usize) -> bool {
+        while self.frames.len() < size.div_ceil(PAGE_SIZE) {
+            match frame_alloc() {
+                Some(frame) => self.frames.push(frame),
+                None => return false,
+            }
+        }
+        true
+    }
+
+    fn truncate(&mut self, size: usize) -> Result<(), isize> {
+        if size > self.size {
+            if !self.reserve(size) {
+                self.frames.truncate(self.size.div_ceil(PAGE_SIZE));
+                return Err(ENOSPC);
+            }
+        } else {
+            self.frames.truncate(size.div_ceil(PAGE_SIZE));
+            if size % PAGE_SIZE != 0 {
+                self.frames[size / PAGE_SIZE].ppn.get_byte_array()[size % PAGE_SIZE..].fill(0);
+            }
+        }
+        self.size = size;
+        Ok(())
+    }
+}
+
+/// `.` and `..` are handled by the VFS and cannot be entries
+fn check_name(name: &str) -> Result<(), isize> {
+    match name {
+        "" | "." | ".." => Err(EINVAL),
+        _ if name.contains('/') => Err(EINVAL),
+        _ => Ok(()),
+    }
+}
+
+impl TmpInode {
+    fn new(content: TmpContent) -> Self {
+        Self {
+            ino: NEXT_INO.fetch_add(1, Ordering::Relaxed),
+            content,
+        }
+    }
+
+    fn new_file() -> Self {
+        Self::new(TmpContent::File(unsafe {
+            UPSyncCell::new(TmpFile {
+                nlink: 1,
+                size: 0,
+                frames: Vec::new(),
+            })
+        }))
+    }
+
+    fn new_dir() -> Self {
+        Self::new(TmpContent::Dir(unsafe { UPSyncCell::new(BTreeMap::new()) }))
+    }
+
+    fn file(&self) -> Result<RefMut<'_, TmpFile>, isize> {
+        match &self.content {
+            TmpContent::File(file) => Ok(file.exclusive_access()),
+            TmpContent::Dir(_) => Err(EISDIR),
+        }
+    }
+
+    fn entries(&self) -> Result<RefMut<'_, BTreeMap<String, Arc<TmpInode>>>, isize> {
+        match &self.content {
+            TmpContent::File(_) => Err(ENOTDIR),
+            TmpContent::Dir(entries) => Ok(entries.exclusive_access()),
+        }
+    }
+}
+
+impl Inode for TmpInode {
+    fn type_(&self) -> InodeType {
+        match self.content {
+            TmpContent::File(_) => InodeType::File,
+            TmpContent::Dir(_) => InodeType::Dir,
+        }
+    }
+
+    fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn nlink(&self) -> usize {
+        match &self.content {
+            TmpContent::File(file) => file.exclusive_access().nlink,
+            TmpContent::Dir(entries) => {
+                let entries = entries.exclusive_access();
+                2 + entries.values().filter(|inode| inode.is_dir()).count()
+            }
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.file().map_or(0, |file| file.size)
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, isize> {
+        let file = self.file()?;
+        let end = file.size.min(offset + buf.len());
+        let mut pos = offset;
+        while pos < end {
+            let page = file.frames[pos / PAGE_SIZE].ppn.get_byte_array();
+            let start = pos % PAGE_SIZE;
+            let len = (PAGE_SIZE - start).min(end - pos);
+            buf[pos - offset..pos - offset + len].copy_from_slice(&page[start..start + len]);
+            pos += len;
+        }
+        Ok(end.saturating_sub(offset))
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, isize> {
+        let mut file = self.file()?;
+        file.reserve(offset + buf.len());
+        let end = (offset + buf.len()).min(file.frames.len() * PAGE_SIZE);
+        if end <= offset {
+            return Ok(0);
+        }
+        let mut pos = offset;
+        while pos < end {
+            let page = file.frames[pos / PAGE_SIZE].ppn.get_byte_array();
+            let start = pos % PAGE_SIZE;
+            let len = (PAGE_SIZE - start).min(end - pos);
+            page[start..start + len].copy_from_slice(&buf[pos - offset..pos - offset + len]);
+            pos += len;
+        }
+        file.size = file.size.max(end);
+        Ok(end - offset)
+    }
+
+    fn truncate(&self, size: usize) -> Result<(), isize> {
+        self.file()?.truncate(size)
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, isize> {
+        let inode = self.entries()?.get(name).cloned().ok_or(ENOENT)?;
+        Ok(inode)
+    }
+
+    fn create(&self, name: &str, type_: InodeType) -> Result<Arc<dyn Inode>, isize> {
+        check_name(name)?;
+        let mut entries = self.entries()?;
+        if entries.contains_key(name) {
+            return Err(EEXIST);
+        }
+        let inode = Arc::new(match type_ {
+            InodeType::File => TmpInode::new_file(),
+            InodeType::Dir => TmpInode::new_dir(),
+            InodeType::CharDevice | InodeType::Symlink => return Err(EINVAL),
+        });
+        entries.insert(name.to_string(), inode.clone());
+        Ok(inode)
+    }
+
+    fn link(&self, name: &str, inode: Arc<dyn Inode>) -> Result<(), isize> {
+        let inode = AsAny::into_any(inode)
+            .downcast::<TmpInode>()
+            .map_err(|_| EXDEV)?;
+        check_name(name)?;
+        let mut entries = self.entries()?;
+        if entries.contains_key(name) {
+            return Err(EEXIST);
+        }
+        inode.file().map_err(|_| EPERM)?.nlink += 1;
+        entries.insert(name.to_string(), inode);
+        Ok(())
+    }
+
+    fn unlink(&self, name: &str) -> Result<(), isize> {
+        let mut entries = self.entries()?;
+        let inode = entries.get(name).ok_or(ENOENT)?;
+        inode.file()?.nlink -= 1;
+        entries.remove(name);
+        Ok(())
+    }
+
+    fn rmdir(&self, name: &str) -> Result<(), isize> {
+        let mut entries = self.entries()?;
+        let inode = entries.get(name).ok_or(ENOENT)?;
+        if !inode.entries()?.is_empty() {
+            return Err(ENOTEMPTY);
+        }
+        entries.remove(name);
+        Ok(())
+    }
+
+    fn rename(&self, old_name: &str, new_dir: &dyn Inode, new_name: &str) -> Result<(), isize> {
+        let new_dir = AsAny::as_any(new_dir)
+            .downcast_ref::<TmpInode>()
+            .ok_or(EXDEV)?;
+        check_name(new_name)?;
+        let inode = self.entries()?.get(old_name).cloned().ok_or(ENOENT)?;
+        let existing = new_dir.entries()?.get(new_name).cloned();
+        if let Some(existing) = existing {
+            if Arc::ptr_eq(&existing, &inode) {
+                return Ok(());
+            }
+            match (inode.is_dir(), existing.is_dir()) {
+                (false, true) => return Err(EISDIR),
+                (true, false) => return Err(ENOTDIR),
+                (true, true) if !existing.entries()?.is_empty() => return Err(ENOTEMPTY),
+                (true, true) => {}
+                (false, false) => existing.file()?.nlink -= 1,
+            }
+        }
+        // Both directories may be the same one, so they are not borrowed at the same time
+        self.entries()?.remove(old_name);
+        new_dir.entries()?.insert(new_name.to_string(), inode);
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<String>, isize> {
+        Ok(self.entries()?.keys().cloned().collect())
+    }
+}
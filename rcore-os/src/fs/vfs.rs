@@ -0,0 +1,373 @@
+//! The virtual filesystem: what every filesystem provides, the mount table and path lookup.
+//!
+//! Paths are resolved lexically into absolute paths first, so `..` never leaves a filesystem
+//! through its root by itself. Mount points are looked up by their absolute path, and need not
+//! exist in the filesystem they are mounted on. Symbolic links are followed by replacing them with
+//! their targets in the path, which is then resolved lexically again.
+
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::{
+    any::Any,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use lazy_static::lazy_static;
+
+use crate::{
+    syscall::errno::{
+        EBUSY, EEXIST, EINVAL, EISDIR, ELOOP, ENOENT, ENOTDIR, EPERM, EROFS, EXDEV,
+    },
+    upsync::UPSyncCell,
+};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum InodeType {
+    File,
+    Dir,
+    /// A device read and written a byte at a time, without offsets. Only devfs has them.
+    CharDevice,
+    /// A symbolic link, whose target is read by [`Inode::read_link`]
+    Symlink,
+}
+
+/// Lets a filesystem recognize its own inodes behind `&dyn Inode`, e.g. the new parent of
+/// [`Inode::rename`]
+pub trait AsAny {
+    fn as_any(&self) -> &dyn Any;
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync>;
+}
+
+impl<T: Any + Send + Sync> AsAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+}
+
+/// A file or a directory of a filesystem. Errors are errnos from [`crate::syscall::errno`], and
+/// the default methods fail the way a read-only filesystem does.
+pub trait Inode: AsAny + Send + Sync {
+    fn type_(&self) -> InodeType;
+
+    /// Number of the inode, unique in its filesystem and never 0
+    fn ino(&self) -> u64;
+
+    /// Number of names of a file, or 2 plus the number of subdirectories of a directory, like
+    /// `st_nlink` of Linux. Filesystems without hard links can keep the default.
+    fn nlink(&self) -> usize {
+        match self.type_() {
+            InodeType::Dir => 2,
+            _ => 1,
+        }
+    }
+
+    fn is_dir(&self) -> bool {
+        self.type_() == InodeType::Dir
+    }
+
+    /// Size in bytes of a file
+    fn size(&self) -> usize {
+        0
+    }
+
+    /// Read into `buf` from `offset` and return how many bytes are read, 0 at the end of file
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> Result<usize, isize> {
+        Err(EISDIR)
+    }
+
+    /// Write `buf` at `offset` and return how many bytes are written, fewer than `buf.len()` only
+    /// when the filesystem is full
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> Result<usize, isize> {
+        Err(EROFS)
+    }
+
+    /// Set the size of a file, filling it with zeros if it grows
+    fn truncate(&self, _size: usize) -> Result<(), isize> {
+        Err(EROFS)
+    }
+
+    /// Find `name` in a directory
+    fn lookup(&self, _name: &str) -> Result<Arc<dyn Inode>, isize> {
+        Err(ENOTDIR)
+    }
+
+    /// Create the file or directory `name` in a directory. `name` does not exist yet.
+    fn create(&self, _name: &str, _type_: InodeType) -> Result<Arc<dyn Inode>, isize> {
+        Err(EROFS)
+    }
+
+    /// Create the symbolic link `name` to `target` in a directory. `name` does not exist yet.
+    fn symlink(&self, _name: &str, _target: &str) -> Result<(), isize> {
+        Err(EROFS)
+    }
+
+    /// The target of a symbolic link
+    fn read_link(&self) -> Result<String, isize> {
+        Err(EINVAL)
+    }
+
+    /// Add `name` to a directory as another name of `inode`, a file of the same filesystem
+    fn link(&self, _name: &str, _inode: Arc<dyn Inode>) -> Result<(), isize> {
+        Err(EROFS)
+    }
+
+    /// Remove the file `name` from a directory. Its content is freed when it has no names and is
+    /// no longer open.
+    fn unlink(&self, _name: &str) -> Result<(), isize> {
+        Err(EROFS)
+    }
+
+    /// Remove the empty directory `name` from a directory
+    fn rmdir(&self, _name: &str) -> Result<(), isize> {
+        Err(EROFS)
+    }
+
+    /// Move `old_name` in this directory to `new_name` in `new_dir` of the same filesystem,
+    /// replacing what `new_name` was like `rename(2)` does
+    fn rename(&self, _old_name: &str, _new_dir: &dyn Inode, _new_name: &str) -> Result<(), isize> {
+        Err(EROFS)
+    }
+
+    /// Names in a directory, without `.` and `..`
+    fn list(&self) -> Result<Vec<String>, isize> {
+        Err(ENOTDIR)
+    }
+}
+
+/// A mounted filesystem
+pub trait FileSystem: Send + Sync {
+    fn root_inode(&self) -> Arc<dyn Inode>;
+}
+
+struct Mount {
+    /// `st_dev` of the files in the filesystem, unique among all mounts since boot
+    dev: u64,
+    fs: Arc<dyn FileSystem>,
+}
+
+static NEXT_DEV: AtomicU64 = AtomicU64::new(1);
+
+lazy_static! {
+    /// Mounted filesystems by the absolute path of their mount points
+    static ref MOUNT_TABLE: UPSyncCell<BTreeMap<String, Mount>> =
+        unsafe { UPSyncCell::new(BTreeMap::new()) };
+}
+
+/// Resolve `path` relative to `cwd` into an absolute path without `.`, `..` or empty components.
+/// `..` of the root is the root.
+pub fn absolute_path(cwd: &str, path: &str) -> String {
+    let base = if path.starts_with('/') { "" } else { cwd };
+    let mut components: Vec<&str> = Vec::new();
+    for component in base.split('/').chain(path.split('/')) {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            name => components.push(name),
+        }
+    }
+    let mut absolute = String::new();
+    for component in components {
+        absolute.push('/');
+        absolute.push_str(component);
+    }
+    if absolute.is_empty() {
+        absolute.push('/');
+    }
+    absolute
+}
+
+/// Split an absolute path into its parent and its last component. The root has no parent.
+pub fn split_parent(path: &str) -> Option<(&str, &str)> {
+    let (parent, name) = path.rsplit_once('/')?;
+    if name.is_empty() {
+        return None;
+    }
+    Some((if parent.is_empty() { "/" } else { parent }, name))
+}
+
+/// Symbolic links followed in one lookup before it fails with `ELOOP`, like Linux
+const MAX_SYMLINKS: usize = 40;
+
+/// Find the inode of an absolute path returned by [`absolute_path`], following symbolic links
+pub fn lookup(path: &str) -> Result<Arc<dyn Inode>, isize> {
+    resolve(path, true).map(|(_, inode)| inode)
+}
+
+/// Find the inode of an absolute path and the absolute path of it without symbolic links. The
+/// last component is only followed if `follow`, so it can be a symbolic link itself.
+pub fn resolve(path: &str, follow: bool) -> Result<(String, Arc<dyn Inode>), isize> {
+    let mut path = path.to_string();
+    let mut links = 0;
+    'restart: loop {
+        let mount_table = MOUNT_TABLE.borrow();
+        let mut inode = mount_table.get("/").ok_or(ENOENT)?.fs.root_inode();
+        let mut prefix = String::new();
+        let names: Vec<&str> = path.split('/').filter(|name| !name.is_empty()).collect();
+        for (i, name) in names.iter().enumerate() {
+            let parent_len = prefix.len();
+            prefix.push('/');
+            prefix.push_str(name);
+            let next = match mount_table.get(&prefix) {
+                Some(mount) => mount.fs.root_inode(),
+                None => inode.lookup(name)?,
+            };
+            let is_last = i + 1 == names.len();
+            if next.type_() == InodeType::Symlink && (follow || !is_last) {
+                links += 1;
+                if links > MAX_SYMLINKS {
+                    return Err(ELOOP);
+                }
+                let parent = if parent_len == 0 { "/" } else { &prefix[..parent_len] };
+                let target = format!("{}/{}", next.read_link()?, names[i + 1..].join("/"));
+                let new_path = absolute_path(parent, &target);
+                drop(mount_table);
+                path = new_path;
+                continue 'restart;
+            }
+            inode = next;
+        }
+        if prefix.is_empty() {
+            prefix.push('/');
+        }
+        return Ok((prefix, inode));
+    }
+}
+
+/// The parent directory of the absolute path `path` with symbolic links followed, the absolute
+/// path of `path` in it, and the last component of `path`. The root has no parent, which fails
+/// with `root_errno`.
+fn resolve_parent(path: &str, root_errno: isize) -> Result<(Arc<dyn Inode>, String, &str), isize> {
+    let (parent, name) = split_parent(path).ok_or(root_errno)?;
+    let (parent_path, parent) = resolve(parent, true)?;
+    Ok((parent, absolute_path(&parent_path, name), name))
+}
+
+/// The absolute path of the mount point of the filesystem `path` is in
+fn mount_point_of(path: &str) -> String {
+    let mount_table = MOUNT_TABLE.borrow();
+    let mut prefix = String::from("/");
+    let mut mount_point = prefix.clone();
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        if prefix.len() > 1 {
+            prefix.push('/');
+        }
+        prefix.push_str(name);
+        if mount_table.contains_key(&prefix) {
+            mount_point = prefix.clone();
+        }
+    }
+    mount_point
+}
+
+fn is_mount_point(path: &str) -> bool {
+    MOUNT_TABLE.borrow().contains_key(path)
+}
+
+/// `st_dev` of the file at the absolute path `path`
+pub fn device_of(path: &str) -> u64 {
+    MOUNT_TABLE
+        .borrow()
+        .get(&mount_point_of(path))
+        .map_or(0, |mount| mount.dev)
+}
+
+/// Create a file or directory at the absolute path `path`
+pub fn create(path: &str, type_: InodeType) -> Result<Arc<dyn Inode>, isize> {
+    let (parent, path, name) = resolve_parent(path, EEXIST)?;
+    if is_mount_point(&path) || parent.lookup(name).is_ok() {
+        return Err(EEXIST);
+    }
+    parent.create(name, type_)
+}
+
+/// Create a symbolic link to `target` at the absolute path `path`. `target` is not resolved.
+pub fn symlink(target: &str, path: &str) -> Result<(), isize> {
+    if target.is_empty() {
+        return Err(ENOENT);
+    }
+    let (parent, path, name) = resolve_parent(path, EEXIST)?;
+    if is_mount_point(&path) || parent.lookup(name).is_ok() {
+        return Err(EEXIST);
+    }
+    parent.symlink(name, target)
+}
+
+/// Remove the file or, if `is_dir`, the empty directory at the absolute path `path`. A symbolic
+/// link is removed itself.
+pub fn remove(path: &str, is_dir: bool) -> Result<(), isize> {
+    let (parent, path, name) = resolve_parent(path, EBUSY)?;
+    if is_mount_point(&path) {
+        return Err(EBUSY);
+    }
+    match is_dir {
+        true => parent.rmdir(name),
+        false => parent.unlink(name),
+    }
+}
+
+/// Make the absolute path `new_path` another name of the file at `old_path` in the same
+/// filesystem. `old_path` is only followed if it is a symbolic link and `follow`.
+pub fn link(old_path: &str, new_path: &str, follow: bool) -> Result<(), isize> {
+    let (old_path, inode) = resolve(old_path, follow)?;
+    let (new_parent, new_path, new_name) = resolve_parent(new_path, EEXIST)?;
+    if mount_point_of(&old_path) != mount_point_of(&new_path) {
+        return Err(EXDEV);
+    }
+    if inode.is_dir() {
+        return Err(EPERM);
+    }
+    if is_mount_point(&new_path) || new_parent.lookup(new_name).is_ok() {
+        return Err(EEXIST);
+    }
+    new_parent.link(new_name, inode)
+}
+
+/// Move the absolute path `old_path` to `new_path` in the same filesystem
+pub fn rename(old_path: &str, new_path: &str) -> Result<(), isize> {
+    let (old_parent, old_path, old_name) = resolve_parent(old_path, EBUSY)?;
+    let (new_parent, new_path, new_name) = resolve_parent(new_path, EBUSY)?;
+    if is_mount_point(&old_path) || is_mount_point(&new_path) {
+        return Err(EBUSY);
+    }
+    if mount_point_of(&old_path) != mount_point_of(&new_path) {
+        return Err(EXDEV);
+    }
+    // A directory cannot be moved into itself
+    if new_path.starts_with(&old_path) && new_path[old_path.len()..].starts_with('/') {
+        return Err(EINVAL);
+    }
+    old_parent.rename(old_name, new_parent.as_ref(), new_name)
+}
+
+/// Mount `fs` at the absolute path `target`. A mount point can only hold one filesystem.
+pub fn mount(target: &str, fs: Arc<dyn FileSystem>) -> Result<(), isize> {
+    let mut mount_table = MOUNT_TABLE.exclusive_access();
+    if mount_table.contains_key(target) {
+        return Err(EBUSY);
+    }
+    let dev = NEXT_DEV.fetch_add(1, Ordering::Relaxed);
+    mount_table.insert(target.to_string(), Mount { dev, fs });
+    Ok(())
+}
+
+/// Unmount the filesystem at the absolute path `target`. Files which are still open keep working.
+pub fn umount(target: &str) -> Result<(), isize> {
+    if target == "/" {
+        return Err(EBUSY);
+    }
+    MOUNT_TABLE
+        .exclusive_access()
+        .remove(target)
+        .map(|_| ())
+        .ok_or(EINVAL)
+}
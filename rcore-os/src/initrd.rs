@@ -0,0 +1,166 @@
+//! Initial ramdisk in the cpio newc format, placed in memory by QEMU with
+//! `-device loader,file=initrd.cpio,addr=...`. Its memory is never handed to the frame allocator,
+//! so the files can be borrowed for the lifetime of the kernel.
+
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+
+use crate::{board, config::INITRD_BASE, log, upsync::UPSyncCell, warn};
+
+const NEWC_MAGIC: &[u8] = b"070701";
+/// Same layout as `NEWC_MAGIC`, with a checksum of the data in the `check` field
+const CRC_MAGIC: &[u8] = b"070702";
+const HEADER_SIZE: usize = 110;
+const TRAILER: &str = "TRAILER!!!";
+
+const MODE_TYPE_MASK: u32 = 0o170000;
+const MODE_REGULAR: u32 = 0o100000;
+
+fn align4(n: usize) -> usize {
+    (n + 3) & !3
+}
+
+/// The `index`-th 8-digit hexadecimal field of the header after the magic
+fn header_field(header: &[u8], index: usize) -> Option<u32> {
+    let start = NEWC_MAGIC.len() + index * 8;
+    let digits = core::str::from_utf8(header.get(start..start + 8)?).ok()?;
+    u32::from_str_radix(digits, 16).ok()
+}
+
+pub struct CpioEntry<'a> {
+    pub name: &'a str,
+    pub mode: u32,
+    pub data: &'a [u8],
+}
+
+impl CpioEntry<'_> {
+    pub fn is_file(&self) -> bool {
+        self.mode & MODE_TYPE_MASK == MODE_REGULAR
+    }
+}
+
+/// Entries of an archive, stopping at the trailer or at the first malformed header
+pub struct CpioIter<'a> {
+    data: &'a [u8],
+    offset: usize,
+    /// Whether the trailer has been reached
+    finished: bool,
+}
+
+impl<'a> CpioIter<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            offset: 0,
+            finished: false,
+        }
+    }
+
+    /// Length of the whole archive including the trailer, or `None` if it is malformed
+    pub fn archive_len(mut self) -> Option<usize> {
+        while self.next().is_some() {}
+        self.finished.then_some(self.offset)
+    }
+}
+
+impl<'a> Iterator for CpioIter<'a> {
+    type Item = CpioEntry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let header = self.data.get(self.offset..self.offset + HEADER_SIZE)?;
+        let magic = &header[..NEWC_MAGIC.len()];
+        if magic != NEWC_MAGIC && magic != CRC_MAGIC {
+            return None;
+        }
+        let mode = header_field(header, 1)?;
+        let file_size = header_field(header, 6)? as usize;
+        let name_size = header_field(header, 11)? as usize;
+
+        // The name is NUL-terminated, and both the name and the data are padded to 4 bytes
+        let name_start = self.offset + HEADER_SIZE;
+        let name_bytes = self.data.get(name_start..name_start + name_size.checked_sub(1)?)?;
+        let name = core::str::from_utf8(name_bytes).ok()?;
+        let data_start = align4(name_start + name_size);
+        let data = self.data.get(data_start..data_start + file_size)?;
+
+        self.offset = align4(data_start + file_size).min(self.data.len());
+        if name == TRAILER {
+            self.finished = true;
+            return None;
+        }
+        Some(CpioEntry { name, mode, data })
+    }
+}
+
+struct Initrd {
+    /// `[start, end)` of the archive in physical memory
+    range: Option<(usize, usize)>,
+    /// Regular files, by their path in the archive without a leading `./` or `/`
+    files: Vec<(&'static str, &'static [u8])>,
+}
+
+lazy_static! {
+    static ref INITRD: UPSyncCell<Initrd> = unsafe {
+        UPSyncCell::new(Initrd {
+            range: None,
+            files: Vec::new(),
+        })
+    };
+}
+
+/// Look for an archive at `INITRD_BASE` when the device tree does not tell where it is
+fn probe() -> Option<(usize, usize)> {
+    let (_, region_end) = board::board_info()
+        .memory
+        .into_iter()
+        .find(|&(start, end)| (start..end).contains(&INITRD_BASE))?;
+    let data = unsafe {
+        core::slice::from_raw_parts(INITRD_BASE as *const u8, region_end - INITRD_BASE)
+    };
+    let len = CpioIter::new(data).archive_len()?;
+    Some((INITRD_BASE, INITRD_BASE + len))
+}
+
+/// Find and parse the initial ramdisk. Must run before the frame allocator is initialized, which
+/// reserves the memory of the archive.
+pub fn init() {
+    let Some((start, end)) = board::board_info().initrd.or_else(probe) else {
+        log!("No initrd");
+        return;
+    };
+    let data = unsafe { core::slice::from_raw_parts(start as *const u8, end - start) };
+
+    let mut initrd = INITRD.exclusive_access();
+    initrd.range = Some((start, end));
+    for entry in CpioIter::new(data).filter(CpioEntry::is_file) {
+        let name = entry.name.trim_start_matches("./").trim_start_matches('/');
+        initrd.files.push((name, entry.data));
+    }
+    if initrd.files.is_empty() {
+        warn!("Initrd {:#x}..{:#x} has no files", start, end);
+    } else {
+        log!("Initrd {:#x}..{:#x} with {} files", start, end, initrd.files.len());
+    }
+}
+
+/// `[start, end)` of the archive, which must not be used as free memory
+pub fn range() -> Option<(usize, usize)> {
+    INITRD.borrow().range
+}
+
+pub fn get_file(name: &str) -> Option<&'static [u8]> {
+    let name = name.trim_start_matches('/');
+    INITRD
+        .borrow()
+        .files
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|&(_, data)| data)
+}
+
+pub fn file_names() -> Vec<&'static str> {
+    INITRD.borrow().files.iter().map(|&(name, _)| name).collect()
+}
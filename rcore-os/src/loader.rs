@@ -3,7 +3,7 @@ use core::arch::{asm, global_asm};
 use alloc::vec::Vec;
 use lazy_static::lazy_static;
 
-use crate::{log, debug};
+use crate::{initrd, log, debug};
 use crate::trap::context::TrapContext;
 
 pub use crate::config::{APP_BASEADDR, APP_SIZE_LIMIT, MAX_APP_NUM};
@@ -54,19 +54,38 @@ lazy_static! {
     };
 }
 
+/// Programs in the initrd take precedence over the ones linked into the kernel
 pub fn get_app_data_by_name(name: &str) -> Option<&'static [u8]> {
     debug!("Get data for app {}", name);
+    if let Some(data) = initrd::get_file(name) {
+        return Some(data);
+    }
     let num_app = get_num_app();
     (0..num_app)
         .find(|&i| APP_NAMES[i] == name)
         .map(get_app_data)
 }
 
+/// Names of all programs [`get_app_data_by_name`] can find
+pub fn app_names() -> Vec<&'static str> {
+    let mut names = initrd::file_names();
+    for &name in APP_NAMES.iter() {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names.sort();
+    names
+}
+
 pub fn list_apps() {
     log!("=== Apps ===");
     for app_name in APP_NAMES.iter() {
         log!("{}", app_name);
     }
+    for file_name in initrd::file_names() {
+        log!("{} (initrd)", file_name);
+    }
     log!("============");
 }
 
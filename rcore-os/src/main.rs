@@ -18,6 +18,11 @@ mod syscall;
 mod timer;
 mod config;
 mod mem;
+mod fdt;
+mod board;
+mod initrd;
+mod drivers;
+mod fs;
 
 #[macro_use]
 extern crate alloc;
@@ -28,11 +33,16 @@ use core::arch::global_asm;
 global_asm!(include_str!("entry.asm"));
 
 #[no_mangle]
-pub fn rust_main() -> ! {
+pub fn rust_main(_hart_id: usize, dtb: usize) -> ! {
     clear_bss();
     log!("Hello, {}!", "World");
+    mem::init_heap();
+    board::init(dtb);
+    initrd::init();
     mem::init();
     log!("Memory Inited");
+    drivers::init();
+    fs::init();
     trap::init();
     trap::enable_timer_interrupt();
     log!("Trap Inited");
@@ -1,79 +1,215 @@
 use super::address::*;
-use alloc::vec::{self, Vec};
+use alloc::{collections::BTreeMap, vec::Vec};
 
 pub trait FrameAllocator {
+    fn new() -> Self;
+    /// Hand frames in `[l, r)` to the allocator. Can be called for several disjoint ranges.
+    fn add_range(&mut self, l: PhysPageNum, r: PhysPageNum);
     fn alloc(&mut self) -> Option<PhysPageNum>;
     fn dealloc(&mut self, ppn: PhysPageNum);
+    /// Allocate `2^order` physically contiguous frames aligned to their total size
+    fn alloc_contiguous(&mut self, order: usize) -> Option<PhysPageNum>;
+    fn dealloc_contiguous(&mut self, ppn: PhysPageNum, order: usize);
     fn create_with(start: PhysPageNum, end: PhysPageNum) -> Self;
+    /// Frames ever handed to the allocator
+    fn total_frames(&self) -> usize;
+    /// Frames which can be allocated now
+    fn free_frames(&self) -> usize;
 }
 
 pub struct StackFrameAllocator {
     current: usize,
     end: usize,
+    /// Ranges which have never been allocated from, besides `[current, end)`
+    ranges: Vec<(usize, usize)>,
     recycled: Vec<usize>,
+    total: usize,
+}
+
+impl StackFrameAllocator {
+    fn is_untouched(&self, ppn: usize) -> bool {
+        (self.current..self.end).contains(&ppn)
+            || self.ranges.iter().any(|&(l, r)| (l..r).contains(&ppn))
+    }
 }
 
 impl FrameAllocator for StackFrameAllocator {
     fn alloc(&mut self) -> Option<PhysPageNum> {
         if let Some(ppn) = self.recycled.pop() {
-            Some(ppn.into())
-        } else if self.current == self.end {
-            None
-        } else {
-            self.current += 1;
-            Some((self.current - 1).into())
+            return Some(ppn.into());
         }
+        while self.current == self.end {
+            (self.current, self.end) = self.ranges.pop()?;
+        }
+        self.current += 1;
+        Some((self.current - 1).into())
     }
 
     fn dealloc(&mut self, ppn: PhysPageNum) {
         let ppn = ppn.0;
-        if ppn >= self.current || self.recycled.iter().any(|v| *v == ppn) {
+        if self.is_untouched(ppn) || self.recycled.iter().any(|v| *v == ppn) {
             panic!("Frame ppn = {:#x} has not been allocated!", ppn);
         }
         self.recycled.push(ppn);
     }
 
+    /// Contiguous frames are only taken from the never allocated ranges
+    fn alloc_contiguous(&mut self, order: usize) -> Option<PhysPageNum> {
+        let count = 1 << order;
+        let fit = |(l, r): (usize, usize)| {
+            let start = l.next_multiple_of(count);
+            (start + count <= r).then_some(start)
+        };
+
+        if let Some(start) = fit((self.current, self.end)) {
+            self.recycled.extend(self.current..start);
+            self.current = start + count;
+            return Some(start.into());
+        }
+
+        let idx = self.ranges.iter().position(|&range| fit(range).is_some())?;
+        let (l, r) = self.ranges[idx];
+        let start = fit((l, r)).unwrap();
+        self.ranges[idx] = (start + count, r);
+        if l < start {
+            self.ranges.push((l, start));
+        }
+        Some(start.into())
+    }
+
+    fn dealloc_contiguous(&mut self, ppn: PhysPageNum, order: usize) {
+        for i in 0..1 << order {
+            self.dealloc((ppn.0 + i).into());
+        }
+    }
+
     fn create_with(start: PhysPageNum, end: PhysPageNum) -> Self {
         let mut allocator = Self::new();
-        allocator.init(start, end);
+        allocator.add_range(start, end);
         allocator
     }
-}
 
-impl StackFrameAllocator {
-    pub fn init(&mut self, l: PhysPageNum, r: PhysPageNum) {
-        self.current = l.0;
-        self.end = r.0;
+    fn add_range(&mut self, l: PhysPageNum, r: PhysPageNum) {
+        self.total += r.0 - l.0;
+        if self.current == self.end {
+            self.current = l.0;
+            self.end = r.0;
+        } else {
+            self.ranges.push((l.0, r.0));
+        }
     }
 
-    pub fn new() -> Self {
+    fn new() -> Self {
         Self {
             current: 0,
             end: 0,
+            ranges: vec![],
             recycled: vec![],
+            total: 0,
         }
     }
+
+    fn total_frames(&self) -> usize {
+        self.total
+    }
+
+    fn free_frames(&self) -> usize {
+        let untouched: usize = self.ranges.iter().map(|(l, r)| r - l).sum();
+        (self.end - self.current) + untouched + self.recycled.len()
+    }
+}
+
+/// Buddy system allocator, see the `buddy-frame-allocator` crate
+#[cfg(feature = "buddy-frame-allocator")]
+pub struct BuddyFrameAllocator(buddy_frame_allocator::BuddyAllocator);
+
+#[cfg(feature = "buddy-frame-allocator")]
+impl FrameAllocator for BuddyFrameAllocator {
+    fn alloc(&mut self) -> Option<PhysPageNum> {
+        self.alloc_contiguous(0)
+    }
+
+    fn dealloc(&mut self, ppn: PhysPageNum) {
+        self.dealloc_contiguous(ppn, 0)
+    }
+
+    fn alloc_contiguous(&mut self, order: usize) -> Option<PhysPageNum> {
+        self.0.alloc(order).map(PhysPageNum)
+    }
+
+    fn dealloc_contiguous(&mut self, ppn: PhysPageNum, order: usize) {
+        self.0.dealloc(ppn.0, order);
+    }
+
+    fn create_with(start: PhysPageNum, end: PhysPageNum) -> Self {
+        let mut allocator = Self::new();
+        allocator.add_range(start, end);
+        allocator
+    }
+
+    fn add_range(&mut self, l: PhysPageNum, r: PhysPageNum) {
+        self.0.add_range(l.0, r.0);
+    }
+
+    fn new() -> Self {
+        Self(buddy_frame_allocator::BuddyAllocator::new())
+    }
+
+    fn total_frames(&self) -> usize {
+        self.0.total_frames()
+    }
+
+    fn free_frames(&self) -> usize {
+        self.0.free_frames()
+    }
 }
 
-use crate::config::MEMORY_END;
+use crate::board;
 use crate::debug;
+use crate::initrd;
 use crate::upsync::UPSyncCell;
 use lazy_static::lazy_static;
+#[cfg(not(feature = "buddy-frame-allocator"))]
 type FrameAllocatorImpl = StackFrameAllocator;
+#[cfg(feature = "buddy-frame-allocator")]
+type FrameAllocatorImpl = BuddyFrameAllocator;
 lazy_static! {
     pub static ref FRAME_ALLOCATOR: UPSyncCell<FrameAllocatorImpl> =
         unsafe { UPSyncCell::new(FrameAllocatorImpl::new()) };
 }
 
+lazy_static! {
+    /// Reference counts of frames shared by copy-on-write. Frames held by only one tracker are not
+    /// recorded here.
+    static ref FRAME_REF_COUNTS: UPSyncCell<BTreeMap<usize, usize>> =
+        unsafe { UPSyncCell::new(BTreeMap::new()) };
+}
+
 pub fn init_frame_allocator() {
     extern "C" {
         fn kernel_end();
     }
 
-    FRAME_ALLOCATOR.exclusive_access().init(
-        PhysAddr::from(kernel_end as usize).ceil(),
-        PhysAddr::from(MEMORY_END).floor(),
-    );
+    let mut allocator = FRAME_ALLOCATOR.exclusive_access();
+    for (start, end) in board::board_info().memory {
+        // Memory before the end of the kernel image is used by the firmware and the kernel
+        let start = start.max(kernel_end as usize);
+        // The files of the initrd are borrowed by the kernel forever
+        let pieces = match initrd::range() {
+            Some((reserved_start, reserved_end)) if reserved_start < end && start < reserved_end => {
+                [(start, reserved_start), (reserved_end, end)]
+            }
+            _ => [(start, end), (0, 0)],
+        };
+        for (start, end) in pieces {
+            let start = PhysAddr::from(start).ceil();
+            let end = PhysAddr::from(end).floor();
+            if start < end {
+                debug!("Frames {:?}..{:?}", start, end);
+                allocator.add_range(start, end);
+            }
+        }
+    }
 }
 
 pub struct FrameTracker {
@@ -88,14 +224,47 @@ impl FrameTracker {
         }
         Self { ppn }
     }
+
+    /// Create another tracker of the same frame without copying its content. The frame is only
+    /// recycled after all of its trackers are dropped.
+    pub fn share(&self) -> Self {
+        let mut ref_counts = FRAME_REF_COUNTS.exclusive_access();
+        *ref_counts.entry(self.ppn.0).or_insert(1) += 1;
+        Self { ppn: self.ppn }
+    }
+
+    /// How many trackers are holding this frame
+    pub fn ref_count(&self) -> usize {
+        FRAME_REF_COUNTS
+            .exclusive_access()
+            .get(&self.ppn.0)
+            .copied()
+            .unwrap_or(1)
+    }
 }
 
 impl Drop for FrameTracker {
     fn drop(&mut self) {
+        let mut ref_counts = FRAME_REF_COUNTS.exclusive_access();
+        if let Some(count) = ref_counts.get_mut(&self.ppn.0) {
+            *count -= 1;
+            if *count == 1 {
+                ref_counts.remove(&self.ppn.0);
+            }
+            return;
+        }
+        drop(ref_counts);
+
         frame_dealloc(self.ppn);
     }
 }
 
+/// Return (total, free) frames
+pub fn frame_stats() -> (usize, usize) {
+    let allocator = FRAME_ALLOCATOR.borrow();
+    (allocator.total_frames(), allocator.free_frames())
+}
+
 pub fn frame_alloc() -> Option<FrameTracker> {
     FRAME_ALLOCATOR
         .exclusive_access()
@@ -106,3 +275,34 @@ pub fn frame_alloc() -> Option<FrameTracker> {
 pub fn frame_dealloc(ppn: PhysPageNum) {
     FRAME_ALLOCATOR.exclusive_access().dealloc(ppn);
 }
+
+/// Physically contiguous frames allocated together, e.g. for DMA buffers
+pub struct ContiguousFrameTracker {
+    pub ppn: PhysPageNum,
+    pub order: usize,
+}
+
+impl ContiguousFrameTracker {
+    pub fn new(ppn: PhysPageNum, order: usize) -> Self {
+        for i in 0..1 << order {
+            PhysPageNum(ppn.0 + i).get_byte_array().fill(0);
+        }
+        Self { ppn, order }
+    }
+}
+
+impl Drop for ContiguousFrameTracker {
+    fn drop(&mut self) {
+        FRAME_ALLOCATOR
+            .exclusive_access()
+            .dealloc_contiguous(self.ppn, self.order);
+    }
+}
+
+/// Allocate `2^order` physically contiguous frames
+pub fn frame_alloc_contiguous(order: usize) -> Option<ContiguousFrameTracker> {
+    FRAME_ALLOCATOR
+        .exclusive_access()
+        .alloc_contiguous(order)
+        .map(|ppn| ContiguousFrameTracker::new(ppn, order))
+}
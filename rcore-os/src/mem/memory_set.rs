@@ -1,9 +1,10 @@
 use core::arch::asm;
 
-use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
 use bitflags::bitflags;
 use lazy_static::lazy_static;
 use riscv::register::satp;
+use xmas_elf::program::ProgramHeader64;
 
 extern "C" {
     fn text_start();
@@ -33,19 +34,29 @@ lazy_static! {
 
 
 use crate::{
-    config::{MEMORY_END, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT},
+    board,
+    config::{PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT, USER_SPACE_END},
     debug,
     loader::USER_STACK_SIZE,
     mem::address::StepByOne,
+    syscall::errno::{EFAULT, ENAMETOOLONG, ENOEXEC},
     upsync::UPSyncCell,
 };
 
 use super::{
     address::{PhysAddr, PhysPageNum, VPNRange, VirtAddr, VirtPageNum},
     frame_allocator::{frame_alloc, FrameTracker},
-    page_table::{PTEFlags, PageTable, PageTableEntry},
+    page_table::{translate_byte_buffer, PTEFlags, PageTable, PageTableEntry},
 };
 
+/// Types of the entries of the auxiliary vector, which follows `envp` on the initial user stack
+const AT_NULL: usize = 0;
+const AT_PHDR: usize = 3;
+const AT_PHENT: usize = 4;
+const AT_PHNUM: usize = 5;
+const AT_PAGESZ: usize = 6;
+const AT_ENTRY: usize = 9;
+
 pub struct MapArea {
     vpn_range: VPNRange,
     data_frames: BTreeMap<VirtPageNum, FrameTracker>,
@@ -69,6 +80,14 @@ bitflags! {
     }
 }
 
+/// The kind of memory access which caused a page fault
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum AccessType {
+    Read,
+    Write,
+    Execute,
+}
+
 impl MapArea {
     pub fn new(
         start_va: VirtAddr,
@@ -86,6 +105,23 @@ impl MapArea {
         }
     }
 
+    /// `[start, end)` virtual addresses of the area
+    pub fn range(&self) -> (VirtAddr, VirtAddr) {
+        (self.vpn_range.get_start().into(), self.vpn_range.get_end().into())
+    }
+
+    pub fn permission(&self) -> MapPermission {
+        self.map_perm
+    }
+
+    /// Pages backed by frames, which are allocated lazily for framed areas
+    pub fn resident_pages(&self) -> usize {
+        match self.map_type {
+            MapType::Identical => self.vpn_range.into_iter().count(),
+            MapType::Framed => self.data_frames.len(),
+        }
+    }
+
     pub fn from_another(another: &MapArea) -> Self {
         Self {
             vpn_range: VPNRange::new(
@@ -98,15 +134,44 @@ impl MapArea {
         }
     }
 
+    /// Framed pages are not allocated here but on their first access. See
+    /// `MemorySet::handle_page_fault`.
     pub fn map(&mut self, page_table: &mut PageTable) {
+        if self.map_type == MapType::Identical {
+            for vpn in self.vpn_range {
+                self.map_one(page_table, vpn);
+            }
+        }
+    }
+
+    /// Allocate frames for all pages now, for areas which are accessed by the kernel and must not
+    /// fault.
+    pub fn populate(&mut self, page_table: &mut PageTable) {
         for vpn in self.vpn_range {
-            self.map_one(page_table, vpn);
+            if !self.data_frames.contains_key(&vpn) {
+                self.map_one(page_table, vpn);
+            }
         }
     }
 
     pub fn unmap(&mut self, page_table: &mut PageTable) {
-        for vpn in self.vpn_range {
-            self.unmap_one(page_table, vpn);
+        match self.map_type {
+            MapType::Identical => {
+                for vpn in self.vpn_range {
+                    self.unmap_one(page_table, vpn);
+                }
+            }
+            MapType::Framed => {
+                let vpns: Vec<VirtPageNum> = self.data_frames.keys().copied().collect();
+                for vpn in vpns {
+                    // Pages of inaccessible areas keep their frames without being mapped
+                    if page_table.translate(vpn).map_or(false, |pte| pte.is_valid()) {
+                        self.unmap_one(page_table, vpn);
+                    } else {
+                        self.data_frames.remove(&vpn);
+                    }
+                }
+            }
         }
     }
 
@@ -117,6 +182,9 @@ impl MapArea {
         let len = data.len();
         loop {
             debug!("Copy data for virtual page 0x{:x}", current_vpn.0);
+            if !self.data_frames.contains_key(&current_vpn) {
+                self.map_one(page_table, current_vpn);
+            }
             let src = &data[start..len.min(start + PAGE_SIZE)];
             let dst = &mut page_table
                 .translate(current_vpn)
@@ -145,8 +213,7 @@ impl MapArea {
                 ppn
             }
         };
-        let pte_flags = PTEFlags::from_bits(self.map_perm.bits()).unwrap();
-        page_table.map(vpn, ppn, pte_flags);
+        page_table.map(vpn, ppn, self.pte_flags());
     }
 
     pub fn unmap_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
@@ -155,6 +222,133 @@ impl MapArea {
         }
         page_table.unmap(vpn);
     }
+
+    /// Split the area at `at`, leaving `[start, at)` in `self` and returning `[at, end)`
+    pub fn split_off(&mut self, at: VirtPageNum) -> MapArea {
+        assert!(self.contains(at), "Cannot split area at {:?}", at);
+        let end = self.vpn_range.get_end();
+        let data_frames = self.data_frames.split_off(&at);
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), at);
+        MapArea {
+            vpn_range: VPNRange::new(at, end),
+            data_frames,
+            map_type: self.map_type,
+            map_perm: self.map_perm,
+        }
+    }
+
+    /// Change the permission of the area and update the page table entries of allocated pages
+    pub fn set_permission(&mut self, page_table: &mut PageTable, map_perm: MapPermission) {
+        assert_eq!(self.map_type, MapType::Framed);
+        self.map_perm = map_perm;
+        let accessible = self.is_accessible();
+        for (vpn, frame) in self.data_frames.iter() {
+            let mapped = page_table.translate(*vpn).map_or(false, |pte| pte.is_valid());
+            // A valid entry without any of R, W and X is a pointer to the next level page table,
+            // so inaccessible pages are unmapped while their frames are kept
+            if !accessible {
+                if mapped {
+                    page_table.unmap(*vpn);
+                }
+                continue;
+            }
+            let pte_flags = if frame.ref_count() > 1 {
+                self.shared_pte_flags()
+            } else {
+                self.pte_flags()
+            };
+            if mapped {
+                page_table.set_flags(*vpn, pte_flags);
+            } else {
+                page_table.map(*vpn, frame.ppn, pte_flags);
+            }
+        }
+    }
+
+    /// Move the end of the area to `new_end`, releasing frames after it
+    pub fn shrink_to(&mut self, page_table: &mut PageTable, new_end: VirtPageNum) {
+        assert_eq!(self.map_type, MapType::Framed);
+        if new_end < self.vpn_range.get_end() {
+            let mut released = self.split_off(new_end);
+            released.unmap(page_table);
+        }
+    }
+
+    /// Move the end of the area to `new_end`. Frames of the new pages are allocated on demand.
+    pub fn append_to(&mut self, new_end: VirtPageNum) {
+        assert_eq!(self.map_type, MapType::Framed);
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), new_end);
+    }
+
+    pub fn contains(&self, vpn: VirtPageNum) -> bool {
+        self.vpn_range.get_start() <= vpn && vpn < self.vpn_range.get_end()
+    }
+
+    /// Allocate a zeroed frame for a page on its first access. Return false if out of memory.
+    fn map_lazily(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        let Some(frame) = frame_alloc() else {
+            return false;
+        };
+        debug!("Demand paging: vpn {:?} -> {:?}", vpn, frame.ppn);
+        page_table.map(vpn, frame.ppn, self.pte_flags());
+        self.data_frames.insert(vpn, frame);
+        true
+    }
+
+    fn allows(&self, access: AccessType) -> bool {
+        self.map_perm.contains(MapPermission::U)
+            && match access {
+                AccessType::Read => self.map_perm.contains(MapPermission::R),
+                AccessType::Write => self.map_perm.contains(MapPermission::W),
+                AccessType::Execute => self.map_perm.contains(MapPermission::X),
+            }
+    }
+
+    fn pte_flags(&self) -> PTEFlags {
+        PTEFlags::from_bits(self.map_perm.bits()).unwrap()
+    }
+
+    /// Whether the pages can be mapped at all, i.e. have any of R, W and X
+    fn is_accessible(&self) -> bool {
+        self.map_perm
+            .intersects(MapPermission::R | MapPermission::W | MapPermission::X)
+    }
+
+    /// Frames shared with another address space are mapped without `W`, so that the first store
+    /// to them traps and the page can be copied.
+    fn shared_pte_flags(&self) -> PTEFlags {
+        self.pte_flags() - PTEFlags::W
+    }
+
+    /// Handle a store to a copy-on-write page. Return false if the store is not allowed.
+    pub fn copy_on_write(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        if self.map_type != MapType::Framed || !self.map_perm.contains(MapPermission::W) {
+            return false;
+        }
+        let Some(frame) = self.data_frames.get(&vpn) else {
+            return false;
+        };
+
+        if frame.ref_count() > 1 {
+            let Some(new_frame) = frame_alloc() else {
+                return false;
+            };
+            debug!("Copy on write: vpn {:?} {:?} -> {:?}", vpn, frame.ppn, new_frame.ppn);
+            new_frame
+                .ppn
+                .get_byte_array()
+                .copy_from_slice(frame.ppn.get_byte_array());
+            page_table.unmap(vpn);
+            page_table.map(vpn, new_frame.ppn, self.pte_flags());
+            // The old tracker is dropped here, which leaves the frame to the other holders
+            self.data_frames.insert(vpn, new_frame);
+        } else {
+            // The other holders have copied or released the page already
+            page_table.set_flags(vpn, self.pte_flags());
+        }
+
+        true
+    }
 }
 
 pub struct MemorySet {
@@ -178,16 +372,19 @@ impl MemorySet {
         self.areas.push(map_area);
     }
 
+    fn push_populated(&mut self, mut map_area: MapArea) {
+        map_area.populate(&mut self.page_table);
+        self.areas.push(map_area);
+    }
+
+    /// Frames of the area are allocated now, as the kernel cannot handle its own page faults
     pub fn insert_framed_area(
         &mut self,
         start_va: VirtAddr,
         end_va: VirtAddr,
         permission: MapPermission,
     ) {
-        self.push(
-            MapArea::new(start_va, end_va, MapType::Framed, permission),
-            None,
-        );
+        self.push_populated(MapArea::new(start_va, end_va, MapType::Framed, permission));
     }
 
     // Without kernel stacks
@@ -244,43 +441,95 @@ impl MemorySet {
             None,
         );
         // mapping remaining physical memory
-        memory_set.push(
-            MapArea::new(
-                (*KERNEL_END).into(),
-                MEMORY_END.into(),
-                MapType::Identical,
-                MapPermission::R | MapPermission::W,
-            ),
-            None,
-        );
+        for (start, end) in board::board_info().memory {
+            let start = start.max(*KERNEL_END);
+            if start < end {
+                memory_set.push(
+                    MapArea::new(
+                        start.into(),
+                        end.into(),
+                        MapType::Identical,
+                        MapPermission::R | MapPermission::W,
+                    ),
+                    None,
+                );
+            }
+        }
+        // mapping device registers
+        for (start, end) in board::mmio_ranges() {
+            debug!("MMIO {:#x}..{:#x}", start, end);
+            memory_set.push(
+                MapArea::new(
+                    start.into(),
+                    end.into(),
+                    MapType::Identical,
+                    MapPermission::R | MapPermission::W,
+                ),
+                None,
+            );
+        }
 
         debug!("Created a new kernel memory set");
         memory_set
     }
 
-    /// return (MemorySet, user_sp, entry_point)
-    pub fn from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
+    /// Return (MemorySet, user_sp, heap_bottom, entry_point). The user stack starts with `argc`,
+    /// `argv`, `envp` and the auxiliary vector like on Linux, see [`MemorySet::push_args`].
+    /// Fail with `ENOEXEC` if `elf_data` is not a valid 64-bit ELF file whose segments fit in user
+    /// space without overlapping.
+    pub fn from_elf(
+        elf_data: &[u8],
+        args: &[String],
+        envs: &[String],
+    ) -> Result<(Self, usize, usize, usize), isize> {
         debug!("Creating app memory set!");
         let mut memory_set = Self::new_bare();
         memory_set.map_trampoline();
 
         debug!("Creating elf context");
-        let elf = xmas_elf::ElfFile::new(elf_data).unwrap();
-        debug!("Get Header");
+        let elf = xmas_elf::ElfFile::new(elf_data).map_err(|_| ENOEXEC)?;
         let elf_header = elf.header;
-        debug!("Get magic");
-        let magic = elf_header.pt1.magic;
-        debug!("Chekc magic");
-        assert_eq!(magic, [0x7f, 0x45, 0x4c, 0x46], "Invalid ELF header!");
+        if elf_header.pt1.class() != xmas_elf::header::Class::SixtyFour {
+            return Err(ENOEXEC);
+        }
 
         debug!("Loading elf sections");
+        // xmas_elf reads the program headers in place without checking them
         let ph_count = elf_header.pt2.ph_count();
+        let ph_offset = elf_header.pt2.ph_offset();
+        let ph_entry_size = elf_header.pt2.ph_entry_size() as u64;
+        let ph_end = ph_offset
+            .checked_add(ph_count as u64 * ph_entry_size)
+            .ok_or(ENOEXEC)?;
+        if ph_count > 0
+            && (ph_entry_size != core::mem::size_of::<ProgramHeader64>() as u64
+                || !ph_offset.is_multiple_of(core::mem::align_of::<ProgramHeader64>() as u64)
+                || ph_end > elf_data.len() as u64)
+        {
+            return Err(ENOEXEC);
+        }
         let mut max_end_vpn = VirtPageNum(0);
+        // The program headers are only in memory if a segment loads them
+        let mut phdr = None;
         for i in 0..ph_count {
-            let ph = elf.program_header(i).unwrap();
-            if ph.get_type().unwrap() == xmas_elf::program::Type::Load {
+            let ph = elf.program_header(i).map_err(|_| ENOEXEC)?;
+            if ph.get_type() == Ok(xmas_elf::program::Type::Load) {
+                let file_end = ph.offset().checked_add(ph.file_size()).ok_or(ENOEXEC)?;
+                let mem_end = ph.virtual_addr().checked_add(ph.mem_size()).ok_or(ENOEXEC)?;
+                if file_end > elf_data.len() as u64
+                    || ph.mem_size() < ph.file_size()
+                    || mem_end > USER_SPACE_END as u64
+                {
+                    return Err(ENOEXEC);
+                }
+                if ph.offset() <= ph_offset && ph_end <= file_end {
+                    phdr = Some((ph.virtual_addr() + ph_offset - ph.offset()) as usize);
+                }
                 let start_va: VirtAddr = (ph.virtual_addr() as usize).into();
-                let end_va: VirtAddr = (ph.virtual_addr() + ph.mem_size()).into();
+                let end_va: VirtAddr = (mem_end as usize).into();
+                if memory_set.overlaps(start_va.floor(), end_va.ceil(), usize::MAX) {
+                    return Err(ENOEXEC);
+                }
 
                 let mut map_perm = MapPermission::U;
                 let ph_flags = ph.flags();
@@ -295,11 +544,11 @@ impl MemorySet {
                 }
 
                 let map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm);
-                max_end_vpn = map_area.vpn_range.get_end();
+                max_end_vpn = max_end_vpn.max(map_area.vpn_range.get_end());
                 debug!("Loading ELF: Mapping 0x{:x}..0x{:x}", start_va.0, end_va.0);
                 memory_set.push(
                     map_area,
-                    Some(&elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize]),
+                    Some(&elf.input[ph.offset() as usize..file_end as usize]),
                 );
             }
         }
@@ -311,11 +560,24 @@ impl MemorySet {
         // guard page
         user_stack_bottom += PAGE_SIZE;
         let user_stack_top = user_stack_bottom + USER_STACK_SIZE;
+        if user_stack_top > USER_SPACE_END {
+            return Err(ENOEXEC);
+        }
 
-        // map user stack and guard page
+        // map user stack and guard page. The kernel writes the arguments to the stack, so its
+        // frames are allocated now.
+        memory_set.push_populated(MapArea::new(
+            user_stack_bottom.into(),
+            user_stack_top.into(),
+            MapType::Framed,
+            MapPermission::R | MapPermission::W | MapPermission::U,
+        ));
+
+        // The heap starts empty right above the user stack and grows with sbrk, so its bottom is
+        // the initial user sp
         memory_set.push(
             MapArea::new(
-                user_stack_bottom.into(),
+                user_stack_top.into(),
                 user_stack_top.into(),
                 MapType::Framed,
                 MapPermission::R | MapPermission::W | MapPermission::U,
@@ -324,37 +586,107 @@ impl MemorySet {
         );
 
         // map trap context and trampoline
-        memory_set.push(
-            MapArea::new(
-                TRAP_CONTEXT.into(),
-                TRAMPOLINE.into(),
-                MapType::Framed,
-                MapPermission::R | MapPermission::W,
-            ),
-            None,
-        );
+        memory_set.push_populated(MapArea::new(
+            TRAP_CONTEXT.into(),
+            TRAMPOLINE.into(),
+            MapType::Framed,
+            MapPermission::R | MapPermission::W,
+        ));
+
+        let entry_point = elf.header.pt2.entry_point() as usize;
+        let mut auxv = vec![(AT_PAGESZ, PAGE_SIZE), (AT_ENTRY, entry_point)];
+        if let Some(phdr) = phdr {
+            auxv.push((AT_PHDR, phdr));
+            auxv.push((AT_PHENT, elf_header.pt2.ph_entry_size() as usize));
+            auxv.push((AT_PHNUM, ph_count as usize));
+        }
+        let user_sp = memory_set.push_args(user_stack_top, args, envs, &auxv);
 
         debug!("Created a new app memory set");
-        (
-            memory_set,
-            user_stack_top,
-            elf.header.pt2.entry_point() as usize,
-        )
+        Ok((memory_set, user_sp, user_stack_top, entry_point))
+    }
+
+    /// Lay out the initial user stack below `stack_top` and return the new stack pointer, which
+    /// points to `argc`. It is followed by the pointers of `argv` and `envp`, each ending with a
+    /// null pointer, and the `(type, value)` pairs of `auxv` ending with `AT_NULL`. The strings
+    /// are above them. The stack pointer is 16-byte aligned as the RISC-V ABI requires.
+    fn push_args(
+        &self,
+        stack_top: usize,
+        args: &[String],
+        envs: &[String],
+        auxv: &[(usize, usize)],
+    ) -> usize {
+        let mut sp = stack_top;
+        let mut push_str = |string: &String| {
+            sp -= string.len() + 1;
+            self.write_user(sp, string.as_bytes());
+            self.write_user(sp + string.len(), &[0]);
+            sp
+        };
+        let env_ptrs: Vec<usize> = envs.iter().map(&mut push_str).collect();
+        let arg_ptrs: Vec<usize> = args.iter().map(&mut push_str).collect();
+
+        let mut words = vec![args.len()];
+        words.extend(arg_ptrs);
+        words.push(0);
+        words.extend(env_ptrs);
+        words.push(0);
+        for &(type_, value) in auxv.iter().chain([(AT_NULL, 0)].iter()) {
+            words.push(type_);
+            words.push(value);
+        }
+        sp = (sp - words.len() * core::mem::size_of::<usize>()) & !0xf;
+        for (i, word) in words.iter().enumerate() {
+            self.write_user(sp + i * core::mem::size_of::<usize>(), &word.to_ne_bytes());
+        }
+        sp
+    }
+
+    /// Copy `data` to the mapped user memory at `va` of this memory set
+    fn write_user(&self, va: usize, data: &[u8]) {
+        let mut data = data;
+        for buffer in translate_byte_buffer(self.token(), va as *const u8, data.len()) {
+            let (head, rest) = data.split_at(buffer.len());
+            buffer.copy_from_slice(head);
+            data = rest;
+        }
     }
 
-    pub fn from_existed_user_space(user_space: &MemorySet) -> Self {
+    /// Create a copy of `user_space` for fork. Pages accessible from user mode are shared with
+    /// `user_space` and copied on the first write of either side, other pages are copied now.
+    /// Pages without any permission stay unmapped on both sides, see [`MapArea::set_permission`].
+    pub fn from_existed_user_space(user_space: &mut MemorySet) -> Self {
         let mut memory_set = Self::new_bare();
         memory_set.map_trampoline();
 
         for area in user_space.areas.iter() {
-            let new_area = MapArea::from_another(area);
-            memory_set.push(new_area, None);
+            let mut new_area = MapArea::from_another(area);
+
+            if area.map_type == MapType::Framed && area.map_perm.contains(MapPermission::U) {
+                let pte_flags = area.shared_pte_flags();
+                let accessible = area.is_accessible();
+                for (vpn, frame) in area.data_frames.iter() {
+                    if accessible {
+                        user_space.page_table.set_flags(*vpn, pte_flags);
+                        memory_set.page_table.map(*vpn, frame.ppn, pte_flags);
+                    }
+                    new_area.data_frames.insert(*vpn, frame.share());
+                }
+                memory_set.areas.push(new_area);
+                continue;
+            }
 
-            for vpn in area.vpn_range {
-                let src_ppn = user_space.translate(vpn).unwrap().ppn();
-                let dst_ppn = memory_set.translate(vpn).unwrap().ppn();
-                dst_ppn.get_byte_array().copy_from_slice(src_ppn.get_byte_array());
+            if area.map_type == MapType::Identical {
+                memory_set.push(new_area, None);
+                continue;
+            }
+            for (vpn, src_frame) in area.data_frames.iter() {
+                new_area.map_one(&mut memory_set.page_table, *vpn);
+                let dst_ppn = new_area.data_frames[vpn].ppn;
+                dst_ppn.get_byte_array().copy_from_slice(src_frame.ppn.get_byte_array());
             }
+            memory_set.areas.push(new_area);
         }
 
         memory_set
@@ -384,6 +716,10 @@ impl MemorySet {
         self.page_table.token()
     }
 
+    pub fn areas(&self) -> impl Iterator<Item = &MapArea> {
+        self.areas.iter()
+    }
+
     pub fn remove_area_with_start_vpn(&mut self, start_vpn: VirtPageNum) {
         if let Some((idx, area)) = self.areas.iter_mut().enumerate().find(|(_, area)| area.vpn_range.get_start() == start_vpn) {
             debug!("Removing area {}: {:?}..{:?}", idx, area.vpn_range.get_start(), area.vpn_range.get_end());
@@ -396,6 +732,249 @@ impl MemorySet {
         panic!("Cannot find area starting with {:?}. Cannot remove!", start_vpn);
     }
 
+    /// Handle a page fault of user code at `va`. Pages of framed areas are allocated on their
+    /// first access, and shared pages are copied on their first write. Return false if the access
+    /// is invalid.
+    pub fn handle_page_fault(&mut self, va: VirtAddr, access: AccessType) -> bool {
+        let vpn = va.floor();
+        let Some(area) = self.areas.iter_mut().find(|area| area.contains(vpn)) else {
+            return false;
+        };
+        if !area.allows(access) {
+            return false;
+        }
+
+        match self.page_table.translate(vpn) {
+            Some(pte) if pte.is_valid() => {
+                if access == AccessType::Write && !pte.is_writable() {
+                    area.copy_on_write(&mut self.page_table, vpn)
+                } else {
+                    // The page has been mapped, e.g. by an access from the kernel
+                    true
+                }
+            }
+            _ if area.map_type == MapType::Framed => area.map_lazily(&mut self.page_table, vpn),
+            _ => false,
+        }
+    }
+
+    /// The kernel accesses user buffers through their physical addresses, which bypasses demand
+    /// paging and copy-on-write. Call this before accessing `len` bytes from `ptr` in the kernel.
+    /// Return false if the buffer is not accessible for the user.
+    pub fn prepare_user_buffer(&mut self, ptr: usize, len: usize, access: AccessType) -> bool {
+        if len == 0 {
+            return true;
+        }
+        if ptr.checked_add(len).map_or(true, |end| end > USER_SPACE_END) {
+            return false;
+        }
+        let start_vpn = VirtAddr::from(ptr).floor();
+        let end_vpn = VirtAddr::from(ptr + len).ceil();
+        for vpn in VPNRange::new(start_vpn, end_vpn) {
+            let ready = match self.translate(vpn) {
+                Some(pte) if pte.is_valid() => access != AccessType::Write || pte.is_writable(),
+                _ => false,
+            };
+            if !ready && !self.handle_page_fault(vpn.into(), access) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Read the `\0` terminated string at `ptr` in user space, preparing one page at a time as
+    /// [`Self::prepare_user_buffer`] does. Fail with `EFAULT` if it is not accessible, or with
+    /// `ENAMETOOLONG` if it is longer than `max_len` bytes without the `\0`.
+    pub fn read_user_str(&mut self, ptr: usize, max_len: usize) -> Result<String, isize> {
+        let mut bytes = Vec::new();
+        let mut va = VirtAddr::from(ptr);
+        loop {
+            if !self.prepare_user_buffer(va.0, 1, AccessType::Read) {
+                return Err(EFAULT);
+            }
+            let ppn = self.translate(va.floor()).unwrap().ppn();
+            let rest = &ppn.get_byte_array()[va.page_offset()..];
+            let end = rest.iter().position(|&byte| byte == 0);
+            let len = end.unwrap_or(rest.len()).min(max_len + 1 - bytes.len());
+            bytes.extend_from_slice(&rest[..len]);
+            if bytes.len() > max_len {
+                return Err(ENAMETOOLONG);
+            }
+            if end.is_some() {
+                return Ok(bytes.into_iter().map(char::from).collect());
+            }
+            va = VirtAddr::from(VirtPageNum(va.floor().0 + 1));
+        }
+    }
+
+    fn overlaps(&self, start_vpn: VirtPageNum, end_vpn: VirtPageNum, except: usize) -> bool {
+        self.areas.iter().enumerate().any(|(idx, area)| {
+            idx != except
+                && area.vpn_range.get_start() < end_vpn
+                && start_vpn < area.vpn_range.get_end()
+        })
+    }
+
+    /// Shrink the area starting at `start` so that it ends at `new_end`
+    pub fn shrink_to(&mut self, start: VirtAddr, new_end: VirtAddr) -> bool {
+        let start_vpn = start.floor();
+        let Some(area) = self
+            .areas
+            .iter_mut()
+            .find(|area| area.vpn_range.get_start() == start_vpn)
+        else {
+            return false;
+        };
+        if new_end.ceil() > area.vpn_range.get_end() || new_end.ceil() < start_vpn {
+            return false;
+        }
+        area.shrink_to(&mut self.page_table, new_end.ceil());
+        true
+    }
+
+    /// Grow the area starting at `start` so that it ends at `new_end`. Fail if the grown area
+    /// would overlap with others.
+    pub fn append_to(&mut self, start: VirtAddr, new_end: VirtAddr) -> bool {
+        let start_vpn = start.floor();
+        let Some(idx) = self
+            .areas
+            .iter()
+            .position(|area| area.vpn_range.get_start() == start_vpn)
+        else {
+            return false;
+        };
+        let old_end_vpn = self.areas[idx].vpn_range.get_end();
+        let new_end_vpn = new_end.ceil();
+        if usize::from(new_end) > USER_SPACE_END || new_end_vpn < old_end_vpn {
+            return false;
+        }
+        if self.overlaps(old_end_vpn, new_end_vpn, idx) {
+            return false;
+        }
+        self.areas[idx].append_to(new_end_vpn);
+        true
+    }
+
+    /// Insert a user area whose frames are allocated on demand
+    pub fn insert_lazy_area(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+    ) {
+        self.push(
+            MapArea::new(start_va, end_va, MapType::Framed, permission),
+            None,
+        );
+    }
+
+    /// Whether no area overlaps with `[start_va, end_va)`
+    pub fn is_free(&self, start_va: VirtAddr, end_va: VirtAddr) -> bool {
+        !self.overlaps(start_va.floor(), end_va.ceil(), usize::MAX)
+    }
+
+    /// Find the lowest free range of `len` bytes from `lowest`
+    pub fn find_free_area(&self, lowest: VirtAddr, len: usize) -> Option<VirtAddr> {
+        let pages = VirtAddr::from(len).ceil().0;
+        let mut start_vpn = lowest.ceil();
+        let mut ranges: Vec<(VirtPageNum, VirtPageNum)> = self
+            .areas
+            .iter()
+            .map(|area| (area.vpn_range.get_start(), area.vpn_range.get_end()))
+            .collect();
+        ranges.sort();
+
+        for (area_start, area_end) in ranges {
+            if area_end <= start_vpn {
+                continue;
+            }
+            if start_vpn.0 + pages <= area_start.0 {
+                break;
+            }
+            start_vpn = area_end;
+        }
+
+        if start_vpn.0 + pages > VirtAddr::from(USER_SPACE_END).floor().0 {
+            return None;
+        }
+        Some(start_vpn.into())
+    }
+
+    /// Cut the framed areas overlapping with `[start_vpn, end_vpn)` at the range boundaries, and
+    /// pass each part inside the range to `f`, which returns the part to keep
+    fn update_range(
+        &mut self,
+        start_vpn: VirtPageNum,
+        end_vpn: VirtPageNum,
+        mut f: impl FnMut(MapArea, &mut PageTable) -> Option<MapArea>,
+    ) {
+        let areas = core::mem::take(&mut self.areas);
+        for mut area in areas {
+            let overlapped = area.vpn_range.get_start() < end_vpn
+                && start_vpn < area.vpn_range.get_end();
+            if !overlapped || area.map_type != MapType::Framed {
+                self.areas.push(area);
+                continue;
+            }
+            if area.vpn_range.get_start() < start_vpn {
+                let right = area.split_off(start_vpn);
+                self.areas.push(area);
+                area = right;
+            }
+            if end_vpn < area.vpn_range.get_end() {
+                let right = area.split_off(end_vpn);
+                self.areas.push(right);
+            }
+            if let Some(area) = f(area, &mut self.page_table) {
+                self.areas.push(area);
+            }
+        }
+    }
+
+    /// Remove all pages in `[start_va, end_va)`. Areas partly in the range are split.
+    pub fn unmap_range(&mut self, start_va: VirtAddr, end_va: VirtAddr) {
+        self.update_range(start_va.floor(), end_va.ceil(), |mut area, page_table| {
+            debug!(
+                "Unmapping {:?}..{:?}",
+                area.vpn_range.get_start(),
+                area.vpn_range.get_end()
+            );
+            area.unmap(page_table);
+            None
+        });
+    }
+
+    /// Change the permission of all pages in `[start_va, end_va)`. Areas partly in the range are
+    /// split. Fail without changing anything if some pages in the range are not mapped.
+    pub fn protect_range(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+    ) -> bool {
+        let start_vpn = start_va.floor();
+        let end_vpn = end_va.ceil();
+        let covered: usize = self
+            .areas
+            .iter()
+            .filter(|area| area.map_type == MapType::Framed)
+            .map(|area| {
+                let l = area.vpn_range.get_start().max(start_vpn);
+                let r = area.vpn_range.get_end().min(end_vpn);
+                r.0.saturating_sub(l.0)
+            })
+            .sum();
+        if covered != end_vpn.0 - start_vpn.0 {
+            return false;
+        }
+
+        self.update_range(start_vpn, end_vpn, |mut area, page_table| {
+            area.set_permission(page_table, permission);
+            Some(area)
+        });
+        true
+    }
+
     pub fn recycle_data_pages(&mut self) {
         self.areas.clear();
     }
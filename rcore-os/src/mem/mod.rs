@@ -8,9 +8,14 @@ pub use memory_set::KERNEL_SPACE;
 
 use crate::log;
 
-pub fn init() {
+/// The heap comes first since parsing the device tree allocates
+pub fn init_heap() {
     heap_allocator::init_heap();
     log!("Heap allocator inited");
+}
+
+/// Needs the memory regions from [`crate::board`]
+pub fn init() {
     frame_allocator::init_frame_allocator();
     log!("Frame allocator inited");
     KERNEL_SPACE.exclusive_access().activate();
@@ -1,11 +1,12 @@
 use crate::{mem::address::*, debug};
-use alloc::{vec::Vec, string::String};
+use alloc::vec::Vec;
 use bitfield::size_of;
 use bitflags::bitflags;
 
 use super::frame_allocator::{frame_alloc, FrameTracker};
 
 bitflags! {
+    #[derive(Clone, Copy)]
     pub struct PTEFlags: u8 {
         const V = 1 << 0;
         const R = 1 << 1;
@@ -46,6 +47,10 @@ impl PageTableEntry {
     pub fn is_valid(&self) -> bool {
         (self.flags() & PTEFlags::V).0 != PTEFlags::empty().0
     }
+
+    pub fn is_writable(&self) -> bool {
+        self.flags().contains(PTEFlags::W)
+    }
 }
 
 pub struct PageTable {
@@ -125,6 +130,12 @@ impl PageTable {
         debug!("Unmap vpn {:?}", vpn);
         *pte = PageTableEntry::empty();
     }
+    /// Change the flags of a mapped page, keeping the frame it points to
+    pub fn set_flags(&mut self, vpn: VirtPageNum, flags: PTEFlags) {
+        let pte = self.find_pte(vpn).unwrap();
+        assert!(pte.is_valid(), "vpn {:?} is invalid before setting flags!", vpn);
+        *pte = PageTableEntry::new(pte.ppn(), flags | PTEFlags::V);
+    }
 }
 
 impl PageTable {
@@ -198,20 +209,22 @@ pub unsafe fn translate_raw<T: Sized>(
     page_table.translate_va(va.into()).unwrap().get_mut()
 }
 
-pub fn translate_str(token: usize, ptr: *const u8) -> String {
-    let page_table = PageTable::from_token(token);
-    let mut string = String::new();
-    let mut va = ptr as usize;
+/// A buffer of a syscall as slices which the kernel can access, e.g. a user space buffer translated
+/// into its pages
+pub struct UserBuffer<'a> {
+    pub buffers: Vec<&'a mut [u8]>,
+}
 
-    loop {
-        let ch: u8 = *(page_table.translate_va(va.into()).unwrap().get_mut());
-        if ch == 0 {
-            break;
-        } else {
-            string.push(ch as char);
-            va += 1;
-        }
+impl<'a> UserBuffer<'a> {
+    pub fn new(buffers: Vec<&'a mut [u8]>) -> Self {
+        Self { buffers }
     }
 
-    string
+    pub fn len(&self) -> usize {
+        self.buffers.iter().map(|b| b.len()).sum()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut u8> + use<'_, 'a> {
+        self.buffers.iter_mut().flat_map(|b| b.iter_mut())
+    }
 }
@@ -0,0 +1,62 @@
+//! Linux error numbers. Syscalls return their negations on failure.
+
+/// Operation not permitted
+pub const EPERM: isize = 1;
+/// No such file or directory
+pub const ENOENT: isize = 2;
+/// No such process
+pub const ESRCH: isize = 3;
+/// Interrupted system call, by a signal
+pub const EINTR: isize = 4;
+/// Input/output error, e.g. a corrupt filesystem
+pub const EIO: isize = 5;
+/// Argument list too long
+pub const E2BIG: isize = 7;
+/// Exec format error
+pub const ENOEXEC: isize = 8;
+/// Bad file descriptor
+pub const EBADF: isize = 9;
+/// No child processes
+pub const ECHILD: isize = 10;
+/// Resource temporarily unavailable, e.g. too many threads
+pub const EAGAIN: isize = 11;
+/// Out of memory
+pub const ENOMEM: isize = 12;
+/// Bad address
+pub const EFAULT: isize = 14;
+/// Device or resource busy
+pub const EBUSY: isize = 16;
+/// File exists
+pub const EEXIST: isize = 17;
+/// Invalid cross-device link
+pub const EXDEV: isize = 18;
+/// No such device
+pub const ENODEV: isize = 19;
+/// Not a directory
+pub const ENOTDIR: isize = 20;
+/// Is a directory
+pub const EISDIR: isize = 21;
+/// Invalid argument
+pub const EINVAL: isize = 22;
+/// Too many open files
+pub const EMFILE: isize = 24;
+/// File too large
+pub const EFBIG: isize = 27;
+/// No space left on device
+pub const ENOSPC: isize = 28;
+/// Read-only file system
+pub const EROFS: isize = 30;
+/// Too many links
+pub const EMLINK: isize = 31;
+/// Broken pipe
+pub const EPIPE: isize = 32;
+/// Result too large
+pub const ERANGE: isize = 34;
+/// Resource deadlock would occur
+pub const EDEADLK: isize = 35;
+/// File name too long
+pub const ENAMETOOLONG: isize = 36;
+/// Directory not empty
+pub const ENOTEMPTY: isize = 39;
+/// Too many levels of symbolic links
+pub const ELOOP: isize = 40;
@@ -1,47 +1,466 @@
-#![allow(unused)]
+use alloc::{string::String, vec};
 
-use crate::{print, mem::page_table::translate_byte_buffer, task::{processor::current_user_token, suspend_and_run_next}, sbi::console_get_char};
+use super::errno::{EBADF, EFAULT, EINVAL, EMFILE, ENOTDIR, ERANGE};
+use crate::{
+    config::{FD_LIMIT, PAGE_SIZE, PATH_MAX},
+    fs::{
+        self, inode_stat, make_pipe, new_filesystem, open_file,
+        vfs::{self, absolute_path, InodeType},
+        OpenFlags, Stat,
+    },
+    mem::{
+        memory_set::AccessType,
+        page_table::{translate_byte_buffer, UserBuffer},
+    },
+    task::processor::current_process,
+    utils::{any_as_u8_slice, copy_to_dsts},
+};
 
-const FD_STDOUT: usize = 1;
-const FD_STDIN: usize = 0;
+/// Bytes which `read` and `write` copy through a kernel buffer at a time. Files may block, which
+/// lets other threads of the process unmap the user buffer or fork, so the user buffer is only
+/// accessed right before or after the file with the process locked.
+const BOUNCE_SIZE: usize = 16 * PAGE_SIZE;
 
-pub fn sys_write(fd: usize, buf: *mut u8, len: usize) -> isize {
-    match fd {
-        FD_STDOUT => {
-            let buffers = translate_byte_buffer(current_user_token(), buf as *const u8, len);
-            for buffer in buffers {
-                print!("{}", core::str::from_utf8(buffer).unwrap());
-            }
-            len as isize
+/// Copy `data.len()` bytes between `data` and `ptr` in user space of the current process, in the
+/// direction of `access`
+fn copy_user(ptr: usize, data: &mut [u8], access: AccessType) -> Result<(), isize> {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    if !inner.memory_set.prepare_user_buffer(ptr, data.len(), access) {
+        return Err(EFAULT);
+    }
+    let mut rest = data;
+    for page in translate_byte_buffer(inner.get_user_token(), ptr as *const u8, rest.len()) {
+        let (chunk, tail) = rest.split_at_mut(page.len());
+        match access {
+            AccessType::Write => page.copy_from_slice(chunk),
+            _ => chunk.copy_from_slice(page),
         }
-        fd => {
-            panic!("Unsupported fd {fd}")
+        rest = tail;
+    }
+    Ok(())
+}
+
+pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let Some(file) = inner.get_file(fd) else {
+        return -EBADF;
+    };
+    if !file.writable() {
+        return -EBADF;
+    }
+    drop(inner);
+
+    let mut done = 0;
+    while done < len {
+        let mut bounce = vec![0u8; (len - done).min(BOUNCE_SIZE)];
+        if let Err(errno) = copy_user(buf as usize + done, &mut bounce, AccessType::Read) {
+            return if done > 0 { done as isize } else { -errno };
+        }
+        let written = match file.write(UserBuffer::new(vec![&mut bounce[..]])) {
+            Ok(written) => written,
+            Err(_) if done > 0 => break,
+            Err(errno) => return -errno,
+        };
+        done += written;
+        if written < bounce.len() {
+            break;
         }
     }
+    done as isize
 }
 
 pub fn sys_read(fd: usize, buf: *mut u8, len: usize) -> isize {
-    match fd {
-        FD_STDIN => {
-            assert_eq!(len, 1, "Only support len = 1 in sys_read");
-            let mut c: usize;
-            loop {
-                c = console_get_char();
-                if c == 0 {
-                    suspend_and_run_next();
-                    continue;
-                } else {
-                    break;
-                }
-            };
-
-            let ch = c as u8;
-            let mut buffers = translate_byte_buffer(current_user_token(), buf, len);
-            unsafe { buffers[0].as_mut_ptr().write_volatile(ch); }
-            1
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let Some(file) = inner.get_file(fd) else {
+        return -EBADF;
+    };
+    if !file.readable() {
+        return -EBADF;
+    }
+    // Fail before taking any data from the file
+    if !inner.memory_set.prepare_user_buffer(buf as usize, len, AccessType::Write) {
+        return -EFAULT;
+    }
+    drop(inner);
+
+    let mut done = 0;
+    while done < len {
+        let mut bounce = vec![0u8; (len - done).min(BOUNCE_SIZE)];
+        // Reading may block, which switches to other tasks
+        let read = match file.read(UserBuffer::new(vec![&mut bounce[..]])) {
+            Ok(read) => read,
+            Err(_) if done > 0 => break,
+            Err(errno) => return -errno,
+        };
+        if let Err(errno) = copy_user(buf as usize + done, &mut bounce[..read], AccessType::Write) {
+            return -errno;
+        }
+        done += read;
+        // Only a full chunk may be followed by more data without blocking
+        if read < bounce.len() {
+            break;
         }
-        fd => {
-            panic!("Unsupported fd {fd}")
+    }
+    done as isize
+}
+
+pub fn sys_close(fd: usize) -> isize {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    match inner.fd_table.get_mut(fd) {
+        Some(file @ Some(_)) => {
+            *file = None;
+            0
+        }
+        _ => -EBADF,
+    }
+}
+
+pub fn sys_dup(fd: usize) -> isize {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let Some(file) = inner.get_file(fd) else {
+        return -EBADF;
+    };
+    let Some(new_fd) = inner.alloc_fd() else {
+        return -EMFILE;
+    };
+    inner.fd_table[new_fd] = Some(file);
+    new_fd as isize
+}
+
+/// Make `new_fd` refer to the file of `old_fd`, closing what `new_fd` referred to before
+pub fn sys_dup2(old_fd: usize, new_fd: usize) -> isize {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let Some(file) = inner.get_file(old_fd) else {
+        return -EBADF;
+    };
+    if new_fd >= FD_LIMIT {
+        return -EBADF;
+    }
+    if inner.fd_table.len() <= new_fd {
+        inner.fd_table.resize(new_fd + 1, None);
+    }
+    inner.fd_table[new_fd] = Some(file);
+    new_fd as isize
+}
+
+/// Create a pipe and store the fds of its read end and write end into `pipe`
+pub fn sys_pipe(pipe: *mut i32) -> isize {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let fds_len = 2 * core::mem::size_of::<i32>();
+    if !inner.memory_set.prepare_user_buffer(pipe as usize, fds_len, AccessType::Write) {
+        return -EFAULT;
+    }
+
+    let (read_end, write_end) = make_pipe();
+    let Some(read_fd) = inner.alloc_fd() else {
+        return -EMFILE;
+    };
+    inner.fd_table[read_fd] = Some(read_end);
+    let Some(write_fd) = inner.alloc_fd() else {
+        inner.fd_table[read_fd] = None;
+        return -EMFILE;
+    };
+    inner.fd_table[write_fd] = Some(write_end);
+
+    let fds = [read_fd as i32, write_fd as i32];
+    let mut dsts = translate_byte_buffer(inner.get_user_token(), pipe as *const u8, fds_len);
+    copy_to_dsts(unsafe { any_as_u8_slice(&fds) }, &mut dsts[..]).unwrap();
+    0
+}
+
+/// `dirfd` of the `*at` syscalls for paths relative to the current working directory
+const AT_FDCWD: isize = -100;
+/// Flag of `unlinkat` to remove a directory instead of a file
+const AT_REMOVEDIR: u32 = 0x200;
+/// Flag of `newfstatat` to stat a symbolic link itself instead of its target
+const AT_SYMLINK_NOFOLLOW: u32 = 0x100;
+/// Flag of `linkat` to link the target of `old_path` if it is a symbolic link
+const AT_SYMLINK_FOLLOW: u32 = 0x400;
+
+/// Read the string at `ptr` in user space, which must not be longer than [`PATH_MAX`]
+fn user_str(ptr: *const u8) -> Result<String, isize> {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    inner.memory_set.read_user_str(ptr as usize, PATH_MAX)
+}
+
+/// Read the path at `path` in user space and resolve it against the current working directory
+pub(super) fn user_path(path: *const u8) -> Result<String, isize> {
+    let path = user_str(path)?;
+    Ok(absolute_path(&current_process().inner_exclusive_access().cwd, &path))
+}
+
+/// [`user_path`] for the `*at` syscalls. Relative paths are only supported with [`AT_FDCWD`].
+fn user_path_at(dirfd: isize, path: *const u8) -> Result<String, isize> {
+    let path = user_str(path)?;
+    if dirfd != AT_FDCWD && !path.starts_with('/') {
+        return Err(EBADF);
+    }
+    Ok(absolute_path(&current_process().inner_exclusive_access().cwd, &path))
+}
+
+pub fn sys_open(path: *const u8, flags: u32) -> isize {
+    let path = match user_path(path) {
+        Ok(path) => path,
+        Err(errno) => return -errno,
+    };
+    let Some(flags) = OpenFlags::from_bits(flags) else {
+        return -EINVAL;
+    };
+    let file = match open_file(&path, flags) {
+        Ok(file) => file,
+        Err(errno) => return -errno,
+    };
+
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let Some(fd) = inner.alloc_fd() else {
+        return -EMFILE;
+    };
+    inner.fd_table[fd] = Some(file);
+    fd as isize
+}
+
+/// Store the current working directory with a trailing `\0` into `buf` and return its length
+pub fn sys_getcwd(buf: *mut u8, size: usize) -> isize {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let mut cwd = inner.cwd.clone();
+    cwd.push('\0');
+    if size < cwd.len() {
+        return -ERANGE;
+    }
+    if !inner.memory_set.prepare_user_buffer(buf as usize, cwd.len(), AccessType::Write) {
+        return -EFAULT;
+    }
+    let mut dsts = translate_byte_buffer(inner.get_user_token(), buf, cwd.len());
+    copy_to_dsts(cwd.as_bytes(), &mut dsts[..]).unwrap();
+    cwd.len() as isize
+}
+
+pub fn sys_chdir(path: *const u8) -> isize {
+    let path = match user_path(path) {
+        Ok(path) => path,
+        Err(errno) => return -errno,
+    };
+    match vfs::lookup(&path) {
+        Ok(inode) if inode.is_dir() => {
+            current_process().inner_exclusive_access().cwd = path;
+            0
         }
+        Ok(_) => -ENOTDIR,
+        Err(errno) => -errno,
+    }
+}
+
+/// Mount a filesystem of type `fstype` at `target`, see [`new_filesystem`]. `flags` and `data` are
+/// ignored.
+pub fn sys_mount(source: *const u8, target: *const u8, fstype: *const u8) -> isize {
+    let source = match source.is_null() {
+        true => Ok(String::new()),
+        false => user_str(source),
+    };
+    let (source, fstype) = match source.and_then(|source| Ok((source, user_str(fstype)?))) {
+        Ok(strings) => strings,
+        Err(errno) => return -errno,
+    };
+    let target = match user_path(target).and_then(|target| vfs::resolve(&target, true)) {
+        Ok((target, inode)) if inode.is_dir() => target,
+        Ok(_) => return -ENOTDIR,
+        Err(errno) => return -errno,
+    };
+    let result = new_filesystem(&fstype, &source).and_then(|fs| vfs::mount(&target, fs));
+    match result {
+        Ok(()) => 0,
+        Err(errno) => -errno,
+    }
+}
+
+/// `flags` are ignored
+pub fn sys_umount2(target: *const u8) -> isize {
+    match user_path(target).and_then(|target| vfs::umount(&target)) {
+        Ok(()) => 0,
+        Err(errno) => -errno,
+    }
+}
+
+pub fn sys_ftruncate(fd: usize, len: usize) -> isize {
+    let process = current_process();
+    let Some(file) = process.inner_exclusive_access().get_file(fd) else {
+        return -EBADF;
+    };
+    match file.truncate(len) {
+        Ok(()) => 0,
+        Err(errno) => -errno,
+    }
+}
+
+/// No `flags` are supported
+pub fn sys_renameat2(
+    old_dirfd: isize,
+    old_path: *const u8,
+    new_dirfd: isize,
+    new_path: *const u8,
+    flags: u32,
+) -> isize {
+    if flags != 0 {
+        return -EINVAL;
+    }
+    let result = user_path_at(old_dirfd, old_path).and_then(|old_path| {
+        let new_path = user_path_at(new_dirfd, new_path)?;
+        vfs::rename(&old_path, &new_path)
+    });
+    match result {
+        Ok(()) => 0,
+        Err(errno) => -errno,
+    }
+}
+
+pub fn sys_getdents64(fd: usize, buf: *mut u8, len: usize) -> isize {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let Some(file) = inner.get_file(fd) else {
+        return -EBADF;
+    };
+    if !inner.memory_set.prepare_user_buffer(buf as usize, len, AccessType::Write) {
+        return -EFAULT;
+    }
+    let token = inner.get_user_token();
+    drop(inner);
+
+    // Entries which do not fit are returned by the next call
+    let mut entries = vec![0u8; len.min(PAGE_SIZE)];
+    let filled = match file.read_dir(&mut entries) {
+        Ok(filled) => filled,
+        Err(errno) => return -errno,
+    };
+    let mut dsts = translate_byte_buffer(token, buf, filled);
+    copy_to_dsts(&entries[..filled], &mut dsts[..]).unwrap();
+    filled as isize
+}
+
+/// `mode` is ignored, as files have no permissions
+pub fn sys_mkdirat(dirfd: isize, path: *const u8, _mode: u32) -> isize {
+    let result = user_path_at(dirfd, path).and_then(|path| vfs::create(&path, InodeType::Dir));
+    match result {
+        Ok(_) => 0,
+        Err(errno) => -errno,
+    }
+}
+
+/// Remove a file, or an empty directory with [`AT_REMOVEDIR`]
+pub fn sys_unlinkat(dirfd: isize, path: *const u8, flags: u32) -> isize {
+    if flags & !AT_REMOVEDIR != 0 {
+        return -EINVAL;
+    }
+    let result = user_path_at(dirfd, path)
+        .and_then(|path| vfs::remove(&path, flags & AT_REMOVEDIR != 0));
+    match result {
+        Ok(()) => 0,
+        Err(errno) => -errno,
+    }
+}
+
+pub fn sys_linkat(
+    old_dirfd: isize,
+    old_path: *const u8,
+    new_dirfd: isize,
+    new_path: *const u8,
+    flags: u32,
+) -> isize {
+    if flags & !AT_SYMLINK_FOLLOW != 0 {
+        return -EINVAL;
+    }
+    let result = user_path_at(old_dirfd, old_path).and_then(|old_path| {
+        let new_path = user_path_at(new_dirfd, new_path)?;
+        vfs::link(&old_path, &new_path, flags & AT_SYMLINK_FOLLOW != 0)
+    });
+    match result {
+        Ok(()) => 0,
+        Err(errno) => -errno,
+    }
+}
+
+pub fn sys_symlinkat(target: *const u8, new_dirfd: isize, link_path: *const u8) -> isize {
+    let result = user_str(target).and_then(|target| {
+        let path = user_path_at(new_dirfd, link_path)?;
+        vfs::symlink(&target, &path)
+    });
+    match result {
+        Ok(()) => 0,
+        Err(errno) => -errno,
+    }
+}
+
+/// Store the target of a symbolic link into `buf`, truncated to `size` bytes and without a `\0`,
+/// and return its length
+pub fn sys_readlinkat(dirfd: isize, path: *const u8, buf: *mut u8, size: usize) -> isize {
+    let target = user_path_at(dirfd, path)
+        .and_then(|path| vfs::resolve(&path, false))
+        .and_then(|(_, inode)| inode.read_link());
+    let target = match target {
+        Ok(target) => target,
+        Err(errno) => return -errno,
+    };
+    let len = target.len().min(size);
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    if !inner.memory_set.prepare_user_buffer(buf as usize, len, AccessType::Write) {
+        return -EFAULT;
+    }
+    let mut dsts = translate_byte_buffer(inner.get_user_token(), buf, len);
+    copy_to_dsts(&target.as_bytes()[..len], &mut dsts[..]).unwrap();
+    len as isize
+}
+
+/// `stat` of a path, or of a symbolic link itself with [`AT_SYMLINK_NOFOLLOW`]
+pub fn sys_newfstatat(dirfd: isize, path: *const u8, stat: *mut Stat, flags: u32) -> isize {
+    if flags & !AT_SYMLINK_NOFOLLOW != 0 {
+        return -EINVAL;
+    }
+    let result = user_path_at(dirfd, path)
+        .and_then(|path| vfs::resolve(&path, flags & AT_SYMLINK_NOFOLLOW == 0));
+    let (path, inode) = match result {
+        Ok(resolved) => resolved,
+        Err(errno) => return -errno,
+    };
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let stat_len = core::mem::size_of::<Stat>();
+    if !inner.memory_set.prepare_user_buffer(stat as usize, stat_len, AccessType::Write) {
+        return -EFAULT;
+    }
+    let stat_value = inode_stat(vfs::device_of(&path), inode.as_ref());
+    let mut dsts = translate_byte_buffer(inner.get_user_token(), stat as *const u8, stat_len);
+    copy_to_dsts(unsafe { any_as_u8_slice(&stat_value) }, &mut dsts[..]).unwrap();
+    0
+}
+
+pub fn sys_fstat(fd: usize, stat: *mut Stat) -> isize {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let Some(file) = inner.get_file(fd) else {
+        return -EBADF;
+    };
+    let stat_len = core::mem::size_of::<Stat>();
+    if !inner.memory_set.prepare_user_buffer(stat as usize, stat_len, AccessType::Write) {
+        return -EFAULT;
+    }
+    let mut dsts = translate_byte_buffer(inner.get_user_token(), stat as *const u8, stat_len);
+    copy_to_dsts(unsafe { any_as_u8_slice(&file.stat()) }, &mut dsts[..]).unwrap();
+    0
+}
+
+pub fn sys_sync() -> isize {
+    match fs::sync() {
+        Ok(()) => 0,
+        Err(errno) => -errno,
     }
 }
@@ -0,0 +1,144 @@
+use bitflags::bitflags;
+
+use crate::{
+    config::{MMAP_BASE, PAGE_SIZE, USER_SPACE_END},
+    mem::{address::VirtAddr, memory_set::MapPermission},
+    task::processor::current_process,
+};
+
+use super::errno::{EEXIST, EINVAL, ENODEV, ENOMEM};
+
+bitflags! {
+    #[derive(Clone, Copy)]
+    pub struct ProtFlags: usize {
+        const READ = 1 << 0;
+        const WRITE = 1 << 1;
+        const EXEC = 1 << 2;
+    }
+}
+
+bitflags! {
+    #[derive(Clone, Copy)]
+    pub struct MapFlags: usize {
+        const SHARED = 0x01;
+        const PRIVATE = 0x02;
+        const FIXED = 0x10;
+        const ANONYMOUS = 0x20;
+        const FIXED_NOREPLACE = 0x100000;
+    }
+}
+
+impl From<ProtFlags> for MapPermission {
+    fn from(prot: ProtFlags) -> Self {
+        let mut map_perm = MapPermission::U;
+        // Pages cannot be writable without being readable in RISC-V
+        if prot.intersects(ProtFlags::READ | ProtFlags::WRITE) {
+            map_perm |= MapPermission::R;
+        }
+        if prot.contains(ProtFlags::WRITE) {
+            map_perm |= MapPermission::W;
+        }
+        if prot.contains(ProtFlags::EXEC) {
+            map_perm |= MapPermission::X;
+        }
+        map_perm
+    }
+}
+
+/// Check that `[start, start + len)` is a page aligned, non-empty range in user space, and
+/// return its end
+fn user_range_end(start: usize, len: usize) -> Option<usize> {
+    if start % PAGE_SIZE != 0 || len == 0 {
+        return None;
+    }
+    let end = start.checked_add(len)?.checked_next_multiple_of(PAGE_SIZE)?;
+    (end <= USER_SPACE_END).then_some(end)
+}
+
+/// Only anonymous private mappings are supported, so `fd` and `offset` are ignored
+pub fn sys_mmap(
+    addr: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    _fd: usize,
+    _offset: usize,
+) -> isize {
+    let Some(prot) = ProtFlags::from_bits(prot) else {
+        return -EINVAL;
+    };
+    // Like Linux, unknown flags are ignored
+    let flags = MapFlags::from_bits_truncate(flags);
+    if flags.contains(MapFlags::SHARED) == flags.contains(MapFlags::PRIVATE) || len == 0 {
+        return -EINVAL;
+    }
+    if !flags.contains(MapFlags::ANONYMOUS) || flags.contains(MapFlags::SHARED) {
+        return -ENODEV;
+    }
+    let Some(len) = len.checked_next_multiple_of(PAGE_SIZE) else {
+        return -ENOMEM;
+    };
+
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let memory_set = &mut inner.memory_set;
+
+    let start = if flags.intersects(MapFlags::FIXED | MapFlags::FIXED_NOREPLACE) {
+        let Some(end) = user_range_end(addr, len) else {
+            return -EINVAL;
+        };
+        if !memory_set.is_free(addr.into(), end.into()) {
+            if flags.contains(MapFlags::FIXED_NOREPLACE) {
+                return -EEXIST;
+            }
+            memory_set.unmap_range(addr.into(), end.into());
+        }
+        addr
+    } else {
+        // Take the address as a hint, and look for another place if it is not usable
+        let hint = user_range_end(addr, len)
+            .filter(|&end| addr != 0 && memory_set.is_free(addr.into(), end.into()));
+        match hint {
+            Some(_) => addr,
+            None => match memory_set.find_free_area(MMAP_BASE.into(), len) {
+                Some(start) => start.into(),
+                None => return -ENOMEM,
+            },
+        }
+    };
+
+    let end = start + len;
+    memory_set.insert_lazy_area(start.into(), end.into(), prot.into());
+    start as isize
+}
+
+pub fn sys_munmap(addr: usize, len: usize) -> isize {
+    let Some(end) = user_range_end(addr, len) else {
+        return -EINVAL;
+    };
+    let process = current_process();
+    process.inner_exclusive_access()
+        .memory_set
+        .unmap_range(VirtAddr::from(addr), VirtAddr::from(end));
+    0
+}
+
+pub fn sys_mprotect(addr: usize, len: usize, prot: usize) -> isize {
+    let Some(end) = user_range_end(addr, len) else {
+        return -EINVAL;
+    };
+    let Some(prot) = ProtFlags::from_bits(prot) else {
+        return -EINVAL;
+    };
+    let process = current_process();
+    let protected = process.inner_exclusive_access().memory_set.protect_range(
+        addr.into(),
+        end.into(),
+        prot.into(),
+    );
+    if protected {
+        0
+    } else {
+        -ENOMEM
+    }
+}
@@ -1,43 +1,251 @@
 use self::time::TimeVal;
+use self::process::Rusage;
+use crate::fs::Stat;
+use crate::task::signal::{SignalAction, SignalSet};
 
+pub mod errno;
 mod fs;
+mod mem;
 mod process;
+mod signal;
+mod sync;
 mod time;
 
+const SYSCALL_GETCWD: usize = 17;
+const SYSCALL_DUP: usize = 23;
+/// `dup3` in Linux, only without flags
+const SYSCALL_DUP2: usize = 24;
+const SYSCALL_MKDIRAT: usize = 34;
+const SYSCALL_UNLINKAT: usize = 35;
+const SYSCALL_SYMLINKAT: usize = 36;
+const SYSCALL_LINKAT: usize = 37;
+const SYSCALL_UMOUNT2: usize = 39;
+const SYSCALL_FTRUNCATE: usize = 46;
+const SYSCALL_MOUNT: usize = 40;
+const SYSCALL_CHDIR: usize = 49;
+const SYSCALL_OPEN: usize = 56;
+const SYSCALL_CLOSE: usize = 57;
+const SYSCALL_PIPE: usize = 59;
+const SYSCALL_GETDENTS64: usize = 61;
 const SYSCALL_READ: usize = 63;
 const SYSCALL_WRITE: usize = 64;
+const SYSCALL_READLINKAT: usize = 78;
+const SYSCALL_NEWFSTATAT: usize = 79;
+const SYSCALL_FSTAT: usize = 80;
+const SYSCALL_SYNC: usize = 81;
 const SYSCALL_EXIT: usize = 93;
 const SYSCALL_YIELD: usize = 124;
+const SYSCALL_KILL: usize = 129;
+const SYSCALL_SIGACTION: usize = 134;
+const SYSCALL_SIGPROCMASK: usize = 135;
+const SYSCALL_SIGRETURN: usize = 139;
 const SYSCALL_GET_TIME: usize = 169;
+const SYSCALL_GETPID: usize = 172;
+const SYSCALL_GETTID: usize = 178;
+const SYSCALL_SBRK: usize = 214;
+const SYSCALL_MUNMAP: usize = 215;
 const SYSCALL_FORK: usize = 220;
 const SYSCALL_EXEC: usize = 221;
-const SYSCALL_WAITPID: usize = 260;
+const SYSCALL_MMAP: usize = 222;
+const SYSCALL_MPROTECT: usize = 226;
+const SYSCALL_WAIT4: usize = 260;
+const SYSCALL_RENAMEAT2: usize = 276;
+const SYSCALL_ENABLE_DEADLOCK_DETECT: usize = 469;
+const SYSCALL_THREAD_CREATE: usize = 1000;
+const SYSCALL_WAITTID: usize = 1002;
+const SYSCALL_MUTEX_CREATE: usize = 1010;
+const SYSCALL_MUTEX_LOCK: usize = 1011;
+const SYSCALL_MUTEX_UNLOCK: usize = 1012;
+const SYSCALL_SEMAPHORE_CREATE: usize = 1020;
+const SYSCALL_SEMAPHORE_UP: usize = 1021;
+const SYSCALL_SEMAPHORE_DOWN: usize = 1022;
+const SYSCALL_CONDVAR_CREATE: usize = 1030;
+const SYSCALL_CONDVAR_SIGNAL: usize = 1031;
+const SYSCALL_CONDVAR_WAIT: usize = 1032;
 
-pub fn syscall(syscall_id: usize, args: [usize; 3]) -> isize {
+pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
     match syscall_id {
+        SYSCALL_GETCWD => {
+            fs::sys_getcwd(args[0] as *mut u8, args[1])
+        }
+        SYSCALL_DUP => {
+            fs::sys_dup(args[0])
+        }
+        SYSCALL_DUP2 => {
+            fs::sys_dup2(args[0], args[1])
+        }
+        SYSCALL_MKDIRAT => {
+            fs::sys_mkdirat(args[0] as isize, args[1] as *const u8, args[2] as u32)
+        }
+        SYSCALL_UNLINKAT => {
+            fs::sys_unlinkat(args[0] as isize, args[1] as *const u8, args[2] as u32)
+        }
+        SYSCALL_SYMLINKAT => {
+            fs::sys_symlinkat(args[0] as *const u8, args[1] as isize, args[2] as *const u8)
+        }
+        SYSCALL_LINKAT => {
+            fs::sys_linkat(
+                args[0] as isize,
+                args[1] as *const u8,
+                args[2] as isize,
+                args[3] as *const u8,
+                args[4] as u32,
+            )
+        }
+        SYSCALL_UMOUNT2 => {
+            fs::sys_umount2(args[0] as *const u8)
+        }
+        SYSCALL_MOUNT => {
+            fs::sys_mount(args[0] as *const u8, args[1] as *const u8, args[2] as *const u8)
+        }
+        SYSCALL_FTRUNCATE => {
+            fs::sys_ftruncate(args[0], args[1])
+        }
+        SYSCALL_CHDIR => {
+            fs::sys_chdir(args[0] as *const u8)
+        }
+        SYSCALL_OPEN => {
+            fs::sys_open(args[0] as *const u8, args[1] as u32)
+        }
+        SYSCALL_CLOSE => {
+            fs::sys_close(args[0])
+        }
+        SYSCALL_PIPE => {
+            fs::sys_pipe(args[0] as *mut i32)
+        }
+        SYSCALL_GETDENTS64 => {
+            fs::sys_getdents64(args[0], args[1] as *mut u8, args[2])
+        }
         SYSCALL_WRITE => {
-            fs::sys_write(args[0], args[1] as *mut u8, args[2])
+            fs::sys_write(args[0], args[1] as *const u8, args[2])
         }
         SYSCALL_READ => {
             fs::sys_read(args[0], args[1] as *mut u8, args[2])
         }
+        SYSCALL_READLINKAT => {
+            fs::sys_readlinkat(args[0] as isize, args[1] as *const u8, args[2] as *mut u8, args[3])
+        }
+        SYSCALL_NEWFSTATAT => {
+            fs::sys_newfstatat(
+                args[0] as isize,
+                args[1] as *const u8,
+                args[2] as *mut Stat,
+                args[3] as u32,
+            )
+        }
+        SYSCALL_FSTAT => {
+            fs::sys_fstat(args[0], args[1] as *mut Stat)
+        }
+        SYSCALL_SYNC => {
+            fs::sys_sync()
+        }
         SYSCALL_EXIT => {
             process::sys_exit(args[0] as i32)
         }
         SYSCALL_YIELD => {
             process::sys_yield()
         }
+        SYSCALL_KILL => {
+            signal::sys_kill(args[0] as isize, args[1])
+        }
+        SYSCALL_SIGACTION => {
+            signal::sys_sigaction(
+                args[0],
+                args[1] as *const SignalAction,
+                args[2] as *mut SignalAction,
+                args[3],
+            )
+        }
+        SYSCALL_SIGPROCMASK => {
+            signal::sys_sigprocmask(
+                args[0],
+                args[1] as *const SignalSet,
+                args[2] as *mut SignalSet,
+                args[3],
+            )
+        }
+        SYSCALL_SIGRETURN => {
+            signal::sys_sigreturn()
+        }
         SYSCALL_GET_TIME => {
             time::sys_get_time(args[0] as *mut TimeVal, args[1])
         }
+        SYSCALL_GETPID => {
+            process::sys_getpid()
+        }
+        SYSCALL_GETTID => {
+            process::sys_gettid()
+        }
+        SYSCALL_SBRK => {
+            process::sys_sbrk(args[0] as i32)
+        }
+        SYSCALL_MUNMAP => {
+            mem::sys_munmap(args[0], args[1])
+        }
         SYSCALL_FORK => {
             process::sys_fork()
         }
         SYSCALL_EXEC => {
-            process::sys_exec(args[0] as *const u8)
+            process::sys_exec(args[0] as *const u8, args[1] as *const usize, args[2] as *const usize)
+        }
+        SYSCALL_MMAP => {
+            mem::sys_mmap(args[0], args[1], args[2], args[3], args[4], args[5])
+        }
+        SYSCALL_MPROTECT => {
+            mem::sys_mprotect(args[0], args[1], args[2])
+        }
+        SYSCALL_WAIT4 => {
+            process::sys_wait4(
+                args[0] as isize,
+                args[1] as *mut i32,
+                args[2] as u32,
+                args[3] as *mut Rusage,
+            )
+        }
+        SYSCALL_RENAMEAT2 => {
+            fs::sys_renameat2(
+                args[0] as isize,
+                args[1] as *const u8,
+                args[2] as isize,
+                args[3] as *const u8,
+                args[4] as u32,
+            )
+        }
+        SYSCALL_ENABLE_DEADLOCK_DETECT => {
+            sync::sys_enable_deadlock_detect(args[0])
+        }
+        SYSCALL_THREAD_CREATE => {
+            process::sys_thread_create(args[0], args[1])
+        }
+        SYSCALL_WAITTID => {
+            process::sys_waittid(args[0], args[1] as *mut i32)
+        }
+        SYSCALL_MUTEX_CREATE => {
+            sync::sys_mutex_create()
+        }
+        SYSCALL_MUTEX_LOCK => {
+            sync::sys_mutex_lock(args[0])
+        }
+        SYSCALL_MUTEX_UNLOCK => {
+            sync::sys_mutex_unlock(args[0])
+        }
+        SYSCALL_SEMAPHORE_CREATE => {
+            sync::sys_semaphore_create(args[0])
+        }
+        SYSCALL_SEMAPHORE_UP => {
+            sync::sys_semaphore_up(args[0])
+        }
+        SYSCALL_SEMAPHORE_DOWN => {
+            sync::sys_semaphore_down(args[0])
+        }
+        SYSCALL_CONDVAR_CREATE => {
+            sync::sys_condvar_create()
+        }
+        SYSCALL_CONDVAR_SIGNAL => {
+            sync::sys_condvar_signal(args[0])
         }
-        SYSCALL_WAITPID => {
-            process::sys_waitpid(args[0] as isize, args[1] as *mut i32)
+        SYSCALL_CONDVAR_WAIT => {
+            sync::sys_condvar_wait(args[0], args[1])
         }
         id => {
             panic!("Unsupported syscall id: {id}")
@@ -2,16 +2,30 @@
 
 use alloc::sync::Arc;
 
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::errno::{
+    E2BIG, EAGAIN, EBUSY, ECHILD, EDEADLK, EFAULT, EINTR, EINVAL, ENAMETOOLONG, ENOENT, ENOEXEC,
+    ESRCH,
+};
+use super::time::TimeVal;
+use crate::config::{ARG_MAX, EXEC_SEARCH_PATH, PATH_MAX};
 use crate::debug;
-use crate::loader::get_app_data_by_name;
+use crate::fs::{open_file, vfs::absolute_path, OpenFlags};
 use crate::log;
-use crate::mem::page_table::translate_raw;
-use crate::mem::page_table::translate_str;
+use crate::mem::memory_set::AccessType;
+use crate::mem::page_table::{translate, translate_raw};
 use crate::task::manager::add_task;
-use crate::task::processor::current_task;
+use crate::task::process::ProcessControlBlock;
+use crate::task::processor::{current_process, current_task};
 use crate::task::processor::current_user_token;
 use crate::task::suspend_and_run_next;
 use crate::task::exit_and_run_next;
+use crate::task::CpuTime;
+use crate::task::signal::signal_pending;
+use crate::timer::MICRO_PER_SEC;
+use crate::utils::{any_as_u8_slice, copy_to_dsts};
 
 pub fn sys_exit(xstate: i32) -> ! {
     log!("Application exited with code {}", xstate);
@@ -24,51 +38,250 @@ pub fn sys_yield() -> isize {
     0
 }
 
+pub fn sys_getpid() -> isize {
+    current_process().get_pid() as isize
+}
+
+pub fn sys_gettid() -> isize {
+    current_task().unwrap().tid as isize
+}
+
 pub fn sys_fork() -> isize {
-    let current_task = current_task().unwrap();
-    let new_task = current_task.fork();
-    let new_pid = new_task.pid.0;
-    let trap_context = new_task.inner_exclusive_access().get_trap_context();
+    let task = current_task().unwrap();
+    let child = task.process().fork(&task);
+    let child_pid = child.get_pid();
+    let child_task = child.inner_exclusive_access().tasks[0].clone().unwrap();
+    let trap_context = child_task.inner_exclusive_access().get_trap_context();
 
     trap_context.x[10] = 0;
-    add_task(new_task);
-    new_pid as isize
+    add_task(child_task);
+    child_pid as isize
 }
 
-pub fn sys_waitpid(pid: isize, exit_code: *mut i32) -> isize {
-    let task = current_task().unwrap();
-    let mut inner = task.inner_exclusive_access();
+/// Option of `wait4` to return 0 at once if no child has exited yet
+const WNOHANG: u32 = 1;
+
+/// `struct rusage` of Linux. Only the CPU times are tracked, the other fields are 0.
+#[repr(C)]
+pub struct Rusage {
+    pub utime: TimeVal,
+    pub stime: TimeVal,
+    __unused: [isize; 14],
+}
 
-    if inner.children.iter().find(|p| pid == -1 || pid as usize == p.get_pid()).is_none() {
-        return -1;
+impl From<CpuTime> for Rusage {
+    fn from(time: CpuTime) -> Self {
+        let timeval = |us: usize| TimeVal {
+            sec: us / MICRO_PER_SEC,
+            usec: us % MICRO_PER_SEC,
+        };
+        Self {
+            utime: timeval(time.user_us),
+            stime: timeval(time.kernel_us),
+            __unused: [0; 14],
+        }
     }
+}
+
+/// Wait for the child `pid`, or any child if `pid` is -1, to exit and reap it. Store its exit code
+/// in `wstatus` and the CPU time of it and its reaped children in `rusage` unless they are null.
+/// Return the pid of the child, or 0 with [`WNOHANG`] if no child has exited yet.
+pub fn sys_wait4(pid: isize, wstatus: *mut i32, options: u32, rusage: *mut Rusage) -> isize {
+    if options & !WNOHANG != 0 {
+        return -EINVAL;
+    }
+    let process = current_process();
+    loop {
+        let mut inner = process.inner_exclusive_access();
+        let matches = |child: &Arc<ProcessControlBlock>| pid == -1 || child.get_pid() == pid as usize;
+        if !inner.children.iter().any(matches) {
+            return -ECHILD;
+        }
+        let mut writable = |ptr: usize, len: usize| {
+            ptr == 0 || inner.memory_set.prepare_user_buffer(ptr, len, AccessType::Write)
+        };
+        if !writable(wstatus as usize, core::mem::size_of::<i32>())
+            || !writable(rusage as usize, core::mem::size_of::<Rusage>())
+        {
+            return -EFAULT;
+        }
 
-    let pair = inner.children.iter().enumerate().find(|(_, p)| {
-        p.inner_exclusive_access().is_zombie() && (pid == -1 || p.get_pid() == pid as usize)
-    });
+        let zombie = inner
+            .children
+            .iter()
+            .position(|child| matches(child) && child.inner_exclusive_access().is_zombie());
+        let Some(idx) = zombie else {
+            if options & WNOHANG != 0 {
+                return 0;
+            }
+            drop(inner);
+            process.children_exited.wait();
+            if signal_pending() {
+                return -EINTR;
+            }
+            continue;
+        };
 
-    if let Some((idx, _)) = pair {
         let child = inner.children.remove(idx);
         assert_eq!(Arc::strong_count(&child), 1);
         let found_pid = child.get_pid();
+        let child_inner = child.inner_exclusive_access();
+        let mut cpu_time = child_inner.cpu_time;
+        cpu_time += child_inner.children_cpu_time;
+        inner.children_cpu_time += cpu_time;
+
+        let token = inner.memory_set.token();
+        if !wstatus.is_null() {
+            unsafe { *translate_raw(token, wstatus) = child_inner.exit_code; }
+        }
+        if !rusage.is_null() {
+            let usage = Rusage::from(cpu_time);
+            let src = unsafe { any_as_u8_slice(&usage) };
+            copy_to_dsts(src, &mut translate(token, rusage)).unwrap();
+        }
+        return found_pid as isize;
+    }
+}
+
+pub fn sys_sbrk(size: i32) -> isize {
+    match current_process().change_program_brk(size) {
+        Some(old_brk) => old_brk as isize,
+        None => -1,
+    }
+}
 
-        let exit_code_result = child.inner_exclusive_access().exit_code;
-        unsafe { *translate_raw(inner.memory_set.token(), exit_code) = exit_code_result; }
-        found_pid as isize
-    } else {
-        -2
+/// Read the program at `path`, which is looked for in [`EXEC_SEARCH_PATH`] if it has no `/`
+fn read_program(path: &str) -> Result<Vec<u8>, isize> {
+    if path.contains('/') {
+        let cwd = current_process().inner_exclusive_access().cwd.clone();
+        return open_file(&absolute_path(&cwd, path), OpenFlags::RDONLY)?.read_all();
+    }
+    let mut result = Err(ENOENT);
+    for dir in EXEC_SEARCH_PATH {
+        result = open_file(&absolute_path(dir, path), OpenFlags::RDONLY)
+            .and_then(|file| file.read_all());
+        if result != Err(ENOENT) {
+            break;
+        }
+    }
+    result
+}
+
+/// The strings of a null-terminated array of string pointers like `argv`, which may be null itself.
+/// `size` counts the bytes they take on the new user stack, which must stay within [`ARG_MAX`].
+fn read_str_array(ptr: *const usize, size: &mut usize) -> Result<Vec<String>, isize> {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let token = inner.memory_set.token();
+    let mut strings = Vec::new();
+    if ptr.is_null() {
+        return Ok(strings);
+    }
+    let word = core::mem::size_of::<usize>();
+    for i in 0.. {
+        let slot = ptr.wrapping_add(i);
+        if !inner.memory_set.prepare_user_buffer(slot as usize, word, AccessType::Read) {
+            return Err(EFAULT);
+        }
+        let string_ptr = unsafe { *translate_raw(token, slot) };
+        if string_ptr == 0 {
+            break;
+        }
+        let string = inner
+            .memory_set
+            .read_user_str(string_ptr, ARG_MAX)
+            .map_err(|errno| if errno == ENAMETOOLONG { E2BIG } else { errno })?;
+        *size += string.len() + 1 + word;
+        if *size > ARG_MAX {
+            return Err(E2BIG);
+        }
+        strings.push(string);
+    }
+    Ok(strings)
+}
+
+/// Run the program at `path` with the arguments `argv` and the environment `envp`, which are
+/// null-terminated arrays of strings. Return `argc`, which the new program gets in `a0`.
+/// Fails with `EBUSY` while other threads of the process have not exited.
+pub fn sys_exec(path: *const u8, argv: *const usize, envp: *const usize) -> isize {
+    let process = current_process();
+    if process.inner_exclusive_access().live_tasks().count() > 1 {
+        return -EBUSY;
+    }
+    let path = process.inner_exclusive_access().memory_set.read_user_str(path as usize, PATH_MAX);
+    let mut size = 0;
+    let (path, args, envs) = match path.and_then(|path| {
+        let args = read_str_array(argv, &mut size)?;
+        Ok((path, args, read_str_array(envp, &mut size)?))
+    }) {
+        Ok(strings) => strings,
+        Err(errno) => return -errno,
+    };
+
+    let data = match read_program(&path) {
+        Ok(data) => data,
+        Err(errno) => return -errno,
+    };
+    if !data.starts_with(b"\x7fELF") {
+        return -ENOEXEC;
+    }
+
+    match process.exec(&data, &args, &envs) {
+        Ok(()) => args.len() as isize,
+        Err(errno) => -errno,
     }
 }
 
-pub fn sys_exec(path: *const u8) -> isize {
-    let token = current_user_token();
-    let path = translate_str(token, path);
+/// Start a thread of the current process at `entry` with `arg` in `a0`. Return its tid.
+pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
+    let task = current_task().unwrap();
+    match task.process().create_thread(&task, entry, arg) {
+        Some(new_task) => {
+            let tid = new_task.tid;
+            add_task(new_task);
+            tid as isize
+        }
+        None => -EAGAIN,
+    }
+}
+
+/// Wait for the thread `tid` of the current process to exit, and store its exit code in
+/// `exit_code` unless it is null. Return `tid`.
+pub fn sys_waittid(tid: usize, exit_code: *mut i32) -> isize {
+    let task = current_task().unwrap();
+    if tid == task.tid {
+        return -EDEADLK;
+    }
+    let process = task.process();
+    drop(task);
+    loop {
+        let mut inner = process.inner_exclusive_access();
+        let Some(Some(waited)) = inner.tasks.get(tid) else {
+            return -ESRCH;
+        };
+        let waited_exit_code = waited.inner_exclusive_access().exit_code;
+        if !exit_code.is_null()
+            && !inner.memory_set.prepare_user_buffer(
+                exit_code as usize,
+                core::mem::size_of::<i32>(),
+                AccessType::Write,
+            )
+        {
+            return -EFAULT;
+        }
 
-    if let Some(data) = get_app_data_by_name(&path) {
-        let task = current_task().unwrap();
-        task.exec(data);
-        0
-    } else {
-        -1
+        let Some(code) = waited_exit_code else {
+            drop(inner);
+            process.thread_exited.wait();
+            if signal_pending() {
+                return -EINTR;
+            }
+            continue;
+        };
+        inner.tasks[tid] = None;
+        if !exit_code.is_null() {
+            unsafe { *translate_raw(inner.memory_set.token(), exit_code) = code; }
+        }
+        return tid as isize;
     }
 }
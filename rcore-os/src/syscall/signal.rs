@@ -0,0 +1,131 @@
+use super::errno::{EFAULT, EINVAL, ESRCH};
+use crate::{
+    mem::{memory_set::AccessType, page_table::translate},
+    task::{
+        manager::find_process,
+        processor::{current_prepare_user_buffer, current_process, current_task, current_user_token},
+        signal::{
+            restore_signal_frame, send_signal, SignalAction, SignalSet, NSIG, SIGKILL, SIGSTOP,
+            SIG_BLOCK, SIG_SETMASK, SIG_UNBLOCK,
+        },
+    },
+    utils::{any_as_u8_slice, any_as_u8_slice_mut, copy_from_srcs, copy_to_dsts},
+};
+
+/// Read a `T` from user space, or `None` if `ptr` is not readable
+fn read_user<T: Copy + Default>(ptr: *const T) -> Option<T> {
+    let len = core::mem::size_of::<T>();
+    if !current_prepare_user_buffer(ptr as *const u8, len, AccessType::Read) {
+        return None;
+    }
+    let mut value = T::default();
+    let dst = unsafe { any_as_u8_slice_mut(&mut value) };
+    copy_from_srcs(&translate(current_user_token(), ptr), dst).ok()?;
+    Some(value)
+}
+
+/// Write `value` to user space. Return `false` if `ptr` is not writable.
+fn write_user<T>(ptr: *mut T, value: &T) -> bool {
+    let len = core::mem::size_of::<T>();
+    if !current_prepare_user_buffer(ptr as *const u8, len, AccessType::Write) {
+        return false;
+    }
+    let src = unsafe { any_as_u8_slice(value) };
+    copy_to_dsts(src, &mut translate(current_user_token(), ptr)).is_ok()
+}
+
+/// Send `sig` to the process `pid`. Signal 0 only checks that the process exists. Process groups do not
+/// exist, so `pid` must be positive.
+pub fn sys_kill(pid: isize, sig: usize) -> isize {
+    if pid <= 0 || sig > NSIG {
+        return -EINVAL;
+    }
+    let Some(process) = find_process(pid as usize) else {
+        return -ESRCH;
+    };
+    if process.inner_exclusive_access().is_zombie() {
+        return -ESRCH;
+    }
+    if sig != 0 {
+        send_signal(&process, sig);
+    }
+    0
+}
+
+/// `rt_sigaction`: Set the action of `sig` to `act` and return the old one in `oldact`, either of
+/// which may be null
+pub fn sys_sigaction(
+    sig: usize,
+    act: *const SignalAction,
+    oldact: *mut SignalAction,
+    sigsetsize: usize,
+) -> isize {
+    if sig == 0 || sig > NSIG || sigsetsize != core::mem::size_of::<SignalSet>() {
+        return -EINVAL;
+    }
+    let new_action = if act.is_null() {
+        None
+    } else {
+        match read_user(act) {
+            Some(action) if action.is_valid() => Some(action),
+            Some(_) => return -EINVAL,
+            None => return -EFAULT,
+        }
+    };
+    if new_action.is_some() && (sig == SIGKILL || sig == SIGSTOP) {
+        return -EINVAL;
+    }
+
+    let process = current_process();
+    let old_action = process.inner_exclusive_access().signal_actions[sig - 1];
+    if !oldact.is_null() && !write_user(oldact, &old_action) {
+        return -EFAULT;
+    }
+    if let Some(mut action) = new_action {
+        action.mask = action.mask.difference(SignalSet::UNCATCHABLE);
+        process.inner_exclusive_access().signal_actions[sig - 1] = action;
+    }
+    0
+}
+
+/// `rt_sigprocmask`: Change the signals blocked in the current thread by `set` as `how` says, and return the old ones in
+/// `oldset`, either of which may be null
+pub fn sys_sigprocmask(
+    how: usize,
+    set: *const SignalSet,
+    oldset: *mut SignalSet,
+    sigsetsize: usize,
+) -> isize {
+    if sigsetsize != core::mem::size_of::<SignalSet>() {
+        return -EINVAL;
+    }
+    let new_set = if set.is_null() {
+        None
+    } else {
+        match read_user(set) {
+            Some(set) => Some(set),
+            None => return -EFAULT,
+        }
+    };
+
+    let task = current_task().unwrap();
+    let old_mask = task.inner_exclusive_access().signal_mask;
+    if !oldset.is_null() && !write_user(oldset, &old_mask) {
+        return -EFAULT;
+    }
+    if let Some(set) = new_set {
+        let mask = match how {
+            SIG_BLOCK => old_mask.union(set),
+            SIG_UNBLOCK => old_mask.difference(set),
+            SIG_SETMASK => set,
+            _ => return -EINVAL,
+        };
+        task.inner_exclusive_access().signal_mask = mask.difference(SignalSet::UNCATCHABLE);
+    }
+    0
+}
+
+/// `rt_sigreturn`: Return from a signal handler to where the signal interrupted the thread
+pub fn sys_sigreturn() -> isize {
+    restore_signal_frame()
+}
@@ -0,0 +1,161 @@
+use alloc::sync::Arc;
+
+use super::errno::{EAGAIN, EDEADLK, EINTR, EINVAL, EPERM};
+use crate::{
+    config::SYNC_LIMIT,
+    task::{
+        processor::{current_process, current_task},
+        signal::signal_pending,
+        sync::{would_deadlock, Condvar, Mutex, ResourceId, Semaphore},
+        TaskControlBlock,
+    },
+};
+
+/// Take `id` for `task`, the current thread, which waits until it is available. Fails with
+/// `EDEADLK` if deadlock detection is on and waiting would deadlock, or with `EINTR` if a signal
+/// arrives while waiting.
+fn acquire(task: &Arc<TaskControlBlock>, id: ResourceId) -> isize {
+    let process = task.process();
+    let Some(resource) = process.inner_exclusive_access().resource(id) else {
+        return -EINVAL;
+    };
+    loop {
+        if resource.try_acquire(task.tid) {
+            return 0;
+        }
+        let inner = process.inner_exclusive_access();
+        if inner.deadlock_detect && would_deadlock(&inner, task.tid, id) {
+            return -EDEADLK;
+        }
+        drop(inner);
+        if signal_pending() {
+            return -EINTR;
+        }
+        task.inner_exclusive_access().waiting_for = Some(id);
+        resource.wait_queue().wait();
+        task.inner_exclusive_access().waiting_for = None;
+    }
+}
+
+/// Create an unlocked mutex and return its id
+pub fn sys_mutex_create() -> isize {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    if inner.mutexes.len() >= SYNC_LIMIT {
+        return -EAGAIN;
+    }
+    inner.mutexes.push(Arc::new(Mutex::new()));
+    inner.mutexes.len() as isize - 1
+}
+
+/// Lock the mutex `mutex_id`, waiting until it is unlocked. Fails with `EDEADLK` if the current
+/// thread holds it already.
+pub fn sys_mutex_lock(mutex_id: usize) -> isize {
+    let task = current_task().unwrap();
+    let mutex = task.process().inner_exclusive_access().mutexes.get(mutex_id).cloned();
+    match mutex {
+        Some(mutex) if mutex.owner() == Some(task.tid) => -EDEADLK,
+        Some(_) => acquire(&task, ResourceId::Mutex(mutex_id)),
+        None => -EINVAL,
+    }
+}
+
+/// Unlock the mutex `mutex_id`, which the current thread must hold
+pub fn sys_mutex_unlock(mutex_id: usize) -> isize {
+    let task = current_task().unwrap();
+    let mutex = task.process().inner_exclusive_access().mutexes.get(mutex_id).cloned();
+    match mutex {
+        Some(mutex) if mutex.unlock(task.tid) => 0,
+        Some(_) => -EPERM,
+        None => -EINVAL,
+    }
+}
+
+/// Create a semaphore with `count` units and return its id
+pub fn sys_semaphore_create(count: usize) -> isize {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    if inner.semaphores.len() >= SYNC_LIMIT {
+        return -EAGAIN;
+    }
+    inner.semaphores.push(Arc::new(Semaphore::new(count)));
+    inner.semaphores.len() as isize - 1
+}
+
+/// Add a unit to the semaphore `sem_id`
+pub fn sys_semaphore_up(sem_id: usize) -> isize {
+    let task = current_task().unwrap();
+    let semaphore = task.process().inner_exclusive_access().semaphores.get(sem_id).cloned();
+    match semaphore {
+        Some(semaphore) => {
+            semaphore.up(task.tid);
+            0
+        }
+        None => -EINVAL,
+    }
+}
+
+/// Take a unit from the semaphore `sem_id`, waiting until there is one
+pub fn sys_semaphore_down(sem_id: usize) -> isize {
+    let task = current_task().unwrap();
+    acquire(&task, ResourceId::Semaphore(sem_id))
+}
+
+/// Create a condition variable and return its id
+pub fn sys_condvar_create() -> isize {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    if inner.condvars.len() >= SYNC_LIMIT {
+        return -EAGAIN;
+    }
+    inner.condvars.push(Arc::new(Condvar::new()));
+    inner.condvars.len() as isize - 1
+}
+
+/// Wake up a thread waiting on the condition variable `condvar_id`, if any
+pub fn sys_condvar_signal(condvar_id: usize) -> isize {
+    let condvar = current_process().inner_exclusive_access().condvars.get(condvar_id).cloned();
+    match condvar {
+        Some(condvar) => {
+            condvar.wait_queue.wake_one();
+            0
+        }
+        None => -EINVAL,
+    }
+}
+
+/// Unlock the mutex `mutex_id`, which the current thread must hold, wait on the condition variable
+/// `condvar_id` and lock the mutex again. Signals may end the wait early. Fails with `EINTR` if a
+/// signal arrives before the mutex is locked again, which leaves it unlocked.
+pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
+    let task = current_task().unwrap();
+    let process = task.process();
+    let inner = process.inner_exclusive_access();
+    let (Some(condvar), Some(mutex)) = (
+        inner.condvars.get(condvar_id).cloned(),
+        inner.mutexes.get(mutex_id).cloned(),
+    ) else {
+        return -EINVAL;
+    };
+    drop(inner);
+    drop(process);
+
+    if !mutex.unlock(task.tid) {
+        return -EPERM;
+    }
+    if !signal_pending() {
+        condvar.wait_queue.wait();
+    }
+    acquire(&task, ResourceId::Mutex(mutex_id))
+}
+
+/// Turn deadlock detection for mutexes and semaphores on if `enabled` is 1, or off if it is 0
+pub fn sys_enable_deadlock_detect(enabled: usize) -> isize {
+    let enabled = match enabled {
+        0 => false,
+        1 => true,
+        _ => return -EINVAL,
+    };
+    current_process().inner_exclusive_access().deadlock_detect = enabled;
+    0
+}
@@ -1,4 +1,5 @@
-use crate::{timer::{get_time_us, MICRO_PER_SEC}, utils::{any_as_u8_slice, copy_to_dsts}, mem::page_table::translate, task::processor::current_user_token};
+use super::errno::EFAULT;
+use crate::{timer::{get_time_us, MICRO_PER_SEC}, utils::{any_as_u8_slice, copy_to_dsts}, mem::page_table::translate, task::processor::{current_prepare_user_buffer, current_user_token}, mem::memory_set::AccessType};
 
 #[repr(C)]
 pub struct TimeVal {
@@ -11,13 +12,16 @@ pub fn sys_get_time(ts: *mut TimeVal, _tz: usize) -> isize {
     let sec = usec / MICRO_PER_SEC;
     let src = TimeVal { sec, usec };
 
+    if !current_prepare_user_buffer(ts as *const u8, core::mem::size_of::<TimeVal>(), AccessType::Write) {
+        return -EFAULT;
+    }
     unsafe {
         let src = any_as_u8_slice(&src);
         let mut dsts = translate(current_user_token(), ts);
 
         match copy_to_dsts(src, &mut dsts[..]) {
             Ok(_) => 0,
-            Err(_) => 1,
+            Err(_) => -EFAULT,
         }
     }
 }
@@ -1,8 +1,12 @@
 use crate::upsync::UPSyncCell;
-use alloc::{collections::VecDeque, sync::Arc};
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
 use lazy_static::lazy_static;
 
-use super::TaskControlBlock;
+use super::{process::ProcessControlBlock, TaskControlBlock};
 
 pub trait TaskManager {
     fn new() -> Self;
@@ -43,3 +47,25 @@ pub fn add_task(task: Arc<TaskControlBlock>) {
 pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
     TASK_MANAGER.exclusive_access().fetch()
 }
+
+lazy_static! {
+    /// Processes by pid, for looking them up from outside of the process tree. Entries of dropped
+    /// processes are dead and replaced when their pids are reused.
+    static ref PID2PROCESS: UPSyncCell<BTreeMap<usize, Weak<ProcessControlBlock>>> =
+        unsafe { UPSyncCell::new(BTreeMap::new()) };
+}
+
+pub fn register_process(process: &Arc<ProcessControlBlock>) {
+    let mut pid2process = PID2PROCESS.exclusive_access();
+    pid2process.retain(|_, process| process.strong_count() > 0);
+    pid2process.insert(process.get_pid(), Arc::downgrade(process));
+}
+
+pub fn find_process(pid: usize) -> Option<Arc<ProcessControlBlock>> {
+    PID2PROCESS.borrow().get(&pid)?.upgrade()
+}
+
+/// Processes which have not been reaped, in the order of their pids
+pub fn all_processes() -> Vec<Arc<ProcessControlBlock>> {
+    PID2PROCESS.borrow().values().filter_map(Weak::upgrade).collect()
+}
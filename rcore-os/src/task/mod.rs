@@ -1,49 +1,82 @@
 pub mod context;
 pub mod manager;
 pub mod pid;
+pub mod process;
 pub mod processor;
+pub mod signal;
 pub mod stack;
 pub mod switch;
+pub mod sync;
+pub mod wait_queue;
 
 use core::cell::RefMut;
 
 use crate::{
     config::{PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT},
     debug,
+    error,
+    fs,
     loader::{self, get_app_data, get_app_data_by_name, KERNEL_STACK_SIZE, MAX_APP_NUM},
     log,
     mem::{
         address::{PhysPageNum, VirtAddr},
-        memory_set::{MapPermission, MemorySet},
         KERNEL_SPACE,
     },
     sbi::shutdown,
+    timer::get_time_us,
     trap::{context::TrapContext, trap_handler},
     upsync::UPSyncCell,
 };
-use alloc::{sync::Arc, sync::Weak, vec::Vec};
+use alloc::{string::String, sync::Arc, sync::Weak, vec::Vec};
 use context::TaskContext;
 use lazy_static::lazy_static;
 
 use self::{
-    manager::add_task,
-    pid::{pid_alloc, PidHandle},
-    processor::{schedule, take_current_task},
+    manager::{add_task, register_process},
+    process::ProcessControlBlock,
+    processor::{current_process, current_task, schedule, take_current_task},
+    signal::{send_signal, SignalSet, SIGCHLD},
     stack::KernelStack,
+    sync::ResourceId,
     switch::__switch,
 };
 
-#[derive(Copy, Clone, Eq, PartialEq)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum TaskStatus {
     UnInit,
     Ready,
     Running,
+    /// Off the ready queue until a [`WaitQueue`](wait_queue::WaitQueue) wakes it up
+    Blocked,
     Exited,
     Zombie,
 }
 
+/// CPU time of a task in microseconds, for `ru_utime` and `ru_stime` of `struct rusage`
+#[derive(Copy, Clone, Default, Debug)]
+pub struct CpuTime {
+    pub user_us: usize,
+    pub kernel_us: usize,
+}
+
+impl core::ops::AddAssign for CpuTime {
+    fn add_assign(&mut self, other: Self) {
+        self.user_us += other.user_us;
+        self.kernel_us += other.kernel_us;
+    }
+}
+
+/// Where the trap context of the thread `tid` is mapped in user space. They are below
+/// [`TRAMPOLINE`], one page for each thread.
+pub fn trap_context_va(tid: usize) -> usize {
+    TRAP_CONTEXT - tid * PAGE_SIZE
+}
+
+/// A thread of a [`ProcessControlBlock`], which is what the scheduler runs
 pub struct TaskControlBlock {
-    pub pid: PidHandle,
+    pub process: Weak<ProcessControlBlock>,
+    /// Index in [`process::InnerProcessControlBlock::tasks`]
+    pub tid: usize,
     pub kernel_stack: KernelStack,
 
     inner: UPSyncCell<InnerTaskControlBlock>,
@@ -51,13 +84,21 @@ pub struct TaskControlBlock {
 
 pub struct InnerTaskControlBlock {
     pub trap_context_ppn: PhysPageNum,
-    pub base_size: usize,
     pub task_context: TaskContext,
     pub task_status: TaskStatus,
-    pub memory_set: MemorySet,
-    pub parent: Option<Weak<TaskControlBlock>>,
-    pub children: Vec<Arc<TaskControlBlock>>,
-    pub exit_code: i32,
+    /// Bottom of the user stack made for the thread. The stack of the main thread is part of the
+    /// program instead.
+    pub user_stack: Option<usize>,
+    /// Set when the thread exits
+    pub exit_code: Option<i32>,
+    /// Since when the thread has run without charging the time to its process
+    time_mark_us: usize,
+    /// Signals for this thread only, e.g. for its faults, which it has not acted on yet
+    pub pending_signals: SignalSet,
+    /// Signals which stay pending until they are unblocked
+    pub signal_mask: SignalSet,
+    /// What the thread is blocked for, for deadlock detection
+    pub waiting_for: Option<ResourceId>,
 }
 
 impl InnerTaskControlBlock {
@@ -65,16 +106,13 @@ impl InnerTaskControlBlock {
         self.trap_context_ppn.get_mut()
     }
 
-    pub fn get_user_token(&self) -> usize {
-        self.memory_set.token()
-    }
-
     pub fn get_status(&self) -> TaskStatus {
         self.task_status
     }
 
-    pub fn is_zombie(&self) -> bool {
-        self.get_status() == TaskStatus::Zombie
+    /// Start charging time again when the thread is switched to, leaving out the time it waited
+    pub fn restart_time(&mut self) {
+        self.time_mark_us = get_time_us();
     }
 }
 
@@ -83,113 +121,81 @@ impl TaskControlBlock {
         self.inner.exclusive_access()
     }
 
-    pub fn new(elf_data: &[u8]) -> Self {
-        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
-        let trap_context_ppn = memory_set
-            .translate(VirtAddr::from(TRAP_CONTEXT).into())
+    /// A thread `tid` of `process`, whose trap context page must be mapped already
+    pub fn new(process: &Arc<ProcessControlBlock>, tid: usize, user_stack: Option<usize>) -> Self {
+        let trap_context_ppn = process
+            .inner_exclusive_access()
+            .memory_set
+            .translate(VirtAddr::from(trap_context_va(tid)).into())
             .unwrap()
             .ppn();
-        let pid = pid_alloc();
-        let kernel_stack = KernelStack::new(&pid);
+        let kernel_stack = KernelStack::new();
         let kernel_stack_top = kernel_stack.get_top();
 
-        let tcb = Self {
-            pid,
+        Self {
+            process: Arc::downgrade(process),
+            tid,
             kernel_stack,
             inner: unsafe {
                 UPSyncCell::new(InnerTaskControlBlock {
                     trap_context_ppn,
-                    base_size: user_sp,
                     task_context: TaskContext::goto_trap_return(kernel_stack_top),
                     task_status: TaskStatus::Ready,
-                    memory_set,
-                    parent: None,
-                    children: vec![],
-                    exit_code: 0,
+                    user_stack,
+                    exit_code: None,
+                    time_mark_us: 0,
+                    pending_signals: SignalSet::default(),
+                    signal_mask: SignalSet::default(),
+                    waiting_for: None,
                 })
             },
-        };
-
-        let trap_context = tcb.inner_exclusive_access().get_trap_context();
-        *trap_context = TrapContext::app_init_context(
-            entry_point,
-            user_sp,
-            KERNEL_SPACE.exclusive_access().token(),
-            kernel_stack_top,
-            trap_handler as usize,
-        );
-
-        tcb
+        }
     }
 
-    pub fn get_pid(&self) -> usize {
-        self.pid.0
+    /// The process of the thread, which lives as long as any of its threads can run
+    pub fn process(&self) -> Arc<ProcessControlBlock> {
+        self.process.upgrade().unwrap()
     }
 
-    /// CAUTIONS: After calling this function, user space pointers and trap context pointer may be invalid.
-    pub fn exec(&self, elf_data: &[u8]) {
-        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
-        let trap_context_ppn = memory_set
-            .translate(VirtAddr::from(TRAP_CONTEXT).into())
-            .unwrap()
-            .ppn();
-        let mut inner = self.inner_exclusive_access();
-
-        inner.memory_set = memory_set;
-        inner.trap_context_ppn = trap_context_ppn;
-        let trap_context = inner.get_trap_context();
-        *trap_context = TrapContext::app_init_context(
-            entry_point,
+    /// A trap context which enters user mode at `entry` with the stack pointer `user_sp`
+    pub fn init_trap_context(&self, entry: usize, user_sp: usize) -> TrapContext {
+        TrapContext::app_init_context(
+            entry,
             user_sp,
             KERNEL_SPACE.exclusive_access().token(),
             self.kernel_stack.get_top(),
             trap_handler as usize,
-        );
+        )
     }
 
-    pub fn fork(self: &Arc<TaskControlBlock>) -> Arc<TaskControlBlock> {
-        let mut parent_inner = self.inner_exclusive_access();
-        let memory_set = MemorySet::from_existed_user_space(&parent_inner.memory_set);
-        let trap_context_ppn = memory_set
-            .translate(VirtAddr::from(TRAP_CONTEXT).into())
-            .unwrap()
-            .ppn();
-        let pid_handle = pid_alloc();
-        let kernel_stack = KernelStack::new(&pid_handle);
-        let kernel_stack_top = kernel_stack.get_top();
-        let tcb = Arc::new(TaskControlBlock {
-            pid: pid_handle,
-            kernel_stack,
-            inner: unsafe {
-                UPSyncCell::new(InnerTaskControlBlock {
-                    trap_context_ppn,
-                    base_size: parent_inner.base_size,
-                    task_context: TaskContext::goto_trap_return(kernel_stack_top),
-                    task_status: TaskStatus::Ready,
-                    memory_set,
-                    parent: Some(Arc::downgrade(self)),
-                    children: vec![],
-                    exit_code: 0,
-                })
-            },
-        });
-
-        parent_inner.children.push(tcb.clone());
-        let trap_context = tcb.inner_exclusive_access().get_trap_context();
-        trap_context.kernel_sp = kernel_stack_top;
-
-        tcb
+    /// Charge the time since the last charge to the user or kernel time of the process
+    pub fn charge_time(&self, user: bool) {
+        let now = get_time_us();
+        let mut inner = self.inner_exclusive_access();
+        let elapsed = now.saturating_sub(inner.time_mark_us);
+        inner.time_mark_us = now;
+        drop(inner);
+
+        let process = self.process();
+        let mut process_inner = process.inner_exclusive_access();
+        match user {
+            true => process_inner.cpu_time.user_us += elapsed,
+            false => process_inner.cpu_time.kernel_us += elapsed,
+        }
     }
 }
 
 lazy_static! {
-    pub static ref INIT_PROC: Arc<TaskControlBlock> = Arc::new(TaskControlBlock::new(
-        get_app_data_by_name("initproc").unwrap()
-    ));
+    pub static ref INIT_PROC: Arc<ProcessControlBlock> = ProcessControlBlock::new(
+        get_app_data_by_name("initproc").unwrap(),
+        &[String::from("initproc")],
+    );
 }
 
 pub fn add_init_proc() {
-    add_task(INIT_PROC.clone());
+    register_process(&INIT_PROC);
+    let task = INIT_PROC.inner_exclusive_access().tasks[0].clone().unwrap();
+    add_task(task);
 }
 
 // pub struct TaskManager {
@@ -289,6 +295,7 @@ pub fn add_init_proc() {
 
 pub fn suspend_and_run_next() {
     let task = take_current_task().unwrap();
+    task.charge_time(false);
 
     let mut inner_task = task.inner_exclusive_access();
     let cur_task_context_ptr = &mut inner_task.task_context as *mut TaskContext;
@@ -299,20 +306,104 @@ pub fn suspend_and_run_next() {
     schedule(cur_task_context_ptr);
 }
 
+/// Switch away from the current task without putting it back to the ready queue. Whoever blocks
+/// it must keep it and hand it to [`wakeup_task`] later, see [`WaitQueue`](wait_queue::WaitQueue).
+pub fn block_and_run_next() {
+    let task = take_current_task().unwrap();
+    task.charge_time(false);
+
+    let mut inner_task = task.inner_exclusive_access();
+    let cur_task_context_ptr = &mut inner_task.task_context as *mut TaskContext;
+    inner_task.task_status = TaskStatus::Blocked;
+    drop(inner_task);
+
+    drop(task);
+    schedule(cur_task_context_ptr);
+}
+
+/// Make a blocked task ready to run again
+pub fn wakeup_task(task: Arc<TaskControlBlock>) {
+    let mut inner = task.inner_exclusive_access();
+    if inner.task_status != TaskStatus::Blocked {
+        return;
+    }
+    inner.task_status = TaskStatus::Ready;
+    drop(inner);
+    add_task(task);
+}
+
+/// End the current thread. If it is the main thread, the whole process ends with `exit_code`.
 pub fn exit_and_run_next(exit_code: i32) -> ! {
+    if current_task().unwrap().tid == 0 {
+        exit_process_and_run_next(exit_code);
+    }
+    exit_thread_and_run_next(exit_code);
+}
+
+/// End the process of the current thread with `exit_code`, unless it is ending already. The other
+/// threads are woken up, and they exit instead of returning to user mode.
+pub fn exit_process_and_run_next(exit_code: i32) -> ! {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    if !inner.exiting {
+        inner.exiting = true;
+        inner.exit_code = exit_code;
+    }
+    let tasks: Vec<Arc<TaskControlBlock>> = inner.tasks.iter().flatten().cloned().collect();
+    drop(inner);
+    drop(process);
+
+    for task in tasks {
+        wakeup_task(task);
+    }
+    exit_thread_and_run_next(exit_code);
+}
+
+/// End the current thread. The last thread to exit makes the process a zombie for its parent.
+pub fn exit_thread_and_run_next(exit_code: i32) -> ! {
     debug!("Exit and run next task");
 
     let task = take_current_task().unwrap();
-    let pid = task.get_pid();
+    task.charge_time(false);
+    let process = task.process();
+
+    let mut task_inner = task.inner_exclusive_access();
+    task_inner.task_status = TaskStatus::Exited;
+    task_inner.exit_code = Some(exit_code);
+    let user_stack = task_inner.user_stack.take();
+    drop(task_inner);
+
+    let mut inner = process.inner_exclusive_access();
+    // The main thread keeps its trap context and stack until the process ends
+    if task.tid != 0 {
+        let trap_context_va = VirtAddr::from(trap_context_va(task.tid));
+        inner.memory_set.remove_area_with_start_vpn(trap_context_va.into());
+    }
+    if let Some(bottom) = user_stack {
+        let stack_end = bottom + loader::USER_STACK_SIZE;
+        inner.memory_set.unmap_range(bottom.into(), stack_end.into());
+    }
+    let last = inner.live_tasks().next().is_none();
+    drop(task);
 
-    if pid == pid::IDLE_PID {
-        log!("Idle Process exit with {}", pid);
+    if !last {
+        drop(inner);
+        process.thread_exited.wake_all();
+        drop(process);
+        let mut unused = TaskContext::zero_init();
+        schedule(&mut unused as *mut _);
+        panic!("Run exited task again");
+    }
+
+    if process.get_pid() == pid::IDLE_PID {
+        log!("Idle Process exit with {}", inner.exit_code);
+        if fs::sync().is_err() {
+            error!("Failed to write cached blocks back before shutdown");
+        }
         shutdown();
     }
 
-    let mut inner = task.inner_exclusive_access();
-    inner.task_status = TaskStatus::Zombie;
-    inner.exit_code = exit_code;
+    inner.is_zombie = true;
 
     // Move exited process's children to init proc
     {
@@ -322,11 +413,23 @@ pub fn exit_and_run_next(exit_code: i32) -> ! {
             init_proc_inner.children.push(child.clone());
         }
     }
+    // Some of them may have exited already, and init proc has to reap them
+    let orphaned = !inner.children.is_empty();
+    let parent = inner.parent.as_ref().and_then(Weak::upgrade);
 
     inner.children.clear();
     inner.memory_set.recycle_data_pages();
+    inner.fd_table.clear();
     drop(inner);
-    drop(task);
+    drop(process);
+
+    if let Some(parent) = parent {
+        send_signal(&parent, SIGCHLD);
+        parent.children_exited.wake_all();
+    }
+    if orphaned {
+        INIT_PROC.children_exited.wake_all();
+    }
 
     let mut unused = TaskContext::zero_init();
     schedule(&mut unused as *mut _);
@@ -19,38 +19,39 @@ impl From<PidHandle> for usize {
     }
 }
 
-struct PidAllocator {
+/// Allocates the lowest ids which have never been used, or ids which have been freed
+pub struct RecycleAllocator {
     current: usize,
     recycled: Vec<usize>,
 }
 
-impl PidAllocator {
-    pub fn new() -> PidAllocator {
-        PidAllocator {
+impl RecycleAllocator {
+    pub fn new() -> RecycleAllocator {
+        RecycleAllocator {
             current: 0,
             recycled: vec![],
         }
     }
 
-    pub fn alloc(&mut self) -> PidHandle {
-        if let Some(pid) = self.recycled.pop() {
-            PidHandle(pid)
+    pub fn alloc(&mut self) -> usize {
+        if let Some(id) = self.recycled.pop() {
+            id
         } else {
             self.current += 1;
-            PidHandle(self.current - 1)
+            self.current - 1
         }
     }
 
-    pub fn dealloc(&mut self, pid: usize) {
-        self.recycled.push(pid);
+    pub fn dealloc(&mut self, id: usize) {
+        self.recycled.push(id);
     }
 }
 
 lazy_static! {
-    static ref PID_ALLOCATOR: UPSyncCell<PidAllocator> =
-        unsafe { UPSyncCell::new(PidAllocator::new()) };
+    static ref PID_ALLOCATOR: UPSyncCell<RecycleAllocator> =
+        unsafe { UPSyncCell::new(RecycleAllocator::new()) };
 }
 
 pub fn pid_alloc() -> PidHandle {
-    PID_ALLOCATOR.exclusive_access().alloc()
+    PidHandle(PID_ALLOCATOR.exclusive_access().alloc())
 }
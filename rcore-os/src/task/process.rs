@@ -0,0 +1,356 @@
+use core::cell::RefMut;
+
+use alloc::{
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+
+use super::{
+    manager::register_process,
+    pid::{pid_alloc, PidHandle},
+    signal::{SignalAction, SignalSet, NSIG, SIG_IGN},
+    sync::{Condvar, Mutex, Resource, ResourceId, Semaphore},
+    trap_context_va,
+    wait_queue::WaitQueue,
+    CpuTime, TaskControlBlock,
+};
+use crate::{
+    config::{FD_LIMIT, MMAP_BASE, PAGE_SIZE, THREAD_LIMIT, USER_SPACE_END},
+    fs::{File, Stdin, Stdout},
+    loader::USER_STACK_SIZE,
+    mem::{
+        address::VirtAddr,
+        memory_set::{MapPermission, MemorySet},
+    },
+    upsync::UPSyncCell,
+};
+
+/// A program with its address space, files and children, run by one or more threads, which are
+/// [`TaskControlBlock`]s
+pub struct ProcessControlBlock {
+    pub pid: PidHandle,
+    /// Threads waiting in `wait4` for a child of this process to exit
+    pub children_exited: WaitQueue,
+    /// Threads waiting in `waittid` for another thread of this process to exit
+    pub thread_exited: WaitQueue,
+
+    inner: UPSyncCell<InnerProcessControlBlock>,
+}
+
+pub struct InnerProcessControlBlock {
+    /// All threads have exited, and the parent has to reap the process
+    pub is_zombie: bool,
+    /// The process is ending, and its threads exit instead of returning to user mode
+    pub exiting: bool,
+    pub heap_bottom: usize,
+    pub program_brk: usize,
+    pub memory_set: MemorySet,
+    pub parent: Option<Weak<ProcessControlBlock>>,
+    pub children: Vec<Arc<ProcessControlBlock>>,
+    pub exit_code: i32,
+    pub fd_table: Vec<Option<Arc<dyn File>>>,
+    /// Absolute path of the current working directory, see [`crate::fs::vfs::absolute_path`]
+    pub cwd: String,
+    /// CPU time of all threads
+    pub cpu_time: CpuTime,
+    /// CPU time of the children which have been waited for, including their own children
+    pub children_cpu_time: CpuTime,
+    /// Signals sent to the process which none of its threads has acted on yet
+    pub pending_signals: SignalSet,
+    /// Action of each signal, where signal `n` is at `n - 1`
+    pub signal_actions: [SignalAction; NSIG],
+    /// Stopped by a signal until `SIGCONT` or `SIGKILL`
+    pub stopped: bool,
+    /// Threads by tid. The main thread is 0, and exited threads stay until they are waited for.
+    pub tasks: Vec<Option<Arc<TaskControlBlock>>>,
+    /// Synchronization objects of the threads by id, see [`super::sync`]
+    pub mutexes: Vec<Arc<Mutex>>,
+    pub semaphores: Vec<Arc<Semaphore>>,
+    pub condvars: Vec<Arc<Condvar>>,
+    /// Waiting for a mutex or a semaphore fails with `EDEADLK` instead of deadlocking
+    pub deadlock_detect: bool,
+}
+
+impl InnerProcessControlBlock {
+    pub fn get_user_token(&self) -> usize {
+        self.memory_set.token()
+    }
+
+    pub fn is_zombie(&self) -> bool {
+        self.is_zombie
+    }
+
+    /// The lowest unused file descriptor, or `None` if all of them are used
+    pub fn alloc_fd(&mut self) -> Option<usize> {
+        if let Some(fd) = self.fd_table.iter().position(|file| file.is_none()) {
+            return Some(fd);
+        }
+        if self.fd_table.len() >= FD_LIMIT {
+            return None;
+        }
+        self.fd_table.push(None);
+        Some(self.fd_table.len() - 1)
+    }
+
+    pub fn get_file(&self, fd: usize) -> Option<Arc<dyn File>> {
+        self.fd_table.get(fd)?.clone()
+    }
+
+    /// The lowest unused tid, or `None` if all of them are used
+    fn alloc_tid(&mut self) -> Option<usize> {
+        if let Some(tid) = self.tasks.iter().position(|task| task.is_none()) {
+            return Some(tid);
+        }
+        if self.tasks.len() >= THREAD_LIMIT {
+            return None;
+        }
+        self.tasks.push(None);
+        Some(self.tasks.len() - 1)
+    }
+
+    /// Threads which have not exited
+    pub fn live_tasks(&self) -> impl Iterator<Item = &Arc<TaskControlBlock>> {
+        self.tasks
+            .iter()
+            .flatten()
+            .filter(|task| task.inner_exclusive_access().exit_code.is_none())
+    }
+
+    pub fn resource(&self, id: ResourceId) -> Option<Arc<dyn Resource>> {
+        match id {
+            ResourceId::Mutex(id) => Some(self.mutexes.get(id)?.clone()),
+            ResourceId::Semaphore(id) => Some(self.semaphores.get(id)?.clone()),
+        }
+    }
+}
+
+impl ProcessControlBlock {
+    pub fn inner_exclusive_access(&self) -> RefMut<'_, InnerProcessControlBlock> {
+        self.inner.exclusive_access()
+    }
+
+    pub fn get_pid(&self) -> usize {
+        self.pid.0
+    }
+
+    fn with_memory_set(
+        memory_set: MemorySet,
+        heap_bottom: usize,
+        parent: Option<Weak<ProcessControlBlock>>,
+    ) -> Self {
+        Self {
+            pid: pid_alloc(),
+            children_exited: WaitQueue::new(),
+            thread_exited: WaitQueue::new(),
+            inner: unsafe {
+                UPSyncCell::new(InnerProcessControlBlock {
+                    is_zombie: false,
+                    exiting: false,
+                    heap_bottom,
+                    program_brk: heap_bottom,
+                    memory_set,
+                    parent,
+                    children: vec![],
+                    exit_code: 0,
+                    fd_table: vec![
+                        // 0 -> stdin
+                        Some(Arc::new(Stdin)),
+                        // 1 -> stdout
+                        Some(Arc::new(Stdout)),
+                        // 2 -> stderr
+                        Some(Arc::new(Stdout)),
+                    ],
+                    cwd: String::from("/"),
+                    cpu_time: CpuTime::default(),
+                    children_cpu_time: CpuTime::default(),
+                    pending_signals: SignalSet::default(),
+                    signal_actions: [SignalAction::default(); NSIG],
+                    stopped: false,
+                    tasks: vec![],
+                    mutexes: vec![],
+                    semaphores: vec![],
+                    condvars: vec![],
+                    deadlock_detect: false,
+                })
+            },
+        }
+    }
+
+    /// A process running the program `elf_data` with the arguments `args` and no environment
+    pub fn new(elf_data: &[u8], args: &[String]) -> Arc<Self> {
+        let (memory_set, user_sp, heap_bottom, entry_point) =
+            MemorySet::from_elf(elf_data, args, &[]).expect("Invalid ELF file of the initial process");
+        let process = Arc::new(Self::with_memory_set(memory_set, heap_bottom, None));
+        let task = Arc::new(TaskControlBlock::new(&process, 0, None));
+        let trap_context = task.inner_exclusive_access().get_trap_context();
+        *trap_context = task.init_trap_context(entry_point, user_sp);
+        trap_context.set_args(user_sp, args.len());
+        process.inner_exclusive_access().tasks.push(Some(task));
+
+        process
+    }
+
+    /// Grow or shrink the heap by `size` bytes. Return the old program break.
+    pub fn change_program_brk(&self, size: i32) -> Option<usize> {
+        let mut inner = self.inner_exclusive_access();
+        let heap_bottom = inner.heap_bottom;
+        let old_brk = inner.program_brk;
+        let new_brk = old_brk as isize + size as isize;
+        if new_brk < heap_bottom as isize || new_brk as usize > USER_SPACE_END {
+            return None;
+        }
+        let new_brk = new_brk as usize;
+
+        let result = if size < 0 {
+            inner
+                .memory_set
+                .shrink_to(heap_bottom.into(), new_brk.into())
+        } else {
+            inner
+                .memory_set
+                .append_to(heap_bottom.into(), new_brk.into())
+        };
+
+        if result {
+            inner.program_brk = new_brk;
+            Some(old_brk)
+        } else {
+            None
+        }
+    }
+
+    /// Replace the program with `elf_data`, which gets `args` and `envs` as its `argv` and `envp`.
+    /// Handlers are gone with the old program, so caught signals get their default actions again.
+    /// The main thread must be the only one which has not exited, and it runs the new program.
+    /// Synchronization objects are gone with the old program as well.
+    /// Fail with `ENOEXEC` before touching the old program if `elf_data` is not a valid program.
+    /// CAUTIONS: After calling this function, user space pointers and trap context pointer may be invalid.
+    pub fn exec(&self, elf_data: &[u8], args: &[String], envs: &[String]) -> Result<(), isize> {
+        let (memory_set, user_sp, heap_bottom, entry_point) = MemorySet::from_elf(elf_data, args, envs)?;
+        let mut inner = self.inner_exclusive_access();
+
+        inner.memory_set = memory_set;
+        inner.heap_bottom = heap_bottom;
+        inner.program_brk = heap_bottom;
+        for action in inner.signal_actions.iter_mut() {
+            if action.handler != SIG_IGN {
+                *action = SignalAction::default();
+            }
+        }
+        inner.tasks.truncate(1);
+        inner.mutexes.clear();
+        inner.semaphores.clear();
+        inner.condvars.clear();
+        inner.deadlock_detect = false;
+        let task = inner.tasks[0].clone().unwrap();
+        let trap_context_ppn = inner
+            .memory_set
+            .translate(VirtAddr::from(trap_context_va(0)).into())
+            .unwrap()
+            .ppn();
+        drop(inner);
+
+        let mut task_inner = task.inner_exclusive_access();
+        task_inner.trap_context_ppn = trap_context_ppn;
+        let trap_context = task_inner.get_trap_context();
+        *trap_context = task.init_trap_context(entry_point, user_sp);
+        trap_context.set_args(user_sp, args.len());
+        Ok(())
+    }
+
+    /// Create a child process with a copy of the address space. Only `task`, the calling thread,
+    /// is copied, and it becomes the main thread of the child. Mutexes and semaphores keep their
+    /// state, but what other threads hold is never released in the child.
+    pub fn fork(self: &Arc<Self>, task: &Arc<TaskControlBlock>) -> Arc<Self> {
+        let mut parent_inner = self.inner_exclusive_access();
+        let mut memory_set = MemorySet::from_existed_user_space(&mut parent_inner.memory_set);
+        for other in parent_inner.live_tasks().filter(|other| other.tid != 0) {
+            memory_set.remove_area_with_start_vpn(VirtAddr::from(trap_context_va(other.tid)).into());
+        }
+        let child = Arc::new(Self::with_memory_set(
+            memory_set,
+            parent_inner.heap_bottom,
+            Some(Arc::downgrade(self)),
+        ));
+        let mut child_inner = child.inner_exclusive_access();
+        child_inner.program_brk = parent_inner.program_brk;
+        child_inner.fd_table = parent_inner.fd_table.clone();
+        child_inner.cwd = parent_inner.cwd.clone();
+        child_inner.signal_actions = parent_inner.signal_actions;
+        child_inner.mutexes = parent_inner
+            .mutexes
+            .iter()
+            .map(|mutex| Arc::new(mutex.fork(task.tid)))
+            .collect();
+        child_inner.semaphores = parent_inner
+            .semaphores
+            .iter()
+            .map(|semaphore| Arc::new(semaphore.fork(task.tid)))
+            .collect();
+        child_inner.condvars = parent_inner
+            .condvars
+            .iter()
+            .map(|_| Arc::new(Condvar::new()))
+            .collect();
+        child_inner.deadlock_detect = parent_inner.deadlock_detect;
+        parent_inner.children.push(child.clone());
+        drop(child_inner);
+        drop(parent_inner);
+
+        let child_task = Arc::new(TaskControlBlock::new(&child, 0, None));
+        let task_inner = task.inner_exclusive_access();
+        let mut child_task_inner = child_task.inner_exclusive_access();
+        child_task_inner.signal_mask = task_inner.signal_mask;
+        let trap_context = child_task_inner.get_trap_context();
+        *trap_context = task_inner.get_trap_context().clone();
+        trap_context.kernel_sp = child_task.kernel_stack.get_top();
+        drop(child_task_inner);
+        drop(task_inner);
+        child.inner_exclusive_access().tasks.push(Some(child_task));
+        register_process(&child);
+
+        child
+    }
+
+    /// Create a thread which starts at `entry` with `arg` in `a0`, on a new user stack. It blocks
+    /// the same signals as `creator`. Return `None` if there are too many threads or no room for
+    /// the stack.
+    pub fn create_thread(
+        self: &Arc<Self>,
+        creator: &Arc<TaskControlBlock>,
+        entry: usize,
+        arg: usize,
+    ) -> Option<Arc<TaskControlBlock>> {
+        let mut inner = self.inner_exclusive_access();
+        // A free page is left below the stack as a guard
+        let guard_bottom = inner
+            .memory_set
+            .find_free_area(MMAP_BASE.into(), USER_STACK_SIZE + PAGE_SIZE)?;
+        let tid = inner.alloc_tid()?;
+        let user_stack_bottom = usize::from(guard_bottom) + PAGE_SIZE;
+        let user_stack_top = user_stack_bottom + USER_STACK_SIZE;
+        inner.memory_set.insert_lazy_area(
+            user_stack_bottom.into(),
+            user_stack_top.into(),
+            MapPermission::R | MapPermission::W | MapPermission::U,
+        );
+        inner.memory_set.insert_framed_area(
+            trap_context_va(tid).into(),
+            (trap_context_va(tid) + PAGE_SIZE).into(),
+            MapPermission::R | MapPermission::W,
+        );
+        drop(inner);
+
+        let task = Arc::new(TaskControlBlock::new(self, tid, Some(user_stack_bottom)));
+        let mut task_inner = task.inner_exclusive_access();
+        task_inner.signal_mask = creator.inner_exclusive_access().signal_mask;
+        let trap_context = task_inner.get_trap_context();
+        *trap_context = task.init_trap_context(entry, user_stack_top);
+        trap_context.x[10] = arg;
+        drop(task_inner);
+        self.inner_exclusive_access().tasks[tid] = Some(task.clone());
+
+        Some(task)
+    }
+}
@@ -1,9 +1,12 @@
 use alloc::sync::Arc;
 use lazy_static::lazy_static;
 
-use crate::{trap::context::TrapContext, upsync::UPSyncCell};
+use crate::{mem::memory_set::AccessType, trap::context::TrapContext, upsync::UPSyncCell};
 
-use super::{context::TaskContext, manager::fetch_task, TaskControlBlock, TaskStatus, switch::__switch};
+use super::{
+    context::TaskContext, manager::fetch_task, process::ProcessControlBlock, switch::__switch,
+    TaskControlBlock, TaskStatus,
+};
 
 pub struct Processor {
     current: Option<Arc<TaskControlBlock>>,
@@ -46,9 +49,22 @@ pub fn current_task() -> Option<Arc<TaskControlBlock>> {
     PROCESSOR.exclusive_access().current()
 }
 
+pub fn current_process() -> Arc<ProcessControlBlock> {
+    current_task().unwrap().process()
+}
+
 pub fn current_user_token() -> usize {
-    let task = current_task().unwrap();
-    let x = task.inner_exclusive_access().get_user_token(); x
+    let process = current_process();
+    let x = process.inner_exclusive_access().get_user_token(); x
+}
+
+/// Prepare `len` bytes from user pointer `ptr` of the current task for accessing in kernel. Return
+/// false if the current task cannot access the buffer itself.
+pub fn current_prepare_user_buffer(ptr: *const u8, len: usize, access: AccessType) -> bool {
+    current_process()
+        .inner_exclusive_access()
+        .memory_set
+        .prepare_user_buffer(ptr as usize, len, access)
 }
 
 pub fn current_trap_context() -> &'static mut TrapContext {
@@ -68,6 +84,7 @@ pub fn run_tasks() {
             let mut task_inner = task.inner_exclusive_access();
             let next_task_context_ptr = &task_inner.task_context as *const TaskContext;
             task_inner.task_status = TaskStatus::Running;
+            task_inner.restart_time();
             drop(task_inner);
 
             processor.current = Some(task);
@@ -0,0 +1,339 @@
+//! POSIX signals. A signal is pending on a process, or on one of its threads for faults, until a
+//! thread returns to user mode with the signal not blocked. Then the thread ignores it, runs the
+//! default action for the process or runs the handler on a [`SignalFrame`] pushed to its stack.
+
+use alloc::{sync::Arc, vec::Vec};
+
+use super::{
+    block_and_run_next, exit_process_and_run_next, exit_thread_and_run_next,
+    manager::all_processes,
+    process::{InnerProcessControlBlock, ProcessControlBlock},
+    processor::current_task,
+    wakeup_task, InnerTaskControlBlock, TaskControlBlock,
+};
+use crate::{
+    mem::{memory_set::AccessType, page_table::translate},
+    trap::context::TrapContext,
+    utils::{any_as_u8_slice, any_as_u8_slice_mut, copy_from_srcs, copy_to_dsts},
+};
+
+pub const SIGINT: usize = 2;
+pub const SIGILL: usize = 4;
+pub const SIGKILL: usize = 9;
+pub const SIGSEGV: usize = 11;
+pub const SIGCHLD: usize = 17;
+pub const SIGCONT: usize = 18;
+pub const SIGSTOP: usize = 19;
+pub const SIGTSTP: usize = 20;
+pub const SIGTTIN: usize = 21;
+pub const SIGTTOU: usize = 22;
+pub const SIGURG: usize = 23;
+pub const SIGWINCH: usize = 28;
+/// Signals are numbered from 1 to this, the ones above 31 are the real-time signals of Linux. Only
+/// those which the kernel sends itself or treats specially are named here.
+pub const NSIG: usize = 64;
+
+/// `sa_handler` for the default action
+pub const SIG_DFL: usize = 0;
+/// `sa_handler` for ignoring the signal
+pub const SIG_IGN: usize = 1;
+
+/// `sa_restorer` is set, which is where the handler returns to
+pub const SA_RESTORER: usize = 0x0400_0000;
+/// The signal is not blocked while its handler runs
+pub const SA_NODEFER: usize = 0x4000_0000;
+/// The action is reset to the default one when the handler is called
+pub const SA_RESETHAND: usize = 0x8000_0000;
+const SA_SUPPORTED: usize = SA_RESTORER | SA_NODEFER | SA_RESETHAND;
+
+/// `how` of `rt_sigprocmask`
+pub const SIG_BLOCK: usize = 0;
+pub const SIG_UNBLOCK: usize = 1;
+pub const SIG_SETMASK: usize = 2;
+
+/// `sigset_t` of Linux, where signal `n` is bit `n - 1`
+#[repr(transparent)]
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub struct SignalSet(pub u64);
+
+impl SignalSet {
+    /// [`SIGKILL`] and [`SIGSTOP`] cannot be blocked, caught or ignored
+    pub const UNCATCHABLE: Self = Self(1 << (SIGKILL - 1) | 1 << (SIGSTOP - 1));
+    const STOP: Self =
+        Self(1 << (SIGSTOP - 1) | 1 << (SIGTSTP - 1) | 1 << (SIGTTIN - 1) | 1 << (SIGTTOU - 1));
+
+    pub fn contains(&self, sig: usize) -> bool {
+        self.0 & 1 << (sig - 1) != 0
+    }
+
+    pub fn insert(&mut self, sig: usize) {
+        self.0 |= 1 << (sig - 1);
+    }
+
+    pub fn remove(&mut self, sig: usize) {
+        self.0 &= !(1 << (sig - 1));
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    pub fn difference(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+
+    /// The lowest signal in the set
+    pub fn first(&self) -> Option<usize> {
+        (self.0 != 0).then(|| self.0.trailing_zeros() as usize + 1)
+    }
+}
+
+/// `struct sigaction` of Linux with `sa_restorer`, which RISC-V leaves out as it returns from
+/// handlers through the vDSO. There is no vDSO here, so `sa_restorer` must call `rt_sigreturn`.
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct SignalAction {
+    pub handler: usize,
+    pub flags: usize,
+    pub restorer: usize,
+    pub mask: SignalSet,
+}
+
+impl SignalAction {
+    pub fn is_valid(&self) -> bool {
+        self.flags & !SA_SUPPORTED == 0
+    }
+}
+
+/// What happens to a task on a signal with [`SIG_DFL`]
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum DefaultAction {
+    Terminate,
+    Ignore,
+    Stop,
+    Continue,
+}
+
+fn default_action(sig: usize) -> DefaultAction {
+    match sig {
+        SIGCHLD | SIGURG | SIGWINCH => DefaultAction::Ignore,
+        SIGCONT => DefaultAction::Continue,
+        SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU => DefaultAction::Stop,
+        _ => DefaultAction::Terminate,
+    }
+}
+
+/// Exit code of a task terminated by `sig`
+pub fn signal_exit_code(sig: usize) -> i32 {
+    -(sig as i32)
+}
+
+/// Saved on the user stack when a handler is called, and restored by `rt_sigreturn`
+#[repr(C)]
+struct SignalFrame {
+    /// `x` and `sepc` of the [`TrapContext`] which the handler interrupted
+    x: [usize; 32],
+    sepc: usize,
+    /// The blocked signals before the handler
+    mask: SignalSet,
+}
+
+/// Send `sig` to `process`. A signal which would be ignored is dropped now, and blocked threads
+/// which do not block the signal are woken up to act on it, which interrupts their syscalls.
+pub fn send_signal(process: &Arc<ProcessControlBlock>, sig: usize) {
+    let mut inner = process.inner_exclusive_access();
+    if inner.is_zombie() || inner.exiting {
+        return;
+    }
+    let action = inner.signal_actions[sig - 1];
+    match sig {
+        SIGKILL => inner.stopped = false,
+        SIGCONT => {
+            inner.stopped = false;
+            inner.pending_signals = inner.pending_signals.difference(SignalSet::STOP);
+        }
+        _ if SignalSet::STOP.contains(sig) => inner.pending_signals.remove(SIGCONT),
+        _ => {}
+    }
+    let tasks: Vec<Arc<TaskControlBlock>> = inner.live_tasks().cloned().collect();
+    let blocked_by = |task: &Arc<TaskControlBlock>| {
+        task.inner_exclusive_access().signal_mask.contains(sig)
+    };
+    let ignored = action.handler == SIG_IGN
+        || action.handler == SIG_DFL
+            && matches!(default_action(sig), DefaultAction::Ignore | DefaultAction::Continue);
+    // A thread blocking the signal may still act on it once it is unblocked and no longer ignored
+    if !ignored || tasks.iter().all(blocked_by) {
+        inner.pending_signals.insert(sig);
+    }
+    drop(inner);
+
+    for task in tasks {
+        // A stopped process continues even if `SIGCONT` is ignored
+        if sig == SIGKILL || sig == SIGCONT || !ignored && !blocked_by(&task) {
+            wakeup_task(task);
+        }
+    }
+}
+
+/// Send `sig` to all processes, like to the foreground process group of a terminal on Linux. There
+/// are no process groups, so the processes which should survive, e.g. the shell, ignore it.
+pub fn signal_all(sig: usize) {
+    for process in all_processes() {
+        send_signal(&process, sig);
+    }
+}
+
+/// Send `sig` to the current thread for a fault of its own, e.g. [`SIGSEGV`]. If the thread blocks
+/// or the process ignores the signal, it is unblocked and gets the default action, as the thread
+/// cannot go on anyway.
+pub fn force_signal(sig: usize) {
+    let task = current_task().unwrap();
+    let process = task.process();
+    let mut process_inner = process.inner_exclusive_access();
+    let mut inner = task.inner_exclusive_access();
+    if inner.signal_mask.contains(sig) || process_inner.signal_actions[sig - 1].handler == SIG_IGN {
+        inner.signal_mask.remove(sig);
+        process_inner.signal_actions[sig - 1] = SignalAction::default();
+    }
+    inner.pending_signals.insert(sig);
+}
+
+/// Pending signals which the thread does not block, and which it acts on
+fn deliverable_signals(
+    inner: &InnerTaskControlBlock,
+    process_inner: &InnerProcessControlBlock,
+) -> SignalSet {
+    inner
+        .pending_signals
+        .union(process_inner.pending_signals)
+        .difference(inner.signal_mask.difference(SignalSet::UNCATCHABLE))
+}
+
+/// Whether the current thread has a signal to act on, or has to exit with its process, which
+/// interrupts blocking syscalls
+pub fn signal_pending() -> bool {
+    let task = current_task().unwrap();
+    let process = task.process();
+    let process_inner = process.inner_exclusive_access();
+    let inner = task.inner_exclusive_access();
+    process_inner.exiting || deliverable_signals(&inner, &process_inner).first().is_some()
+}
+
+/// Act on the pending signals of the current thread before it returns to user mode. Return after
+/// calling a handler or when no signal is left, or do not return if the thread exits. Threads wait
+/// here while the process is stopped.
+pub fn handle_signals() {
+    loop {
+        let task = current_task().unwrap();
+        let process = task.process();
+        let mut process_inner = process.inner_exclusive_access();
+        if process_inner.exiting {
+            let exit_code = process_inner.exit_code;
+            drop(process_inner);
+            drop(process);
+            drop(task);
+            exit_thread_and_run_next(exit_code);
+        }
+        if process_inner.stopped {
+            drop(process_inner);
+            // Until `SIGCONT` or `SIGKILL`, which clear `stopped` and wake the thread up
+            block_and_run_next();
+            continue;
+        }
+
+        let mut inner = task.inner_exclusive_access();
+        let Some(sig) = deliverable_signals(&inner, &process_inner).first() else {
+            return;
+        };
+        if inner.pending_signals.contains(sig) {
+            inner.pending_signals.remove(sig);
+        } else {
+            process_inner.pending_signals.remove(sig);
+        }
+        let action = process_inner.signal_actions[sig - 1];
+        match action.handler {
+            SIG_IGN => {}
+            SIG_DFL => match default_action(sig) {
+                DefaultAction::Terminate => {
+                    drop(inner);
+                    drop(process_inner);
+                    drop(process);
+                    drop(task);
+                    exit_process_and_run_next(signal_exit_code(sig));
+                }
+                // The other threads stop on their way to user mode
+                DefaultAction::Stop => process_inner.stopped = true,
+                DefaultAction::Ignore | DefaultAction::Continue => {}
+            },
+            handler => {
+                let trap_context = inner.get_trap_context();
+                let frame = SignalFrame {
+                    x: trap_context.x,
+                    sepc: trap_context.sepc,
+                    mask: inner.signal_mask,
+                };
+                let size = core::mem::size_of::<SignalFrame>();
+                let frame_ptr = (trap_context.x[2] - size) & !0xf;
+                let memory_set = &mut process_inner.memory_set;
+                if !memory_set.prepare_user_buffer(frame_ptr, size, AccessType::Write) {
+                    drop(inner);
+                    drop(process_inner);
+                    drop(process);
+                    drop(task);
+                    exit_process_and_run_next(signal_exit_code(SIGSEGV));
+                }
+                let token = memory_set.token();
+                let src = unsafe { any_as_u8_slice(&frame) };
+                copy_to_dsts(src, &mut translate(token, frame_ptr as *const SignalFrame)).unwrap();
+
+                trap_context.x[2] = frame_ptr;
+                trap_context.x[10] = sig;
+                trap_context.x[1] = action.restorer;
+                trap_context.sepc = handler;
+                let mut mask = inner.signal_mask.union(action.mask);
+                if action.flags & SA_NODEFER == 0 {
+                    mask.insert(sig);
+                }
+                inner.signal_mask = mask.difference(SignalSet::UNCATCHABLE);
+                if action.flags & SA_RESETHAND != 0 {
+                    process_inner.signal_actions[sig - 1] = SignalAction::default();
+                }
+                return;
+            }
+        }
+    }
+}
+
+/// Restore the [`TrapContext`] and the blocked signals from the [`SignalFrame`] at the user stack
+/// pointer, which is where the handler started. Return `a0` of the restored context, as the
+/// trap handler stores the result of the syscall there. A bad frame terminates the process.
+pub fn restore_signal_frame() -> isize {
+    let task = current_task().unwrap();
+    let process = task.process();
+    let mut process_inner = process.inner_exclusive_access();
+    let mut inner = task.inner_exclusive_access();
+    let trap_context: &mut TrapContext = inner.get_trap_context();
+    let frame_ptr = trap_context.x[2];
+    let size = core::mem::size_of::<SignalFrame>();
+    if !process_inner.memory_set.prepare_user_buffer(frame_ptr, size, AccessType::Read) {
+        drop(inner);
+        drop(process_inner);
+        drop(process);
+        drop(task);
+        exit_process_and_run_next(signal_exit_code(SIGSEGV));
+    }
+    let token = process_inner.get_user_token();
+    let mut frame = SignalFrame {
+        x: [0; 32],
+        sepc: 0,
+        mask: SignalSet::default(),
+    };
+    let dst = unsafe { any_as_u8_slice_mut(&mut frame) };
+    copy_from_srcs(&translate(token, frame_ptr as *const SignalFrame), dst).unwrap();
+
+    trap_context.x = frame.x;
+    trap_context.sepc = frame.sepc;
+    inner.signal_mask = frame.mask.difference(SignalSet::UNCATCHABLE);
+    trap_context.x[10] as isize
+}
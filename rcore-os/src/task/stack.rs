@@ -1,32 +1,41 @@
+use lazy_static::lazy_static;
+
 use crate::{
     config::{PAGE_SIZE, TRAMPOLINE},
     loader::KERNEL_STACK_SIZE,
     mem::{memory_set::MapPermission, KERNEL_SPACE, address::{VirtPageNum, VirtAddr}}, debug,
+    upsync::UPSyncCell,
 };
 
-use super::pid::PidHandle;
+use super::pid::RecycleAllocator;
+
+lazy_static! {
+    /// Each thread has a kernel stack, so they are numbered apart from pids
+    static ref KERNEL_STACK_ALLOCATOR: UPSyncCell<RecycleAllocator> =
+        unsafe { UPSyncCell::new(RecycleAllocator::new()) };
+}
 
 pub struct KernelStack {
-    pid: usize,
+    id: usize,
 }
 
-pub fn kernel_stack_position(app_id: usize) -> (usize, usize) {
-    let top = TRAMPOLINE - app_id * (KERNEL_STACK_SIZE + PAGE_SIZE);
+pub fn kernel_stack_position(id: usize) -> (usize, usize) {
+    let top = TRAMPOLINE - id * (KERNEL_STACK_SIZE + PAGE_SIZE);
     let bot = top - KERNEL_STACK_SIZE;
     (bot, top)
 }
 
 impl KernelStack {
-    pub fn new(pid_handle: &PidHandle) -> Self {
-        let pid: usize = pid_handle.0;
-        let (kernel_stack_bottom, kernel_stack_top) = kernel_stack_position(pid);
+    pub fn new() -> Self {
+        let id = KERNEL_STACK_ALLOCATOR.exclusive_access().alloc();
+        let (kernel_stack_bottom, kernel_stack_top) = kernel_stack_position(id);
         debug!("Mapping kernel stack: {:x}..{:x}", kernel_stack_bottom, kernel_stack_top);
         KERNEL_SPACE.exclusive_access().insert_framed_area(
             kernel_stack_bottom.into(),
             kernel_stack_top.into(),
             MapPermission::R | MapPermission::W,
         );
-        KernelStack { pid }
+        KernelStack { id }
     }
 
     pub fn push_on_top<T: Sized>(&self, value: T) -> *mut T {
@@ -37,16 +46,17 @@ impl KernelStack {
     }
 
     pub fn get_top(&self) -> usize {
-        let (_, kernel_stack_top) = kernel_stack_position(self.pid);
+        let (_, kernel_stack_top) = kernel_stack_position(self.id);
         kernel_stack_top
     }
 }
 
 impl Drop for KernelStack {
     fn drop(&mut self) {
-        let (bot, top) = kernel_stack_position(self.pid);
+        let (bot, top) = kernel_stack_position(self.id);
         let start_vpn: VirtPageNum = VirtAddr::from(bot).floor();
         debug!("Kernel stack starting from {:x}..{:x} released", bot, top);
         KERNEL_SPACE.exclusive_access().remove_area_with_start_vpn(start_vpn);
+        KERNEL_STACK_ALLOCATOR.exclusive_access().dealloc(self.id);
     }
 }
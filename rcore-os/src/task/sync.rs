@@ -0,0 +1,228 @@
+//! Mutexes, semaphores and condition variables of user threads. A process keeps them in lists, and
+//! their ids are the indices in the lists.
+
+use alloc::{sync::Arc, vec, vec::Vec};
+
+use super::{process::InnerProcessControlBlock, wait_queue::WaitQueue};
+use crate::upsync::UPSyncCell;
+
+/// Owner of a mutex or a unit of a semaphore held by a thread which is not in the process, e.g. in
+/// a child made by `fork` from another thread
+const FOREIGN_TID: usize = usize::MAX;
+
+/// What a thread may hold and wait for, either a mutex or a unit of a semaphore
+pub trait Resource {
+    /// Take a unit for the thread `tid`. Return `false` if none is available.
+    fn try_acquire(&self, tid: usize) -> bool;
+    fn available(&self) -> usize;
+    /// Units held by the thread `tid`
+    fn held_by(&self, tid: usize) -> usize;
+    /// Threads waiting for a unit
+    fn wait_queue(&self) -> &WaitQueue;
+}
+
+/// A [`Resource`] by its kind and id in the lists of the process
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ResourceId {
+    Mutex(usize),
+    Semaphore(usize),
+}
+
+pub struct Mutex {
+    /// The thread holding the mutex
+    owner: UPSyncCell<Option<usize>>,
+    wait_queue: WaitQueue,
+}
+
+impl Mutex {
+    pub fn new() -> Self {
+        Self {
+            owner: unsafe { UPSyncCell::new(None) },
+            wait_queue: WaitQueue::new(),
+        }
+    }
+
+    pub fn owner(&self) -> Option<usize> {
+        *self.owner.exclusive_access()
+    }
+
+    /// Release the mutex held by `tid` and wake up a waiting thread. Return `false` if `tid` does
+    /// not hold it.
+    pub fn unlock(&self, tid: usize) -> bool {
+        let mut owner = self.owner.exclusive_access();
+        if *owner != Some(tid) {
+            return false;
+        }
+        *owner = None;
+        drop(owner);
+        self.wait_queue.wake_one();
+        true
+    }
+
+    /// A copy for a child made by `fork` from the thread `tid`, which is its main thread
+    pub fn fork(&self, tid: usize) -> Self {
+        let owner = self.owner().map(|owner| if owner == tid { 0 } else { FOREIGN_TID });
+        Self {
+            owner: unsafe { UPSyncCell::new(owner) },
+            wait_queue: WaitQueue::new(),
+        }
+    }
+}
+
+impl Resource for Mutex {
+    fn try_acquire(&self, tid: usize) -> bool {
+        let mut owner = self.owner.exclusive_access();
+        if owner.is_some() {
+            return false;
+        }
+        *owner = Some(tid);
+        true
+    }
+
+    fn available(&self) -> usize {
+        self.owner().is_none() as usize
+    }
+
+    fn held_by(&self, tid: usize) -> usize {
+        (self.owner() == Some(tid)) as usize
+    }
+
+    fn wait_queue(&self) -> &WaitQueue {
+        &self.wait_queue
+    }
+}
+
+pub struct Semaphore {
+    inner: UPSyncCell<InnerSemaphore>,
+    wait_queue: WaitQueue,
+}
+
+struct InnerSemaphore {
+    count: usize,
+    /// The thread of each unit taken by `down`, for deadlock detection. `up` from a thread which
+    /// has taken none adds a unit instead of returning one.
+    holders: Vec<usize>,
+}
+
+impl Semaphore {
+    pub fn new(count: usize) -> Self {
+        Self {
+            inner: unsafe {
+                UPSyncCell::new(InnerSemaphore {
+                    count,
+                    holders: vec![],
+                })
+            },
+            wait_queue: WaitQueue::new(),
+        }
+    }
+
+    /// Return a unit from `tid` and wake up a waiting thread
+    pub fn up(&self, tid: usize) {
+        let mut inner = self.inner.exclusive_access();
+        inner.count += 1;
+        if let Some(idx) = inner.holders.iter().position(|&holder| holder == tid) {
+            inner.holders.swap_remove(idx);
+        }
+        drop(inner);
+        self.wait_queue.wake_one();
+    }
+
+    /// A copy for a child made by `fork` from the thread `tid`, which is its main thread
+    pub fn fork(&self, tid: usize) -> Self {
+        let inner = self.inner.exclusive_access();
+        let holders = inner
+            .holders
+            .iter()
+            .map(|&holder| if holder == tid { 0 } else { FOREIGN_TID })
+            .collect();
+        let count = inner.count;
+        drop(inner);
+        Self {
+            inner: unsafe { UPSyncCell::new(InnerSemaphore { count, holders }) },
+            wait_queue: WaitQueue::new(),
+        }
+    }
+}
+
+impl Resource for Semaphore {
+    fn try_acquire(&self, tid: usize) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        if inner.count == 0 {
+            return false;
+        }
+        inner.count -= 1;
+        inner.holders.push(tid);
+        true
+    }
+
+    fn available(&self) -> usize {
+        self.inner.exclusive_access().count
+    }
+
+    fn held_by(&self, tid: usize) -> usize {
+        let inner = self.inner.exclusive_access();
+        inner.holders.iter().filter(|&&holder| holder == tid).count()
+    }
+
+    fn wait_queue(&self) -> &WaitQueue {
+        &self.wait_queue
+    }
+}
+
+/// Threads waiting for a condition, which another thread signals while holding a mutex
+pub struct Condvar {
+    pub wait_queue: WaitQueue,
+}
+
+impl Condvar {
+    pub fn new() -> Self {
+        Self {
+            wait_queue: WaitQueue::new(),
+        }
+    }
+}
+
+/// Whether the threads of a process could end up waiting for each other forever if the thread `tid`
+/// waited for `request`. Like the safety check of the banker's algorithm, the threads are assumed
+/// to release what they hold once they get what they wait for, and threads waiting for nothing
+/// here are assumed to go on. If not all of them can finish this way, some of them would deadlock.
+pub fn would_deadlock(inner: &InnerProcessControlBlock, tid: usize, request: ResourceId) -> bool {
+    let index = |id: ResourceId| match id {
+        ResourceId::Mutex(id) => id,
+        ResourceId::Semaphore(id) => inner.mutexes.len() + id,
+    };
+    let resources: Vec<Arc<dyn Resource>> = inner
+        .mutexes
+        .iter()
+        .map(|mutex| mutex.clone() as Arc<dyn Resource>)
+        .chain(
+            inner
+                .semaphores
+                .iter()
+                .map(|semaphore| semaphore.clone() as Arc<dyn Resource>),
+        )
+        .collect();
+    let mut work: Vec<usize> = resources.iter().map(|resource| resource.available()).collect();
+    let needs: Vec<Option<ResourceId>> = inner
+        .tasks
+        .iter()
+        .enumerate()
+        .map(|(other, task)| match task {
+            _ if other == tid => Some(request),
+            Some(task) => task.inner_exclusive_access().waiting_for,
+            None => None,
+        })
+        .collect();
+
+    let mut finished = vec![false; needs.len()];
+    while let Some(next) = (0..needs.len())
+        .find(|&t| !finished[t] && needs[t].is_none_or(|need| work[index(need)] > 0))
+    {
+        finished[next] = true;
+        for (available, resource) in work.iter_mut().zip(&resources) {
+            *available += resource.held_by(next);
+        }
+    }
+    !finished.iter().all(|&finished| finished)
+}
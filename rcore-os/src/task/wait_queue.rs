@@ -0,0 +1,63 @@
+use alloc::{
+    collections::VecDeque,
+    sync::{Arc, Weak},
+};
+
+use crate::upsync::UPSyncCell;
+
+use super::{
+    block_and_run_next, processor::current_task, wakeup_task, TaskControlBlock, TaskStatus,
+};
+
+/// Tasks blocked until an event, e.g. the exit of a child. They are off the ready queue of
+/// [`super::manager::TASK_MANAGER`] until the event wakes them up. A task may leave the queue
+/// without the event, e.g. when a signal kills it, so the queue does not keep it alive.
+pub struct WaitQueue {
+    tasks: UPSyncCell<VecDeque<Weak<TaskControlBlock>>>,
+}
+
+impl WaitQueue {
+    pub fn new() -> Self {
+        Self {
+            tasks: unsafe { UPSyncCell::new(VecDeque::new()) },
+        }
+    }
+
+    /// Block the current task until the queue is woken up. Another task may have changed the
+    /// state again before this one runs, and signals wake it up as well, so the waited condition
+    /// must be checked again.
+    pub fn wait(&self) {
+        let task = Arc::downgrade(&current_task().unwrap());
+        self.tasks.exclusive_access().push_back(task.clone());
+        block_and_run_next();
+        // Still queued if something else woke it up, so that `wake_one` does not pick it later
+        self.tasks
+            .exclusive_access()
+            .retain(|queued| !Weak::ptr_eq(queued, &task));
+    }
+
+    /// Make the first task of the queue which is still blocked ready to run again. Return `false`
+    /// if there is none.
+    pub fn wake_one(&self) -> bool {
+        loop {
+            let Some(task) = self.tasks.exclusive_access().pop_front() else {
+                return false;
+            };
+            let Some(task) = task.upgrade() else {
+                continue;
+            };
+            if task.inner_exclusive_access().get_status() == TaskStatus::Blocked {
+                wakeup_task(task);
+                return true;
+            }
+        }
+    }
+
+    /// Make all tasks of the queue ready to run again
+    pub fn wake_all(&self) {
+        let tasks = core::mem::take(&mut *self.tasks.exclusive_access());
+        for task in tasks.iter().filter_map(Weak::upgrade) {
+            wakeup_task(task);
+        }
+    }
+}
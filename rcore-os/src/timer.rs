@@ -1,18 +1,19 @@
 use riscv::register::time;
-use crate::{sbi::set_timer, config::CLOCK_FREQ};
+use crate::{sbi::set_timer, board::clock_freq};
 
 pub fn get_time() -> usize {
     time::read()
 }
 
-const TICKS_PER_SEC: usize = 100;
+pub const TICKS_PER_SEC: usize = 100;
 
 pub fn set_next_trigger() {
-    set_timer(get_time() + CLOCK_FREQ / TICKS_PER_SEC);
+    set_timer(get_time() + clock_freq() / TICKS_PER_SEC);
 }
 
 pub const MICRO_PER_SEC: usize = 1_000_000;
 
+/// Computed in `u128`, as the clock may be slower than 1 MHz and the product overflows `usize`
 pub fn get_time_us() -> usize {
-    time::read() / (CLOCK_FREQ / MICRO_PER_SEC)
+    (time::read() as u128 * MICRO_PER_SEC as u128 / clock_freq() as u128) as usize
 }
@@ -6,6 +6,7 @@ use bitfield::BitMut;
 use riscv::register::sstatus::{self, Sstatus, SPP};
 
 #[repr(C)]
+#[derive(Clone)]
 pub struct TrapContext {
     pub x: [usize; 32],
     pub sstatus: Sstatus,
@@ -21,6 +22,15 @@ impl TrapContext {
         self.x[2] = sp;
     }
 
+    /// Pass `argc`, `argv` and `envp` of the initial user stack at `sp` in `a0`, `a1` and `a2`
+    /// as well, so the entry of a program does not have to find them on its stack
+    pub fn set_args(&mut self, sp: usize, argc: usize) {
+        let word = core::mem::size_of::<usize>();
+        self.x[10] = argc;
+        self.x[11] = sp + word;
+        self.x[12] = sp + word * (argc + 2);
+    }
+
     pub fn app_init_context(
         entry: usize,
         sp: usize,
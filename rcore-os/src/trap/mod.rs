@@ -8,10 +8,16 @@ use riscv::register::{
 };
 
 use crate::{
-    config::{TRAMPOLINE, TRAP_CONTEXT},
+    config::{TRAMPOLINE, TRAP_CONTEXT, USER_SPACE_END},
     error,
+    mem::memory_set::AccessType,
     syscall::syscall,
-    task::{processor::{current_trap_context, current_user_token}, exit_and_run_next, suspend_and_run_next},
+    fs::poll_console,
+    task::{
+        processor::{current_process, current_task, current_trap_context, current_user_token},
+        signal::{force_signal, handle_signals, SIGILL, SIGSEGV},
+        suspend_and_run_next, trap_context_va,
+    },
     timer::set_next_trigger, debug,
 };
 
@@ -41,6 +47,7 @@ pub fn trap_handler() -> ! {
 
     // Ignore traps from kernel
     set_kernel_trap_entry();
+    current_task().unwrap().charge_time(true);
 
     let cx = current_trap_context();
     let scause = scause::read();
@@ -49,22 +56,37 @@ pub fn trap_handler() -> ! {
     match scause.cause() {
         Trap::Exception(Exception::UserEnvCall) => {
             cx.sepc += 4;
-            let result = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12]]) as usize;
+            let result = syscall(
+                cx.x[17],
+                [cx.x[10], cx.x[11], cx.x[12], cx.x[13], cx.x[14], cx.x[15]],
+            ) as usize;
 
             // Syscall may change the memory mapping (e.g exec)
             let cx = current_trap_context();
             cx.x[10] = result as usize;
         }
-        Trap::Exception(Exception::StoreFault | Exception::StorePageFault) => {
-            error!("PageFault in appication, killed.");
-            exit_and_run_next(-2);
+        Trap::Exception(Exception::LoadPageFault) => {
+            handle_page_fault(stval, AccessType::Read);
+        }
+        Trap::Exception(Exception::StorePageFault) => {
+            handle_page_fault(stval, AccessType::Write);
+        }
+        Trap::Exception(Exception::InstructionPageFault) => {
+            handle_page_fault(stval, AccessType::Execute);
+        }
+        Trap::Exception(
+            Exception::LoadFault | Exception::StoreFault | Exception::InstructionFault,
+        ) => {
+            error!("Access fault in application at {:#x}.", stval);
+            force_signal(SIGSEGV);
         }
         Trap::Exception(Exception::IllegalInstruction) => {
-            error!("Illegal instruction in application, killed.");
-            exit_and_run_next(-3);
+            error!("Illegal instruction in application.");
+            force_signal(SIGILL);
         }
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
             set_next_trigger();
+            poll_console();
             suspend_and_run_next();
         }
         _ => {
@@ -79,6 +101,18 @@ pub fn trap_handler() -> ! {
     trap_return();
 }
 
+fn handle_page_fault(va: usize, access: AccessType) {
+    let handled = va < USER_SPACE_END
+        && current_process()
+            .inner_exclusive_access()
+            .memory_set
+            .handle_page_fault(va.into(), access);
+    if !handled {
+        error!("Invalid {:?} access in application at {:#x}.", access, va);
+        force_signal(SIGSEGV);
+    }
+}
+
 pub fn enable_timer_interrupt() {
     unsafe { sie::set_stimer() };
 }
@@ -100,8 +134,12 @@ pub fn trap_from_kernel() -> ! {
 }
 
 pub fn trap_return() -> ! {
+    handle_signals();
+    let task = current_task().unwrap();
+    task.charge_time(false);
     set_user_trap_entry();
-    let trap_context_ptr = TRAP_CONTEXT;
+    let trap_context_ptr = trap_context_va(task.tid);
+    drop(task);
     let user_satp = current_user_token();
 
     extern "C" {
@@ -19,3 +19,26 @@ pub fn copy_to_dsts(mut src: &[u8], dsts: &mut [&mut [u8]]) -> Result<(), ()> {
 
     Ok(())
 }
+
+/// # Safety
+///
+/// Any bytes must be a valid `T`, e.g. a `repr(C)` struct of integers
+pub unsafe fn any_as_u8_slice_mut<T: Sized>(p: &mut T) -> &mut [u8] {
+    core::slice::from_raw_parts_mut((p as *mut T) as *mut u8, core::mem::size_of::<T>())
+}
+
+/// The reverse of [`copy_to_dsts`], e.g. for reading a struct from a user buffer
+pub fn copy_from_srcs(srcs: &[&mut [u8]], mut dst: &mut [u8]) -> Result<(), ()> {
+    let len: usize = srcs.iter().map(|b| b.len()).sum();
+    if dst.len() != len {
+        return Err(());
+    }
+
+    for src in srcs {
+        let (head, rest) = dst.split_at_mut(src.len());
+        head.copy_from_slice(src);
+        dst = rest;
+    }
+
+    Ok(())
+}
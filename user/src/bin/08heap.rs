@@ -0,0 +1,38 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::sbrk;
+
+#[macro_use]
+extern crate user_lib;
+
+const LEN: usize = 256 * 1024;
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("Test heap: allocating {} bytes, more than the initial heap", LEN);
+    let mut v: Vec<u8> = Vec::with_capacity(LEN);
+    for i in 0..LEN {
+        v.push((i % 251) as u8);
+    }
+    for (i, x) in v.iter().enumerate() {
+        assert_eq!(*x, (i % 251) as u8);
+    }
+    drop(v);
+
+    let brk = sbrk(0);
+    println!("Program break: {:#x}", brk);
+    assert_eq!(sbrk(4096), brk);
+    let page = brk as usize as *mut u8;
+    unsafe {
+        page.write_volatile(42);
+        assert_eq!(page.read_volatile(), 42);
+    }
+    assert_eq!(sbrk(-4096), brk + 4096);
+    assert_eq!(sbrk(0), brk);
+    println!("Test heap OK!");
+    0
+}
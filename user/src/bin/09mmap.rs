@@ -0,0 +1,66 @@
+#![no_std]
+#![no_main]
+
+use user_lib::{
+    exit,
+    mman::*,
+    process::{fork, waitpid},
+};
+
+#[macro_use]
+extern crate user_lib;
+
+/// `li a0, 42` and `ret`
+const CODE: [u32; 2] = [0x02a00513, 0x00008067];
+
+#[no_mangle]
+fn main() -> i32 {
+    let pages = map_anonymous(PAGE_SIZE * 4, PROT_READ | PROT_WRITE).expect("mmap failed");
+    let addr = pages.as_ptr() as usize;
+    println!("Mapped 4 pages at {:#x}", addr);
+    assert!(pages.iter().all(|b| *b == 0));
+    pages.fill(7);
+
+    // Unmap the second page, which splits the mapping
+    assert_eq!(munmap(addr + PAGE_SIZE, PAGE_SIZE), 0);
+    assert_eq!(pages[0], 7);
+    assert_eq!(pages[PAGE_SIZE * 2], 7);
+
+    // The hole can be mapped again, but not without replacing the pages around it
+    let flags = MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED_NOREPLACE;
+    assert!(mmap(addr, PAGE_SIZE * 2, PROT_READ, flags) < 0);
+    assert_eq!(mmap(addr + PAGE_SIZE, PAGE_SIZE, PROT_READ, flags), (addr + PAGE_SIZE) as isize);
+    assert_eq!(pages[PAGE_SIZE], 0);
+
+    // Protecting a range which is not fully mapped fails
+    assert!(mprotect(addr, PAGE_SIZE * 8, PROT_READ) < 0);
+
+    // Write some code into the last page and run it
+    let code_page = &mut pages[PAGE_SIZE * 3..];
+    for (i, inst) in CODE.iter().enumerate() {
+        code_page[i * 4..i * 4 + 4].copy_from_slice(&inst.to_le_bytes());
+    }
+    assert_eq!(mprotect(addr + PAGE_SIZE * 3, PAGE_SIZE, PROT_READ | PROT_EXEC), 0);
+    let f: fn() -> usize = unsafe { core::mem::transmute(addr + PAGE_SIZE * 3) };
+    assert_eq!(f(), 42);
+
+    // A page made inaccessible after it has been touched survives fork, and each side gets it
+    // back with its own copy
+    assert_eq!(mprotect(addr, PAGE_SIZE, PROT_NONE), 0);
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(mprotect(addr, PAGE_SIZE, PROT_READ | PROT_WRITE), 0);
+        assert_eq!(pages[0], 7);
+        pages[0] = 9;
+        exit(0);
+    }
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(mprotect(addr, PAGE_SIZE, PROT_READ | PROT_WRITE), 0);
+    assert_eq!(pages[0], 7);
+
+    assert_eq!(munmap(addr, PAGE_SIZE * 4), 0);
+    println!("Test mmap OK!");
+    0
+}
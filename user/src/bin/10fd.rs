@@ -0,0 +1,30 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, dup, dup2, write};
+
+const EBADF: isize = -9;
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(write(42, b"bad fd\n"), EBADF);
+    assert_eq!(write(0, b"stdin is not writable\n"), EBADF);
+    assert_eq!(close(42), EBADF);
+
+    let fd = dup(1);
+    assert!(fd > 2);
+    assert_eq!(write(fd as usize, b"Written through a duplicated stdout\n"), 36);
+    assert_eq!(close(fd as usize), 0);
+    assert_eq!(close(fd as usize), EBADF);
+
+    assert_eq!(dup2(1, 10), 10);
+    assert_eq!(write(10, b"Written through fd 10\n"), 22);
+    assert_eq!(close(10), 0);
+    assert_eq!(dup2(10, 11), EBADF);
+
+    println!("fd test passed!");
+    0
+}
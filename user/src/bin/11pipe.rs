@@ -0,0 +1,56 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fork, pipe, read, wait, write};
+
+const EPIPE: isize = -32;
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut fds = [0i32; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let (read_end, write_end) = (fds[0] as usize, fds[1] as usize);
+
+    if fork() == 0 {
+        close(read_end);
+        // Larger than the pipe buffer, so the writer has to wait for the reader
+        for i in 0..1000 {
+            let line = [b'a' + (i % 26) as u8; 10];
+            assert_eq!(write(write_end, &line), 10);
+        }
+        close(write_end);
+        return 0;
+    }
+
+    close(write_end);
+    let mut buf = [0u8; 64];
+    let mut total = 0;
+    loop {
+        let len = read(read_end, &mut buf);
+        assert!(len >= 0);
+        if len == 0 {
+            break;
+        }
+        for &byte in buf[..len as usize].iter() {
+            assert_eq!(byte, b'a' + (total / 10 % 26) as u8);
+            total += 1;
+        }
+    }
+    assert_eq!(total, 10000);
+    close(read_end);
+
+    let mut exit_code = 0;
+    wait(&mut exit_code);
+    assert_eq!(exit_code, 0);
+
+    assert_eq!(pipe(&mut fds), 0);
+    close(fds[0] as usize);
+    assert_eq!(write(fds[1] as usize, b"nobody reads this"), EPIPE);
+    close(fds[1] as usize);
+
+    println!("pipe test passed!");
+    0
+}
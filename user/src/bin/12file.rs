@@ -0,0 +1,49 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close,
+    fcntl::{open, O_CREAT, O_RDONLY, O_TRUNC, O_WRONLY},
+    read, write,
+};
+
+const ENOENT: isize = -2;
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(open("no_such_file\0", O_RDONLY), ENOENT);
+
+    let content = b"Hello, easy-fs!\n";
+    let fd = open("filetest_simple\0", O_CREAT | O_TRUNC | O_WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    for _ in 0..100 {
+        assert_eq!(write(fd, content), content.len() as isize);
+    }
+    close(fd);
+
+    let fd = open("filetest_simple\0", O_RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut buffer = [0u8; 100];
+    let mut total = 0;
+    loop {
+        let len = read(fd, &mut buffer);
+        assert!(len >= 0);
+        if len == 0 {
+            break;
+        }
+        for &byte in buffer[..len as usize].iter() {
+            assert_eq!(byte, content[total % content.len()]);
+            total += 1;
+        }
+    }
+    close(fd);
+    assert_eq!(total, 100 * content.len());
+
+    println!("file test passed!");
+    0
+}
@@ -0,0 +1,57 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close,
+    fcntl::{chdir, getcwd, mount, open, umount, O_RDONLY},
+};
+
+const ENOENT: isize = -2;
+const EBUSY: isize = -16;
+const ENOTDIR: isize = -20;
+
+fn assert_cwd(expected: &str) {
+    let mut buf = [0u8; 64];
+    let len = getcwd(&mut buf);
+    assert_eq!(len, expected.len() as isize + 1);
+    assert_eq!(&buf[..expected.len()], expected.as_bytes());
+}
+
+/// Whether `path` can be opened for reading
+fn exists(path: &str) -> bool {
+    let fd = open(path, O_RDONLY);
+    if fd >= 0 {
+        close(fd as usize);
+    }
+    fd >= 0
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_cwd("/");
+    assert_eq!(chdir("/bin\0"), 0);
+    assert_cwd("/bin");
+    assert!(exists("initproc\0"));
+    assert!(exists("./../bin/./initproc\0"));
+    assert_eq!(chdir("initproc\0"), ENOTDIR);
+    assert_eq!(chdir("no_such_dir\0"), ENOENT);
+    assert_eq!(chdir("../..\0"), 0);
+    assert_cwd("/");
+
+    // `/mnt/README.txt` is packed into the disk, and hidden by the mounted programs
+    assert!(exists("/mnt/README.txt\0"));
+    assert_eq!(mount("\0", "/mnt/README.txt\0", "appfs\0"), ENOTDIR);
+    assert_eq!(mount("\0", "/mnt\0", "appfs\0"), 0);
+    assert_eq!(mount("\0", "/mnt\0", "appfs\0"), EBUSY);
+    assert!(!exists("/mnt/README.txt\0"));
+    assert!(exists("/mnt/initproc\0"));
+    assert_eq!(umount("/mnt\0"), 0);
+    assert!(exists("/mnt/README.txt\0"));
+    assert_eq!(umount("/\0"), EBUSY);
+
+    println!("vfs test passed!");
+    0
+}
@@ -0,0 +1,71 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close,
+    fcntl::{ftruncate, open, rename, O_CREAT, O_RDONLY, O_RDWR, O_TRUNC},
+    read, write,
+};
+
+const ENOENT: isize = -2;
+const EXDEV: isize = -18;
+const EINVAL: isize = -22;
+
+/// Read the whole file at `path` into `buf` and return its size
+fn read_file(path: &str, buf: &mut [u8]) -> usize {
+    let fd = open(path, O_RDONLY);
+    assert!(fd >= 0);
+    let mut total = 0;
+    loop {
+        let len = read(fd as usize, &mut buf[total..]);
+        assert!(len >= 0);
+        if len == 0 {
+            break;
+        }
+        total += len as usize;
+    }
+    close(fd as usize);
+    total
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    // Larger than a page, so that the file takes several frames
+    let mut content = [0u8; 5000];
+    for (i, byte) in content.iter_mut().enumerate() {
+        *byte = (i % 251) as u8;
+    }
+    let fd = open("/tmp/a\0", O_CREAT | O_TRUNC | O_RDWR);
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, &content), content.len() as isize);
+
+    // Shrinking drops the tail, and growing again fills it with zeros
+    assert_eq!(ftruncate(fd, 100), 0);
+    assert_eq!(ftruncate(fd, 6000), 0);
+    close(fd);
+    let mut buf = [0xffu8; 8192];
+    assert_eq!(read_file("/tmp/a\0", &mut buf), 6000);
+    assert_eq!(&buf[..100], &content[..100]);
+    assert!(buf[100..6000].iter().all(|&byte| byte == 0));
+
+    let fd = open("/tmp/a\0", O_RDONLY);
+    assert_eq!(ftruncate(fd as usize, 0), EINVAL);
+    close(fd as usize);
+
+    // Renaming replaces an existing file
+    let fd = open("/tmp/b\0", O_CREAT | O_RDWR);
+    assert_eq!(write(fd as usize, b"old"), 3);
+    close(fd as usize);
+    assert_eq!(rename("/tmp/a\0", "/tmp/b\0"), 0);
+    assert_eq!(open("/tmp/a\0", O_RDONLY), ENOENT);
+    assert_eq!(read_file("/tmp/b\0", &mut buf), 6000);
+    assert_eq!(rename("/tmp/no_such_file\0", "/tmp/c\0"), ENOENT);
+    assert_eq!(rename("/tmp/b\0", "/b\0"), EXDEV);
+
+    println!("tmpfs test passed!");
+    0
+}
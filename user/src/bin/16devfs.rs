@@ -0,0 +1,50 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close,
+    fcntl::{open, read_dir, DT_CHR, O_RDONLY, O_RDWR, O_WRONLY},
+    read, write,
+};
+
+#[no_mangle]
+fn main() -> i32 {
+    let entries = read_dir("/dev\0").unwrap();
+    for name in ["console", "null", "random", "zero"] {
+        let entry = entries.iter().find(|entry| entry.name == name).unwrap();
+        assert_eq!(entry.type_, DT_CHR);
+    }
+
+    let null = open("/dev/null\0", O_RDWR);
+    assert!(null >= 0);
+    let mut buf = [0xffu8; 64];
+    assert_eq!(write(null as usize, b"discarded"), 9);
+    assert_eq!(read(null as usize, &mut buf), 0);
+    close(null as usize);
+
+    let zero = open("/dev/zero\0", O_RDONLY);
+    assert!(zero >= 0);
+    assert_eq!(read(zero as usize, &mut buf), buf.len() as isize);
+    assert!(buf.iter().all(|&byte| byte == 0));
+    close(zero as usize);
+
+    let random = open("/dev/random\0", O_RDONLY);
+    assert!(random >= 0);
+    let mut other = [0u8; 64];
+    assert_eq!(read(random as usize, &mut buf), buf.len() as isize);
+    assert_eq!(read(random as usize, &mut other), other.len() as isize);
+    assert_ne!(buf, other);
+    close(random as usize);
+
+    let console = open("/dev/console\0", O_WRONLY);
+    assert!(console >= 0);
+    let message = b"written to /dev/console\n";
+    assert_eq!(write(console as usize, message), message.len() as isize);
+    close(console as usize);
+
+    println!("devfs test passed!");
+    0
+}
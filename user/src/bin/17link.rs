@@ -0,0 +1,75 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close,
+    fcntl::{
+        fstat, link, mkdir, open, read_dir, rmdir, stat, unlink, Stat, DT_DIR, DT_REG, O_CREAT,
+        O_RDONLY, O_WRONLY, S_IFIFO, S_IFMT,
+    },
+    pipe, write,
+};
+
+const ENOENT: isize = -2;
+const EEXIST: isize = -17;
+const EISDIR: isize = -21;
+const ENOTEMPTY: isize = -39;
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(mkdir("/tmp/d\0"), 0);
+    assert_eq!(mkdir("/tmp/d\0"), EEXIST);
+    assert_eq!(mkdir("/tmp/d/sub\0"), 0);
+    assert_eq!(stat("/tmp/d\0").unwrap().nlink, 3);
+
+    let fd = open("/tmp/d/a\0", O_CREAT | O_WRONLY);
+    assert!(fd >= 0);
+    assert_eq!(write(fd as usize, b"hello"), 5);
+    let mut info = Stat::default();
+    assert_eq!(fstat(fd as usize, &mut info), 0);
+    assert_eq!(info.size, 5);
+    assert_eq!(info.nlink, 1);
+
+    // Both names are the same inode, which keeps its content until the last name is removed
+    assert_eq!(link("/tmp/d/a\0", "/tmp/d/b\0"), 0);
+    assert_eq!(link("/tmp/d/a\0", "/tmp/d/b\0"), EEXIST);
+    let linked = stat("/tmp/d/b\0").unwrap();
+    assert_eq!(linked.ino, info.ino);
+    assert_eq!(linked.dev, info.dev);
+    assert_eq!(linked.nlink, 2);
+    assert_eq!(unlink("/tmp/d/a\0"), 0);
+    assert_eq!(open("/tmp/d/a\0", O_RDONLY), ENOENT);
+    assert_eq!(stat("/tmp/d/b\0").unwrap().size, 5);
+    assert_eq!(fstat(fd as usize, &mut info), 0);
+    assert_eq!(info.nlink, 1);
+    close(fd as usize);
+
+    let entries = read_dir("/tmp/d\0").unwrap();
+    assert_eq!(entries.len(), 2);
+    for entry in entries {
+        match entry.name.as_str() {
+            "b" => assert!(entry.type_ == DT_REG && entry.ino == info.ino),
+            "sub" => assert_eq!(entry.type_, DT_DIR),
+            name => panic!("unexpected entry {}", name),
+        }
+    }
+
+    assert_eq!(unlink("/tmp/d/sub\0"), EISDIR);
+    assert_eq!(rmdir("/tmp/d\0"), ENOTEMPTY);
+    assert_eq!(rmdir("/tmp/d/sub\0"), 0);
+    assert_eq!(unlink("/tmp/d/b\0"), 0);
+    assert_eq!(rmdir("/tmp/d\0"), 0);
+
+    let mut fds = [0i32; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    assert_eq!(fstat(fds[0] as usize, &mut info), 0);
+    assert_eq!(info.mode & S_IFMT, S_IFIFO);
+    close(fds[0] as usize);
+    close(fds[1] as usize);
+
+    println!("link test passed!");
+    0
+}
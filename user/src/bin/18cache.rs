@@ -0,0 +1,52 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+#[macro_use]
+extern crate user_lib;
+
+use alloc::{string::String, vec::Vec};
+use user_lib::{
+    close,
+    fcntl::{mkdir, mount, open, read_file, rmdir, sync, umount, O_CREAT, O_WRONLY},
+    write,
+};
+
+/// The value of `field` in `/proc/blockcache`
+fn counter(field: &str) -> usize {
+    let content = String::from_utf8(read_file("/proc/blockcache\0").unwrap()).unwrap();
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix(field)?.strip_prefix(":\t"))
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(mkdir("/tmp/ram\0"), 0);
+    assert_eq!(mount("ram\0", "/tmp/ram\0", "easyfs\0"), 0);
+
+    // More blocks than the cache holds, so some are written back when they are evicted
+    let content: Vec<u8> = (0..100_000).map(|i| (i % 251) as u8).collect();
+    let fd = open("/tmp/ram/data\0", O_CREAT | O_WRONLY);
+    assert!(fd >= 0);
+    assert_eq!(write(fd as usize, &content), content.len() as isize);
+    close(fd as usize);
+    assert!(counter("Writebacks") > 0);
+
+    let hits = counter("Hits");
+    assert_eq!(read_file("/tmp/ram/data\0").unwrap(), content);
+    assert!(counter("Hits") > hits);
+
+    assert_eq!(sync(), 0);
+    assert_eq!(counter("Dirty"), 0);
+    assert!(counter("Cached") <= counter("Capacity"));
+
+    assert_eq!(umount("/tmp/ram\0"), 0);
+    assert_eq!(rmdir("/tmp/ram\0"), 0);
+    println!("block cache test passed!");
+    0
+}
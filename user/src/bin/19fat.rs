@@ -0,0 +1,31 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::fcntl::{mkdir, open, read_dir, read_file, stat, O_CREAT, O_WRONLY};
+
+/// Needs the disk of `just run-fat`, with the files in `user/data/`
+#[no_mangle]
+fn main() -> i32 {
+    if stat("/fat\0").is_err() {
+        println!("no FAT32 disk at /fat, run with `just run-fat`");
+        return 0;
+    }
+
+    let entries = read_dir("/fat\0").unwrap();
+    assert!(entries.iter().any(|entry| entry.name == "hello.txt"));
+    let content = read_file("/fat/hello.txt\0").unwrap();
+    assert_eq!(content, b"Hello from the disk image!\n");
+    // Names are looked up ignoring case, like Windows does
+    assert_eq!(read_file("/fat/HELLO.TXT\0").unwrap(), content);
+    assert!(stat("/fat/mnt\0").unwrap().is_dir());
+    assert_eq!(stat("/fat/hello.txt\0").unwrap().size as usize, content.len());
+
+    assert!(open("/fat/new.txt\0", O_CREAT | O_WRONLY) < 0);
+    assert!(mkdir("/fat/dir\0") < 0);
+
+    println!("fat32 test passed!");
+    0
+}
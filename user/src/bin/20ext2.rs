@@ -0,0 +1,88 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+#[macro_use]
+extern crate user_lib;
+
+use alloc::format;
+use user_lib::{
+    close,
+    fcntl::{
+        ftruncate, link, lstat, mkdir, open, read_file, readlink, rename, rmdir, stat, symlink,
+        sync, unlink, O_CREAT, O_WRONLY,
+    },
+    write,
+};
+
+const ENOENT: isize = -2;
+const ENOTEMPTY: isize = -39;
+const ELOOP: isize = -40;
+
+/// Needs the disk of `just run-ext2`. Everything made here is removed again, so `just
+/// verify-ext2-img` should find no errors afterwards.
+#[no_mangle]
+fn main() -> i32 {
+    if stat("/ext2\0").is_err() {
+        println!("no ext2 disk at /ext2, run with `just run-ext2`");
+        return 0;
+    }
+
+    assert_eq!(read_file("/ext2/hello.txt\0").unwrap(), b"Hello from the disk image!\n");
+
+    assert_eq!(mkdir("/ext2/t\0"), 0);
+    let fd = open("/ext2/t/a\0", O_CREAT | O_WRONLY);
+    assert!(fd >= 0);
+    // More than the 12 direct blocks, so the indirect block is used as well
+    let block = [b'x'; 1024];
+    for _ in 0..16 {
+        assert_eq!(write(fd as usize, &block), block.len() as isize);
+    }
+    assert_eq!(ftruncate(fd as usize, 15000), 0);
+    close(fd as usize);
+    let content = read_file("/ext2/t/a\0").unwrap();
+    assert_eq!(content.len(), 15000);
+    assert!(content.iter().all(|&byte| byte == b'x'));
+
+    assert_eq!(link("/ext2/t/a\0", "/ext2/t/b\0"), 0);
+    assert_eq!(stat("/ext2/t/a\0").unwrap().nlink, 2);
+    assert_eq!(rename("/ext2/t/b\0", "/ext2/c\0"), 0);
+    assert_eq!(stat("/ext2/t/b\0").unwrap_err(), ENOENT);
+    assert_eq!(stat("/ext2/c\0").unwrap().ino, stat("/ext2/t/a\0").unwrap().ino);
+
+    // A short target lives in the inode, a long one in a block of its own
+    assert_eq!(symlink("a\0", "/ext2/t/short\0"), 0);
+    let long_target = "../t/./../t/./../t/./../t/./../t/./../t/./../t/./../t/./../t/a\0";
+    assert_eq!(symlink(long_target, "/ext2/t/long\0"), 0);
+    assert_eq!(readlink("/ext2/t/short\0").unwrap(), "a");
+    assert_eq!(readlink("/ext2/t/long\0").unwrap(), long_target.trim_end_matches('\0'));
+    assert!(lstat("/ext2/t/short\0").unwrap().is_symlink());
+    assert_eq!(read_file("/ext2/t/short\0").unwrap(), content);
+    assert_eq!(read_file("/ext2/t/long\0").unwrap(), content);
+    // Links into other mounts are followed as well
+    assert_eq!(symlink("/tmp\0", "/ext2/t/tmp\0"), 0);
+    let fd = open("/ext2/t/tmp/ext2\0", O_CREAT | O_WRONLY);
+    assert!(fd >= 0);
+    close(fd as usize);
+    assert_eq!(unlink("/tmp/ext2\0"), 0);
+
+    assert_eq!(symlink("loop\0", "/ext2/t/loop\0"), 0);
+    assert_eq!(stat("/ext2/t/loop\0").unwrap_err(), ELOOP);
+    assert_eq!(symlink("missing\0", "/ext2/t/dangling\0"), 0);
+    assert_eq!(stat("/ext2/t/dangling\0").unwrap_err(), ENOENT);
+
+    assert_eq!(rmdir("/ext2/t\0"), ENOTEMPTY);
+    for name in ["a", "short", "long", "tmp", "loop", "dangling"] {
+        assert_eq!(unlink(&format!("/ext2/t/{}\0", name)), 0);
+    }
+    assert_eq!(read_file("/ext2/c\0").unwrap(), content);
+    assert_eq!(stat("/ext2/c\0").unwrap().nlink, 1);
+    assert_eq!(unlink("/ext2/c\0"), 0);
+    assert_eq!(rmdir("/ext2/t\0"), 0);
+    sync();
+
+    println!("ext2 test passed!");
+    0
+}
+
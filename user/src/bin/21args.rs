@@ -0,0 +1,61 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+#[macro_use]
+extern crate user_lib;
+
+use alloc::vec::Vec;
+use user_lib::{
+    close,
+    fcntl::{open, unlink, O_CREAT, O_TRUNC, O_WRONLY},
+    process::{env, exec, execve, fork, getauxval, getenv, waitpid, AT_ENTRY, AT_PAGESZ},
+    write,
+};
+
+const E2BIG: isize = -7;
+const ENOEXEC: isize = -8;
+
+const CHILD_ARGS: [&str; 4] = ["21args", "child", "two words", ""];
+
+/// Run itself again with arguments and an environment, which the child checks
+#[no_mangle]
+fn main(argc: usize, argv: &[&str]) -> i32 {
+    assert_eq!(argc, argv.len());
+    if argv.get(1) == Some(&"child") {
+        assert_eq!(argv, CHILD_ARGS);
+        assert_eq!(env().collect::<Vec<_>>(), [("FOO", "bar"), ("EMPTY", "")]);
+        assert_eq!(getenv("FOO"), Some("bar"));
+        assert_eq!(getenv("BAR"), None);
+        assert_eq!(getauxval(AT_PAGESZ), Some(4096));
+        assert_eq!(getauxval(AT_ENTRY), Some(user_lib::_start as *const () as usize));
+        return 0;
+    }
+
+    let pid = fork();
+    if pid == 0 {
+        execve("21args\0", &CHILD_ARGS, &["FOO=bar", "EMPTY="]);
+        panic!("exec failed");
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+
+    // The program keeps running if its arguments do not fit
+    let long = "x".repeat(4096);
+    assert_eq!(exec("21args\0", &[&long]), E2BIG);
+
+    // Nor if the program only starts like a 64-bit ELF file, with program headers out of the file
+    let mut bad_elf = [0xffu8; 64];
+    bad_elf[..8].copy_from_slice(b"\x7fELF\x02\x01\x01\x00");
+    let fd = open("/21args_bad_elf\0", O_CREAT | O_WRONLY | O_TRUNC);
+    assert!(fd >= 0);
+    assert_eq!(write(fd as usize, &bad_elf), bad_elf.len() as isize);
+    close(fd as usize);
+    assert_eq!(exec("/21args_bad_elf\0", &[]), ENOEXEC);
+    assert_eq!(unlink("/21args_bad_elf\0"), 0);
+
+    println!("args test passed!");
+    0
+}
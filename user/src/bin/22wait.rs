@@ -0,0 +1,52 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::hint::black_box;
+use user_lib::{
+    exit,
+    process::{fork, wait, wait4, waitpid, Rusage, WNOHANG},
+};
+
+const ECHILD: isize = -10;
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut exit_code = 0;
+    assert_eq!(wait(&mut exit_code), ECHILD);
+
+    let pid = fork();
+    if pid == 0 {
+        // Long enough to be still running when the parent checks, and to take measurable time
+        for i in 0..20_000_000usize {
+            black_box(i);
+        }
+        exit(7);
+    }
+    let mut usage = Rusage::default();
+    assert_eq!(wait4(pid, &mut exit_code, WNOHANG, &mut usage), 0);
+    assert_eq!(wait4(pid, &mut exit_code, 0, &mut usage), pid);
+    assert_eq!(exit_code, 7);
+    assert!(usage.utime.sec > 0 || usage.utime.usec > 0);
+    println!("child used {}.{:06}s of CPU time", usage.utime.sec, usage.utime.usec);
+    assert_eq!(waitpid(pid, &mut exit_code), ECHILD);
+
+    // Several children, reaped in the order they exit
+    for code in 1..=3 {
+        if fork() == 0 {
+            exit(code);
+        }
+    }
+    let mut codes = [false; 3];
+    for _ in 0..3 {
+        assert!(wait(&mut exit_code) > 0);
+        codes[exit_code as usize - 1] = true;
+    }
+    assert_eq!(codes, [true; 3]);
+    assert_eq!(wait(&mut exit_code), ECHILD);
+
+    println!("wait test passed!");
+    0
+}
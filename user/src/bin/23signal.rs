@@ -0,0 +1,137 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::{
+    arch::asm,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use user_lib::{
+    exit,
+    process::{fork, getpid, wait4, waitpid, yield_, Rusage, WNOHANG},
+    signal::*,
+};
+
+const EINTR: isize = -4;
+const EINVAL: isize = -22;
+
+static HANDLED: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn count_usr1(sig: usize) {
+    assert_eq!(sig, SIGUSR1);
+    HANDLED.fetch_add(1, Ordering::Relaxed);
+}
+
+extern "C" fn exit_on_fault(sig: usize) {
+    exit(100 + sig as i32);
+}
+
+fn handled() -> usize {
+    HANDLED.load(Ordering::Relaxed)
+}
+
+/// Fork a child which runs `child` and then spins until a signal ends it
+fn spawn(child: impl FnOnce()) -> isize {
+    let pid = fork();
+    if pid == 0 {
+        child();
+        loop {
+            yield_();
+        }
+    }
+    pid
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let pid = getpid();
+    let action = SignalAction::new(count_usr1 as *const () as usize, 0, SignalSet::default());
+    assert_eq!(sigaction(SIGUSR1, Some(&action), None), 0);
+    assert_eq!(sigaction(SIGKILL, Some(&action), None), EINVAL);
+
+    // The handler runs before `kill` returns, and its result survives the handler
+    assert_eq!(kill(pid, SIGUSR1), 0);
+    assert_eq!(handled(), 1);
+
+    // A blocked signal waits until it is unblocked
+    let usr1 = SignalSet::of(&[SIGUSR1]);
+    assert_eq!(sigprocmask(SIG_BLOCK, Some(&usr1), None), 0);
+    kill(pid, SIGUSR1);
+    assert_eq!(handled(), 1);
+    let mut old_set = SignalSet::default();
+    sigprocmask(SIG_UNBLOCK, Some(&usr1), Some(&mut old_set));
+    assert_eq!(old_set, usr1);
+    assert_eq!(handled(), 2);
+
+    signal(SIGUSR2, SIG_IGN);
+    kill(pid, SIGUSR2);
+
+    // Default actions and uncatchable signals
+    let mut exit_code = 0;
+    let child = spawn(|| {});
+    kill(child, SIGKILL);
+    assert_eq!(waitpid(child, &mut exit_code), child);
+    assert_eq!(exit_code, -(SIGKILL as i32));
+
+    // Ignored signals stay ignored in the child
+    signal(SIGTERM, SIG_IGN);
+    let child = spawn(|| {});
+    signal(SIGTERM, SIG_DFL);
+    kill(child, SIGTERM);
+    kill(child, SIGKILL);
+    assert_eq!(waitpid(child, &mut exit_code), child);
+    assert_eq!(exit_code, -(SIGKILL as i32));
+
+    // A stopped child acts on signals only once it continues
+    let child = spawn(|| {});
+    kill(child, SIGSTOP);
+    for _ in 0..3 {
+        yield_();
+    }
+    kill(child, SIGTERM);
+    let mut usage = Rusage::default();
+    for _ in 0..3 {
+        yield_();
+        assert_eq!(wait4(child, &mut exit_code, WNOHANG, &mut usage), 0);
+    }
+    kill(child, SIGCONT);
+    assert_eq!(waitpid(child, &mut exit_code), child);
+    assert_eq!(exit_code, -(SIGTERM as i32));
+
+    // Faults raise signals, which kill the program or go to its handler
+    let child = spawn(|| unsafe {
+        core::ptr::null_mut::<u8>().write_volatile(0);
+    });
+    assert_eq!(waitpid(child, &mut exit_code), child);
+    assert_eq!(exit_code, -(SIGSEGV as i32));
+
+    let child = spawn(|| unsafe {
+        signal(SIGSEGV, exit_on_fault as *const () as usize);
+        core::ptr::null_mut::<u8>().write_volatile(0);
+    });
+    assert_eq!(waitpid(child, &mut exit_code), child);
+    assert_eq!(exit_code, 100 + SIGSEGV as i32);
+
+    let child = spawn(|| unsafe {
+        signal(SIGILL, exit_on_fault as *const () as usize);
+        asm!("sret");
+    });
+    assert_eq!(waitpid(child, &mut exit_code), child);
+    assert_eq!(exit_code, 100 + SIGILL as i32);
+
+    // A signal with a handler interrupts a blocking syscall
+    let before = handled();
+    let child = spawn(|| loop {
+        kill(pid, SIGUSR1);
+        yield_();
+    });
+    assert_eq!(waitpid(child, &mut exit_code), EINTR);
+    assert!(handled() > before);
+    kill(child, SIGKILL);
+    assert_eq!(waitpid(child, &mut exit_code), child);
+
+    println!("signal test passed!");
+    0
+}
@@ -0,0 +1,158 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use user_lib::{
+    close, exit,
+    mman::{map_anonymous, munmap, PAGE_SIZE, PROT_READ, PROT_WRITE},
+    pipe,
+    process::{exec, fork, getpid, waitpid, yield_},
+    read,
+    signal::{kill, SIGKILL},
+    thread::{gettid, spawn, waittid},
+    write,
+};
+
+const ESRCH: isize = -3;
+const EFAULT: isize = -14;
+const EBUSY: isize = -16;
+const EDEADLK: isize = -35;
+
+const THREADS: usize = 4;
+const ROUNDS: usize = 1000;
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+static RELEASED: AtomicBool = AtomicBool::new(false);
+
+/// Spin until the main thread sets [`RELEASED`]
+fn wait_for_release() {
+    while !RELEASED.load(Ordering::Acquire) {
+        yield_();
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(gettid(), 0);
+    let mut exit_code = 0;
+    assert_eq!(waittid(0, &mut exit_code), EDEADLK);
+    assert_eq!(waittid(1, &mut exit_code), ESRCH);
+
+    // Threads share the memory of the process, including the heap
+    let mut tids = Vec::new();
+    for i in 0..THREADS {
+        let tid = spawn(move || {
+            let mut values = Vec::new();
+            for round in 0..ROUNDS {
+                COUNTER.fetch_add(1, Ordering::Relaxed);
+                values.push(round);
+                if round % 100 == 0 {
+                    yield_();
+                }
+            }
+            assert_eq!(values.iter().sum::<usize>(), ROUNDS * (ROUNDS - 1) / 2);
+            gettid() as i32 * 10 + i as i32
+        });
+        assert!(tid > 0);
+        tids.push(tid);
+    }
+    for (i, &tid) in tids.iter().enumerate() {
+        assert_eq!(waittid(tid as usize, &mut exit_code), tid);
+        assert_eq!(exit_code, tid as i32 * 10 + i as i32);
+        assert_eq!(waittid(tid as usize, &mut exit_code), ESRCH);
+    }
+    assert_eq!(COUNTER.load(Ordering::Relaxed), THREADS * ROUNDS);
+
+    // Tids of reaped threads are used again
+    let tid = spawn(|| 0);
+    assert_eq!(tid, tids[0]);
+    assert_eq!(waittid(tid as usize, &mut exit_code), tid);
+
+    // A program cannot be replaced while other threads run
+    let tid = spawn(|| {
+        wait_for_release();
+        0
+    });
+    assert_eq!(exec("24thread\0", &["24thread"]), EBUSY);
+    RELEASED.store(true, Ordering::Release);
+    assert_eq!(waittid(tid as usize, &mut exit_code), tid);
+
+    // Forking in a thread copies only that thread, which is the main thread of the child
+    let tid = spawn(|| {
+        let pid = fork();
+        if pid == 0 {
+            exit(gettid() as i32 + 5);
+        }
+        let mut exit_code = 0;
+        assert_eq!(waitpid(pid, &mut exit_code), pid);
+        exit_code
+    });
+    assert_eq!(waittid(tid as usize, &mut exit_code), tid);
+    assert_eq!(exit_code, 5);
+
+    // A thread blocked reading into a buffer which another thread unmaps meanwhile gets `EFAULT`,
+    // rather than the data going to the freed frames
+    let addr = map_anonymous(PAGE_SIZE, PROT_READ | PROT_WRITE)
+        .expect("mmap failed")
+        .as_mut_ptr() as usize;
+    let mut fds = [0i32; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let (reader, writer) = (fds[0] as usize, fds[1] as usize);
+    let tid = spawn(move || {
+        let buf = unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, PAGE_SIZE) };
+        read(reader, buf) as i32
+    });
+    for _ in 0..3 {
+        yield_();
+    }
+    assert_eq!(munmap(addr, PAGE_SIZE), 0);
+    assert_eq!(write(writer, b"data"), 4);
+    assert_eq!(waittid(tid as usize, &mut exit_code), tid);
+    assert_eq!(exit_code, EFAULT as i32);
+    close(reader);
+    close(writer);
+
+    // The process ends when the main thread exits, or when it is killed, whatever its other
+    // threads are doing
+    RELEASED.store(false, Ordering::Release);
+    let pid = fork();
+    if pid == 0 {
+        spawn(|| loop {
+            yield_();
+        });
+        spawn(|| {
+            wait_for_release();
+            0
+        });
+        exit(3);
+    }
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    assert_eq!(exit_code, 3);
+
+    let pid = fork();
+    if pid == 0 {
+        for _ in 0..THREADS {
+            spawn(|| {
+                let mut exit_code = 0;
+                waittid(0, &mut exit_code);
+                0
+            });
+        }
+        wait_for_release();
+        exit(0);
+    }
+    yield_();
+    kill(pid, SIGKILL);
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    assert_eq!(exit_code, -(SIGKILL as i32));
+    assert_ne!(getpid(), pid);
+
+    println!("thread test passed!");
+    0
+}
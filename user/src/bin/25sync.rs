@@ -0,0 +1,138 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::{collections::VecDeque, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{
+    process::yield_,
+    sync::{enable_deadlock_detect, Condvar, Mutex, Semaphore},
+    thread::{spawn, waittid},
+};
+
+const EDEADLK: isize = -35;
+
+const THREADS: usize = 4;
+const ROUNDS: usize = 200;
+const BUFFER_SIZE: usize = 3;
+const ITEMS: usize = 50;
+
+/// Wait for the thread `tid` and return its exit code
+fn join(tid: isize) -> i32 {
+    assert!(tid > 0);
+    let mut exit_code = 0;
+    assert_eq!(waittid(tid as usize, &mut exit_code), tid);
+    exit_code
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    // Threads switched away in the middle of an update do not lose the updates of the others
+    let counter = Arc::new(Mutex::new(0));
+    let tids: Vec<isize> = (0..THREADS)
+        .map(|_| {
+            let counter = counter.clone();
+            spawn(move || {
+                for _ in 0..ROUNDS {
+                    let mut count = counter.lock().unwrap();
+                    let old = *count;
+                    yield_();
+                    *count = old + 1;
+                }
+                0
+            })
+        })
+        .collect();
+    tids.into_iter().for_each(|tid| assert_eq!(join(tid), 0));
+    let count = counter.lock().unwrap();
+    assert_eq!(*count, THREADS * ROUNDS);
+    assert_eq!(counter.lock().err(), Some(EDEADLK));
+    drop(count);
+
+    // A bounded buffer, where a semaphore counts the free slots and another one the items
+    let queue = Arc::new(Mutex::new(VecDeque::new()));
+    let free = Arc::new(Semaphore::new(BUFFER_SIZE));
+    let items = Arc::new(Semaphore::new(0));
+    let producer = {
+        let (queue, free, items) = (queue.clone(), free.clone(), items.clone());
+        spawn(move || {
+            for item in 0..ITEMS {
+                assert_eq!(free.down(), 0);
+                let mut queue = queue.lock().unwrap();
+                queue.push_back(item);
+                assert!(queue.len() <= BUFFER_SIZE);
+                drop(queue);
+                items.up();
+            }
+            0
+        })
+    };
+    for expected in 0..ITEMS {
+        assert_eq!(items.down(), 0);
+        assert_eq!(queue.lock().unwrap().pop_front(), Some(expected));
+        free.up();
+        if expected % 7 == 0 {
+            yield_();
+        }
+    }
+    assert_eq!(join(producer), 0);
+
+    // A thread waits on a condition variable until the flag is set
+    let state = Arc::new((Mutex::new(false), Condvar::new()));
+    let waiter = {
+        let state = state.clone();
+        spawn(move || {
+            let (ready, condvar) = &*state;
+            let mut ready = ready.lock().unwrap();
+            while !*ready {
+                ready = condvar.wait(ready).unwrap();
+            }
+            7
+        })
+    };
+    for _ in 0..3 {
+        yield_();
+    }
+    let (ready, condvar) = &*state;
+    *ready.lock().unwrap() = true;
+    condvar.signal();
+    assert_eq!(join(waiter), 7);
+
+    // Two threads locking two mutexes in opposite orders: one of them waits for the other, and
+    // the other one gets `EDEADLK` instead of waiting forever
+    assert_eq!(enable_deadlock_detect(true), 0);
+    let first = Arc::new(Mutex::new(()));
+    let second = Arc::new(Mutex::new(()));
+    let holding = Arc::new(AtomicUsize::new(0));
+    let lock_both = |a: Arc<Mutex<()>>, b: Arc<Mutex<()>>, holding: Arc<AtomicUsize>| {
+        move || {
+            let _a = a.lock().unwrap();
+            holding.fetch_add(1, Ordering::Relaxed);
+            while holding.load(Ordering::Relaxed) < 2 {
+                yield_();
+            }
+            match b.lock() {
+                Ok(_) => 0,
+                Err(errno) => errno as i32,
+            }
+        }
+    };
+    let tid_a = spawn(lock_both(first.clone(), second.clone(), holding.clone()));
+    let tid_b = spawn(lock_both(second.clone(), first.clone(), holding.clone()));
+    let mut results = [join(tid_a), join(tid_b)];
+    results.sort();
+    assert_eq!(results, [EDEADLK as i32, 0]);
+
+    // Nobody else can give back the only unit of a semaphore, which this thread holds
+    let semaphore = Semaphore::new(1);
+    let _unit = semaphore.acquire().unwrap();
+    assert_eq!(semaphore.down(), EDEADLK);
+    assert_eq!(enable_deadlock_detect(false), 0);
+
+    println!("sync test passed!");
+    0
+}
@@ -0,0 +1,56 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::vec;
+use core::{
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use user_lib::{
+    exit,
+    process::{fork, waitpid, yield_},
+};
+
+/// Not zero, so it is in `.data` rather than `.bss`
+static DATA: AtomicUsize = AtomicUsize::new(1);
+
+const PARENT: usize = 2;
+const CHILD: usize = 3;
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut stack = black_box([1usize; 512]);
+    let mut heap = vec![1usize; 512];
+
+    let pid = fork();
+    let mine = if pid == 0 { CHILD } else { PARENT };
+    // Each side writes the pages shared after fork, then lets the other side write them as well
+    stack[0] = mine;
+    heap[0] = mine;
+    DATA.store(mine, Ordering::Relaxed);
+    for _ in 0..3 {
+        yield_();
+    }
+    assert_eq!(black_box(&stack)[0], mine);
+    assert_eq!(heap[0], mine);
+    assert_eq!(DATA.load(Ordering::Relaxed), mine);
+    // The rest of the pages is still what both sides had before fork
+    assert!(stack[1..].iter().chain(&heap[1..]).all(|&value| value == 1));
+    if pid == 0 {
+        exit(0);
+    }
+
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(black_box(&stack)[0], PARENT);
+    assert_eq!(heap[0], PARENT);
+    assert_eq!(DATA.load(Ordering::Relaxed), PARENT);
+    println!("copy-on-write test passed!");
+    0
+}
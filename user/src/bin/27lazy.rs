@@ -0,0 +1,87 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    mman::*,
+    process::{fork, waitpid},
+    sbrk,
+    signal::SIGSEGV,
+};
+
+/// Far more than the memory of the board, which only works if untouched pages take no frames
+const SPARSE_SIZE: usize = 512 << 20;
+/// One page is touched in each stride
+const STRIDE: usize = 16 << 20;
+
+/// Write a different value to one page of each stride of `start..start + SPARSE_SIZE`, then read
+/// them back
+fn touch_sparse(start: usize) {
+    for (i, addr) in (start..start + SPARSE_SIZE).step_by(STRIDE).enumerate() {
+        unsafe { (addr as *mut usize).write_volatile(i + 1) };
+    }
+    for (i, addr) in (start..start + SPARSE_SIZE).step_by(STRIDE).enumerate() {
+        assert_eq!(unsafe { (addr as *const usize).read_volatile() }, i + 1);
+        // The rest of the page is still zero
+        assert_eq!(unsafe { ((addr + PAGE_SIZE / 2) as *const usize).read_volatile() }, 0);
+    }
+}
+
+/// Fork a child which runs `child`, and return its exit code
+fn exit_code_of(child: fn()) -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        child();
+        return 0;
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    exit_code
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    // A sparse heap
+    let old_brk = sbrk(SPARSE_SIZE as i32);
+    assert!(old_brk > 0);
+    let heap_start = (old_brk as usize).next_multiple_of(PAGE_SIZE);
+    touch_sparse(heap_start);
+    assert_eq!(sbrk(-(SPARSE_SIZE as i32)), old_brk + SPARSE_SIZE as isize);
+
+    // A sparse mapping
+    let pages = map_anonymous(SPARSE_SIZE, PROT_READ | PROT_WRITE).expect("mmap failed");
+    let addr = pages.as_ptr() as usize;
+    touch_sparse(addr);
+    assert_eq!(munmap(addr, SPARSE_SIZE), 0);
+
+    // Accesses outside of all areas kill the program with `SIGSEGV`, unlike a normal exit
+    let segv = -(SIGSEGV as i32);
+    assert_eq!(exit_code_of(|| {}), 0);
+    assert_eq!(
+        exit_code_of(|| unsafe {
+            core::ptr::null::<u8>().read_volatile();
+        }),
+        segv
+    );
+    // The heap is gone after shrinking it
+    assert_eq!(
+        exit_code_of(|| unsafe {
+            let brk = sbrk(0) as usize;
+            ((brk + STRIDE) as *mut u8).write_volatile(1);
+        }),
+        segv
+    );
+    // Code cannot run where nothing is mapped
+    assert_eq!(
+        exit_code_of(|| {
+            let f: fn() = unsafe { core::mem::transmute(sbrk(0) as usize + STRIDE) };
+            f();
+        }),
+        segv
+    );
+
+    println!("demand paging test passed!");
+    0
+}
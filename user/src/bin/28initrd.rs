@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit,
+    fcntl::{open, O_RDONLY},
+    process::{exec, fork, waitpid},
+};
+
+const ENOENT: isize = -2;
+
+/// Only in the archive of `just run-initrd`, see `initrd-img` in the justfile
+const PROGRAM: &str = "/bin/initrd_hello\0";
+
+#[no_mangle]
+fn main() -> i32 {
+    // Neither on the disk nor linked into the kernel
+    assert_eq!(open("/initrd_hello\0", O_RDONLY), ENOENT);
+    let fd = open(PROGRAM, O_RDONLY);
+    if fd < 0 {
+        println!("{} not found, run the kernel with `just run-initrd`", PROGRAM);
+        return 1;
+    }
+    close(fd as usize);
+
+    let pid = fork();
+    if pid == 0 {
+        exec(PROGRAM, &["initrd_hello"]);
+        exit(-1);
+    }
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    println!("initrd test passed!");
+    0
+}
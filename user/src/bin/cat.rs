@@ -0,0 +1,51 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+#[macro_use]
+extern crate user_lib;
+
+use alloc::format;
+use user_lib::{
+    fcntl::{open, O_RDONLY},
+    close, read, write,
+};
+
+/// Copy `fd` to stdout until the end of file
+fn copy(fd: usize) -> isize {
+    let mut buf = [0u8; 256];
+    loop {
+        match read(fd, &mut buf) {
+            len if len <= 0 => return len,
+            len => {
+                write(1, &buf[..len as usize]);
+            }
+        }
+    }
+}
+
+/// Print the files named by the arguments, or copy stdin if no files are named, e.g.
+/// `00hello_world | cat`
+#[no_mangle]
+fn main(_argc: usize, argv: &[&str]) -> i32 {
+    let paths = &argv[1..];
+    if paths.is_empty() {
+        return if copy(0) < 0 { -1 } else { 0 };
+    }
+    let mut exit_code = 0;
+    for path in paths {
+        let fd = open(&format!("{}\0", path), O_RDONLY);
+        if fd < 0 {
+            println!("cat: {}: error {}", path, fd);
+            exit_code = 1;
+            continue;
+        }
+        if copy(fd as usize) < 0 {
+            println!("cat: {}: read error", path);
+            exit_code = 1;
+        }
+        close(fd as usize);
+    }
+    exit_code
+}
@@ -0,0 +1,41 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+#[macro_use]
+extern crate user_lib;
+
+use alloc::string::String;
+use user_lib::fcntl::read_file;
+
+/// The value in KiB of `field` in `/proc/meminfo`
+fn field(meminfo: &str, field: &str) -> usize {
+    meminfo
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| *name == field)
+        .and_then(|(_, value)| value.trim().trim_end_matches("kB").trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Show the usage of frames and of the kernel heap from `/proc/meminfo`, in KiB
+#[no_mangle]
+fn main() -> i32 {
+    let meminfo = match read_file("/proc/meminfo\0") {
+        Ok(meminfo) => String::from_utf8_lossy(&meminfo).into_owned(),
+        Err(errno) => {
+            println!("free: cannot read /proc/meminfo: {}", errno);
+            return -1;
+        }
+    };
+    let mem_total = field(&meminfo, "MemTotal");
+    let mem_used = field(&meminfo, "MemUsed");
+    let heap_total = field(&meminfo, "HeapTotal");
+    let heap_used = field(&meminfo, "HeapUsed");
+
+    println!("{:<6}{:>10}{:>10}{:>10}", "", "total", "used", "free");
+    println!("{:<6}{:>10}{:>10}{:>10}", "Mem:", mem_total, mem_used, mem_total - mem_used);
+    println!("{:<6}{:>10}{:>10}{:>10}", "Heap:", heap_total, heap_used, heap_total - heap_used);
+    0
+}
@@ -1,20 +1,26 @@
 #![no_std]
 #![no_main]
 
-use user_lib::process::{fork, exec, wait, yield_};
+use user_lib::{
+    process::{execve, fork, wait, yield_},
+    signal::{signal, SIGINT, SIG_IGN},
+};
 
 #[macro_use]
 extern crate user_lib;
 
 #[no_mangle]
 fn main() -> i32 {
+    // Ctrl-C must not kill the init process
+    signal(SIGINT, SIG_IGN);
     if fork() == 0 {
-        exec("user_shell\0");
+        execve("user_shell\0", &["user_shell"], &["HOME=/"]);
     } else {
         loop {
             let mut exit_code: i32 = 0;
+            // Blocks while there are children, and orphans may become children later
             let pid = wait(&mut exit_code);
-            if pid == -1 {
+            if pid < 0 {
                 yield_();
             } else {
                 println!("[initproc] Released a zombie, pid={}, exit_code={}", pid, exit_code);
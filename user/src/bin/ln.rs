@@ -0,0 +1,34 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+#[macro_use]
+extern crate user_lib;
+
+use alloc::format;
+use user_lib::fcntl::{link, symlink};
+
+/// Make a hard link `LINK_NAME` to the file `TARGET`, or a symbolic link with `-s`
+#[no_mangle]
+fn main(_argc: usize, argv: &[&str]) -> i32 {
+    let mut paths = &argv[1..];
+    let symbolic = paths.first() == Some(&"-s");
+    if symbolic {
+        paths = &paths[1..];
+    }
+    let &[target, link_name] = paths else {
+        println!("ln: expected a target and a link name");
+        return 1;
+    };
+    let (c_target, c_link_name) = (format!("{}\0", target), format!("{}\0", link_name));
+    let errno = match symbolic {
+        true => symlink(&c_target, &c_link_name),
+        false => link(&c_target, &c_link_name),
+    };
+    if errno < 0 {
+        println!("ln: cannot link {} to {}: error {}", link_name, target, errno);
+        return 1;
+    }
+    0
+}
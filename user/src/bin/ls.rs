@@ -0,0 +1,84 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+#[macro_use]
+extern crate user_lib;
+
+use alloc::{format, string::String, vec::Vec};
+use user_lib::{
+    fcntl::{lstat, read_dir, readlink, stat, Stat, S_IFCHR, S_IFDIR, S_IFIFO, S_IFLNK, S_IFMT, S_IFREG},
+};
+
+/// `-` for a regular file, `d` for a directory and so on, like `ls -l`
+fn type_char(stat: &Stat) -> char {
+    match stat.mode & S_IFMT {
+        S_IFREG => '-',
+        S_IFDIR => 'd',
+        S_IFCHR => 'c',
+        S_IFIFO => 'p',
+        S_IFLNK => 'l',
+        _ => '?',
+    }
+}
+
+/// One line of `ino type nlink size name` for each file, followed by `-> target` for a symbolic
+/// link at `path`
+fn print_entry(stat: &Stat, name: &str, path: &str) {
+    let target = match stat.is_symlink() {
+        true => format!(" -> {}", readlink(path).unwrap_or_default()),
+        false => String::new(),
+    };
+    println!("{:>8} {} {:>3} {:>8} {}{}", stat.ino, type_char(stat), stat.nlink, stat.size, name, target);
+}
+
+/// List the directories and files named by the arguments, or the current working directory
+#[no_mangle]
+fn main(_argc: usize, argv: &[&str]) -> i32 {
+    // The listing is always long, so `-l` changes nothing
+    let mut paths: Vec<&str> = argv[1..].iter().copied().filter(|&arg| arg != "-l").collect();
+    if paths.is_empty() {
+        paths.push(".");
+    }
+    let mut exit_code = 0;
+    for (i, path) in paths.iter().enumerate() {
+        let info = match stat(&format!("{}\0", path)) {
+            Ok(info) => info,
+            Err(errno) => {
+                println!("ls: cannot access {}: error {}", path, errno);
+                exit_code = 1;
+                continue;
+            }
+        };
+        if !info.is_dir() {
+            let c_path = format!("{}\0", path);
+            print_entry(&lstat(&c_path).unwrap_or(info), path, &c_path);
+            continue;
+        }
+        if paths.len() > 1 {
+            if i > 0 {
+                println!("");
+            }
+            println!("{}:", path);
+        }
+        let mut entries = match read_dir(&format!("{}\0", path)) {
+            Ok(entries) => entries,
+            Err(errno) => {
+                println!("ls: cannot open directory {}: error {}", path, errno);
+                exit_code = 1;
+                continue;
+            }
+        };
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        for entry in entries {
+            let entry_path = format!("{}/{}\0", path, entry.name);
+            match lstat(&entry_path) {
+                Ok(info) => print_entry(&info, &entry.name, &entry_path),
+                // E.g. a task in `/proc` which has exited since
+                Err(_) => println!("{:>8} ? {:>3} {:>8} {}", entry.ino, "?", "?", entry.name),
+            }
+        }
+    }
+    exit_code
+}
@@ -0,0 +1,28 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+#[macro_use]
+extern crate user_lib;
+
+use alloc::format;
+use user_lib::fcntl::mkdir;
+
+#[no_mangle]
+fn main(_argc: usize, argv: &[&str]) -> i32 {
+    let paths = &argv[1..];
+    if paths.is_empty() {
+        println!("mkdir: missing operand");
+        return 1;
+    }
+    let mut exit_code = 0;
+    for path in paths {
+        let errno = mkdir(&format!("{}\0", path));
+        if errno < 0 {
+            println!("mkdir: cannot create directory {}: error {}", path, errno);
+            exit_code = 1;
+        }
+    }
+    exit_code
+}
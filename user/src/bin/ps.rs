@@ -0,0 +1,54 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+#[macro_use]
+extern crate user_lib;
+
+use alloc::{format, string::String, vec::Vec};
+use user_lib::fcntl::{read_dir, read_file, DT_DIR};
+
+/// The value of `field` in a `/proc/<pid>/status` file
+fn field<'a>(status: &'a str, field: &str) -> &'a str {
+    status
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| *name == field)
+        .map_or("?", |(_, value)| value.trim())
+}
+
+/// List the tasks from `/proc`
+#[no_mangle]
+fn main() -> i32 {
+    let entries = match read_dir("/proc\0") {
+        Ok(entries) => entries,
+        Err(errno) => {
+            println!("ps: cannot read /proc: {}", errno);
+            return -1;
+        }
+    };
+    let mut pids: Vec<usize> = entries
+        .iter()
+        .filter(|entry| entry.type_ == DT_DIR)
+        .filter_map(|entry| entry.name.parse().ok())
+        .collect();
+    pids.sort();
+
+    println!("{:>5} {:>5} {:<8} CHILDREN", "PID", "PPID", "STATE");
+    for pid in pids {
+        // The task may have been reaped since `/proc` was read
+        let Ok(status) = read_file(&format!("/proc/{}/status\0", pid)) else {
+            continue;
+        };
+        let status = String::from_utf8_lossy(&status);
+        println!(
+            "{:>5} {:>5} {:<8} {}",
+            field(&status, "Pid"),
+            field(&status, "PPid"),
+            field(&status, "State"),
+            field(&status, "Children"),
+        );
+    }
+    0
+}
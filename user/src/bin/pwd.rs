@@ -0,0 +1,19 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::fcntl::getcwd;
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut buf = [0u8; 256];
+    let len = getcwd(&mut buf);
+    if len < 0 {
+        println!("pwd: error {}", len);
+        return -1;
+    }
+    println!("{}", core::str::from_utf8(&buf[..len as usize - 1]).unwrap());
+    0
+}
@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+#[macro_use]
+extern crate user_lib;
+
+use alloc::format;
+use user_lib::fcntl::{rmdir, unlink};
+
+const EISDIR: isize = -21;
+
+/// Remove files, and empty directories with `-d`
+#[no_mangle]
+fn main(_argc: usize, argv: &[&str]) -> i32 {
+    let mut paths = &argv[1..];
+    let dirs = paths.first() == Some(&"-d");
+    if dirs {
+        paths = &paths[1..];
+    }
+    if paths.is_empty() {
+        println!("rm: missing operand");
+        return 1;
+    }
+    let mut exit_code = 0;
+    for path in paths {
+        let c_path = format!("{}\0", path);
+        let mut errno = unlink(&c_path);
+        if errno == EISDIR && dirs {
+            errno = rmdir(&c_path);
+        }
+        if errno < 0 {
+            println!("rm: cannot remove {}: error {}", path, errno);
+            exit_code = 1;
+        }
+    }
+    exit_code
+}
@@ -3,8 +3,16 @@
 
 extern crate alloc;
 
-use alloc::string::String;
-use user_lib::{process::{fork, exec, wait, yield_}, console::getchar, waitpid};
+use alloc::{string::String, vec::Vec};
+use user_lib::{
+    process::{fork, exec, getenv},
+    console::getchar,
+    close, dup2,
+    fcntl::chdir,
+    pipe,
+    signal::{signal, SIGINT, SIG_DFL, SIG_IGN},
+    waitpid,
+};
 
 #[macro_use]
 extern crate user_lib;
@@ -14,8 +22,89 @@ const CR: u8 = 0x0du8;
 const DL: u8 = 0x7fu8;
 const BS: u8 = 0x08u8;
 
+/// Run `a | b | ...`, connecting the stdout of each program to the stdin of the next one. The
+/// words of each command separated by whitespace are the arguments of its program.
+fn run_pipeline(line: &str) {
+    let commands: Vec<&str> = line.split('|').map(|cmd| cmd.trim()).collect();
+    if commands.iter().any(|cmd| cmd.is_empty()) {
+        println!("Syntax error: empty command in pipeline");
+        return;
+    }
+
+    let mut pipes: Vec<[i32; 2]> = Vec::new();
+    for _ in 1..commands.len() {
+        let mut fds = [0i32; 2];
+        if pipe(&mut fds) < 0 {
+            println!("Error when creating a pipe");
+            close_pipes(&pipes);
+            return;
+        }
+        pipes.push(fds);
+    }
+
+    let mut pids = Vec::new();
+    for (i, cmd) in commands.iter().enumerate() {
+        let pid = fork();
+        if pid == 0 {
+            if i > 0 {
+                dup2(pipes[i - 1][0] as usize, 0);
+            }
+            if i + 1 < commands.len() {
+                dup2(pipes[i][1] as usize, 1);
+            }
+            close_pipes(&pipes);
+            // Ignored signals stay ignored through exec, and Ctrl-C should stop the program
+            signal(SIGINT, SIG_DFL);
+
+            let args: Vec<&str> = cmd.split_whitespace().collect();
+            let mut path = String::from(args[0]);
+            path.push('\0');
+            if exec(path.as_str(), &args) < 0 {
+                println!("Error when executing {}", cmd);
+                user_lib::exit(-4);
+            }
+            unreachable!();
+        }
+        pids.push(pid);
+    }
+
+    // Otherwise the readers never see the end of file
+    close_pipes(&pipes);
+    for pid in pids {
+        let mut exit_code: i32 = 0;
+        let exit_pid = waitpid(pid, &mut exit_code);
+        assert_eq!(pid, exit_pid);
+        println!("Shell: Process {} exited with code {}", pid, exit_code);
+    }
+}
+
+/// `cd` changes the directory of the shell itself, so it cannot be a program. Without a directory
+/// it goes to `$HOME`. Return whether `line` is a builtin command.
+fn run_builtin(line: &str) -> bool {
+    let mut words = line.split_whitespace();
+    if words.next() != Some("cd") {
+        return false;
+    }
+    let dir = words.next().or(getenv("HOME")).unwrap_or("/");
+    let mut path = String::from(dir);
+    path.push('\0');
+    if chdir(path.as_str()) < 0 {
+        println!("cd: cannot change directory to {}", dir);
+    }
+    true
+}
+
+fn close_pipes(pipes: &[[i32; 2]]) {
+    for fds in pipes {
+        close(fds[0] as usize);
+        close(fds[1] as usize);
+    }
+}
+
 #[no_mangle]
 fn main() -> isize {
+    // Ctrl-C sends `SIGINT` to all processes, and it is for the running programs
+    signal(SIGINT, SIG_IGN);
     println!("Rust User Shell");
     let mut line: String = String::new();
     print!(">> ");
@@ -25,19 +114,8 @@ fn main() -> isize {
             LF | CR => {
                 println!("");
                 if !line.is_empty() {
-                    line.push('\0');
-                    let pid = fork();
-                    if pid == 0 {
-                        if exec(line.as_str()) == -1 {
-                            println!("Error when executing");
-                            return -4;
-                        }
-                        unreachable!();
-                    } else {
-                        let mut exit_code: i32 = 0;
-                        let exit_pid = waitpid(pid, &mut exit_code);
-                        assert_eq!(pid, exit_pid);
-                        println!("Shell: Process {} exited with code {}", pid, exit_code);
+                    if !run_builtin(line.as_str()) {
+                        run_pipeline(line.as_str());
                     }
                     line.clear();
                 }
@@ -1,4 +1,5 @@
-use crate::{write, syscall::sys_read, read};
+use crate::{write, syscall::sys_read, read, fcntl::isatty};
+use alloc::string::String;
 use core::fmt::{self, Write};
 
 pub struct Stdout;
@@ -35,6 +36,43 @@ pub fn getchar() -> u8 {
     c[0]
 }
 
+const LF: u8 = 0x0au8;
+const CR: u8 = 0x0du8;
+const DL: u8 = 0x7fu8;
+const BS: u8 = 0x08u8;
+
+/// Read a line from stdin without its line break, or `None` at the end of file. The console does
+/// not echo what is typed, so the line is echoed when stdin is the console.
+pub fn read_line() -> Option<String> {
+    let echo = isatty(STDIN);
+    let mut line = String::new();
+    let mut c = [0u8; 1];
+    loop {
+        if Stdin.read(&mut c) != Ok(1) {
+            return (!line.is_empty()).then_some(line);
+        }
+        match c[0] {
+            LF | CR => {
+                if echo {
+                    print(format_args!("\n"));
+                }
+                return Some(line);
+            }
+            BS | DL if echo => {
+                if line.pop().is_some() {
+                    print(format_args!("{} {}", BS as char, BS as char));
+                }
+            }
+            c => {
+                if echo {
+                    print(format_args!("{}", c as char));
+                }
+                line.push(c as char);
+            }
+        }
+    }
+}
+
 #[macro_export]
 macro_rules! print {
     ($fmt:literal $(,$($arg:tt)+)?) => {
@@ -0,0 +1,244 @@
+use alloc::{string::String, vec::Vec};
+
+use crate::{close, read, syscall::*};
+
+pub const O_RDONLY: u32 = 0;
+pub const O_WRONLY: u32 = 1 << 0;
+pub const O_RDWR: u32 = 1 << 1;
+pub const O_CREAT: u32 = 1 << 6;
+pub const O_TRUNC: u32 = 1 << 9;
+
+/// `d_type` of a directory entry
+pub const DT_UNKNOWN: u8 = 0;
+pub const DT_CHR: u8 = 2;
+pub const DT_DIR: u8 = 4;
+pub const DT_REG: u8 = 8;
+pub const DT_LNK: u8 = 10;
+
+/// Type bits of [`Stat::mode`]
+pub const S_IFMT: u32 = 0o170000;
+pub const S_IFIFO: u32 = 0o010000;
+pub const S_IFCHR: u32 = 0o020000;
+pub const S_IFDIR: u32 = 0o040000;
+pub const S_IFREG: u32 = 0o100000;
+pub const S_IFLNK: u32 = 0o120000;
+
+/// `d_ino`, `d_off`, `d_reclen` and `d_type` of `struct linux_dirent64`, before `d_name`
+const DIRENT64_HEADER_SIZE: usize = 19;
+
+/// `dirfd` of the `*at` syscalls for paths relative to the current working directory
+pub const AT_FDCWD: isize = -100;
+const AT_SYMLINK_NOFOLLOW: u32 = 0x100;
+const AT_REMOVEDIR: u32 = 0x200;
+
+/// `struct stat` of Linux on RISC-V
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct Stat {
+    pub dev: u64,
+    pub ino: u64,
+    /// Type and permission bits
+    pub mode: u32,
+    pub nlink: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub rdev: u64,
+    __pad1: u64,
+    pub size: i64,
+    pub blksize: i32,
+    __pad2: i32,
+    /// Number of 512-byte blocks
+    pub blocks: i64,
+    pub atime_sec: i64,
+    pub atime_nsec: i64,
+    pub mtime_sec: i64,
+    pub mtime_nsec: i64,
+    pub ctime_sec: i64,
+    pub ctime_nsec: i64,
+    __unused: [u32; 2],
+}
+
+impl Stat {
+    pub fn is_dir(&self) -> bool {
+        self.mode & S_IFMT == S_IFDIR
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.mode & S_IFMT == S_IFLNK
+    }
+}
+
+/// `path` must end with `\0`. Return the fd, or a negative error number.
+pub fn open(path: &str, flags: u32) -> isize {
+    sys_open(path, flags)
+}
+
+/// Set the size of the file `fd`, filling it with zeros if it grows
+pub fn ftruncate(fd: usize, len: usize) -> isize {
+    sys_ftruncate(fd, len)
+}
+
+/// Move `old_path` to `new_path` in the same filesystem. Both paths must end with `\0`.
+pub fn rename(old_path: &str, new_path: &str) -> isize {
+    sys_renameat2(AT_FDCWD, old_path, AT_FDCWD, new_path, 0)
+}
+
+/// `path` must end with `\0`
+pub fn mkdir(path: &str) -> isize {
+    sys_mkdirat(AT_FDCWD, path, 0o755)
+}
+
+/// Remove the file at `path`, which must end with `\0`
+pub fn unlink(path: &str) -> isize {
+    sys_unlinkat(AT_FDCWD, path, 0)
+}
+
+/// Remove the empty directory at `path`, which must end with `\0`
+pub fn rmdir(path: &str) -> isize {
+    sys_unlinkat(AT_FDCWD, path, AT_REMOVEDIR)
+}
+
+/// Make `new_path` another name of the file at `old_path`. Both paths must end with `\0`.
+pub fn link(old_path: &str, new_path: &str) -> isize {
+    sys_linkat(AT_FDCWD, old_path, AT_FDCWD, new_path, 0)
+}
+
+pub fn fstat(fd: usize, stat: &mut Stat) -> isize {
+    sys_fstat(fd, stat)
+}
+
+/// Whether `fd` is a terminal, which is the console as there are no others
+pub fn isatty(fd: usize) -> bool {
+    let mut stat = Stat::default();
+    fstat(fd, &mut stat) == 0 && stat.mode & S_IFMT == S_IFCHR
+}
+
+/// The [`Stat`] of the file at `path`, which must end with `\0`
+pub fn stat(path: &str) -> Result<Stat, isize> {
+    let mut stat = Stat::default();
+    match sys_newfstatat(AT_FDCWD, path, &mut stat, 0) {
+        0 => Ok(stat),
+        errno => Err(errno),
+    }
+}
+
+/// Like [`stat`], but of the symbolic link itself if `path` names one
+pub fn lstat(path: &str) -> Result<Stat, isize> {
+    let mut stat = Stat::default();
+    match sys_newfstatat(AT_FDCWD, path, &mut stat, AT_SYMLINK_NOFOLLOW) {
+        0 => Ok(stat),
+        errno => Err(errno),
+    }
+}
+
+/// Make a symbolic link at `link_path` to `target`, which is not checked to exist. Both must end
+/// with `\0`.
+pub fn symlink(target: &str, link_path: &str) -> isize {
+    sys_symlinkat(target, AT_FDCWD, link_path)
+}
+
+/// The target of the symbolic link at `path`, which must end with `\0`
+pub fn readlink(path: &str) -> Result<String, isize> {
+    let mut buf = [0u8; 256];
+    let len = sys_readlinkat(AT_FDCWD, path, &mut buf);
+    if len < 0 {
+        return Err(len);
+    }
+    Ok(String::from_utf8_lossy(&buf[..len as usize]).into())
+}
+
+/// Write everything the kernel caches for the filesystems back to the disks
+pub fn sync() -> isize {
+    sys_sync()
+}
+
+/// Store the current working directory with a trailing `\0` into `buf` and return its length
+pub fn getcwd(buf: &mut [u8]) -> isize {
+    sys_getcwd(buf)
+}
+
+/// `path` must end with `\0`
+pub fn chdir(path: &str) -> isize {
+    sys_chdir(path)
+}
+
+/// Mount a filesystem of type `fstype`, e.g. `easyfs` or `appfs`, at `target`. `source` names the
+/// block device of filesystems on a disk, e.g. `/dev/vdb`. All strings must end with `\0`.
+pub fn mount(source: &str, target: &str, fstype: &str) -> isize {
+    sys_mount(source, target, fstype, 0)
+}
+
+/// `target` must end with `\0`
+pub fn umount(target: &str) -> isize {
+    sys_umount2(target, 0)
+}
+
+/// Fill `buf` with `struct linux_dirent64` records of the directory `fd` and return how many
+/// bytes are filled, 0 at the end of the directory
+pub fn getdents64(fd: usize, buf: &mut [u8]) -> isize {
+    sys_getdents64(fd, buf)
+}
+
+pub struct DirEntry {
+    pub ino: u64,
+    /// One of `DT_*`
+    pub type_: u8,
+    pub name: String,
+}
+
+/// Entries of the directory at `path`, which must end with `\0`
+pub fn read_dir(path: &str) -> Result<Vec<DirEntry>, isize> {
+    let fd = open(path, O_RDONLY);
+    if fd < 0 {
+        return Err(fd);
+    }
+    let fd = fd as usize;
+    let mut entries = Vec::new();
+    let mut buf = [0u8; 512];
+    let result = loop {
+        let len = getdents64(fd, &mut buf);
+        if len <= 0 {
+            break len;
+        }
+        let mut records = &buf[..len as usize];
+        while !records.is_empty() {
+            let reclen = u16::from_ne_bytes([records[16], records[17]]) as usize;
+            let name = &records[DIRENT64_HEADER_SIZE..reclen];
+            let name_len = name.iter().position(|&byte| byte == 0).unwrap_or(name.len());
+            entries.push(DirEntry {
+                ino: u64::from_ne_bytes(records[..8].try_into().unwrap()),
+                type_: records[18],
+                name: String::from_utf8_lossy(&name[..name_len]).into(),
+            });
+            records = &records[reclen..];
+        }
+    };
+    close(fd);
+    match result {
+        0 => Ok(entries),
+        errno => Err(errno),
+    }
+}
+
+/// The whole content of the file at `path`, which must end with `\0`
+pub fn read_file(path: &str) -> Result<Vec<u8>, isize> {
+    let fd = open(path, O_RDONLY);
+    if fd < 0 {
+        return Err(fd);
+    }
+    let fd = fd as usize;
+    let mut content = Vec::new();
+    let mut buf = [0u8; 512];
+    let result = loop {
+        let len = read(fd, &mut buf);
+        if len <= 0 {
+            break len;
+        }
+        content.extend_from_slice(&buf[..len as usize]);
+    };
+    close(fd);
+    match result {
+        0 => Ok(content),
+        errno => Err(errno),
+    }
+}
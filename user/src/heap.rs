@@ -0,0 +1,51 @@
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    ptr::{null_mut, NonNull},
+};
+
+use buddy_system_allocator::LockedHeap;
+
+use crate::syscall::sys_sbrk;
+
+/// The heap grows by at least this many bytes each time
+const HEAP_GROW_SIZE: usize = 16384;
+
+/// A buddy allocator which asks the kernel for more memory with `sbrk` when it is exhausted
+pub struct GrowableHeap {
+    heap: LockedHeap<32>,
+}
+
+impl GrowableHeap {
+    pub const fn empty() -> Self {
+        Self {
+            heap: LockedHeap::empty(),
+        }
+    }
+}
+
+unsafe impl GlobalAlloc for GrowableHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut heap = self.heap.lock();
+        if let Ok(ptr) = heap.alloc(layout) {
+            return ptr.as_ptr();
+        }
+
+        // Only a range twice as large as the block guarantees a block aligned to its size
+        let block_size = layout.size().max(layout.align()).next_power_of_two();
+        let grow_size = (block_size * 2).max(HEAP_GROW_SIZE);
+        if grow_size > i32::MAX as usize {
+            return null_mut();
+        }
+        let old_brk = sys_sbrk(grow_size as i32);
+        if old_brk == -1 {
+            return null_mut();
+        }
+
+        heap.add_to_heap(old_brk as usize, old_brk as usize + grow_size);
+        heap.alloc(layout).map_or(null_mut(), |ptr| ptr.as_ptr())
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.heap.lock().dealloc(NonNull::new_unchecked(ptr), layout)
+    }
+}
@@ -3,33 +3,39 @@
 #![feature(panic_info_message)]
 #![feature(alloc_error_handler)]
 
+extern crate alloc;
+
 pub mod console;
+mod heap;
 mod lang_items;
+pub mod fcntl;
+pub mod mman;
 pub mod process;
+pub mod signal;
+pub mod sync;
 mod syscall;
+pub mod thread;
 pub mod time;
 
-use buddy_system_allocator::LockedHeap;
-const USER_HEAP_SIZE: usize = 16384;
-static mut HEAP_SPACE: [u8; USER_HEAP_SIZE] = [0; USER_HEAP_SIZE];
+use alloc::vec::Vec;
+use heap::GrowableHeap;
 
 #[global_allocator]
-static HEAP: LockedHeap<32> = LockedHeap::empty();
+static HEAP: GrowableHeap = GrowableHeap::empty();
 
 #[alloc_error_handler]
 pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
     panic!("Heap allocation error, layout = {:?}", layout);
 }
 
+/// The kernel passes `argc`, `argv` and `envp` in `a0`, `a1` and `a2`
 #[no_mangle]
 #[link_section = ".text.entry"]
-pub extern "C" fn _start() -> ! {
+pub extern "C" fn _start(argc: usize, argv: usize, envp: usize) -> ! {
     clear_bss();
-    unsafe {
-        HEAP.lock()
-            .init(HEAP_SPACE.as_ptr() as usize, USER_HEAP_SIZE);
-    }
-    exit(main());
+    process::init_env(envp);
+    let args: Vec<&'static str> = process::c_str_array(argv).take(argc).collect();
+    exit(main(argc, &args));
     panic!("The application should have exited!");
 }
 
@@ -42,14 +48,30 @@ fn clear_bss() {
     (bss_start as usize..bss_end as usize).for_each(|p| unsafe { (p as *mut u8).write_volatile(0) })
 }
 
+/// `argv[0]` is the name of the program. Programs which take no arguments may define
+/// `fn main() -> i32` instead.
 #[no_mangle]
 #[linkage = "weak"]
-fn main() -> i32 {
+fn main(_argc: usize, _argv: &[&str]) -> i32 {
     panic!("Cannot find main()");
 }
 
 use syscall::*;
 
+pub fn dup(fd: usize) -> isize {
+    sys_dup(fd)
+}
+/// Make `new_fd` refer to the file of `old_fd`
+pub fn dup2(old_fd: usize, new_fd: usize) -> isize {
+    sys_dup2(old_fd, new_fd)
+}
+pub fn close(fd: usize) -> isize {
+    sys_close(fd)
+}
+/// Create a pipe, `pipe[0]` is the read end and `pipe[1]` is the write end
+pub fn pipe(pipe: &mut [i32; 2]) -> isize {
+    sys_pipe(pipe)
+}
 pub fn write(fd: usize, buf: &[u8]) -> isize {
     sys_write(fd, buf)
 }
@@ -59,5 +81,9 @@ pub fn read(fd: usize, buf: &mut [u8]) -> isize {
 pub fn exit(xstate: i32) -> isize {
     sys_exit(xstate)
 }
+/// Grow or shrink the heap by `size` bytes, returning the old program break or -1
+pub fn sbrk(size: i32) -> isize {
+    sys_sbrk(size)
+}
 
 pub use process::*;
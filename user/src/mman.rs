@@ -0,0 +1,36 @@
+use crate::syscall::*;
+
+pub const PROT_NONE: usize = 0;
+pub const PROT_READ: usize = 1 << 0;
+pub const PROT_WRITE: usize = 1 << 1;
+pub const PROT_EXEC: usize = 1 << 2;
+
+pub const MAP_PRIVATE: usize = 0x02;
+pub const MAP_FIXED: usize = 0x10;
+pub const MAP_ANONYMOUS: usize = 0x20;
+pub const MAP_FIXED_NOREPLACE: usize = 0x100000;
+
+pub const PAGE_SIZE: usize = 4096;
+
+/// Return the address of the mapping, or a negative error number
+pub fn mmap(addr: usize, len: usize, prot: usize, flags: usize) -> isize {
+    sys_mmap(addr, len, prot, flags, usize::MAX, 0)
+}
+
+pub fn munmap(addr: usize, len: usize) -> isize {
+    sys_munmap(addr, len)
+}
+
+pub fn mprotect(addr: usize, len: usize, prot: usize) -> isize {
+    sys_mprotect(addr, len, prot)
+}
+
+/// Map `len` bytes of zeroed private memory anywhere in the address space
+pub fn map_anonymous(len: usize, prot: usize) -> Option<&'static mut [u8]> {
+    let addr = mmap(0, len, prot, MAP_PRIVATE | MAP_ANONYMOUS);
+    if addr < 0 {
+        None
+    } else {
+        Some(unsafe { core::slice::from_raw_parts_mut(addr as usize as *mut u8, len) })
+    }
+}
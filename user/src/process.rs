@@ -1,4 +1,7 @@
-use crate::syscall::*;
+use alloc::{format, string::String, vec::Vec};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{syscall::*, time::TimeVal};
 
 pub fn yield_() -> isize {
     sys_yield()
@@ -6,25 +9,134 @@ pub fn yield_() -> isize {
 pub fn fork() -> isize {
     sys_fork()
 }
+pub fn getpid() -> isize {
+    sys_getpid()
+}
+
+/// Option of [`wait4`] to return 0 at once if no child has exited yet
+pub const WNOHANG: u32 = 1;
+
+/// `struct rusage` of Linux. Only the CPU times are filled in.
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct Rusage {
+    /// Time spent running the program
+    pub utime: TimeVal,
+    /// Time spent in the kernel for the program
+    pub stime: TimeVal,
+    __unused: [isize; 14],
+}
 
+/// Block until any child exits and return its pid, or a negative error number if there are no
+/// children
 pub fn wait(exit_code: &mut i32) -> isize {
-    loop {
-        match sys_waitpid(-1, exit_code as *mut _) {
-            -2 => yield_(),
-            exit_pid => return exit_pid,
-        };
-    }
+    sys_wait4(-1, exit_code, 0, core::ptr::null_mut())
 }
 
+/// Block until the child `pid` exits and return its pid
 pub fn waitpid(pid: isize, exit_code: &mut i32) -> isize {
-    loop {
-        match sys_waitpid(pid, exit_code as *mut _) {
-            -2 => yield_(),
-            exit_pid => return exit_pid,
-        };
+    sys_wait4(pid, exit_code, 0, core::ptr::null_mut())
+}
+
+/// Like [`waitpid`] with `options` such as [`WNOHANG`]. `rusage` gets the CPU time of the child and
+/// the children it has waited for.
+pub fn wait4(pid: isize, exit_code: &mut i32, options: u32, rusage: &mut Rusage) -> isize {
+    sys_wait4(pid, exit_code, options, rusage)
+}
+
+/// Run the program at `path` with the arguments `args`, keeping the environment. `path` must end
+/// with `\0`, `args` must not. `args[0]` should be the name of the program.
+pub fn exec(path: &str, args: &[&str]) -> isize {
+    let args = CStrArray::new(args);
+    sys_exec(path, args.as_ptr(), ENVP.load(Ordering::Relaxed) as *const usize)
+}
+
+/// Like [`exec`], but with the environment `envs` of `NAME=value` strings
+pub fn execve(path: &str, args: &[&str], envs: &[&str]) -> isize {
+    let (args, envs) = (CStrArray::new(args), CStrArray::new(envs));
+    sys_exec(path, args.as_ptr(), envs.as_ptr())
+}
+
+/// `envp` of the program, set by `_start`
+static ENVP: AtomicUsize = AtomicUsize::new(0);
+
+pub(crate) fn init_env(envp: usize) {
+    ENVP.store(envp, Ordering::Relaxed);
+}
+
+/// Variables of the environment as `(name, value)` pairs
+pub fn env() -> impl Iterator<Item = (&'static str, &'static str)> {
+    c_str_array(ENVP.load(Ordering::Relaxed)).map(|var| var.split_once('=').unwrap_or((var, "")))
+}
+
+/// The value of the environment variable `name`
+pub fn getenv(name: &str) -> Option<&'static str> {
+    env().find(|&(var, _)| var == name).map(|(_, value)| value)
+}
+
+/// Type of the entry of the auxiliary vector for the page size
+pub const AT_PAGESZ: usize = 6;
+/// For the address of the program headers
+pub const AT_PHDR: usize = 3;
+/// For the entry point of the program
+pub const AT_ENTRY: usize = 9;
+
+/// The value of the entry of type `type_` in the auxiliary vector, which follows `envp` on the
+/// initial stack
+pub fn getauxval(type_: usize) -> Option<usize> {
+    let envp = ENVP.load(Ordering::Relaxed) as *const usize;
+    if envp.is_null() {
+        return None;
+    }
+    unsafe {
+        let mut entry = envp;
+        while *entry != 0 {
+            entry = entry.add(1);
+        }
+        entry = entry.add(1);
+        // Ends with `AT_NULL`, which is 0
+        while *entry != 0 {
+            if *entry == type_ {
+                return Some(*entry.add(1));
+            }
+            entry = entry.add(2);
+        }
     }
+    None
 }
 
-pub fn exec(path: &str) -> isize {
-    sys_exec(path)
+/// The strings of a null-terminated array of pointers to C strings at `ptr`, e.g. `argv`
+pub(crate) fn c_str_array(ptr: usize) -> impl Iterator<Item = &'static str> {
+    let mut ptr = ptr as *const *const u8;
+    core::iter::from_fn(move || unsafe {
+        if ptr.is_null() || (*ptr).is_null() {
+            return None;
+        }
+        let start = *ptr;
+        ptr = ptr.add(1);
+        let mut len = 0;
+        while *start.add(len) != 0 {
+            len += 1;
+        }
+        Some(core::str::from_utf8(core::slice::from_raw_parts(start, len)).unwrap_or_default())
+    })
+}
+
+/// A null-terminated array of pointers to copies of strings with `\0` appended, for `exec`
+struct CStrArray {
+    _strings: Vec<String>,
+    ptrs: Vec<usize>,
+}
+
+impl CStrArray {
+    fn new(strings: &[&str]) -> Self {
+        let strings: Vec<String> = strings.iter().map(|string| format!("{}\0", string)).collect();
+        let mut ptrs: Vec<usize> = strings.iter().map(|string| string.as_ptr() as usize).collect();
+        ptrs.push(0);
+        Self { _strings: strings, ptrs }
+    }
+
+    fn as_ptr(&self) -> *const usize {
+        self.ptrs.as_ptr()
+    }
 }
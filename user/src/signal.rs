@@ -0,0 +1,132 @@
+use core::{arch::global_asm, ptr};
+
+use crate::syscall::*;
+
+pub const SIGHUP: usize = 1;
+pub const SIGINT: usize = 2;
+pub const SIGQUIT: usize = 3;
+pub const SIGILL: usize = 4;
+pub const SIGTRAP: usize = 5;
+pub const SIGABRT: usize = 6;
+pub const SIGBUS: usize = 7;
+pub const SIGFPE: usize = 8;
+pub const SIGKILL: usize = 9;
+pub const SIGUSR1: usize = 10;
+pub const SIGSEGV: usize = 11;
+pub const SIGUSR2: usize = 12;
+pub const SIGPIPE: usize = 13;
+pub const SIGALRM: usize = 14;
+pub const SIGTERM: usize = 15;
+pub const SIGCHLD: usize = 17;
+pub const SIGCONT: usize = 18;
+pub const SIGSTOP: usize = 19;
+pub const SIGTSTP: usize = 20;
+
+/// Handler for the default action of a signal
+pub const SIG_DFL: usize = 0;
+/// Handler for ignoring a signal
+pub const SIG_IGN: usize = 1;
+
+/// The signal is not blocked while its handler runs
+pub const SA_NODEFER: usize = 0x4000_0000;
+/// The action is reset to the default one when the handler is called
+pub const SA_RESETHAND: usize = 0x8000_0000;
+const SA_RESTORER: usize = 0x0400_0000;
+
+/// `how` of [`sigprocmask`]
+pub const SIG_BLOCK: usize = 0;
+pub const SIG_UNBLOCK: usize = 1;
+pub const SIG_SETMASK: usize = 2;
+
+/// A set of signals, where signal `n` is bit `n - 1`
+#[repr(transparent)]
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub struct SignalSet(pub u64);
+
+impl SignalSet {
+    pub fn of(signals: &[usize]) -> Self {
+        let mut set = Self::default();
+        for &sig in signals {
+            set.insert(sig);
+        }
+        set
+    }
+
+    pub fn contains(&self, sig: usize) -> bool {
+        self.0 & 1 << (sig - 1) != 0
+    }
+
+    pub fn insert(&mut self, sig: usize) {
+        self.0 |= 1 << (sig - 1);
+    }
+}
+
+/// `struct sigaction` of Linux. `handler` is [`SIG_DFL`], [`SIG_IGN`] or the address of an
+/// `extern "C" fn(sig: usize)`, which runs with the signals in `mask` blocked as well.
+#[repr(C)]
+#[derive(Copy, Clone, Default, Debug)]
+pub struct SignalAction {
+    pub handler: usize,
+    pub flags: usize,
+    restorer: usize,
+    pub mask: SignalSet,
+}
+
+impl SignalAction {
+    pub fn new(handler: usize, flags: usize, mask: SignalSet) -> Self {
+        Self {
+            handler,
+            flags,
+            restorer: 0,
+            mask,
+        }
+    }
+}
+
+// Handlers return here. It must not touch the stack, as the kernel finds the context which the
+// signal interrupted at the stack pointer.
+global_asm!(
+    ".globl __sigreturn",
+    "__sigreturn:",
+    "li a7, {sigreturn}",
+    "ecall",
+    sigreturn = const Syscalls::Sigreturn as usize,
+);
+
+extern "C" {
+    fn __sigreturn();
+}
+
+/// Set the action of `sig` to `action` and return the old one in `old_action`, either of which
+/// may be `None`
+pub fn sigaction(sig: usize, action: Option<&SignalAction>, old_action: Option<&mut SignalAction>) -> isize {
+    let action = action.map(|action| SignalAction {
+        flags: action.flags | SA_RESTORER,
+        restorer: __sigreturn as *const () as usize,
+        ..*action
+    });
+    sys_sigaction(
+        sig,
+        action.as_ref().map_or(ptr::null(), |action| action as *const _),
+        old_action.map_or(ptr::null_mut(), |action| action as *mut _),
+    )
+}
+
+/// Set the handler of `sig` without flags, e.g. to [`SIG_IGN`]
+pub fn signal(sig: usize, handler: usize) -> isize {
+    sigaction(sig, Some(&SignalAction::new(handler, 0, SignalSet::default())), None)
+}
+
+/// Change the blocked signals by `set` as `how` says, and return the old ones in `old_set`
+pub fn sigprocmask(how: usize, set: Option<&SignalSet>, old_set: Option<&mut SignalSet>) -> isize {
+    sys_sigprocmask(
+        how,
+        set.map_or(ptr::null(), |set| set as *const _),
+        old_set.map_or(ptr::null_mut(), |set| set as *mut _),
+    )
+}
+
+/// Send `sig` to the process `pid`
+pub fn kill(pid: isize, sig: usize) -> isize {
+    sys_kill(pid, sig)
+}
@@ -0,0 +1,162 @@
+//! Mutexes, semaphores and condition variables of the kernel, where waiting threads block instead
+//! of spinning. Their ids are never freed, so a process should make them once and keep them.
+
+use core::{
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut},
+};
+
+use crate::syscall::*;
+
+const EINTR: isize = -4;
+
+/// Run `syscall` again while signal handlers interrupt it
+fn restart(syscall: impl Fn() -> isize) -> isize {
+    loop {
+        let result = syscall();
+        if result != EINTR {
+            return result;
+        }
+    }
+}
+
+/// A mutex protecting a `T`
+pub struct Mutex<T> {
+    id: usize,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Send for Mutex<T> {}
+unsafe impl<T: Send> Sync for Mutex<T> {}
+
+impl<T> Mutex<T> {
+    pub fn new(data: T) -> Self {
+        let id = sys_mutex_create();
+        assert!(id >= 0, "Cannot create a mutex: {}", id);
+        Self {
+            id: id as usize,
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Lock the mutex until the guard is dropped, waiting until other threads unlock it. Fails
+    /// with a negative error number, e.g. `EDEADLK` if this thread holds it already or deadlock
+    /// detection finds that waiting would deadlock.
+    pub fn lock(&self) -> Result<MutexGuard<'_, T>, isize> {
+        match restart(|| sys_mutex_lock(self.id)) {
+            0 => Ok(MutexGuard { mutex: self }),
+            errno => Err(errno),
+        }
+    }
+}
+
+pub struct MutexGuard<'a, T> {
+    mutex: &'a Mutex<T>,
+}
+
+impl<T> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        sys_mutex_unlock(self.mutex.id);
+    }
+}
+
+/// A counting semaphore
+pub struct Semaphore {
+    id: usize,
+}
+
+impl Semaphore {
+    /// A semaphore with `count` units
+    pub fn new(count: usize) -> Self {
+        let id = sys_semaphore_create(count);
+        assert!(id >= 0, "Cannot create a semaphore: {}", id);
+        Self { id: id as usize }
+    }
+
+    /// Add a unit, waking up a thread waiting in [`Semaphore::down`]
+    pub fn up(&self) {
+        sys_semaphore_up(self.id);
+    }
+
+    /// Take a unit, waiting until there is one. Fails with `EDEADLK` if deadlock detection finds
+    /// that waiting would deadlock.
+    pub fn down(&self) -> isize {
+        restart(|| sys_semaphore_down(self.id))
+    }
+
+    /// Take a unit until the guard is dropped
+    pub fn acquire(&self) -> Result<SemaphoreGuard<'_>, isize> {
+        match self.down() {
+            0 => Ok(SemaphoreGuard { semaphore: self }),
+            errno => Err(errno),
+        }
+    }
+}
+
+pub struct SemaphoreGuard<'a> {
+    semaphore: &'a Semaphore,
+}
+
+impl Drop for SemaphoreGuard<'_> {
+    fn drop(&mut self) {
+        self.semaphore.up();
+    }
+}
+
+/// A condition variable, which is waited on and signaled with a [`Mutex`] locked
+pub struct Condvar {
+    id: usize,
+}
+
+impl Condvar {
+    pub fn new() -> Self {
+        let id = sys_condvar_create();
+        assert!(id >= 0, "Cannot create a condition variable: {}", id);
+        Self { id: id as usize }
+    }
+
+    /// Wake up a thread waiting on the condition variable, if any
+    pub fn signal(&self) {
+        sys_condvar_signal(self.id);
+    }
+
+    /// Unlock the mutex of `guard`, wait until the condition variable is signaled and lock the
+    /// mutex again. The wait may also end without a signal, so the condition must be checked
+    /// again.
+    pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> Result<MutexGuard<'a, T>, isize> {
+        let mutex = guard.mutex;
+        core::mem::forget(guard);
+        match sys_condvar_wait(self.id, mutex.id) {
+            0 => Ok(MutexGuard { mutex }),
+            // The mutex is unlocked if a signal handler has interrupted the wait
+            EINTR => mutex.lock(),
+            errno => Err(errno),
+        }
+    }
+}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Make waiting for a mutex or a semaphore fail with `EDEADLK` if it would deadlock, like the
+/// safety check of the banker's algorithm finds
+pub fn enable_deadlock_detect(enabled: bool) -> isize {
+    sys_enable_deadlock_detect(enabled)
+}
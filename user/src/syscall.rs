@@ -1,5 +1,11 @@
 use core::arch::asm;
 
+use crate::{
+    fcntl::Stat,
+    process::Rusage,
+    signal::{SignalAction, SignalSet},
+};
+
 pub fn syscall(id: usize, args: [usize; 3]) -> isize {
     let mut ret: isize;
     unsafe {
@@ -15,16 +21,152 @@ pub fn syscall(id: usize, args: [usize; 3]) -> isize {
     ret
 }
 
+pub fn syscall6(id: usize, args: [usize; 6]) -> isize {
+    let mut ret: isize;
+    unsafe {
+        asm!(
+            "ecall",
+            inlateout("x10") args[0] => ret,
+            in("x11") args[1],
+            in("x12") args[2],
+            in("x13") args[3],
+            in("x14") args[4],
+            in("x15") args[5],
+            in("x17") id,
+        );
+    }
+
+    ret
+}
+
 #[repr(usize)]
 pub enum Syscalls {
+    Getcwd = 17,
+    Dup = 23,
+    Dup2 = 24,
+    Mkdirat = 34,
+    Unlinkat = 35,
+    Symlinkat = 36,
+    Linkat = 37,
+    Umount2 = 39,
+    Ftruncate = 46,
+    Mount = 40,
+    Chdir = 49,
+    Open = 56,
+    Close = 57,
+    Pipe = 59,
+    Getdents64 = 61,
     Read = 63,
     Write = 64,
+    Readlinkat = 78,
+    Newfstatat = 79,
+    Fstat = 80,
+    Sync = 81,
     Exit = 93,
     Yield = 124,
+    Kill = 129,
+    Sigaction = 134,
+    Sigprocmask = 135,
+    Sigreturn = 139,
     GetTime = 169,
+    Getpid = 172,
+    Gettid = 178,
+    Sbrk = 214,
+    Munmap = 215,
     Fork = 220,
     Exec = 221,
-    WaitPID = 260,
+    Mmap = 222,
+    Mprotect = 226,
+    Wait4 = 260,
+    Renameat2 = 276,
+    EnableDeadlockDetect = 469,
+    ThreadCreate = 1000,
+    Waittid = 1002,
+    MutexCreate = 1010,
+    MutexLock = 1011,
+    MutexUnlock = 1012,
+    SemaphoreCreate = 1020,
+    SemaphoreUp = 1021,
+    SemaphoreDown = 1022,
+    CondvarCreate = 1030,
+    CondvarSignal = 1031,
+    CondvarWait = 1032,
+}
+
+pub fn sys_getcwd(buf: &mut [u8]) -> isize {
+    syscall(Syscalls::Getcwd as usize, [buf.as_mut_ptr() as usize, buf.len(), 0])
+}
+
+pub fn sys_dup(fd: usize) -> isize {
+    syscall(Syscalls::Dup as usize, [fd, 0, 0])
+}
+
+pub fn sys_dup2(old_fd: usize, new_fd: usize) -> isize {
+    syscall(Syscalls::Dup2 as usize, [old_fd, new_fd, 0])
+}
+
+pub fn sys_mkdirat(dirfd: isize, path: &str, mode: u32) -> isize {
+    syscall(Syscalls::Mkdirat as usize, [dirfd as usize, path.as_ptr() as usize, mode as usize])
+}
+
+pub fn sys_unlinkat(dirfd: isize, path: &str, flags: u32) -> isize {
+    syscall(Syscalls::Unlinkat as usize, [dirfd as usize, path.as_ptr() as usize, flags as usize])
+}
+
+pub fn sys_symlinkat(target: &str, new_dirfd: isize, link_path: &str) -> isize {
+    syscall(
+        Syscalls::Symlinkat as usize,
+        [target.as_ptr() as usize, new_dirfd as usize, link_path.as_ptr() as usize],
+    )
+}
+
+pub fn sys_linkat(old_dirfd: isize, old_path: &str, new_dirfd: isize, new_path: &str, flags: u32) -> isize {
+    syscall6(
+        Syscalls::Linkat as usize,
+        [
+            old_dirfd as usize,
+            old_path.as_ptr() as usize,
+            new_dirfd as usize,
+            new_path.as_ptr() as usize,
+            flags as usize,
+            0,
+        ],
+    )
+}
+
+pub fn sys_umount2(target: &str, flags: usize) -> isize {
+    syscall(Syscalls::Umount2 as usize, [target.as_ptr() as usize, flags, 0])
+}
+
+pub fn sys_mount(source: &str, target: &str, fstype: &str, flags: usize) -> isize {
+    syscall6(
+        Syscalls::Mount as usize,
+        [source.as_ptr() as usize, target.as_ptr() as usize, fstype.as_ptr() as usize, flags, 0, 0],
+    )
+}
+
+pub fn sys_ftruncate(fd: usize, len: usize) -> isize {
+    syscall(Syscalls::Ftruncate as usize, [fd, len, 0])
+}
+
+pub fn sys_chdir(path: &str) -> isize {
+    syscall(Syscalls::Chdir as usize, [path.as_ptr() as usize, 0, 0])
+}
+
+pub fn sys_open(path: &str, flags: u32) -> isize {
+    syscall(Syscalls::Open as usize, [path.as_ptr() as usize, flags as usize, 0])
+}
+
+pub fn sys_close(fd: usize) -> isize {
+    syscall(Syscalls::Close as usize, [fd, 0, 0])
+}
+
+pub fn sys_pipe(pipe: &mut [i32; 2]) -> isize {
+    syscall(Syscalls::Pipe as usize, [pipe.as_mut_ptr() as usize, 0, 0])
+}
+
+pub fn sys_getdents64(fd: usize, buf: &mut [u8]) -> isize {
+    syscall(Syscalls::Getdents64 as usize, [fd, buf.as_mut_ptr() as usize, buf.len()])
 }
 
 pub fn sys_read(fd: usize, buf: &mut [u8]) -> isize {
@@ -38,6 +180,28 @@ pub fn sys_write(fd: usize, buf: &[u8]) -> isize {
     )
 }
 
+pub fn sys_readlinkat(dirfd: isize, path: &str, buf: &mut [u8]) -> isize {
+    syscall6(
+        Syscalls::Readlinkat as usize,
+        [dirfd as usize, path.as_ptr() as usize, buf.as_mut_ptr() as usize, buf.len(), 0, 0],
+    )
+}
+
+pub fn sys_newfstatat(dirfd: isize, path: &str, stat: &mut Stat, flags: u32) -> isize {
+    syscall6(
+        Syscalls::Newfstatat as usize,
+        [dirfd as usize, path.as_ptr() as usize, stat as *mut _ as usize, flags as usize, 0, 0],
+    )
+}
+
+pub fn sys_fstat(fd: usize, stat: &mut Stat) -> isize {
+    syscall(Syscalls::Fstat as usize, [fd, stat as *mut _ as usize, 0])
+}
+
+pub fn sys_sync() -> isize {
+    syscall(Syscalls::Sync as usize, [0, 0, 0])
+}
+
 pub fn sys_exit(xstate: i32) -> isize {
     syscall(Syscalls::Exit as usize, [xstate as usize, 0, 0])
 }
@@ -46,20 +210,127 @@ pub fn sys_yield() -> isize {
     syscall(Syscalls::Yield as usize, [0, 0, 0])
 }
 
+pub fn sys_kill(pid: isize, sig: usize) -> isize {
+    syscall(Syscalls::Kill as usize, [pid as usize, sig, 0])
+}
+
+pub fn sys_sigaction(sig: usize, act: *const SignalAction, oldact: *mut SignalAction) -> isize {
+    syscall6(
+        Syscalls::Sigaction as usize,
+        [sig, act as usize, oldact as usize, core::mem::size_of::<SignalSet>(), 0, 0],
+    )
+}
+
+pub fn sys_sigprocmask(how: usize, set: *const SignalSet, oldset: *mut SignalSet) -> isize {
+    syscall6(
+        Syscalls::Sigprocmask as usize,
+        [how, set as usize, oldset as usize, core::mem::size_of::<SignalSet>(), 0, 0],
+    )
+}
+
 use crate::time::TimeVal;
 
 pub fn sys_get_time(ts: &mut TimeVal, tz: usize) -> isize {
     syscall(Syscalls::GetTime as usize, [ts as *mut _ as usize, tz, 0])
 }
 
+pub fn sys_getpid() -> isize {
+    syscall(Syscalls::Getpid as usize, [0, 0, 0])
+}
+
+pub fn sys_gettid() -> isize {
+    syscall(Syscalls::Gettid as usize, [0, 0, 0])
+}
+
+pub fn sys_sbrk(size: i32) -> isize {
+    syscall(Syscalls::Sbrk as usize, [size as usize, 0, 0])
+}
+
+pub fn sys_mmap(addr: usize, len: usize, prot: usize, flags: usize, fd: usize, offset: usize) -> isize {
+    syscall6(Syscalls::Mmap as usize, [addr, len, prot, flags, fd, offset])
+}
+
+pub fn sys_munmap(addr: usize, len: usize) -> isize {
+    syscall(Syscalls::Munmap as usize, [addr, len, 0])
+}
+
+pub fn sys_mprotect(addr: usize, len: usize, prot: usize) -> isize {
+    syscall(Syscalls::Mprotect as usize, [addr, len, prot])
+}
+
 pub fn sys_fork() -> isize {
     syscall(Syscalls::Fork as usize, [0, 0, 0])
 }
 
-pub fn sys_waitpid(pid: isize, exit_code: *mut i32) -> isize {
-    syscall(Syscalls::WaitPID as usize, [pid as usize, exit_code as usize, 0])
+pub fn sys_wait4(pid: isize, exit_code: *mut i32, options: u32, rusage: *mut Rusage) -> isize {
+    syscall6(
+        Syscalls::Wait4 as usize,
+        [pid as usize, exit_code as usize, options as usize, rusage as usize, 0, 0],
+    )
+}
+
+pub fn sys_renameat2(old_dirfd: isize, old_path: &str, new_dirfd: isize, new_path: &str, flags: u32) -> isize {
+    syscall6(
+        Syscalls::Renameat2 as usize,
+        [
+            old_dirfd as usize,
+            old_path.as_ptr() as usize,
+            new_dirfd as usize,
+            new_path.as_ptr() as usize,
+            flags as usize,
+            0,
+        ],
+    )
+}
+
+pub fn sys_exec(path: &str, argv: *const usize, envp: *const usize) -> isize {
+    syscall(Syscalls::Exec as usize, [path.as_ptr() as usize, argv as usize, envp as usize])
+}
+
+pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
+    syscall(Syscalls::ThreadCreate as usize, [entry, arg, 0])
+}
+
+pub fn sys_waittid(tid: usize, exit_code: *mut i32) -> isize {
+    syscall(Syscalls::Waittid as usize, [tid, exit_code as usize, 0])
+}
+
+pub fn sys_mutex_create() -> isize {
+    syscall(Syscalls::MutexCreate as usize, [0, 0, 0])
+}
+
+pub fn sys_mutex_lock(mutex_id: usize) -> isize {
+    syscall(Syscalls::MutexLock as usize, [mutex_id, 0, 0])
+}
+
+pub fn sys_mutex_unlock(mutex_id: usize) -> isize {
+    syscall(Syscalls::MutexUnlock as usize, [mutex_id, 0, 0])
+}
+
+pub fn sys_semaphore_create(count: usize) -> isize {
+    syscall(Syscalls::SemaphoreCreate as usize, [count, 0, 0])
+}
+
+pub fn sys_semaphore_up(sem_id: usize) -> isize {
+    syscall(Syscalls::SemaphoreUp as usize, [sem_id, 0, 0])
+}
+
+pub fn sys_semaphore_down(sem_id: usize) -> isize {
+    syscall(Syscalls::SemaphoreDown as usize, [sem_id, 0, 0])
+}
+
+pub fn sys_condvar_create() -> isize {
+    syscall(Syscalls::CondvarCreate as usize, [0, 0, 0])
+}
+
+pub fn sys_condvar_signal(condvar_id: usize) -> isize {
+    syscall(Syscalls::CondvarSignal as usize, [condvar_id, 0, 0])
+}
+
+pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
+    syscall(Syscalls::CondvarWait as usize, [condvar_id, mutex_id, 0])
 }
 
-pub fn sys_exec(path: &str) -> isize {
-    syscall(Syscalls::Exec as usize, [path.as_ptr() as usize, 0, 0])
+pub fn sys_enable_deadlock_detect(enabled: bool) -> isize {
+    syscall(Syscalls::EnableDeadlockDetect as usize, [enabled as usize, 0, 0])
 }
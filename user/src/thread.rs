@@ -0,0 +1,36 @@
+use alloc::boxed::Box;
+
+use crate::{exit, syscall::*};
+
+/// Start a thread at `entry`, which gets `arg` and must end with [`exit`]. Return its tid.
+pub fn thread_create(entry: extern "C" fn(usize) -> !, arg: usize) -> isize {
+    sys_thread_create(entry as *const () as usize, arg)
+}
+
+/// Run `f` in a new thread, which exits with what `f` returns. Return its tid.
+pub fn spawn<F: FnOnce() -> i32 + Send + 'static>(f: F) -> isize {
+    let f: Box<Box<dyn FnOnce() -> i32 + Send>> = Box::new(Box::new(f));
+    let arg = Box::into_raw(f);
+    let tid = thread_create(thread_start, arg as usize);
+    if tid < 0 {
+        drop(unsafe { Box::from_raw(arg) });
+    }
+    tid
+}
+
+extern "C" fn thread_start(arg: usize) -> ! {
+    let f = unsafe { Box::from_raw(arg as *mut Box<dyn FnOnce() -> i32 + Send>) };
+    exit(f());
+    unreachable!("The thread should have exited!");
+}
+
+/// The tid of the calling thread. The main thread is 0.
+pub fn gettid() -> isize {
+    sys_gettid()
+}
+
+/// Block until the thread `tid` of this process exits, and return `tid`. A thread cannot wait for
+/// itself.
+pub fn waittid(tid: usize, exit_code: &mut i32) -> isize {
+    sys_waittid(tid, exit_code)
+}
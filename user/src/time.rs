@@ -1,7 +1,7 @@
 use crate::syscall::sys_get_time;
 
 #[repr(C)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Default, Debug)]
 pub struct TimeVal {
     pub sec: usize,
     pub usec: usize,